@@ -0,0 +1,19 @@
+//! Compiles the gRPC service definition (see `proto/tenzi_sim.proto`) when the `grpc` feature is enabled.
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+
+    #[cfg(feature = "grpc")]
+    compile_protos();
+}
+
+#[cfg(feature = "grpc")]
+fn compile_protos() {
+    // Compile with the vendored `protoc`, unless another one is given, so that the service builds without one installed.
+
+    if std::env::var_os("PROTOC").is_none() {
+        std::env::set_var("PROTOC", protoc_bin_vendored::protoc_bin_path().unwrap());
+    }
+
+    tonic_build::configure().compile_protos(&["proto/tenzi_sim.proto"], &["proto"]).unwrap();
+}
\ No newline at end of file
@@ -0,0 +1,148 @@
+#ifndef TENZI_SIM_H
+#define TENZI_SIM_H
+
+/* Generated by cbindgen from `src/ffi.rs`; do not edit by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * The return code for success.
+ */
+#define TENZI_OK 0
+
+/**
+ * The return code for failure; see [`tenzi_last_error`].
+ */
+#define TENZI_ERROR -1
+
+/**
+ * An opaque simulation configuration, created with [`tenzi_config_new`] and freed with [`tenzi_config_free`].
+ */
+typedef struct TenziConfig TenziConfig;
+
+/**
+ * The statistics of a strategy, as a plain struct.
+ */
+typedef struct TenziSummary {
+  uint64_t num_simulations;
+  double average_rolls;
+  double std_dev_rolls;
+  double average_steps;
+  double std_dev_steps;
+  uint64_t duration_micros;
+} TenziSummary;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Returns a new configuration with the defaults: 10 6-sided die, the naive strategy, and 10,000 simulations.
+ */
+TenziConfig *tenzi_config_new(void);
+
+/**
+ * Frees a configuration.
+ *
+ * # Safety
+ *
+ * The configuration must have come from [`tenzi_config_new`] (or be null), and must not be used afterwards.
+ */
+void tenzi_config_free(TenziConfig *config);
+
+/**
+ * Sets the number of sides on each die.
+ *
+ * # Safety
+ *
+ * The configuration must be valid.
+ */
+int32_t tenzi_config_set_sides(TenziConfig *config, uint64_t num_sides);
+
+/**
+ * Sets the number of dice.
+ *
+ * # Safety
+ *
+ * The configuration must be valid.
+ */
+int32_t tenzi_config_set_dice(TenziConfig *config, uint64_t num_dice);
+
+/**
+ * Sets the number of simulations.
+ *
+ * # Safety
+ *
+ * The configuration must be valid.
+ */
+int32_t tenzi_config_set_simulations(TenziConfig *config, uint64_t num_simulations);
+
+/**
+ * Sets the number of threads to run the simulations on.
+ *
+ * # Safety
+ *
+ * The configuration must be valid.
+ */
+int32_t tenzi_config_set_threads(TenziConfig *config, uintptr_t num_threads);
+
+/**
+ * Sets the strategy spec (e.g., "merge").
+ *
+ * # Safety
+ *
+ * The configuration must be valid, and the strategy must be a nul-terminated string.
+ */
+int32_t tenzi_config_set_strategy(TenziConfig *config, const char *strategy);
+
+/**
+ * Sets the bucket state (i.e., the number of dice already kept for each face) that every game starts from.
+ *
+ * # Safety
+ *
+ * The configuration must be valid, and the state must point to `len` numbers.
+ */
+int32_t tenzi_config_set_initial_state(TenziConfig *config, const uint64_t *state, uintptr_t len);
+
+/**
+ * Runs the simulations, and writes the summary to `out`.
+ *
+ * # Safety
+ *
+ * The configuration must be valid, and `out` must point to a writable summary.
+ */
+int32_t tenzi_run(const TenziConfig *config, TenziSummary *out);
+
+/**
+ * Runs the simulations, and returns the full results as a JSON string, or null on failure.
+ *
+ * # Safety
+ *
+ * The configuration must be valid, and the string must be freed with [`tenzi_string_free`].
+ */
+char *tenzi_run_json(const TenziConfig *config);
+
+/**
+ * Frees a string returned by this library.
+ *
+ * # Safety
+ *
+ * The string must have come from this library (or be null), and must not be used afterwards.
+ */
+void tenzi_string_free(char *string);
+
+/**
+ * Returns the message of the last error on the calling thread, or null if there has not been one.
+ *
+ * The message is owned by the library, and is valid until the next failing call on the same thread.
+ */
+const char *tenzi_last_error(void);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* TENZI_SIM_H */
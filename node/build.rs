@@ -0,0 +1,4 @@
+fn main() {
+    // Node provides the N-API symbols at runtime, which needs platform-specific link arguments.
+    napi_build::setup();
+}
\ No newline at end of file
@@ -0,0 +1,119 @@
+//! Native Node.js bindings for the simulator via napi-rs.
+//!
+//! Build with `cargo build --release -p tenzi_sim_node`, and rename the library to `tenzi_sim.node` (or build with
+//! the `napi` CLI, which does the same).  The simulations run on libuv's worker pool,
+//! so they never block the event loop.
+
+// `Num` is switchable via features, so casts that are no-ops in one configuration are required in another.
+#![allow(clippy::unnecessary_cast)]
+
+use napi::{bindgen_prelude::{AbortSignal, AsyncTask, Float64Array}, Env, Task};
+use napi_derive::napi;
+
+use tenzi_sim::{error::{Result, TenziError}, types::Num, RunResults, SimulationConfig, StrategySummary};
+
+/// The configuration of a monte carlo run, where every field is optional (e.g., `{ strategy: "merge" }`).
+#[napi(object)]
+#[derive(Clone, Debug, Default)]
+pub struct NodeConfig {
+    pub num_sides: Option<u32>,
+    pub num_dice: Option<u32>,
+    pub strategy: Option<String>,
+    pub initial_state: Option<Vec<u32>>,
+    pub num_simulations: Option<u32>,
+    pub num_threads: Option<u32>,
+    pub histogram: Option<bool>,
+}
+
+/// The statistics of the number of rolls and steps it took a strategy to achieve a "tenzi".
+///
+/// The histogram is a typed array, so it can be transferred to a worker without being copied.
+#[napi(object)]
+pub struct NodeSummary {
+    pub strategy: String,
+    pub num_simulations: f64,
+    pub average_rolls: f64,
+    pub std_dev_rolls: f64,
+    pub average_steps: f64,
+    pub std_dev_steps: f64,
+    pub duration_ms: f64,
+    pub rolls_histogram: Option<Float64Array>,
+}
+
+impl From<&StrategySummary> for NodeSummary {
+    fn from(summary: &StrategySummary) -> Self {
+        Self {
+            strategy: summary.strategy().to_string(),
+            num_simulations: summary.num_simulations() as f64,
+            average_rolls: summary.average_rolls() as f64,
+            std_dev_rolls: summary.std_dev_rolls() as f64,
+            average_steps: summary.average_steps() as f64,
+            std_dev_steps: summary.std_dev_steps() as f64,
+            duration_ms: summary.duration().as_secs_f64() * 1_000.0,
+            rolls_histogram: summary.rolls_histogram().map(|h| Float64Array::new(h.iter().map(|&count| count as f64).collect())),
+        }
+    }
+}
+
+/// A monte carlo run on libuv's worker pool.
+pub struct MonteCarloTask {
+    config: SimulationConfig,
+}
+
+impl Task for MonteCarloTask {
+    type Output = RunResults;
+    type JsValue = NodeSummary;
+
+    fn compute(&mut self) -> napi::Result<Self::Output> {
+        self.config.run().map_err(to_napi)
+    }
+
+    fn resolve(&mut self, _env: Env, output: Self::Output) -> napi::Result<Self::JsValue> {
+        Ok(NodeSummary::from(&output.summaries()[0]))
+    }
+}
+
+/// Runs the monte carlo simulation, and resolves with the summary.
+///
+/// Aborting the signal rejects the promise if the run has not started yet.
+#[napi(ts_return_type = "Promise<NodeSummary>")]
+pub fn run_monte_carlo(config: Option<NodeConfig>, signal: Option<AbortSignal>) -> napi::Result<AsyncTask<MonteCarloTask>> {
+    let config = build(config.unwrap_or_default()).map_err(to_napi)?;
+
+    Ok(AsyncTask::with_optional_signal(MonteCarloTask { config }, signal))
+}
+
+/// Validates a config from JS.
+fn build(config: NodeConfig) -> Result<SimulationConfig> {
+    let mut builder = SimulationConfig::builder();
+
+    if let Some(num_sides) = config.num_sides {
+        builder = builder.sides(num_sides as Num);
+    }
+
+    if let Some(num_dice) = config.num_dice {
+        builder = builder.dice(num_dice as Num);
+    }
+
+    if let Some(strategy) = config.strategy {
+        builder = builder.strategy(strategy);
+    }
+
+    if let Some(state) = config.initial_state {
+        builder = builder.initial_state(state.into_iter().map(|count| count as Num).collect());
+    }
+
+    if let Some(num_simulations) = config.num_simulations {
+        builder = builder.simulations(num_simulations as Num);
+    }
+
+    if let Some(num_threads) = config.num_threads {
+        builder = builder.threads(num_threads as usize);
+    }
+
+    builder.histogram(config.histogram.unwrap_or_default()).build()
+}
+
+fn to_napi(e: TenziError) -> napi::Error {
+    napi::Error::from_reason(e.to_string())
+}
\ No newline at end of file
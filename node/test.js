@@ -0,0 +1,13 @@
+// A smoke test for the built bindings: `cp target/debug/libtenzi_sim_node.so node/tenzi_sim.node && node node/test.js`.
+
+const assert = require("node:assert");
+const tenzi = require("./tenzi_sim.node");
+
+(async () => {
+    const summary = await tenzi.runMonteCarlo({ strategy: "merge", numSimulations: 100, histogram: true });
+
+    assert.strictEqual(summary.strategy, "merge");
+    assert.strictEqual(summary.numSimulations, 100);
+    assert.ok(summary.rollsHistogram instanceof Float64Array);
+    assert.throws(() => tenzi.runMonteCarlo({ numSides: 0 }), /at least one side/);
+})();
\ No newline at end of file
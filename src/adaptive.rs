@@ -0,0 +1,177 @@
+//! Comparing strategies with an adaptive budget, which spends its games on the strategies whose ranking is still
+//! uncertain rather than the same number on every strategy.
+//!
+//! The comparison is a race of confidence intervals, in the spirit of successive halving: every strategy plays a first
+//! round of games, and after each round, a strategy whose interval of the average number of rolls overlaps no other
+//! strategy's is settled (its rank among the others is known at the confidence level), and stops playing.  The rest
+//! play a round twice as long as the last, until every strategy is settled or has played its cap.  Since clear winners
+//! and losers settle after a round or two, the games go to the close calls, and a confident ranking takes a fraction of
+//! the games that an equal budget would.
+//!
+//! A seeded comparison plays each strategy on the same games (i.e., the game at an index rolls the same dice for every
+//! strategy), which correlates their errors and settles them sooner.
+
+use crate::{cancel::CancelToken, calibrate, error::{Result, TenziError}, metrics::{MetricSink, Moments}, progress::ProgressHook, results::{RunParameters, RunResults}, types::{Float, Num}, SimulationConfig};
+
+/// The budget of an adaptive comparison.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct AdaptiveOptions {
+    /// The number of games that each strategy plays in the first round.
+    pub initial_simulations: Num,
+    /// The most games that any strategy plays.
+    pub max_simulations: Num,
+    /// The confidence level of the intervals (e.g., `0.99`).
+    pub confidence: Float,
+}
+
+impl Default for AdaptiveOptions {
+    fn default() -> Self {
+        Self { initial_simulations: 1_000, max_simulations: 1_000_000, confidence: 0.99 }
+    }
+}
+
+/// The results of an adaptive comparison, along with which strategies were settled.
+#[derive(Clone, Debug)]
+pub struct AdaptiveComparison {
+    results: RunResults,
+    settled: Vec<bool>,
+    num_rounds: usize,
+    z: f64,
+}
+
+impl AdaptiveComparison {
+    /// Returns the results, with a summary per strategy (in the order they were given), each with the number of games
+    /// that the strategy played.  The parameters carry the most games that any strategy played.
+    pub fn results(&self) -> &RunResults {
+        &self.results
+    }
+
+    /// Returns the results.
+    pub fn into_results(self) -> RunResults {
+        self.results
+    }
+
+    /// Returns whether the rank of the strategy at the given index is known at the confidence level (rather than
+    /// having run out of games).
+    pub fn is_settled(&self, index: usize) -> bool {
+        self.settled[index]
+    }
+
+    /// Returns the half-width of the interval of the average number of rolls of the strategy at the given index, at the
+    /// confidence level.
+    pub fn margin(&self, index: usize) -> Float {
+        (self.z * self.results.summaries()[index].std_err_rolls() as f64) as Float
+    }
+
+    /// Returns the number of rounds that were played.
+    pub fn num_rounds(&self) -> usize {
+        self.num_rounds
+    }
+
+    /// Returns the total number of games that were played, over every strategy.
+    pub fn num_simulations(&self) -> Num {
+        self.results.summaries().iter().map(|summary| summary.num_simulations()).sum()
+    }
+
+    /// Returns the indices of the strategies, from the fewest average rolls to the most.
+    pub fn ranking(&self) -> Vec<usize> {
+        let summaries = self.results.summaries();
+        let mut ranking = (0..summaries.len()).collect::<Vec<_>>();
+
+        ranking.sort_by(|&a, &b| summaries[a].average_rolls().total_cmp(&summaries[b].average_rolls()));
+        ranking
+    }
+}
+
+/// Compares the configurations (e.g., the same game with each strategy) with an adaptive budget (see the
+/// [module](self) docs).
+///
+/// Fails with [`TenziError::InvalidConfig`] if there are no configurations, if the options are out of range, or if a
+/// configuration is not played on the CPU, and with [`TenziError::Cancelled`] if the token is cancelled first.
+pub fn compare(configs: &[SimulationConfig], options: AdaptiveOptions, cancel: &CancelToken) -> Result<AdaptiveComparison> {
+    let AdaptiveOptions { initial_simulations, max_simulations, confidence } = options;
+
+    if configs.is_empty() || initial_simulations < 2 || max_simulations < initial_simulations || !(confidence > 0.0 && confidence < 1.0) {
+        return Err(TenziError::InvalidConfig("an adaptive comparison needs a strategy, at least two games a round up to its cap, and a confidence level between 0 and 1".to_string()));
+    }
+
+    let z = calibrate::z_score(confidence as f64);
+    let mut moments = configs.iter().map(|config| Moments::new(config.histogram())).collect::<Vec<_>>();
+    let mut durations = vec![std::time::Duration::ZERO; configs.len()];
+    let mut settled = vec![false; configs.len()];
+    let mut active = vec![true; configs.len()];
+    let mut round = initial_simulations;
+    let mut num_rounds = 0;
+
+    while active.contains(&true) {
+        for (index, config) in configs.iter().enumerate().filter(|(index, _)| active[*index]) {
+            // Each round picks up at the next game, so a seeded strategy plays the same games as it would in one run.
+
+            let first = moments[index].num_games();
+            let count = round.min(max_simulations - first);
+            let (results, games) = config.run_range(first, count, Moments::new(config.histogram()), cancel, ProgressHook::none())?;
+
+            if games.num_games() != count {
+                return Err(TenziError::Cancelled);
+            }
+
+            moments[index].merge(games);
+            durations[index] += results.duration();
+        }
+
+        num_rounds += 1;
+        round = round.saturating_mul(2);
+
+        let intervals = moments.iter().map(|moments| {
+            let margin = z * moments.std_dev_rolls() as f64 / (moments.num_games() as f64).sqrt();
+            (moments.average_rolls() as f64 - margin, moments.average_rolls() as f64 + margin)
+        }).collect::<Vec<_>>();
+
+        for (index, &(low, high)) in intervals.iter().enumerate() {
+            settled[index] = intervals.iter().enumerate().all(|(other, &(other_low, other_high))| other == index || high < other_low || other_high < low);
+            active[index] = !settled[index] && moments[index].num_games() < max_simulations;
+        }
+    }
+
+    let most = moments.iter().map(Moments::num_games).max().unwrap();
+    let parameters = RunParameters { num_simulations: most, ..configs[0].parameters() };
+    let summaries = moments.into_iter().zip(configs).zip(durations).map(|((moments, config), duration)| moments.summarize(config.strategy(), duration)).collect();
+
+    Ok(AdaptiveComparison { results: RunResults::new(parameters, summaries), settled, num_rounds, z })
+}
+
+// Tests.
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_adaptive_compare() {
+        let configs = ["naive", "divide", "merge"].map(|strategy| SimulationConfig::builder().strategy(strategy).seed(42).build().unwrap());
+        let options = AdaptiveOptions { initial_simulations: 1_000, max_simulations: 16_000, confidence: 0.95 };
+        let comparison = compare(&configs, options, &CancelToken::new()).unwrap();
+        let summaries = comparison.results().summaries();
+
+        // The naive strategy is far behind the others, so it settles in the first round, while the close call goes on.
+
+        assert_eq!(summaries[0].num_simulations(), 1_000);
+        assert!(comparison.is_settled(0));
+        assert_eq!(*comparison.ranking().last().unwrap(), 0);
+        assert!(summaries[1].num_simulations() > 1_000);
+        assert!(comparison.num_simulations() < 3 * 16_000);
+        assert_eq!(comparison.results().parameters().num_simulations, summaries.iter().map(|summary| summary.num_simulations()).max().unwrap());
+
+        // A seeded strategy plays the same games over its rounds as it would in one run.
+
+        let games = summaries[1].num_simulations();
+        let single = SimulationConfig::builder().strategy("divide").seed(42).simulations(games).build().unwrap().run().unwrap();
+
+        assert_eq!(summaries[1].average_rolls(), single.summaries()[0].average_rolls());
+        assert!(matches!(compare(&[], options, &CancelToken::new()), Err(TenziError::InvalidConfig(_))));
+        assert!(matches!(compare(&configs, AdaptiveOptions { confidence: 1.0, ..options }, &CancelToken::new()), Err(TenziError::InvalidConfig(_))));
+    }
+}
\ No newline at end of file
@@ -0,0 +1,205 @@
+//! Auditing the decisions of a strategy: how often each class of decision comes up over many games, which is what
+//! explains why the strategies differ (rather than only by how much).
+//!
+//! The audit plays the games itself, one step at a time (see
+//! [`Strategy::step_with`](crate::simulation::Strategy::step_with)), so that it sees the dice that were rolled as well
+//! as the dice that were kept, and classifies each step by the roll and what the policy made of it.  Since it only
+//! looks at the buckets, every strategy can be audited (including registered ones), and the classes mean the most for
+//! the strategy whose choice they capture:
+//!
+//! * [`kept_faces`](DecisionAudit::kept_faces) tells how often divide keeps two groups, rather than collapsing to one.
+//! * [`kept_non_mode`](DecisionAudit::kept_non_mode) tells how often naive's cached mode (i.e., the face it chose on
+//!   the first roll) was not a mode of the roll.
+//! * [`tied_rolls`](DecisionAudit::tied_rolls) tells how often merge hits the fallback of its anti-modes, where every
+//!   face with dice is tied, and it re-rolls the first.
+//!
+//! A seeded audit plays exactly the games of a seeded run of the same configuration.
+
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
+use crate::{types::{Float, Num}, SimulationConfig};
+
+/// The number of times each class of decision came up over the games of a strategy.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DecisionAudit {
+    /// The name of the strategy.
+    pub strategy: String,
+    pub num_games: u64,
+    pub num_steps: u64,
+    /// The steps whose roll showed a single face (i.e., rolled a "tenzi"), so there was nothing to decide.
+    pub single_face: u64,
+    /// The steps that rolled only the last die, with every other die kept on one face, so the only sensible decision
+    /// is to keep it if it matches (which the built-in strategies make without asking their policy).
+    pub waits: u64,
+    /// The rest of the steps, where the policy decided which dice to keep.
+    pub decisions: u64,
+    /// The decisions by the number of faces whose dice were kept (i.e., index `k` is the number of decisions that kept
+    /// `k` faces).
+    pub kept_faces: Vec<u64>,
+    /// The decisions where the face with the most kept dice was not a mode of the roll.
+    pub kept_non_mode: u64,
+    /// The decisions whose roll had every face with dice tied.
+    pub tied_rolls: u64,
+}
+
+impl DecisionAudit {
+    /// Returns an empty audit of the strategy.
+    fn new(strategy: &str) -> Self {
+        Self { strategy: strategy.to_string(), ..Self::default() }
+    }
+
+    /// Returns the share of the decisions that the given count is of (e.g., `audit.share(audit.tied_rolls)`).
+    pub fn share(&self, count: u64) -> Float {
+        count as Float / self.decisions.max(1) as Float
+    }
+
+    /// Classifies a step from the buckets before the roll (i.e., the kept dice), after the roll, and after the keep.
+    fn record(&mut self, before: &[Num], rolled: &[Num], after: &[Num], num_dice: Num) {
+        self.num_steps += 1;
+
+        let faces = rolled.iter().filter(|&&count| count != 0).count();
+
+        if faces <= 1 {
+            self.single_face += 1;
+            return;
+        }
+
+        if num_dice > 2 && before.contains(&(num_dice - 1)) {
+            self.waits += 1;
+            return;
+        }
+
+        self.decisions += 1;
+
+        // Count the faces that were kept, and whether the face with the most of them was a mode of the roll.
+
+        let kept = after.iter().filter(|&&count| count != 0).count();
+
+        if self.kept_faces.len() <= kept {
+            self.kept_faces.resize(kept + 1, 0);
+        }
+
+        self.kept_faces[kept] += 1;
+
+        let mode = rolled.iter().copied().max().unwrap_or(0);
+        let top = (0..after.len()).filter(|&k| after[k] != 0).max_by_key(|&k| after[k]);
+
+        if top.is_some_and(|k| rolled[k] < mode) {
+            self.kept_non_mode += 1;
+        }
+
+        if rolled.iter().all(|&count| count == 0 || count == mode) {
+            self.tied_rolls += 1;
+        }
+    }
+
+    /// Adds the counts of another audit of the same strategy.
+    fn merge(mut self, other: Self) -> Self {
+        self.num_games += other.num_games;
+        self.num_steps += other.num_steps;
+        self.single_face += other.single_face;
+        self.waits += other.waits;
+        self.decisions += other.decisions;
+        self.kept_non_mode += other.kept_non_mode;
+        self.tied_rolls += other.tied_rolls;
+
+        if self.kept_faces.len() < other.kept_faces.len() {
+            self.kept_faces.resize(other.kept_faces.len(), 0);
+        }
+
+        self.kept_faces.iter_mut().zip(other.kept_faces).for_each(|(count, other)| *count += other);
+        self
+    }
+}
+
+/// Audits the decisions of the configuration's strategy over its games (see the [module](self) docs), on the global
+/// thread pool.  An unseeded configuration is audited from a random seed.
+pub fn audit(config: &SimulationConfig) -> DecisionAudit {
+    let seed = config.seed().unwrap_or_else(::rand::random);
+    let template = config.simulation();
+
+    // Each worker audits its own games, on its own game and buffers, and the audits are merged at the end.
+
+    (0..config.num_simulations())
+        .into_par_iter()
+        .fold(|| (template.clone(), Vec::new(), Vec::new(), DecisionAudit::new(config.strategy())), |(mut simulation, mut before, mut rolled, mut audit), index| {
+            let game = simulation.as_strategy_mut();
+            let mut dice = config.game_dice(seed, index as u64);
+
+            game.reset();
+            audit.num_games += 1;
+
+            while !game.done() {
+                before.clear();
+                before.extend_from_slice(game.buckets());
+                rolled.clone_from(&before);
+
+                game.step_with(&mut |num_sides| {
+                    let face = dice.roll(num_sides);
+                    rolled[face as usize - 1] += 1;
+                    face
+                });
+
+                audit.record(&before, &rolled, game.buckets(), game.num_dice());
+            }
+
+            (simulation, before, rolled, audit)
+        })
+        .map(|(_, _, _, audit)| audit)
+        .reduce(|| DecisionAudit::new(config.strategy()), DecisionAudit::merge)
+}
+
+// Tests.
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_record() {
+        let mut audit = DecisionAudit::new("test");
+
+        // A stale mode, two groups kept from a tie, a wait for the last die, and a "tenzi".
+
+        audit.record(&[0, 2, 0, 0], &[3, 2, 0, 1], &[0, 2, 0, 0], 6);
+        audit.record(&[0, 0, 0, 0], &[2, 2, 0, 2], &[2, 2, 0, 0], 6);
+        audit.record(&[0, 5, 0, 0], &[0, 5, 1, 0], &[0, 5, 0, 0], 6);
+        audit.record(&[0, 5, 0, 0], &[0, 6, 0, 0], &[0, 6, 0, 0], 6);
+
+        assert_eq!((audit.num_steps, audit.decisions, audit.waits, audit.single_face), (4, 2, 1, 1));
+        assert_eq!(audit.kept_faces, vec![0, 1, 1]);
+        assert_eq!((audit.kept_non_mode, audit.tied_rolls), (1, 1));
+        assert_eq!(audit.share(audit.tied_rolls), 0.5);
+    }
+
+    #[test]
+    fn test_audit() {
+        let audit_of = |strategy: &str| {
+            let config = SimulationConfig::builder().strategy(strategy).simulations(2_000).seed(42).build().unwrap();
+            (audit(&config), config.run().unwrap())
+        };
+
+        // A seeded audit plays the games of the run, step for step.
+
+        for strategy in ["naive", "divide", "merge"] {
+            let (audit, results) = audit_of(strategy);
+            let average_steps = audit.num_steps as f64 / audit.num_games as f64;
+
+            assert_eq!(audit.num_games, 2_000);
+            assert_eq!(audit.num_steps, audit.single_face + audit.waits + audit.decisions);
+            assert!((average_steps - results.summaries()[0].average_steps() as f64).abs() < 1e-3);
+        }
+
+        // Only divide keeps two groups, only naive keeps a face that is not a mode, and merge never keeps nothing.
+
+        let (naive, _) = audit_of("naive");
+        let (divide, _) = audit_of("divide");
+        let (merge, _) = audit_of("merge");
+
+        assert!(naive.kept_non_mode > 0 && divide.kept_non_mode == 0);
+        assert!(naive.kept_faces.len() == 2 && divide.kept_faces[2] > 0);
+        assert!(merge.tied_rolls > 0 && merge.kept_faces[0] == 0);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,443 @@
+//! A batched engine that plays [`LANES`] games of a built-in strategy in lockstep, with the buckets of every game
+//! stored face by face (i.e., as a structure of arrays), so that finding the modes (and zeroing out the buckets that are
+//! not kept) is one pass over the faces that is vectorized across the games.
+//!
+//! This is a different execution model from the one game per worker of [`monte_carlo`](crate::monte_carlo): there is
+//! no [`KeepPolicy`](crate::simulation::KeepPolicy) to call, so it only plays the built-in strategies (with the same
+//! rules as their policies), and it is selected per run with [`Backend::Batched`](crate::Backend::Batched).
+
+use std::{sync::Mutex, time::Instant};
+
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
+use crate::{cancel::CancelToken, error::{Result, TenziError}, metrics::{GameOutcome, MetricSink, Moments}, monte_carlo::Tally, progress::{Progress, ProgressHook}, rand::roll, results::StrategySummary, simulation::StrategyKind, types::Num};
+
+/// The number of games that are played in lockstep.
+pub const LANES: usize = 64;
+
+/// The number of games in each block of work that is handed to a worker.
+const BLOCK_SIZE: Num = 4_096;
+
+/// [`LANES`] games of a built-in strategy, played in lockstep.
+///
+/// A lane that finishes its game is refilled with the next game right away, so every lane stays busy until there are
+/// no more games to play.
+#[derive(Clone, Debug)]
+pub struct BatchedGames {
+    kind: StrategyKind,
+    num_sides: usize,
+    num_dice: Num,
+    initial_state: Vec<Num>,
+
+    /// The buckets of every game, face by face: the count of face `k` in lane `lane` is at `k * LANES + lane`.
+    buckets: Vec<Num>,
+    num_to_roll: [Num; LANES],
+    num_rolls: [Num; LANES],
+    num_steps: [Num; LANES],
+    active: [bool; LANES],
+    /// The face that the naive strategy keeps, once it is chosen.
+    modes: [Option<usize>; LANES],
+}
+
+impl BatchedGames {
+    /// Returns the lanes for the given kind of strategy, with `num_sides`-sided die.
+    pub fn new(kind: StrategyKind, num_sides: Num, num_dice: Num) -> Self {
+        Self {
+            kind,
+            num_sides: num_sides as usize,
+            num_dice,
+            initial_state: vec![0; num_sides as usize],
+
+            buckets: vec![0; num_sides as usize * LANES],
+            num_to_roll: [0; LANES],
+            num_rolls: [0; LANES],
+            num_steps: [0; LANES],
+            active: [false; LANES],
+            modes: [None; LANES],
+        }
+    }
+
+    /// Starts every game from the given bucket state (i.e., the dice that are already kept) rather than from scratch.
+    pub fn with_initial_state(mut self, state: &[Num]) -> Self {
+        self.initial_state = state.to_vec();
+        self
+    }
+
+    /// Plays the given number of games, and hands each of their outcomes to the callback.
+    pub fn play(&mut self, num_games: Num, mut record: impl FnMut(&GameOutcome)) {
+        let mut num_started = 0;
+
+        loop {
+            // Refill the idle lanes, and stop once they are all idle.
+
+            for lane in 0..LANES {
+                while !self.active[lane] && num_started < num_games {
+                    num_started += 1;
+
+                    if let Some(outcome) = self.start(lane) {
+                        record(&outcome);
+                    }
+                }
+            }
+
+            if !self.active.contains(&true) {
+                break;
+            }
+
+            self.roll();
+            self.keep();
+
+            // Record the games that are done, which frees their lanes.
+
+            for lane in 0..LANES {
+                if self.active[lane] && self.num_to_roll[lane] == 0 {
+                    self.active[lane] = false;
+                    record(&GameOutcome { num_rolls: self.num_rolls[lane], num_steps: self.num_steps[lane] });
+                }
+            }
+        }
+    }
+
+    /// Starts a game in the given lane, or returns its outcome if it is already done (i.e., from its initial state).
+    fn start(&mut self, lane: usize) -> Option<GameOutcome> {
+        for (k, &count) in self.initial_state.iter().enumerate() {
+            self.buckets[k * LANES + lane] = count;
+        }
+
+        self.num_to_roll[lane] = self.num_dice - self.initial_state.iter().sum::<Num>();
+        self.num_rolls[lane] = 0;
+        self.num_steps[lane] = 0;
+        self.modes[lane] = None;
+
+        match self.initial_state.contains(&self.num_dice) {
+            true => Some(GameOutcome { num_rolls: 0, num_steps: 0 }),
+            false => {
+                self.active[lane] = true;
+                None
+            }
+        }
+    }
+
+    /// Rolls the dice that every active game has left to roll.
+    fn roll(&mut self) {
+        for lane in 0..LANES {
+            if !self.active[lane] {
+                continue;
+            }
+
+            for _ in 0..self.num_to_roll[lane] {
+                let face = roll(self.num_sides as Num) as usize - 1;
+                self.buckets[face * LANES + lane] += 1;
+            }
+
+            self.num_rolls[lane] += self.num_to_roll[lane];
+            self.num_steps[lane] += 1;
+        }
+    }
+
+    /// Zeroes out the buckets that each game does not keep, and updates the number of dice each has left to roll (which
+    /// is zero once a game is done).
+    fn keep(&mut self) {
+        match self.kind {
+            StrategyKind::Naive => self.keep_naive(),
+            StrategyKind::Divide => self.keep_divide(),
+            StrategyKind::Merge => self.keep_merge(),
+        }
+
+        // Each game rolls the dice that are not kept, so a game is done once every die is kept (which, by the rules of
+        // the built-in strategies, is only once one face has every die).
+
+        let mut kept = [0; LANES];
+
+        for faces in self.buckets.chunks_exact(LANES) {
+            for lane in 0..LANES {
+                kept[lane] += faces[lane];
+            }
+        }
+
+        for (num_to_roll, kept) in self.num_to_roll.iter_mut().zip(kept) {
+            *num_to_roll = self.num_dice - kept;
+        }
+    }
+
+    /// Keeps the mode of each game's first roll (the last face with the most dice, like
+    /// [`histogram::mode_from_counts`](crate::histogram::mode_from_counts)).
+    fn keep_naive(&mut self) {
+        if self.modes.iter().zip(&self.active).any(|(mode, &active)| active && mode.is_none()) {
+            let mut max = [0; LANES];
+            let mut modes = [0; LANES];
+
+            for (k, faces) in self.buckets.chunks_exact(LANES).enumerate() {
+                for lane in 0..LANES {
+                    if faces[lane] >= max[lane] {
+                        max[lane] = faces[lane];
+                        modes[lane] = k;
+                    }
+                }
+            }
+
+            for (mode, found) in self.modes.iter_mut().zip(modes) {
+                mode.get_or_insert(found);
+            }
+        }
+
+        let modes = self.modes.map(|mode| mode.unwrap_or(usize::MAX));
+        self.retain(|k, lane, _| k == modes[lane]);
+    }
+
+    /// Keeps the top two modes of each game, or only the first once it has at least half of the dice (like the divide
+    /// policy).
+    fn keep_divide(&mut self) {
+        let (first, second) = self.top_two();
+        let half = self.num_dice / 2;
+
+        let second = core::array::from_fn::<_, LANES, _>(|lane| match self.buckets[first[lane] * LANES + lane] >= half {
+            true => first[lane],
+            false => second[lane],
+        });
+
+        self.retain(|k, lane, _| k == first[lane] || k == second[lane]);
+    }
+
+    /// Zeroes out the faces of each game with the fewest dice (like
+    /// [`histogram::anti_modes`](crate::histogram::anti_modes)).
+    fn keep_merge(&mut self) {
+        // One pass for the statistics of every game.
+
+        let mut max = [0; LANES];
+        let mut max_occurrences = [0; LANES];
+        let mut min_nonzero = [Num::MAX; LANES];
+        let mut nonzero = [0; LANES];
+        let mut first_nonzero = [usize::MAX; LANES];
+
+        for (k, faces) in self.buckets.chunks_exact(LANES).enumerate() {
+            for lane in 0..LANES {
+                let count = faces[lane];
+
+                if count > max[lane] {
+                    max[lane] = count;
+                    max_occurrences[lane] = 1;
+                } else if count == max[lane] {
+                    max_occurrences[lane] += 1;
+                }
+
+                if count > 0 {
+                    nonzero[lane] += 1;
+                    min_nonzero[lane] = min_nonzero[lane].min(count);
+                    first_nonzero[lane] = first_nonzero[lane].min(k);
+                }
+            }
+        }
+
+        // With a single face, there is nothing to re-roll, and if every face is tied, the first is re-rolled.
+
+        self.retain(|k, lane, count| match nonzero[lane] {
+            0 | 1 => true,
+            n if max_occurrences[lane] == n => k != first_nonzero[lane],
+            _ => count != min_nonzero[lane],
+        });
+    }
+
+    /// Returns the faces with the largest and second largest counts of each game (like
+    /// [`histogram::top_two_modes_from_counts`](crate::histogram::top_two_modes_from_counts)).
+    fn top_two(&self) -> ([usize; LANES], [usize; LANES]) {
+        let mut first_index = [0; LANES];
+        let mut second_index = [0; LANES];
+        let mut first = [0; LANES];
+        let mut second = [0; LANES];
+
+        first.copy_from_slice(&self.buckets[..LANES]);
+
+        for (k, faces) in self.buckets.chunks_exact(LANES).enumerate().skip(1) {
+            for lane in 0..LANES {
+                let count = faces[lane];
+
+                if count > first[lane] {
+                    second[lane] = first[lane];
+                    second_index[lane] = first_index[lane];
+                    first[lane] = count;
+                    first_index[lane] = k;
+                } else if count > second[lane] {
+                    second[lane] = count;
+                    second_index[lane] = k;
+                }
+            }
+        }
+
+        (first_index, second_index)
+    }
+
+    /// Zeroes out every bucket that the predicate, which is handed each face, lane, and count, rejects.
+    ///
+    /// Every count is stored (as either itself or zero), rather than branching on the predicate, so that each face's
+    /// lanes are written as a vector.
+    fn retain(&mut self, keep: impl Fn(usize, usize, Num) -> bool) {
+        for (k, faces) in self.buckets.chunks_exact_mut(LANES).enumerate() {
+            for (lane, count) in faces.iter_mut().enumerate() {
+                *count = if keep(k, lane, *count) { *count } else { 0 };
+            }
+        }
+    }
+}
+
+/// Runs an entire monte carlo simulation with the batched engine, recording every game into the given sink, like
+/// [`monte_carlo_with`](crate::monte_carlo_with).
+///
+/// Each worker plays its blocks of games with its own clone of the lanes.
+pub fn monte_carlo_batched<S: MetricSink>(games: &BatchedGames, num_simulations: Num, mut sink: S, cancel: &CancelToken, progress: ProgressHook<impl Fn(&Progress) + Send + Sync>) -> S {
+    let shared = Mutex::new(Tally::default());
+
+    let recorded = (0..num_simulations.div_ceil(BLOCK_SIZE))
+        .into_par_iter()
+        .fold(|| (games.clone(), sink.fork()), |(mut games, mut sink), block| {
+            if cancel.is_cancelled() {
+                return (games, sink);
+            }
+
+            let num_games = BLOCK_SIZE.min(num_simulations - block * BLOCK_SIZE);
+
+            let mut tally = Tally::default();
+            games.play(num_games, |outcome| {
+                sink.record(outcome);
+                tally = tally.add(outcome);
+            });
+
+            let (before, after) = {
+                let mut shared = shared.lock().unwrap();
+                let before = *shared;
+                *shared = shared.merge(tally);
+                (before, *shared)
+            };
+
+            if progress.is_due(before.completed, after.completed) {
+                progress.report(&after.progress(num_simulations));
+            }
+
+            (games, sink)
+        })
+        .map(|(_, sink)| sink)
+        .reduce(|| sink.fork(), |mut left, right| {
+            left.merge(right);
+            left
+        });
+
+    progress.report(&shared.into_inner().unwrap().progress(num_simulations));
+
+    sink.merge(recorded);
+
+    sink
+}
+
+/// Runs an entire monte carlo simulation with the batched engine, and summarizes the games that were completed before
+/// the token was cancelled (see [`monte_carlo::run`](crate::monte_carlo)), while also recording every game into the
+/// given sink.
+pub(crate) fn run<S: MetricSink>(games: &BatchedGames, num_simulations: Num, histogram: bool, sink: S, cancel: &CancelToken, progress: ProgressHook<impl Fn(&Progress) + Send + Sync>) -> Result<(StrategySummary, S)> {
+    let start = Instant::now();
+
+    let (moments, sink) = monte_carlo_batched(games, num_simulations, (Moments::new(histogram), sink), cancel, progress);
+
+    if moments.num_games() == 0 {
+        return Err(TenziError::Cancelled);
+    }
+
+    Ok((moments.summarize(games.kind.name(), start.elapsed()), sink))
+}
+
+// Tests.
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "nightly")]
+    use std::hint::black_box;
+
+    use super::*;
+    use crate::{monte_carlo, simulation::SimulationType, types::Float};
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_batched_matches_games() {
+        // The lanes play by the same rules as the games, so their averages agree.
+
+        for kind in StrategyKind::ALL {
+            let batched = run(&BatchedGames::new(kind, 6, 10), 20_000, false, (), &CancelToken::new(), ProgressHook::none()).unwrap().0;
+            let expected = monte_carlo(SimulationType::new(kind, 6, 10), 20_000);
+
+            assert_eq!(batched.strategy(), kind.name());
+            assert!((batched.average_rolls() - expected.average_rolls()).abs() / expected.average_rolls() < 0.05 as Float, "{kind}");
+            assert!((batched.average_steps() - expected.average_steps()).abs() / expected.average_steps() < 0.05 as Float, "{kind}");
+        }
+    }
+
+    #[test]
+    fn test_batched_plays_every_game() {
+        // Including a number of games that does not fill the lanes (or the blocks).
+
+        for num_games in [1, LANES as Num + 1, BLOCK_SIZE + 3] {
+            let moments = monte_carlo_batched(&BatchedGames::new(StrategyKind::Merge, 6, 10), num_games, Moments::new(true), &CancelToken::new(), ProgressHook::none());
+
+            assert_eq!(moments.num_games(), num_games);
+            assert_eq!(moments.rolls_histogram().unwrap().iter().sum::<Num>(), num_games);
+        }
+    }
+
+    #[test]
+    fn test_batched_finished_state() {
+        let games = BatchedGames::new(StrategyKind::Naive, 6, 10).with_initial_state(&[0, 0, 10, 0, 0, 0]);
+        let output = run(&games, 100, false, (), &CancelToken::new(), ProgressHook::none()).unwrap().0;
+
+        assert_eq!(output.num_simulations(), 100);
+        assert_eq!(output.average_rolls(), 0.0);
+        assert_eq!(output.average_steps(), 0.0);
+    }
+
+    #[test]
+    fn test_batched_initial_state() {
+        // Nine dice are kept, so every game is a wait for the last die.
+
+        let games = BatchedGames::new(StrategyKind::Naive, 6, 10).with_initial_state(&[0, 9, 0, 0, 0, 0]);
+        let output = run(&games, 10_000, false, (), &CancelToken::new(), ProgressHook::none()).unwrap().0;
+
+        assert!((output.average_rolls() - 6.0).abs() < 0.5);
+        assert_eq!(output.average_rolls(), output.average_steps());
+    }
+
+    #[test]
+    fn test_batched_cancelled() {
+        let cancel = CancelToken::new();
+        cancel.cancel();
+
+        let result = run(&BatchedGames::new(StrategyKind::Naive, 6, 10), 100, false, (), &cancel, ProgressHook::none());
+
+        assert!(matches!(result, Err(TenziError::Cancelled)));
+    }
+
+    #[cfg(feature = "nightly")]
+    #[bench]
+    fn bench_batched_divide(b: &mut test::Bencher) {
+        let mut games = BatchedGames::new(StrategyKind::Divide, 6, 10);
+        let mut moments = Moments::new(false);
+
+        b.iter(|| games.play(black_box(1_000), |outcome| moments.record(outcome)));
+    }
+
+    // The baseline that the lanes are measured against: one game at a time.
+
+    #[cfg(feature = "nightly")]
+    #[bench]
+    fn bench_unbatched_divide(b: &mut test::Bencher) {
+        let mut simulation = SimulationType::new(StrategyKind::Divide, 6, 10);
+
+        b.iter(|| {
+            for _ in 0..black_box(1_000) {
+                let strategy = simulation.as_strategy_mut();
+                strategy.reset();
+
+                while !strategy.done() {
+                    strategy.step();
+                }
+
+                black_box(strategy.num_rolls());
+            }
+        });
+    }
+}
\ No newline at end of file
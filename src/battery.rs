@@ -0,0 +1,216 @@
+//! A small battery of statistical tests over a stream of rolls, for validating a dice source (e.g., a new generator)
+//! end to end, through the same sampling that the games roll with.
+//!
+//! The battery is far from a full suite like TestU01 (which tests the raw words of a generator), but it tests the
+//! faces, so it also catches a biased mapping from words to faces.  Each test reduces the stream to a statistic with a
+//! known distribution for a fair die, and fails if the statistic is less likely than the significance level:
+//!
+//! * The frequency test checks that every face comes up equally often.
+//! * The runs test checks that a face repeats on the next roll as often as it should (i.e., one in `num_sides`).
+//! * The serial correlation test checks that each roll is uncorrelated with the one before it.
+//! * The gap test checks that the gaps between the rolls of a 1 are geometrically distributed.
+
+use crate::{dice::DiceRng, error::{Result, TenziError}, types::{Float, Num}};
+
+/// The fewest rolls of each face that the battery needs, so that the distributions of the statistics are close to
+/// their approximations.
+const MIN_ROLLS_PER_FACE: usize = 10;
+
+/// The fewest expected gaps of any length that the gap test puts in a category of its own.
+const MIN_EXPECTED_GAPS: f64 = 5.0;
+
+/// The outcome of one of the tests of the battery.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct StatTest {
+    /// The name of the test.
+    pub name: &'static str,
+    /// The statistic that the stream was reduced to: a chi-squared statistic, or a z-score.
+    pub statistic: Float,
+    /// The number of degrees of freedom of a chi-squared statistic, or `None` for a z-score.
+    pub degrees_of_freedom: Option<Num>,
+    /// The probability of a statistic at least as extreme from a fair die.
+    pub p_value: Float,
+}
+
+impl StatTest {
+    /// Returns whether the test passed at the given significance level (e.g., `0.001`).
+    pub fn passed(&self, alpha: Float) -> bool {
+        self.p_value >= alpha
+    }
+}
+
+/// Rolls the given number of dice with the given number of sides from the source, one at a time.
+pub fn roll_stream(dice: &mut dyn DiceRng, num_sides: Num, num_rolls: usize) -> Vec<Num> {
+    (0..num_rolls).map(|_| dice.roll(num_sides)).collect()
+}
+
+/// Runs every test of the battery over the rolls of a die with the given number of sides.
+///
+/// Fails with [`TenziError::InvalidConfig`] if the die has fewer than two sides, or if there are too few rolls for the
+/// tests to be meaningful (i.e., ten times the square of the number of sides).
+pub fn run_battery(rolls: &[Num], num_sides: Num) -> Result<Vec<StatTest>> {
+    if num_sides < 2 {
+        return Err(TenziError::InvalidConfig("the battery needs a die with at least two sides".to_string()));
+    }
+
+    let min_rolls = MIN_ROLLS_PER_FACE * (num_sides as usize).pow(2);
+
+    if rolls.len() < min_rolls {
+        return Err(TenziError::InvalidConfig(format!("the battery needs at least {} rolls of a {}-sided die", min_rolls, num_sides)));
+    }
+
+    if let Some(face) = rolls.iter().find(|&&face| face == 0 || face > num_sides) {
+        return Err(TenziError::InvalidDice(format!("`{}` is not a face of a {}-sided die", face, num_sides)));
+    }
+
+    Ok(vec![frequency_test(rolls, num_sides), runs_test(rolls, num_sides), serial_correlation_test(rolls), gap_test(rolls, num_sides)])
+}
+
+/// Tests that every face comes up equally often, with a chi-squared test of the counts of the faces.
+pub fn frequency_test(rolls: &[Num], num_sides: Num) -> StatTest {
+    let mut counts = vec![0.0; num_sides as usize];
+
+    for &face in rolls {
+        counts[face as usize - 1] += 1.0;
+    }
+
+    let expected = rolls.len() as f64 / num_sides as f64;
+
+    chi_squared_test("frequency", &counts, &vec![expected; num_sides as usize])
+}
+
+/// Tests that a face repeats on the next roll one time in `num_sides`: the repeats are pairwise independent, so their
+/// number is close to normal, with the variance of a binomial.
+pub fn runs_test(rolls: &[Num], num_sides: Num) -> StatTest {
+    let num_pairs = (rolls.len() - 1) as f64;
+    let p = 1.0 / num_sides as f64;
+    let repeats = rolls.windows(2).filter(|pair| pair[0] == pair[1]).count() as f64;
+
+    z_test("runs", (repeats - num_pairs * p) / (num_pairs * p * (1.0 - p)).sqrt())
+}
+
+/// Tests that each roll is uncorrelated with the one before it: the lag-one autocorrelation of a fair stream is close
+/// to normal, with a variance of `1 / n`.
+pub fn serial_correlation_test(rolls: &[Num]) -> StatTest {
+    let n = rolls.len() as f64;
+    let mean = rolls.iter().map(|&face| face as f64).sum::<f64>() / n;
+    let variance = rolls.iter().map(|&face| (face as f64 - mean).powi(2)).sum::<f64>();
+    let covariance = rolls.windows(2).map(|pair| (pair[0] as f64 - mean) * (pair[1] as f64 - mean)).sum::<f64>();
+
+    // A constant stream has no variance, and is as correlated as a stream can be.
+
+    let correlation = if variance > 0.0 { covariance / variance } else { 1.0 };
+
+    z_test("serial correlation", correlation * n.sqrt())
+}
+
+/// Tests that the gaps between the rolls of a 1 (i.e., the number of other faces in between) are geometrically
+/// distributed, with a chi-squared test of the counts of each length, where the lengths that are too rare to count on
+/// their own are pooled into the last category.
+pub fn gap_test(rolls: &[Num], num_sides: Num) -> StatTest {
+    let p = 1.0 / num_sides as f64;
+    let ones = rolls.iter().enumerate().filter(|(_, &face)| face == 1).map(|(index, _)| index).collect::<Vec<_>>();
+    let num_gaps = ones.len().saturating_sub(1) as f64;
+
+    // The gaps of each length from zero are expected `num_gaps * p * (1 - p)^length` times, so every length short of
+    // the cutoff is expected at least a few times (as is the pooled tail, which is expected `num_gaps * (1 - p)^cutoff`
+    // times).
+
+    let cutoff = ((MIN_EXPECTED_GAPS / (num_gaps * p)).ln() / (1.0 - p).ln()).floor().max(1.0) as usize;
+    let mut counts = vec![0.0; cutoff + 1];
+
+    for pair in ones.windows(2) {
+        counts[(pair[1] - pair[0] - 1).min(cutoff)] += 1.0;
+    }
+
+    let mut expected = (0..cutoff).map(|length| num_gaps * p * (1.0 - p).powi(length as i32)).collect::<Vec<_>>();
+    expected.push(num_gaps * (1.0 - p).powi(cutoff as i32));
+
+    chi_squared_test("gap", &counts, &expected)
+}
+
+/// Returns the outcome of a chi-squared test of the observed counts against the expected ones (with one degree of
+/// freedom fewer than there are counts).
+fn chi_squared_test(name: &'static str, counts: &[f64], expected: &[f64]) -> StatTest {
+    let statistic = counts.iter().zip(expected).map(|(&count, &expected)| (count - expected).powi(2) / expected).sum::<f64>();
+    let degrees_of_freedom = counts.len() - 1;
+
+    StatTest { name, statistic: statistic as Float, degrees_of_freedom: Some(degrees_of_freedom as Num), p_value: chi_squared_p_value(statistic, degrees_of_freedom as f64) as Float }
+}
+
+/// Returns the outcome of a two-sided test of a z-score.
+fn z_test(name: &'static str, z: f64) -> StatTest {
+    StatTest { name, statistic: z as Float, degrees_of_freedom: None, p_value: erfc(z.abs() / std::f64::consts::SQRT_2) as Float }
+}
+
+/// Returns the probability that a chi-squared statistic with the given degrees of freedom is at least the given one,
+/// by the Wilson-Hilferty transform of the statistic to a standard normal one.
+fn chi_squared_p_value(statistic: f64, degrees_of_freedom: f64) -> f64 {
+    let scale = 2.0 / (9.0 * degrees_of_freedom);
+    let z = ((statistic / degrees_of_freedom).cbrt() - (1.0 - scale)) / scale.sqrt();
+
+    erfc(z / std::f64::consts::SQRT_2) / 2.0
+}
+
+/// Returns the complementary error function, by the approximation of Abramowitz and Stegun (7.1.26), which is within
+/// 1.5e-7.
+fn erfc(x: f64) -> f64 {
+    if x < 0.0 {
+        return 2.0 - erfc(-x);
+    }
+
+    let t = 1.0 / (1.0 + 0.3275911 * x);
+    let polynomial = t * (0.254829592 + t * (-0.284496736 + t * (1.421413741 + t * (-1.453152027 + t * 1.061405429))));
+
+    polynomial * (-x * x).exp()
+}
+
+// Tests.
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rand::{AliasDice, AliasTable, SeededDice};
+    use pretty_assertions::assert_eq;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_battery_passes() {
+        for num_sides in [2, 6, 20] {
+            let rolls = roll_stream(&mut SeededDice::new(42), num_sides, 100_000);
+            let battery = run_battery(&rolls, num_sides).unwrap();
+
+            assert_eq!(battery.iter().map(|test| test.name).collect::<Vec<_>>(), vec!["frequency", "runs", "serial correlation", "gap"]);
+            assert!(battery.iter().all(|test| test.passed(0.001)), "{:?}", battery);
+        }
+
+        assert!((erfc(1.0) - 0.157299).abs() < 1e-6);
+        assert!((chi_squared_p_value(11.07, 5.0) - 0.05).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_battery_fails() {
+        // A die that favors the 6 fails the frequency test.
+
+        let table = Arc::new(AliasTable::new(&[1.0, 1.0, 1.0, 1.0, 1.0, 1.1]).unwrap());
+        let rolls = roll_stream(&mut AliasDice::seeded(table, 42), 6, 100_000);
+
+        assert!(!frequency_test(&rolls, 6).passed(0.001));
+
+        // A die that counts up is perfectly even, but never repeats, rises more often than not, and its gaps are all the same.
+
+        let rolls = (0..1_000).map(|index| index % 6 + 1).collect::<Vec<_>>();
+        let battery = run_battery(&rolls, 6).unwrap();
+
+        assert_eq!(battery.iter().map(|test| test.passed(0.001)).collect::<Vec<_>>(), vec![true, false, false, false]);
+
+        // A die that sticks to its last face for a while is correlated.
+
+        let rolls = roll_stream(&mut SeededDice::new(42), 6, 10_000).iter().flat_map(|&face| [face; 3]).collect::<Vec<_>>();
+        assert!(!serial_correlation_test(&rolls).passed(0.001));
+
+        assert!(matches!(run_battery(&rolls[..100], 6), Err(TenziError::InvalidConfig(_))));
+        assert!(matches!(run_battery(&rolls, 1), Err(TenziError::InvalidConfig(_))));
+        assert!(matches!(run_battery(&[7; 1_000], 6), Err(TenziError::InvalidDice(_))));
+    }
+}
\ No newline at end of file
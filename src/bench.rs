@@ -0,0 +1,230 @@
+use std::{hint::black_box, sync::Arc, time::{Duration, Instant}};
+
+use colored::Colorize;
+use rand::{rngs::SmallRng, SeedableRng};
+
+use tenzi_sim::{batch::BatchedGames, dice::DiceRng, histogram, rand::{AliasDice, AliasTable, BufferedDice, ModuloDice, RngKind, SeededDice, ThreadDice}, simulation::{SimulationType, StrategyKind, FIXED_SIDES}, types::Num};
+
+/// The number of games that each strategy timing plays, which is a full set of the batched engine's lanes.
+const GAMES: Num = 64;
+
+/// The number of dice that each rng timing rolls.
+const ROLLS: Num = 1_000;
+
+/// The sizes (i.e., sides and dice) that the strategies are played at.
+const GAME_SIZES: [(Num, Num); 3] = [(6, 10), (20, 50), (100, 100)];
+
+/// The numbers of faces that the mode helpers are timed over.
+const COUNT_SIZES: [usize; 3] = [6, 100, 10_000];
+
+/// The numbers of sides that the dice are rolled with.
+const DIE_SIZES: [Num; 3] = [6, 20, 100];
+
+/// The groups of timings that the `bench` command can run.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum Group {
+    /// Every built-in strategy, with each kind of buckets, and batched.
+    Strategies,
+    /// The mode helpers that the strategies keep their dice with.
+    Modes,
+    /// The dice sources.
+    Rng,
+}
+
+/// Runs the timings of the given groups (or every group), each for about the given time, and prints a table per
+/// group.
+pub fn bench(groups: &[Group], budget: Duration) {
+    let all = groups.is_empty();
+
+    println!("{}", format!("Counters (and buckets) are {}-bit.", Num::BITS).dimmed());
+
+    if all || groups.contains(&Group::Strategies) {
+        strategies(budget);
+    }
+
+    if all || groups.contains(&Group::Modes) {
+        modes(budget);
+    }
+
+    if all || groups.contains(&Group::Rng) {
+        rng(budget);
+    }
+}
+
+/// Times a game of each strategy, at each size, with the usual buckets, the fixed-size buckets, and the batched
+/// engine.
+fn strategies(budget: Duration) {
+    let mut rows = Vec::new();
+
+    for kind in StrategyKind::ALL {
+        for (num_sides, num_dice) in GAME_SIZES {
+            let inline = per(GAMES, time(budget, || play(SimulationType::new(kind, num_sides, num_dice))));
+            let fixed = FIXED_SIDES.contains(&num_sides).then(|| per(GAMES, time(budget, || play(SimulationType::fast(kind, num_sides, num_dice)))));
+
+            let mut games = BatchedGames::new(kind, num_sides, num_dice);
+            let batched = per(GAMES, time(budget, || games.play(GAMES, |outcome| { black_box(outcome); })));
+
+            rows.push((format!("{} ({}d{})", kind, num_dice, num_sides), vec![Some(inline), fixed, Some(batched)]));
+        }
+    }
+
+    print_table("Strategies (per game)", &["inline", "fixed", "batched"], &rows);
+}
+
+/// Plays [`GAMES`] games with the same simulation.
+fn play(mut simulation: SimulationType) {
+    let strategy = simulation.as_strategy_mut();
+
+    for _ in 0..GAMES {
+        strategy.reset();
+
+        while !strategy.done() {
+            strategy.step();
+        }
+
+        black_box(strategy.num_rolls());
+    }
+}
+
+/// Times each mode helper over counts of each size.
+fn modes(budget: Duration) {
+    let mut rng = SeededDice::new(42);
+    let mut result = Vec::new();
+
+    let rows = COUNT_SIZES.into_iter().map(|num_faces| {
+        let counts = (0..num_faces).map(|_| rng.roll(10) - 1).collect::<Vec<_>>();
+
+        let mode = time(budget, || { black_box(histogram::mode_from_counts(black_box(&counts))); });
+        let top_two = time(budget, || { black_box(histogram::top_two_modes_from_counts(black_box(&counts))); });
+        let anti = time(budget, || histogram::anti_modes_into(black_box(&counts), &mut result));
+        let analyze = time(budget, || { black_box(histogram::analyze(black_box(counts.as_slice()))); });
+
+        (format!("{} faces", num_faces), vec![Some(mode), Some(top_two), Some(anti), Some(analyze)])
+    }).collect::<Vec<_>>();
+
+    print_table("Mode helpers (per call)", &["mode", "top two", "anti-modes", "analyze"], &rows);
+}
+
+/// Times each dice source at each number of sides.
+fn rng(budget: Duration) {
+    let mut seeded = SeededDice::new(42);
+    let mut buffered = BufferedDice::new(SmallRng::seed_from_u64(42));
+    let mut modulo = ModuloDice::new(SmallRng::seed_from_u64(42));
+
+    let rows = DIE_SIZES.into_iter().map(|num_sides| {
+        let mut alias = AliasDice::seeded(Arc::new(AliasTable::uniform(num_sides).unwrap()), 42);
+
+        let columns = vec![
+            Some(per(ROLLS, time(budget, || roll(&mut ThreadDice, num_sides)))),
+            Some(per(ROLLS, time(budget, || roll(&mut seeded, num_sides)))),
+            Some(per(ROLLS, time(budget, || roll(&mut buffered, num_sides)))),
+            Some(per(ROLLS, time(budget, || roll(&mut modulo, num_sides)))),
+            Some(per(ROLLS, time(budget, || roll(&mut alias, num_sides)))),
+        ];
+
+        (format!("d{}", num_sides), columns)
+    }).collect::<Vec<_>>();
+
+    print_table("Dice sources (per roll)", &["thread", "seeded", "buffered", "modulo", "alias"], &rows);
+
+    let rows = DIE_SIZES.into_iter().map(|num_sides| {
+        let columns = RngKind::ALL.into_iter().map(|kind| {
+            let mut dice = kind.dice(42, None);
+            Some(per(ROLLS, time(budget, || roll(&mut dice, num_sides))))
+        }).collect();
+
+        (format!("d{}", num_sides), columns)
+    }).collect::<Vec<_>>();
+
+    print_table("Generators (per roll)", &RngKind::ALL.map(|kind| kind.name()), &rows);
+}
+
+/// Rolls a number of dice with the given source.
+fn roll(dice: &mut impl DiceRng, num_sides: Num) {
+    for _ in 0..ROLLS {
+        black_box(dice.roll(black_box(num_sides)));
+    }
+}
+
+/// Returns the time that one run of the work takes: the work is run once to warm up, and then in rounds of doubling
+/// length until a round takes at least the budget, which is then averaged.
+fn time(budget: Duration, mut work: impl FnMut()) -> Duration {
+    work();
+
+    let mut iterations = 1u32;
+
+    loop {
+        let start = Instant::now();
+
+        for _ in 0..iterations {
+            work();
+        }
+
+        let elapsed = start.elapsed();
+
+        if elapsed >= budget || iterations == u32::MAX / 2 {
+            return elapsed / iterations;
+        }
+
+        iterations *= 2;
+    }
+}
+
+/// Divides a time over the given number of items.
+fn per(count: Num, time: Duration) -> Duration {
+    time / count as u32
+}
+
+/// Prints a table of times, with a row per configuration and a column per variant (or a dash, if the variant does not
+/// apply).
+fn print_table(title: &str, columns: &[&str], rows: &[(String, Vec<Option<Duration>>)]) {
+    let width = rows.iter().map(|(label, _)| label.len()).max().unwrap_or(0);
+
+    println!();
+    println!("{}", title.bold());
+    println!("{:width$}  {}", "", columns.iter().map(|column| format!("{:>12}", column)).collect::<String>());
+
+    for (label, times) in rows {
+        let times = times.iter().map(|time| format!("{:>12}", time.map_or("-".to_string(), format_time))).collect::<String>();
+        println!("{}  {}", format!("{:width$}", label).cyan(), times);
+    }
+}
+
+/// Formats a time with the largest unit that keeps it at least one.
+fn format_time(time: Duration) -> String {
+    let nanos = time.as_nanos();
+
+    match nanos {
+        0..1_000 => format!("{} ns", nanos),
+        1_000..1_000_000 => format!("{:.1} µs", nanos as f64 / 1e3),
+        _ => format!("{:.1} ms", nanos as f64 / 1e6),
+    }
+}
+
+// Tests.
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_format_time() {
+        assert_eq!(format_time(Duration::from_nanos(950)), "950 ns");
+        assert_eq!(format_time(Duration::from_nanos(12_345)), "12.3 µs");
+        assert_eq!(format_time(Duration::from_micros(4_500)), "4.5 ms");
+    }
+
+    #[test]
+    fn test_time() {
+        // The work is repeated until a round takes the budget.
+
+        let mut runs = 0;
+        time(Duration::from_millis(1), || {
+            runs += 1;
+            std::thread::sleep(Duration::from_micros(100));
+        });
+
+        assert!(runs >= 8);
+    }
+}
\ No newline at end of file
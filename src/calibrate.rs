@@ -0,0 +1,226 @@
+//! Calibrating weighted dice from observed rolls (e.g., to simulate a game with a real set of slightly unfair dice).
+//!
+//! The observed rolls are tallied as [`FaceCounts`]: a count per face, read from text with a line per face of the
+//! form "face count" (or "face: count"), where a face that is listed more than once is summed (so the tallies of
+//! several sessions can be appended to one log), and everything after a `#` on a line is a comment.
+//!
+//! A [`Calibration`] estimates the probability of each face as its share of the rolls, with a Wilson score interval
+//! (which stays sensible for faces that rarely come up), and its [`weights`](Calibration::weights) are the weights of
+//! the weighted dice (see [`MonteCarloBuilder::weights`](crate::config::MonteCarloBuilder::weights)).
+
+use std::path::Path;
+
+use crate::{distribution, error::{Result, TenziError}, types::{Float, Num}};
+
+/// The number of times each face (from 1) came up.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FaceCounts {
+    counts: Vec<u64>,
+}
+
+/// The estimated probability of a face, and its confidence interval.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FaceEstimate {
+    pub face: Num,
+    /// The number of times the face came up.
+    pub count: u64,
+    /// The share of the rolls that came up as the face.
+    pub probability: Float,
+    /// The low end of the interval.
+    pub low: Float,
+    /// The high end of the interval.
+    pub high: Float,
+}
+
+/// The estimated probabilities of the faces of a die, from its observed rolls.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Calibration {
+    estimates: Vec<FaceEstimate>,
+    num_rolls: u64,
+    chi_squared: Float,
+}
+
+impl FaceCounts {
+    /// Returns the given counts of each face (from 1).
+    pub fn new(counts: Vec<u64>) -> Self {
+        Self { counts }
+    }
+
+    /// Tallies the given rolls (e.g., the dice of a trace) of a die with the given number of sides.
+    pub fn from_rolls(num_sides: Num, rolls: &[Num]) -> Result<Self> {
+        let mut counts = vec![0; num_sides as usize];
+
+        for &face in rolls {
+            *counts.get_mut((face as usize).wrapping_sub(1)).ok_or_else(|| not_a_face(face, num_sides))? += 1;
+        }
+
+        Ok(Self { counts })
+    }
+
+    /// Parses counts from text (see the [module](self) docs).  The die has as many sides as the highest face that is
+    /// listed.
+    pub fn parse_text(text: &str) -> Result<Self> {
+        let mut counts = Vec::new();
+
+        for line in text.lines().map(|line| line.split('#').next().unwrap().trim()).filter(|line| !line.is_empty()) {
+            let fields = line.split(|c: char| c == ':' || c.is_whitespace()).filter(|field| !field.is_empty()).collect::<Vec<_>>();
+
+            let [face, count] = fields[..] else {
+                return Err(TenziError::InvalidDice(format!("the count `{}` is not of the form \"face count\"", line)));
+            };
+
+            let face = face.parse::<usize>().ok().filter(|&face| face > 0).ok_or_else(|| TenziError::InvalidDice(format!("`{}` is not a face", face)))?;
+            let count = count.parse::<u64>().map_err(|_| TenziError::InvalidDice(format!("`{}` is not a count", count)))?;
+
+            if counts.len() < face {
+                counts.resize(face, 0);
+            }
+
+            counts[face - 1] += count;
+        }
+
+        Ok(Self { counts })
+    }
+
+    /// Reads counts from a file (see [`parse_text`](Self::parse_text)).
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        Self::parse_text(&std::fs::read_to_string(path)?)
+    }
+
+    /// Returns the count of each face (from 1).
+    pub fn counts(&self) -> &[u64] {
+        &self.counts
+    }
+
+    /// Returns the number of sides of the die (i.e., the highest face that was counted, or that was padded to).
+    pub fn num_sides(&self) -> Num {
+        self.counts.len() as Num
+    }
+
+    /// Pads the counts with zeros up to the given number of sides (e.g., for a face that never came up), or fails with
+    /// [`TenziError::InvalidDice`] if a higher face was counted.
+    pub fn with_sides(mut self, num_sides: Num) -> Result<Self> {
+        if self.num_sides() > num_sides {
+            return Err(not_a_face(self.num_sides(), num_sides));
+        }
+
+        self.counts.resize(num_sides as usize, 0);
+
+        Ok(self)
+    }
+}
+
+impl Calibration {
+    /// Estimates the probability of each face from the counts, with intervals at the given confidence level (e.g.,
+    /// `0.95`).
+    ///
+    /// Fails with [`TenziError::InvalidDice`] if there are no rolls, or if the confidence level is not between 0 and 1.
+    pub fn new(counts: &FaceCounts, confidence: Float) -> Result<Self> {
+        let num_rolls = counts.counts().iter().sum::<u64>();
+
+        if num_rolls == 0 {
+            return Err(TenziError::InvalidDice("there are no rolls to calibrate from".to_string()));
+        }
+
+        if !(confidence > 0.0 && confidence < 1.0) {
+            return Err(TenziError::InvalidDice(format!("`{}` is not a confidence level between 0 and 1", confidence)));
+        }
+
+        let n = num_rolls as f64;
+        let z = z_score(confidence as f64);
+        let fair = n / counts.num_sides() as f64;
+
+        let estimates = counts.counts().iter().zip(1..).map(|(&count, face)| {
+            let (low, high) = distribution::wilson(count, num_rolls, z);
+
+            FaceEstimate { face, count, probability: (count as f64 / n) as Float, low: low as Float, high: high as Float }
+        }).collect();
+
+        let chi_squared = counts.counts().iter().map(|&count| (count as f64 - fair).powi(2) / fair).sum::<f64>();
+
+        Ok(Self { estimates, num_rolls, chi_squared: chi_squared as Float })
+    }
+
+    /// Returns the estimate of each face, in order.
+    pub fn estimates(&self) -> &[FaceEstimate] {
+        &self.estimates
+    }
+
+    /// Returns the number of rolls that the estimates are from.
+    pub fn num_rolls(&self) -> u64 {
+        self.num_rolls
+    }
+
+    /// Returns Pearson's chi-squared statistic of the counts against a fair die (with one degree of freedom fewer than
+    /// there are sides), where a high value is evidence that the die is unfair.
+    pub fn chi_squared(&self) -> Float {
+        self.chi_squared
+    }
+
+    /// Returns the weight of each face, for rolling weighted dice like the observed ones (i.e., the estimated
+    /// probabilities).  A face that never came up has no weight, so it is never rolled.
+    pub fn weights(&self) -> Vec<Float> {
+        self.estimates.iter().map(|estimate| estimate.probability).collect()
+    }
+}
+
+/// Returns the z-score of a two-sided confidence interval at the given level (e.g., 1.96 for 0.95), by the rational
+/// approximation of Abramowitz and Stegun (26.2.23), which is within 4.5e-4.
+pub(crate) fn z_score(confidence: f64) -> f64 {
+    let t = (-2.0 * ((1.0 - confidence) / 2.0).ln()).sqrt();
+
+    t - (2.515517 + 0.802853 * t + 0.010328 * t * t) / (1.0 + 1.432788 * t + 0.189269 * t * t + 0.001308 * t * t * t)
+}
+
+/// Builds the error for a face that a die does not have.
+fn not_a_face(face: Num, num_sides: Num) -> TenziError {
+    TenziError::InvalidDice(format!("`{}` is not a face of a {}-sided die", face, num_sides))
+}
+
+// Tests.
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_parse_counts() {
+        let text = "# session one\n1: 10\n2 12\n4: 8 # no 3s\n\n# session two\n1 5\n";
+        let counts = FaceCounts::parse_text(text).unwrap();
+
+        assert_eq!(counts.counts(), &[15, 12, 0, 8]);
+        assert_eq!(counts, FaceCounts::from_rolls(4, &[&[1; 15][..], &[2; 12], &[4; 8]].concat()).unwrap());
+        assert_eq!(counts.clone().with_sides(6).unwrap().counts(), &[15, 12, 0, 8, 0, 0]);
+
+        assert!(matches!(counts.with_sides(3), Err(TenziError::InvalidDice(_))));
+        assert!(matches!(FaceCounts::parse_text("0 10"), Err(TenziError::InvalidDice(_))));
+        assert!(matches!(FaceCounts::parse_text("1 10 2"), Err(TenziError::InvalidDice(_))));
+        assert!(matches!(FaceCounts::from_rolls(6, &[7]), Err(TenziError::InvalidDice(_))));
+    }
+
+    #[test]
+    fn test_calibration() {
+        let calibration = Calibration::new(&FaceCounts::new(vec![100, 100, 100, 100, 100, 100]), 0.95).unwrap();
+        let estimate = calibration.estimates()[0];
+
+        // A fair die: every face is a sixth (within a bit over 3 percentage points), and fits a fair die perfectly.
+
+        assert_eq!(calibration.num_rolls(), 600);
+        assert_eq!(calibration.chi_squared(), 0.0);
+        assert!((estimate.probability - 1.0 / 6.0).abs() < 1e-6);
+        assert!((estimate.low - 0.1390).abs() < 1e-3 && (estimate.high - 0.1986).abs() < 1e-3);
+
+        // A face that never came up has no weight, but is not ruled out by its interval.
+
+        let calibration = Calibration::new(&FaceCounts::new(vec![0, 30, 30]), 0.95).unwrap();
+
+        assert_eq!(calibration.weights(), vec![0.0, 0.5, 0.5]);
+        assert!(calibration.estimates()[0].high > 0.0);
+        assert!((calibration.chi_squared() - 30.0).abs() < 1e-3);
+
+        assert!((z_score(0.95) - 1.96).abs() < 1e-3);
+        assert!(matches!(Calibration::new(&FaceCounts::new(vec![0, 0]), 0.95), Err(TenziError::InvalidDice(_))));
+        assert!(matches!(Calibration::new(&FaceCounts::new(vec![1, 1]), 1.0), Err(TenziError::InvalidDice(_))));
+    }
+}
\ No newline at end of file
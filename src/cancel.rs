@@ -0,0 +1,27 @@
+use std::sync::{atomic::{AtomicBool, Ordering}, Arc};
+
+/// A token for cooperatively cancelling a running monte carlo simulation.
+///
+/// Clones share the same flag, so one clone can be handed to the simulation while another cancels it.  Games
+/// already in flight run to completion, but no new games are started once the token is cancelled.
+#[derive(Clone, Debug, Default)]
+pub struct CancelToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancelToken {
+    /// Returns a new, uncancelled token.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancels every simulation that holds a clone of this token.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// Returns whether or not the token has been cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
\ No newline at end of file
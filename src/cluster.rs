@@ -0,0 +1,370 @@
+//! Distributed runs, where a coordinator splits each run into shards of games, and hands them out to workers on other
+//! machines over TCP.
+//!
+//! Workers connect to a [`Coordinator`] (see [`work`]), and run one shard at a time: the coordinator sends the run's
+//! configuration and a range of its games, and the worker sends back the [`Moments`] of those games, which are merged
+//! into the statistics of the whole run.  A seeded run rolls every game from the same seed on any worker (see
+//! [`MonteCarloBuilder::seed`](crate::config::MonteCarloBuilder::seed)), so a distributed run has exactly the results of
+//! a local one.
+//!
+//! The protocol is a line of JSON per message.  A shard whose worker disconnects is handed to another worker, and a
+//! cancelled run stops handing out shards (the shards in flight run to completion, and are discarded).
+
+use std::{collections::VecDeque, io::{BufRead, BufReader, Write}, net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs}, sync::{mpsc, Arc, Condvar, Mutex}, time::{Duration, Instant}};
+
+use crate::{cancel::CancelToken, error::{Result, TenziError}, metrics::{MetricSink, Moments}, progress::{Progress, ProgressHook}, results::RunResults, types::Num, SimulationConfig};
+
+/// The version of the protocol, which a worker must speak to be handed shards.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// The default number of games in a shard.
+pub const DEFAULT_SHARD_SIZE: Num = 1_000_000;
+
+/// How long a new connection has to introduce itself as a worker.
+const HELLO_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How often a run checks for cancellation (and for idle workers) while it waits for its shards.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// A message of the protocol.
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum Message {
+    /// A worker introduces itself, with the version of the protocol that it speaks.
+    Hello { version: u32 },
+    /// The coordinator hands a worker the given number of the games of a run, from the game at the given index.
+    Shard { config: SimulationConfig, first: Num, count: Num },
+    /// A worker hands back the moments of the games of its shard.
+    Partial { moments: Moments },
+    /// A worker could not run its shard.
+    Failed { error: String },
+}
+
+/// A coordinator, which listens for workers, and runs the games of each run on them.
+pub struct Coordinator {
+    address: SocketAddr,
+    idle: Arc<Idle>,
+    shard_size: Num,
+}
+
+/// The workers that are waiting for a shard.
+#[derive(Default)]
+struct Idle {
+    workers: Mutex<Vec<Connection>>,
+    ready: Condvar,
+}
+
+/// The connection to a worker.
+struct Connection {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+}
+
+/// What became of a shard.
+enum Event {
+    Done(Moments),
+    Failed(String),
+    /// The worker disconnected, so the shard (from the game at the given index, of the given number of games) must be
+    /// run again.
+    Lost(Num, Num),
+}
+
+impl Coordinator {
+    /// Listens for workers on the given address (e.g., "0.0.0.0:7070"), and hands out shards of the given number of
+    /// games.
+    pub fn bind(address: impl ToSocketAddrs, shard_size: Num) -> Result<Self> {
+        if shard_size == 0 {
+            return Err(TenziError::InvalidConfig("a shard needs at least one game".to_string()));
+        }
+
+        let listener = TcpListener::bind(address)?;
+        let address = listener.local_addr()?;
+        let idle = Arc::new(Idle::default());
+
+        let accepting = idle.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let idle = accepting.clone();
+
+                // Each connection introduces itself on its own thread, so that a slow one cannot hold up the rest.
+
+                std::thread::spawn(move || {
+                    if let Ok(connection) = Connection::accept(stream) {
+                        idle.put(connection);
+                    }
+                });
+            }
+        });
+
+        Ok(Self { address, idle, shard_size })
+    }
+
+    /// Returns the address that the coordinator listens on.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.address
+    }
+
+    /// Returns the number of workers that are waiting for a shard.
+    pub fn idle_workers(&self) -> usize {
+        self.idle.workers.lock().unwrap().len()
+    }
+
+    /// Waits until at least the given number of workers are waiting for a shard, or the timeout passes.  Returns
+    /// whether they are.
+    pub fn wait_for_workers(&self, num_workers: usize, timeout: Duration) -> bool {
+        let workers = self.idle.workers.lock().unwrap();
+        let (workers, _) = self.idle.ready.wait_timeout_while(workers, timeout, |workers| workers.len() < num_workers).unwrap();
+
+        workers.len() >= num_workers
+    }
+
+    /// Runs the configuration on the workers, and returns its results, or fails with [`TenziError::Cancelled`] if the
+    /// token is cancelled first.  The run waits for workers for as long as there are none.
+    ///
+    /// The progress hook is handed the progress of the run as each shard comes back.
+    pub fn run(&self, config: &SimulationConfig, cancel: &CancelToken, progress: ProgressHook<impl Fn(&Progress) + Send + Sync>) -> Result<RunResults> {
+        let start = Instant::now();
+        let total = config.num_simulations();
+
+        let mut shards = (0..total).step_by(self.shard_size as usize).map(|first| (first, self.shard_size.min(total - first))).collect::<VecDeque<_>>();
+        let mut moments = Moments::new(config.histogram());
+        let mut in_flight = 0;
+
+        let (sender, events) = mpsc::channel();
+
+        while !shards.is_empty() || in_flight > 0 {
+            if cancel.is_cancelled() {
+                return Err(TenziError::Cancelled);
+            }
+
+            // Hand a shard to every idle worker.
+
+            while !shards.is_empty() {
+                let Some(connection) = self.idle.take() else {
+                    break;
+                };
+
+                let (first, count) = shards.pop_front().unwrap();
+                let (config, sender, idle) = (config.clone(), sender.clone(), self.idle.clone());
+
+                in_flight += 1;
+
+                std::thread::spawn(move || {
+                    let _ = sender.send(connection.run(&idle, config, first, count));
+                });
+            }
+
+            match events.recv_timeout(POLL_INTERVAL) {
+                Ok(Event::Done(partial)) => {
+                    let before = moments.num_games();
+                    moments.merge(partial);
+                    in_flight -= 1;
+
+                    if progress.is_due(before, moments.num_games()) {
+                        progress.report(&moments.progress(total));
+                    }
+                }
+                Ok(Event::Failed(error)) => return Err(TenziError::InvalidConfig(format!("a worker could not run its shard: {}", error))),
+                Ok(Event::Lost(first, count)) => {
+                    shards.push_front((first, count));
+                    in_flight -= 1;
+                }
+                Err(_) => {}
+            }
+        }
+
+        progress.report(&moments.progress(total));
+
+        Ok(RunResults::new(config.parameters(), vec![moments.summarize(config.strategy(), start.elapsed())]))
+    }
+}
+
+impl Idle {
+    fn put(&self, connection: Connection) {
+        self.workers.lock().unwrap().push(connection);
+        self.ready.notify_all();
+    }
+
+    fn take(&self) -> Option<Connection> {
+        self.workers.lock().unwrap().pop()
+    }
+}
+
+impl Connection {
+    /// Reads the introduction of a new connection, and returns it if it is a worker that speaks the protocol.
+    fn accept(stream: TcpStream) -> Result<Self> {
+        stream.set_read_timeout(Some(HELLO_TIMEOUT))?;
+
+        let mut reader = BufReader::new(stream.try_clone()?);
+
+        match receive(&mut reader)? {
+            Some(Message::Hello { version: PROTOCOL_VERSION }) => {
+                stream.set_read_timeout(None)?;
+                Ok(Self { reader, writer: stream })
+            }
+            _ => Err(protocol_error("the connection is not a worker that speaks the protocol")),
+        }
+    }
+
+    /// Hands the worker a shard, and waits for it to come back.  The worker is put back with the idle ones, unless it
+    /// disconnected.
+    fn run(mut self, idle: &Idle, config: SimulationConfig, first: Num, count: Num) -> Event {
+        let reply = send(&mut self.writer, &Message::Shard { config, first, count }).and_then(|()| receive(&mut self.reader));
+
+        let event = match reply {
+            Ok(Some(Message::Partial { moments })) if moments.num_games() == count => Event::Done(moments),
+            Ok(Some(Message::Failed { error })) => Event::Failed(error),
+            _ => return Event::Lost(first, count),
+        };
+
+        idle.put(self);
+
+        event
+    }
+}
+
+/// Connects to the coordinator at the given address (e.g., "10.0.0.1:7070"), and runs the shards that it hands out on
+/// the given number of threads (or one per logical core), until the coordinator hangs up.
+pub fn work(address: impl ToSocketAddrs, num_threads: Option<usize>) -> Result<()> {
+    let pool = rayon::ThreadPoolBuilder::new().num_threads(num_threads.unwrap_or(0)).build()?;
+
+    let mut writer = TcpStream::connect(address)?;
+    let mut reader = BufReader::new(writer.try_clone()?);
+
+    send(&mut writer, &Message::Hello { version: PROTOCOL_VERSION })?;
+
+    while let Some(message) = receive(&mut reader)? {
+        let Message::Shard { config, first, count } = message else {
+            return Err(protocol_error("the coordinator sent a message that is not a shard"));
+        };
+
+        let sink = Moments::new(config.histogram());
+
+        let reply = match pool.install(|| config.run_range(first, count, sink, &CancelToken::new(), ProgressHook::none())) {
+            Ok((_, moments)) => Message::Partial { moments },
+            Err(e) => Message::Failed { error: e.to_string() },
+        };
+
+        send(&mut writer, &reply)?;
+    }
+
+    Ok(())
+}
+
+/// Writes a message, as a line of JSON.
+fn send(stream: &mut TcpStream, message: &Message) -> Result<()> {
+    let mut line = serde_json::to_vec(message).map_err(|e| protocol_error(e.to_string()))?;
+    line.push(b'\n');
+
+    Ok(stream.write_all(&line)?)
+}
+
+/// Reads a message, or returns `None` if the other end hung up.
+fn receive(reader: &mut impl BufRead) -> Result<Option<Message>> {
+    let mut line = String::new();
+
+    if reader.read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+
+    serde_json::from_str(&line).map(Some).map_err(|e| protocol_error(e.to_string()))
+}
+
+/// Builds the error for a message that breaks the protocol.
+fn protocol_error(reason: impl Into<String>) -> TenziError {
+    TenziError::Io(std::io::Error::new(std::io::ErrorKind::InvalidData, reason.into()))
+}
+
+// Tests.
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_distributed_run() {
+        let coordinator = Coordinator::bind("127.0.0.1:0", 300).unwrap();
+
+        for _ in 0..2 {
+            let address = coordinator.local_addr();
+            std::thread::spawn(move || work(address, Some(1)));
+        }
+
+        assert!(coordinator.wait_for_workers(2, Duration::from_secs(10)));
+
+        // A seeded run has exactly the results of the local run, however it is sharded.
+
+        let config = SimulationConfig::builder().strategy("merge").simulations(1_000).seed(42).histogram(true).build().unwrap();
+        let reports = Mutex::new(Vec::new());
+
+        let distributed = coordinator.run(&config, &CancelToken::new(), ProgressHook::new(|progress: &Progress| reports.lock().unwrap().push(progress.completed)).every(1)).unwrap();
+        let local = config.run().unwrap();
+
+        let (distributed, local) = (&distributed.summaries()[0], &local.summaries()[0]);
+
+        assert_eq!(distributed.strategy(), "merge");
+        assert_eq!(distributed.num_simulations(), 1_000);
+        assert_eq!(distributed.average_rolls(), local.average_rolls());
+        assert_eq!(distributed.std_dev_steps(), local.std_dev_steps());
+        assert_eq!(distributed.rolls_histogram(), local.rolls_histogram());
+        assert_eq!(reports.into_inner().unwrap().last(), Some(&1_000));
+
+        let cancel = CancelToken::new();
+        cancel.cancel();
+
+        assert!(matches!(coordinator.run(&config, &cancel, ProgressHook::none()), Err(TenziError::Cancelled)));
+        assert!(matches!(Coordinator::bind("127.0.0.1:0", 0), Err(TenziError::InvalidConfig(_))));
+    }
+
+    #[test]
+    fn test_misbehaving_workers() {
+        let coordinator = Coordinator::bind("127.0.0.1:0", 100).unwrap();
+
+        // A worker that hangs up as soon as it is handed a shard loses the shard to the next worker.
+
+        let mut quitter = TcpStream::connect(coordinator.local_addr()).unwrap();
+        send(&mut quitter, &Message::Hello { version: PROTOCOL_VERSION }).unwrap();
+        assert!(coordinator.wait_for_workers(1, Duration::from_secs(10)));
+
+        let config = SimulationConfig::builder().simulations(500).seed(7).build().unwrap();
+        let address = coordinator.local_addr();
+
+        std::thread::spawn(move || {
+            let mut reader = BufReader::new(quitter.try_clone().unwrap());
+            let _ = receive(&mut reader);
+            quitter.shutdown(std::net::Shutdown::Both).unwrap();
+
+            work(address, Some(1))
+        });
+
+        let results = coordinator.run(&config, &CancelToken::new(), ProgressHook::none()).unwrap();
+
+        assert_eq!(results.summaries()[0].num_simulations(), 500);
+        assert_eq!(results.summaries()[0].average_rolls(), config.run().unwrap().summaries()[0].average_rolls());
+
+        // A connection that does not speak the protocol is never handed a shard.
+
+        let mut stranger = TcpStream::connect(coordinator.local_addr()).unwrap();
+        stranger.write_all(b"{\"type\":\"hello\",\"version\":0}\n").unwrap();
+
+        assert!(!coordinator.wait_for_workers(2, Duration::from_millis(200)));
+
+        // A worker that fails its shard fails the run.
+
+        let coordinator = Coordinator::bind("127.0.0.1:0", 100).unwrap();
+        let mut failer = TcpStream::connect(coordinator.local_addr()).unwrap();
+
+        send(&mut failer, &Message::Hello { version: PROTOCOL_VERSION }).unwrap();
+
+        std::thread::spawn(move || {
+            let mut reader = BufReader::new(failer.try_clone().unwrap());
+
+            while let Ok(Some(_)) = receive(&mut reader) {
+                send(&mut failer, &Message::Failed { error: "out of memory".to_string() }).unwrap();
+            }
+        });
+
+        assert!(matches!(coordinator.run(&config, &CancelToken::new(), ProgressHook::none()), Err(TenziError::InvalidConfig(_))));
+    }
+}
\ No newline at end of file
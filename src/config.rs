@@ -0,0 +1,941 @@
+use std::{num::NonZero, sync::Arc};
+
+use rayon::ThreadPool;
+
+use crate::{batch::{self, BatchedGames}, cancel::CancelToken, dice::DiceRng, error::{Result, TenziError}, memory::{self, MemoryUsage}, metrics::{MetricSink, Moments}, monte_carlo::{self, Backend, Execution, GameDice}, progress::{Progress, ProgressHook}, rand::{self, AliasDice, AliasTable, RngKind}, registry::StrategyRegistry, results::{RunParameters, RunResults, StrategySummary}, rules::RuleSet, seeds::SeedLog, sequence::{DiceSequence, SequenceMode, SEQUENCE_RNG}, shard::{PartialResults, Shard}, simulation::{self, SimulationType, StrategyKind}, types::{Float, Num}};
+
+/// A validated monte carlo configuration.
+///
+/// Built with a [`MonteCarloBuilder`], so every instance is known to be valid (including when deserialized).
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "MonteCarloBuilder"))]
+pub struct SimulationConfig {
+    num_sides: Num,
+    num_dice: Num,
+    strategy: String,
+    initial_state: Option<Vec<Num>>,
+    num_simulations: Num,
+    num_threads: Option<usize>,
+    histogram: bool,
+    execution: Execution,
+    block_size: Option<Num>,
+    backend: Backend,
+    seed: Option<u64>,
+    rng: Option<RngKind>,
+    weights: Option<Vec<Float>>,
+    rules: RuleSet,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    table: Option<Arc<AliasTable>>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pool: Option<Arc<ThreadPool>>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    registry: StrategyRegistry,
+}
+
+impl SimulationConfig {
+    /// Returns a builder with the default configuration.
+    pub fn builder() -> MonteCarloBuilder {
+        MonteCarloBuilder::new()
+    }
+
+    /// Returns the number of sides on each die.
+    pub fn num_sides(&self) -> Num {
+        self.num_sides
+    }
+
+    /// Returns the number of dice.
+    pub fn num_dice(&self) -> Num {
+        self.num_dice
+    }
+
+    /// Returns the strategy spec.
+    pub fn strategy(&self) -> &str {
+        &self.strategy
+    }
+
+    /// Returns the bucket state that every game starts from, if any.
+    pub fn initial_state(&self) -> Option<&[Num]> {
+        self.initial_state.as_deref()
+    }
+
+    /// Returns the number of simulations.
+    pub fn num_simulations(&self) -> Num {
+        self.num_simulations
+    }
+
+    /// Returns the number of threads, or `None` for the global (or an injected) thread pool.
+    pub fn num_threads(&self) -> Option<usize> {
+        self.num_threads
+    }
+
+    /// Returns the thread pool that the simulations are run on, if one was injected.
+    pub fn pool(&self) -> Option<&ThreadPool> {
+        self.pool.as_deref()
+    }
+
+    /// Returns whether or not the histogram of the number of rolls is recorded.
+    pub fn histogram(&self) -> bool {
+        self.histogram
+    }
+
+    /// Returns how the games are scheduled.
+    pub fn execution(&self) -> Execution {
+        self.execution
+    }
+
+    /// Returns the number of games in each block of work that is handed to a worker, or `None` if it is tuned for the
+    /// run (see [`monte_carlo_blocks`](crate::monte_carlo_blocks)).
+    pub fn block_size(&self) -> Option<Num> {
+        self.block_size
+    }
+
+    /// Returns where the games are played.
+    pub fn backend(&self) -> Backend {
+        self.backend
+    }
+
+    /// Returns the seed that the dice are rolled from, if any.
+    pub fn seed(&self) -> Option<u64> {
+        self.seed
+    }
+
+    /// Returns the generator that the dice are rolled from, if one was chosen.
+    pub fn rng(&self) -> Option<RngKind> {
+        self.rng
+    }
+
+    /// Returns the weight of each face, if the dice are weighted.
+    pub fn weights(&self) -> Option<&[Float]> {
+        self.weights.as_deref()
+    }
+
+    /// Returns the house rules that the games are played by.
+    pub fn rules(&self) -> RuleSet {
+        self.rules
+    }
+
+    /// Returns the parameters that the results of this configuration are reported with.
+    pub fn parameters(&self) -> RunParameters {
+        RunParameters {
+            num_sides: self.num_sides,
+            num_dice: self.num_dice,
+            num_simulations: self.num_simulations,
+            initial_state: self.initial_state.clone(),
+            rng: self.rng_name().to_string(),
+            rules: self.rules,
+        }
+    }
+
+    /// Returns this configuration with the given execution: a serial one runs on the calling thread (so it has no
+    /// threads or thread pool), and a parallel one on the threads of this one (or the global pool).
+    pub(crate) fn with_execution(&self, execution: Execution) -> Self {
+        match execution {
+            Execution::Serial => Self { execution, num_threads: None, pool: None, ..self.clone() },
+            Execution::Parallel => Self { execution, ..self.clone() },
+        }
+    }
+
+    /// Returns the name of the rng that the dice are rolled with, which is recorded in the results.
+    fn rng_name(&self) -> &'static str {
+        match (self.backend, self.rng, self.seed) {
+            (Backend::Gpu, _, _) => "pcg-hash",
+            (_, Some(rng), _) => rng.name(),
+            (_, None, Some(_)) => RngKind::Std.name(),
+            (_, None, None) => rand::THREAD_RNG,
+        }
+    }
+
+    /// Returns a fresh game for this configuration.
+    pub fn simulation(&self) -> SimulationType {
+        let mut simulation = self.registry.build(&self.strategy, self.num_sides, self.num_dice).expect("the strategy is validated when the configuration is built");
+
+        if let Some(state) = &self.initial_state {
+            simulation = simulation.with_initial_state(state);
+        }
+
+        if !self.rules.is_plain() {
+            simulation = simulation.with_rules(self.rules).expect("the rules are checked when the configuration is built");
+        }
+
+        // Every clone of the game (i.e., every worker's) shares the table of the weighted dice.  A generator that cannot
+        // be seeded rolls every game from the same (stateless) source, rather than from a seed per game.
+
+        match (&self.table, self.rng) {
+            (table, Some(rng)) if !rng.is_seedable() => simulation.with_rng(rng.dice(0, table.as_ref())),
+            (Some(table), _) => simulation.with_rng(AliasDice::new(table.clone())),
+            (None, _) => simulation,
+        }
+    }
+
+    /// Returns the game at the given index of a seeded run of this configuration, which plays exactly as it did in the
+    /// run (e.g., to trace a game that a [`SeedLog`] picked out).
+    ///
+    /// Fails with [`TenziError::InvalidConfig`] if the configuration is not seeded.
+    pub fn game(&self, index: Num) -> Result<SimulationType> {
+        let seed = self.seed.ok_or_else(|| TenziError::InvalidConfig("only the games of a seeded run can be replayed".to_string()))?;
+
+        Ok(self.simulation().with_rng(self.game_dice(seed, index as u64)))
+    }
+
+    /// Returns a game of this configuration that is rolled from the given seed of a game (see [`SeedEntry::seed`]),
+    /// which plays exactly as the game that the seed was derived for.
+    ///
+    /// [`SeedEntry::seed`]: crate::seeds::SeedEntry::seed
+    pub fn game_from_seed(&self, game_seed: u64) -> SimulationType {
+        self.simulation().with_rng(self.rng.unwrap_or(RngKind::Std).dice(game_seed, self.table.as_ref()))
+    }
+
+    /// Returns a sink that logs the seeds of the games of a run of this configuration: every game, or only the given
+    /// number of games that took the most rolls.  Returns `None` if the configuration is not seeded.
+    pub fn seed_log(&self, limit: Option<usize>) -> Option<SeedLog> {
+        let rng = self.rng.unwrap_or(RngKind::Std);
+
+        self.seed.map(|seed| match limit {
+            Some(limit) => SeedLog::most_rolls(seed, rng, limit),
+            None => SeedLog::all(seed, rng),
+        })
+    }
+
+    /// Returns the dice of the game at the given index of a run from the given seed (rolled with [`RngKind::Std`],
+    /// unless another generator was chosen).
+    pub(crate) fn game_dice(&self, seed: u64, index: u64) -> Box<dyn DiceRng> {
+        self.rng.unwrap_or(RngKind::Std).game_dice(seed, index, self.table.as_ref())
+    }
+
+    /// Runs the monte carlo simulation.
+    pub fn run(&self) -> Result<RunResults> {
+        self.run_cancellable(&CancelToken::new(), ProgressHook::none())
+    }
+
+    /// Runs the monte carlo simulation until it finishes, or fails with [`TenziError::Cancelled`] if the token is
+    /// cancelled first.
+    ///
+    /// The progress hook is periodically handed the progress of the run.
+    pub fn run_cancellable(&self, cancel: &CancelToken, progress: ProgressHook<impl Fn(&Progress) + Send + Sync>) -> Result<RunResults> {
+        let results = self.run_until_cancelled(cancel, progress)?;
+
+        match results.is_complete() {
+            true => Ok(results),
+            false => Err(TenziError::Cancelled),
+        }
+    }
+
+    /// Runs the monte carlo simulation until it finishes, or the token is cancelled, in which case the results only
+    /// cover the games that were completed (see [`RunResults::is_complete`]).
+    ///
+    /// Fails with [`TenziError::Cancelled`] only if the token is cancelled before any game is completed.
+    pub fn run_until_cancelled(&self, cancel: &CancelToken, progress: ProgressHook<impl Fn(&Progress) + Send + Sync>) -> Result<RunResults> {
+        if self.backend == Backend::Gpu {
+            return self.run_on_gpu(cancel, progress);
+        }
+
+        self.run_into((), cancel, progress).map(|(results, ())| results)
+    }
+
+    /// Runs the monte carlo simulation like [`SimulationConfig::run_until_cancelled`], but also records every game
+    /// into the given sink (e.g., a [`RecordWriter`](crate::records::RecordWriter)), which is returned with the
+    /// results.
+    ///
+    /// The GPU backend does not hand back its games, so it fails with [`TenziError::InvalidConfig`].
+    pub fn run_into<S: MetricSink>(&self, sink: S, cancel: &CancelToken, progress: ProgressHook<impl Fn(&Progress) + Send + Sync>) -> Result<(RunResults, S)> {
+        self.run_range(0, self.num_simulations, sink, cancel, progress)
+    }
+
+    /// Runs the games of one shard of the run (see [`Shard`]), into partial results that merge with those of the other
+    /// shards (see [`shard::merge`](crate::shard::merge)).
+    ///
+    /// Fails with [`TenziError::Cancelled`] if the token is cancelled before the shard is done, since the partial
+    /// results of an incomplete shard cannot be merged.
+    pub fn run_shard(&self, shard: Shard, cancel: &CancelToken, progress: ProgressHook<impl Fn(&Progress) + Send + Sync>) -> Result<PartialResults> {
+        let (first, count) = shard.range(self.num_simulations);
+        let (results, moments) = self.run_range(first, count, Moments::new(self.histogram), cancel, progress)?;
+
+        if moments.num_games() != count {
+            return Err(TenziError::Cancelled);
+        }
+
+        Ok(PartialResults::new(shard, self.parameters(), &self.strategy, self.seed, moments, results.duration()))
+    }
+
+    /// Runs the given number of the games of the run, from the game at the given index, like
+    /// [`SimulationConfig::run_into`] (e.g., a shard of a distributed run).  A seeded run rolls each game from the same
+    /// seed as the whole run does, so the shards of a run play the same games as the run.
+    ///
+    /// The results are reported as a run of the given number of games.
+    pub(crate) fn run_range<S: MetricSink>(&self, first: Num, count: Num, sink: S, cancel: &CancelToken, progress: ProgressHook<impl Fn(&Progress) + Send + Sync>) -> Result<(RunResults, S)> {
+        if self.backend == Backend::Gpu {
+            return Err(TenziError::InvalidConfig("the gpu backend does not record its games".to_string()));
+        }
+
+        // A seeded run rolls each game from its own seed (see `RngKind::game_dice`), so that it plays the same games on any
+        // number of threads.  So does a run with a chosen generator, from a fresh seed if it is not seeded.
+
+        let seed = self.seed.or_else(|| self.rng.filter(RngKind::is_seedable).map(|_| ::rand::random()));
+        let dice = seed.map(|seed| move |index: Num| self.game_dice(seed, (first + index) as u64));
+
+        let start = memory::start_run();
+        let run = |simulation| match self.backend {
+            Backend::Batched => batch::run(&self.batched_games(), count, self.histogram, sink, cancel, progress),
+            _ => monte_carlo::run_with(simulation, count, self.histogram, self.execution, self.block_size.and_then(NonZero::new), dice.as_ref().map(|dice| dice as GameDice), sink, cancel, progress),
+        };
+
+        let (mut summary, sink) = self.install(|| run(self.simulation()))??;
+
+        // Report the strategy by its spec, since registered strategies are otherwise all "custom".
+
+        summary.strategy = self.strategy.clone();
+        summary.memory = MemoryUsage::measure(start, histogram_bytes(&summary), sink.heap_bytes() as u64);
+
+        let parameters = RunParameters { num_simulations: count, ..self.parameters() };
+
+        Ok((RunResults::new(parameters, vec![summary]), sink))
+    }
+
+    /// Runs the monte carlo simulation with pre-generated dice (e.g., from a physical dice-rolling machine) in place
+    /// of the rng, until every game is played, the dice run out, or the token is cancelled (see
+    /// [`DiceSequence::play`]).  The results only cover the games that had all of their dice.
+    ///
+    /// Fails with [`TenziError::InvalidDice`] if a die is not a face of the game's die, and with
+    /// [`TenziError::InvalidConfig`] if the configuration chooses how the dice are rolled (i.e., it is seeded,
+    /// weighted, or has a generator) or is not played on the CPU.
+    pub fn run_sequence(&self, sequence: &DiceSequence, mode: SequenceMode, cancel: &CancelToken) -> Result<RunResults> {
+        if self.backend != Backend::Cpu || self.seed.is_some() || self.rng.is_some() || self.weights.is_some() {
+            return Err(TenziError::InvalidConfig("pre-generated dice are played on the cpu, and cannot be seeded, weighted, or generated".to_string()));
+        }
+
+        sequence.check(self.num_sides)?;
+
+        let start = std::time::Instant::now();
+        let heap = memory::start_run();
+        let moments = self.install(|| sequence.play(&self.simulation(), mode, self.num_simulations, Moments::new(self.histogram), cancel))?;
+
+        let mut parameters = self.parameters();
+        parameters.rng = SEQUENCE_RNG.to_string();
+
+        let mut summary = moments.summarize(&self.strategy, start.elapsed());
+        summary.memory = MemoryUsage::measure(heap, histogram_bytes(&summary), sequence.heap_bytes() as u64);
+
+        Ok(RunResults::new(parameters, vec![summary]))
+    }
+
+    /// Runs the given work on the threads of this configuration: the calling thread, the injected thread pool, a pool
+    /// of the given number of threads, or the global pool.
+    fn install<T: Send>(&self, work: impl FnOnce() -> T + Send) -> Result<T> {
+        match (self.execution, self.num_threads) {
+            (Execution::Parallel, None) if self.pool.is_some() => Ok(self.pool.as_ref().unwrap().install(work)),
+            (Execution::Serial, _) => Ok(work()),
+            (Execution::Parallel, Some(num_threads)) => {
+                let pool = rayon::ThreadPoolBuilder::new().num_threads(num_threads).build()?;
+                Ok(pool.install(work))
+            }
+            (Execution::Parallel, None) => Ok(work()),
+        }
+    }
+
+    /// Returns the lanes of the batched engine for this configuration (see [`Backend::Batched`]).
+    fn batched_games(&self) -> BatchedGames {
+        let kind = self.strategy.parse().expect("the strategy is validated when the configuration is built");
+        let games = BatchedGames::new(kind, self.num_sides, self.num_dice);
+
+        match &self.initial_state {
+            Some(state) => games.with_initial_state(state),
+            None => games,
+        }
+    }
+
+    /// Runs the monte carlo simulation on the GPU (see [`Backend::Gpu`]).
+    #[cfg(feature = "gpu")]
+    fn run_on_gpu(&self, cancel: &CancelToken, progress: ProgressHook<impl Fn(&Progress) + Send + Sync>) -> Result<RunResults> {
+        let summary = crate::gpu::monte_carlo_gpu(self.num_sides, self.num_dice, self.num_simulations, self.histogram, self.seed, cancel, progress)?;
+
+        Ok(RunResults::new(self.parameters(), vec![summary]))
+    }
+
+    #[cfg(not(feature = "gpu"))]
+    fn run_on_gpu(&self, _cancel: &CancelToken, _progress: ProgressHook<impl Fn(&Progress) + Send + Sync>) -> Result<RunResults> {
+        unreachable!("the gpu backend is rejected when the configuration is built without the `gpu` feature")
+    }
+}
+
+/// A builder for a [`SimulationConfig`].
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct MonteCarloBuilder {
+    num_sides: Num,
+    num_dice: Num,
+    strategy: String,
+    initial_state: Option<Vec<Num>>,
+    num_simulations: Num,
+    num_threads: Option<usize>,
+    histogram: bool,
+    execution: Execution,
+    block_size: Option<Num>,
+    backend: Backend,
+    seed: Option<u64>,
+    rng: Option<RngKind>,
+    weights: Option<Vec<Float>>,
+    rules: RuleSet,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pool: Option<Arc<ThreadPool>>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    registry: StrategyRegistry,
+}
+
+impl Default for MonteCarloBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MonteCarloBuilder {
+    /// Returns a builder with the default configuration: 10 6-sided die, the naive strategy, and 10,000 simulations.
+    pub fn new() -> Self {
+        Self {
+            num_sides: 6,
+            num_dice: 10,
+            strategy: StrategyKind::Naive.to_string(),
+            initial_state: None,
+            num_simulations: 10_000,
+            num_threads: None,
+            histogram: false,
+            execution: Execution::Parallel,
+            block_size: None,
+            backend: Backend::Cpu,
+            seed: None,
+            rng: None,
+            weights: None,
+            rules: RuleSet::default(),
+            pool: None,
+            registry: StrategyRegistry::default(),
+        }
+    }
+
+    /// Sets the number of sides on each die.
+    pub fn sides(mut self, num_sides: Num) -> Self {
+        self.num_sides = num_sides;
+        self
+    }
+
+    /// Sets the number of dice.
+    pub fn dice(mut self, num_dice: Num) -> Self {
+        self.num_dice = num_dice;
+        self
+    }
+
+    /// Sets the strategy, either as a [`StrategyKind`] or as a spec that is looked up in the registry.
+    pub fn strategy(mut self, strategy: impl ToString) -> Self {
+        self.strategy = strategy.to_string();
+        self
+    }
+
+    /// Sets the bucket state (i.e., the number of dice already kept for each face) that every game starts from.
+    pub fn initial_state(mut self, state: Vec<Num>) -> Self {
+        self.initial_state = Some(state);
+        self
+    }
+
+    /// Sets the number of simulations.
+    pub fn simulations(mut self, num_simulations: Num) -> Self {
+        self.num_simulations = num_simulations;
+        self
+    }
+
+    /// Runs the simulations on a dedicated thread pool with the given number of threads, rather than the global one.
+    pub fn threads(mut self, num_threads: usize) -> Self {
+        self.num_threads = Some(num_threads);
+        self
+    }
+
+    /// Runs the simulations on an existing thread pool, rather than the global one.
+    ///
+    /// This is how an embedding application controls where the work lands; the pool is shared, not owned.
+    pub fn pool(mut self, pool: Arc<ThreadPool>) -> Self {
+        self.pool = Some(pool);
+        self
+    }
+
+    /// Sets the registry that the strategy is looked up in, in place of the built-in strategies.
+    pub fn registry(mut self, registry: StrategyRegistry) -> Self {
+        self.registry = registry;
+        self
+    }
+
+    /// Sets whether or not to record the histogram of the number of rolls.
+    pub fn histogram(mut self, histogram: bool) -> Self {
+        self.histogram = histogram;
+        self
+    }
+
+    /// Sets how the games are scheduled (the default is [`Execution::Parallel`]).
+    pub fn execution(mut self, execution: Execution) -> Self {
+        self.execution = execution;
+        self
+    }
+
+    /// Hands the workers blocks of the given number of games, rather than a size that is tuned from the first games
+    /// of the run.
+    pub fn block_size(mut self, block_size: Num) -> Self {
+        self.block_size = Some(block_size);
+        self
+    }
+
+    /// Sets where the games are played (the default is [`Backend::Cpu`]).
+    pub fn backend(mut self, backend: Backend) -> Self {
+        self.backend = backend;
+        self
+    }
+
+    /// Rolls the dice from the given seed, which makes the results exactly reproducible.
+    ///
+    /// Each game is rolled from its own seed, which is derived from this one and the game's index in the run (see
+    /// [`rand::game_seed`]), so the results are the same on any number of threads (or serially).  The GPU backend
+    /// rolls each game from its own counter instead, and the batched backend cannot be seeded.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Rolls the dice from the given generator, rather than the thread-local rng (or [`RngKind::Std`], for a seeded
+    /// run).
+    ///
+    /// Every game is rolled from its own seed (see [`SimulationConfig::seed`]), which is drawn from entropy if the run
+    /// is not seeded, except with [`RngKind::Os`], which cannot be seeded at all.  The batched and GPU backends roll
+    /// their own dice, so they cannot choose a generator.
+    pub fn rng(mut self, rng: RngKind) -> Self {
+        self.rng = Some(rng);
+        self
+    }
+
+    /// Rolls weighted dice, with the given weight for each face (from 1), in place of fair ones.
+    ///
+    /// The faces are drawn from an [`AliasTable`], which is built once for the run and shared by every worker.  The
+    /// batched and GPU backends roll their own (fair) dice, so they cannot be weighted.
+    pub fn weights(mut self, weights: Vec<Float>) -> Self {
+        self.weights = Some(weights);
+        self
+    }
+
+    /// Plays the games by the given house rules, which the engine enforces whatever the strategy decides (see
+    /// [`rules`](crate::rules)).  The batched and GPU backends play the plain game, so they cannot have house rules.
+    pub fn rules(mut self, rules: RuleSet) -> Self {
+        self.rules = rules;
+        self
+    }
+
+    /// Validates the configuration.
+    pub fn build(self) -> Result<SimulationConfig> {
+        if self.num_sides == 0 {
+            return Err(TenziError::ZeroSides);
+        }
+
+        if self.num_dice == 0 {
+            return Err(TenziError::ZeroDice);
+        }
+
+        if self.num_simulations == 0 {
+            return Err(TenziError::ZeroSimulations);
+        }
+
+        if self.num_threads == Some(0) {
+            return Err(TenziError::ZeroThreads);
+        }
+
+        if self.block_size == Some(0) {
+            return Err(TenziError::InvalidConfig("a block must have at least one game".to_string()));
+        }
+
+        if self.execution == Execution::Serial && self.num_threads.is_some() {
+            return Err(TenziError::InvalidConfig("serial execution runs on the calling thread, so it cannot have threads".to_string()));
+        }
+
+        if self.pool.is_some() && self.num_threads.is_some() {
+            return Err(TenziError::InvalidConfig("a config can either have threads or a thread pool, but not both".to_string()));
+        }
+
+        if self.execution == Execution::Serial && self.pool.is_some() {
+            return Err(TenziError::InvalidConfig("serial execution runs on the calling thread, so it cannot have a thread pool".to_string()));
+        }
+
+        if self.backend == Backend::Batched {
+            self.check_batched()?;
+        }
+
+        if self.backend == Backend::Gpu {
+            self.check_gpu()?;
+        }
+
+        if self.rng.is_some() && self.backend != Backend::Cpu {
+            return Err(TenziError::InvalidConfig(format!("the {} backend rolls its own dice, so it cannot choose an rng", self.backend)));
+        }
+
+        self.rules.check()?;
+
+        if !self.rules.is_plain() && self.backend != Backend::Cpu {
+            return Err(TenziError::InvalidConfig(format!("the {} backend cannot play by house rules", self.backend)));
+        }
+
+        if let Some(rng) = self.rng.filter(|rng| self.seed.is_some() && !rng.is_seedable()) {
+            return Err(TenziError::InvalidConfig(format!("the {} rng cannot be seeded", rng)));
+        }
+
+        self.registry.build(&self.strategy, self.num_sides, self.num_dice)?;
+
+        if let Some(state) = &self.initial_state {
+            simulation::check_state(state, self.num_sides, self.num_dice)?;
+        }
+
+        let table = match &self.weights {
+            Some(weights) if weights.len() != self.num_sides as usize => return Err(TenziError::InvalidDice(format!("a {}-sided die needs {} weights, but there are {}", self.num_sides, self.num_sides, weights.len()))),
+            Some(weights) if self.backend != Backend::Cpu => return Err(TenziError::InvalidConfig(format!("the {} backend cannot roll weighted dice", self.backend))),
+            Some(weights) => Some(Arc::new(AliasTable::new(weights)?)),
+            None => None,
+        };
+
+        Ok(SimulationConfig {
+            num_sides: self.num_sides,
+            num_dice: self.num_dice,
+            strategy: self.strategy,
+            initial_state: self.initial_state,
+            num_simulations: self.num_simulations,
+            num_threads: self.num_threads,
+            histogram: self.histogram,
+            execution: self.execution,
+            block_size: self.block_size,
+            backend: self.backend,
+            seed: self.seed,
+            rng: self.rng,
+            weights: self.weights,
+            rules: self.rules,
+            table,
+            pool: self.pool,
+            registry: self.registry,
+        })
+    }
+}
+
+impl MonteCarloBuilder {
+    /// Ensures that the configuration can be played by the batched engine.
+    fn check_batched(&self) -> Result<()> {
+        if self.strategy.parse::<StrategyKind>().is_err() {
+            return Err(TenziError::InvalidConfig("the batched backend only plays the built-in strategies".to_string()));
+        }
+
+        if self.execution == Execution::Serial || self.block_size.is_some() {
+            return Err(TenziError::InvalidConfig("the batched backend schedules its own batches of games".to_string()));
+        }
+
+        if self.seed.is_some() {
+            return Err(TenziError::InvalidConfig("the batched backend rolls from the thread-local rng, so it cannot be seeded".to_string()));
+        }
+
+        Ok(())
+    }
+
+    /// Ensures that the configuration can be played by the GPU backend.
+    fn check_gpu(&self) -> Result<()> {
+        if !cfg!(feature = "gpu") {
+            return Err(TenziError::InvalidConfig("the gpu backend needs the `gpu` feature".to_string()));
+        }
+
+        if self.strategy != StrategyKind::Naive.name() {
+            return Err(TenziError::InvalidConfig("the gpu backend only plays the naive strategy".to_string()));
+        }
+
+        if self.initial_state.is_some() {
+            return Err(TenziError::InvalidConfig("the gpu backend cannot start from an initial state".to_string()));
+        }
+
+        if self.execution == Execution::Serial || self.num_threads.is_some() || self.pool.is_some() || self.block_size.is_some() {
+            return Err(TenziError::InvalidConfig("the gpu backend does not play the games on CPU threads".to_string()));
+        }
+
+        #[cfg(feature = "gpu")]
+        crate::gpu::check(self.num_sides, self.num_dice, self.num_simulations)?;
+
+        Ok(())
+    }
+}
+
+impl TryFrom<MonteCarloBuilder> for SimulationConfig {
+    type Error = TenziError;
+
+    fn try_from(builder: MonteCarloBuilder) -> Result<Self> {
+        builder.build()
+    }
+}
+
+/// Returns the bytes of the histogram of a run's summary, if it was recorded.
+fn histogram_bytes(summary: &StrategySummary) -> u64 {
+    summary.rolls_histogram().map_or(0, |histogram| std::mem::size_of_val(histogram) as u64)
+}
+
+// Tests.
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_build_defaults() {
+        let config = MonteCarloBuilder::new().build().unwrap();
+
+        assert_eq!(config.num_sides(), 6);
+        assert_eq!(config.num_dice(), 10);
+        assert_eq!(config.strategy(), "naive");
+        assert_eq!(config.initial_state(), None);
+        assert_eq!(config.num_simulations(), 10_000);
+        assert_eq!(config.num_threads(), None);
+        assert!(!config.histogram());
+        assert_eq!(config.execution(), Execution::Parallel);
+        assert_eq!(config.block_size(), None);
+        assert_eq!(config.backend(), Backend::Cpu);
+        assert_eq!(config.seed(), None);
+        assert_eq!(config.rng(), None);
+        assert_eq!(config.parameters().rng, rand::THREAD_RNG);
+    }
+
+    #[test]
+    fn test_build_invalid() {
+        assert!(matches!(MonteCarloBuilder::new().sides(0).build(), Err(TenziError::ZeroSides)));
+        assert!(matches!(MonteCarloBuilder::new().dice(0).build(), Err(TenziError::ZeroDice)));
+        assert!(matches!(MonteCarloBuilder::new().simulations(0).build(), Err(TenziError::ZeroSimulations)));
+        assert!(matches!(MonteCarloBuilder::new().threads(0).build(), Err(TenziError::ZeroThreads)));
+        assert!(matches!(MonteCarloBuilder::new().initial_state(vec![0, 11, 0, 0, 0, 0]).build(), Err(TenziError::InvalidState(_))));
+        assert!(matches!(MonteCarloBuilder::new().strategy("bogus").build(), Err(TenziError::UnknownStrategy(_))));
+        assert!(matches!(MonteCarloBuilder::new().execution(Execution::Serial).threads(2).build(), Err(TenziError::InvalidConfig(_))));
+        assert!(matches!(MonteCarloBuilder::new().seed(42).backend(Backend::Batched).build(), Err(TenziError::InvalidConfig(_))));
+        assert!(matches!(MonteCarloBuilder::new().rng(RngKind::Pcg64).backend(Backend::Batched).build(), Err(TenziError::InvalidConfig(_))));
+        assert!(matches!(MonteCarloBuilder::new().block_size(0).build(), Err(TenziError::InvalidConfig(_))));
+        assert!(matches!(MonteCarloBuilder::new().backend(Backend::Gpu).strategy(StrategyKind::Merge).build(), Err(TenziError::InvalidConfig(_))));
+        assert!(matches!(MonteCarloBuilder::new().backend(Backend::Batched).strategy("custom").build(), Err(TenziError::InvalidConfig(_))));
+        assert!(matches!(MonteCarloBuilder::new().backend(Backend::Batched).block_size(10).build(), Err(TenziError::InvalidConfig(_))));
+        assert!(matches!(MonteCarloBuilder::new().backend(Backend::Gpu).execution(Execution::Serial).build(), Err(TenziError::InvalidConfig(_))));
+        assert!(matches!(MonteCarloBuilder::new().pool(pool(1)).threads(2).build(), Err(TenziError::InvalidConfig(_))));
+        assert!(matches!(MonteCarloBuilder::new().pool(pool(1)).execution(Execution::Serial).build(), Err(TenziError::InvalidConfig(_))));
+        assert!(matches!(MonteCarloBuilder::new().rules(RuleSet { max_rerolls: Some(0), ..RuleSet::default() }).build(), Err(TenziError::InvalidConfig(_))));
+        assert!(matches!(MonteCarloBuilder::new().rules(RuleSet { lock_kept: true, ..RuleSet::default() }).backend(Backend::Batched).build(), Err(TenziError::InvalidConfig(_))));
+    }
+
+    #[test]
+    fn test_run_on_pool() {
+        let pool = pool(2);
+        let config = SimulationConfig::builder().simulations(100).histogram(true).pool(pool.clone()).build().unwrap();
+
+        // The work lands on the injected pool, rather than the global one.
+
+        let ran_on = std::sync::Mutex::new(Vec::new());
+        let results = config.run_cancellable(&CancelToken::new(), ProgressHook::new(|_: &Progress| ran_on.lock().unwrap().push(rayon::current_thread_index().is_some()))).unwrap();
+
+        assert_eq!(results.summaries()[0].rolls_histogram().unwrap().iter().sum::<Num>(), 100);
+        assert!(ran_on.into_inner().unwrap().iter().all(|&on_pool| on_pool));
+        assert_eq!(config.pool().unwrap().current_num_threads(), 2);
+    }
+
+    fn pool(num_threads: usize) -> Arc<ThreadPool> {
+        Arc::new(rayon::ThreadPoolBuilder::new().num_threads(num_threads).build().unwrap())
+    }
+
+    #[test]
+    fn test_run_until_cancelled() {
+        let cancel = CancelToken::new();
+        let config = SimulationConfig::builder().simulations(1_000_000).build().unwrap();
+
+        let results = config.run_until_cancelled(&cancel, ProgressHook::new(|_: &Progress| cancel.cancel()).every(100)).unwrap();
+
+        assert!(!results.is_complete());
+        assert!(results.summaries()[0].num_simulations() < 1_000_000);
+    }
+
+    #[test]
+    fn test_run_parallel_seeded() {
+        // A seeded run plays the same games on any number of threads, in any order, with any block size.
+
+        let builder = || SimulationConfig::builder().strategy(StrategyKind::Merge).simulations(2_000).histogram(true).seed(42);
+
+        let serial = builder().execution(Execution::Serial).build().unwrap().run().unwrap().into_summaries().remove(0);
+
+        for config in [builder().threads(1), builder().threads(3), builder().threads(4).block_size(7)] {
+            let parallel = config.build().unwrap().run().unwrap().into_summaries().remove(0);
+
+            assert_eq!(parallel.rolls_histogram(), serial.rolls_histogram());
+            assert_eq!(parallel.average_steps(), serial.average_steps());
+        }
+
+        let other = builder().seed(43).build().unwrap().run().unwrap().into_summaries().remove(0);
+        assert_ne!(other.rolls_histogram(), serial.rolls_histogram());
+    }
+
+    #[test]
+    fn test_replay_logged_games() {
+        // Every logged game plays again exactly as it did in the (parallel) run, by its index or by its seed.
+
+        let rolls = |game: SimulationType| crate::trace::GameTrace::record("merge", game).frames.last().unwrap().num_rolls;
+
+        for rng in [RngKind::Std, RngKind::Philox] {
+            let config = SimulationConfig::builder().strategy(StrategyKind::Merge).simulations(500).threads(3).seed(42).rng(rng).build().unwrap();
+            let (_, log) = config.run_into(config.seed_log(Some(3)).unwrap(), &CancelToken::new(), ProgressHook::none()).unwrap();
+
+            let entries = log.into_entries();
+            assert_eq!(entries.len(), 3);
+
+            for entry in entries {
+                assert_eq!(rolls(config.game(entry.index).unwrap()), entry.num_rolls);
+
+                if let Some(seed) = entry.seed {
+                    assert_eq!(rolls(config.game_from_seed(seed)), entry.num_rolls);
+                }
+            }
+        }
+
+        let unseeded = SimulationConfig::builder().build().unwrap();
+
+        assert!(unseeded.seed_log(None).is_none());
+        assert!(matches!(unseeded.game(0), Err(TenziError::InvalidConfig(_))));
+    }
+
+    #[test]
+    fn test_run_sequence() {
+        // Every game that has all of its dice is played, and the results record where the dice came from.
+
+        let sequence = DiceSequence::new([vec![1, 1], vec![1, 2, 2], vec![2, 1]]);
+        let config = SimulationConfig::builder().sides(2).dice(2).simulations(10).histogram(true).build().unwrap();
+
+        let results = config.run_sequence(&sequence, SequenceMode::PerGame, &CancelToken::new()).unwrap();
+        let summary = &results.summaries()[0];
+
+        assert!(!results.is_complete());
+        assert_eq!(results.parameters().rng, SEQUENCE_RNG);
+        assert_eq!(summary.num_simulations(), 2);
+        assert_eq!(summary.rolls_histogram(), Some(&[0, 0, 1, 1][..]));
+
+        let seeded = SimulationConfig::builder().sides(2).dice(2).seed(42).build().unwrap();
+        let invalid = DiceSequence::new([vec![1, 3]]);
+
+        assert!(matches!(seeded.run_sequence(&sequence, SequenceMode::Sequential, &CancelToken::new()), Err(TenziError::InvalidConfig(_))));
+        assert!(matches!(config.run_sequence(&invalid, SequenceMode::Sequential, &CancelToken::new()), Err(TenziError::InvalidDice(_))));
+    }
+
+    #[test]
+    fn test_run_rng() {
+        // Every generator plays its own games from a seed (the same on any number of threads), which are recorded
+        // with its name, and which agree on the expected number of rolls.
+
+        let builder = |rng| SimulationConfig::builder().simulations(2_000).histogram(true).seed(42).rng(rng);
+        let standard = SimulationConfig::builder().simulations(2_000).histogram(true).seed(42).build().unwrap().run().unwrap();
+
+        assert_eq!(standard.parameters().rng, "std");
+
+        for rng in RngKind::ALL.into_iter().filter(RngKind::is_seedable) {
+            let serial = builder(rng).execution(Execution::Serial).build().unwrap().run().unwrap();
+            let parallel = builder(rng).threads(3).build().unwrap().run().unwrap();
+
+            assert_eq!(serial.parameters().rng, rng.name());
+            assert_eq!(parallel.summaries()[0].rolls_histogram(), serial.summaries()[0].rolls_histogram());
+            assert_eq!(serial.summaries()[0].rolls_histogram() == standard.summaries()[0].rolls_histogram(), rng == RngKind::Std);
+            assert!((serial.summaries()[0].average_rolls() - standard.summaries()[0].average_rolls()).abs() < 2.0);
+        }
+
+        // An unseeded run with a generator rolls from a fresh seed, and the operating system's rng is never seeded.
+
+        let results = SimulationConfig::builder().simulations(100).rng(RngKind::ChaCha8).build().unwrap().run().unwrap();
+        assert_eq!(results.parameters().rng, "chacha8");
+
+        let results = SimulationConfig::builder().simulations(100).threads(2).rng(RngKind::Os).build().unwrap().run().unwrap();
+
+        assert_eq!(results.parameters().rng, "os");
+        assert!(results.summaries()[0].average_rolls() > 10.0);
+        assert!(matches!(SimulationConfig::builder().rng(RngKind::Os).seed(42).build(), Err(TenziError::InvalidConfig(_))));
+    }
+
+    #[test]
+    fn test_run_weighted() {
+        // A die that (almost) always shows one face needs (almost) a single roll of the 10 dice, in parallel or seeded.
+
+        let weights = vec![1.0, 0.0, 0.0, 0.0, 0.0, 1e-9];
+        let config = SimulationConfig::builder().simulations(1_000).weights(weights.clone()).build().unwrap();
+
+        assert_eq!(config.weights(), Some(weights.as_slice()));
+        assert!(config.run().unwrap().summaries()[0].average_rolls() < 10.1);
+
+        let config = SimulationConfig::builder().simulations(100).weights(weights).execution(Execution::Serial).seed(42).build().unwrap();
+        assert!(config.run().unwrap().summaries()[0].average_rolls() < 10.1);
+
+        assert!(matches!(SimulationConfig::builder().weights(vec![1.0; 5]).build(), Err(TenziError::InvalidDice(_))));
+        assert!(matches!(SimulationConfig::builder().weights(vec![0.0; 6]).build(), Err(TenziError::InvalidDice(_))));
+        assert!(matches!(SimulationConfig::builder().weights(vec![1.0; 6]).backend(Backend::Batched).build(), Err(TenziError::InvalidConfig(_))));
+    }
+
+    #[test]
+    fn test_run_serial_seeded() {
+        let config = SimulationConfig::builder().strategy(StrategyKind::Merge).simulations(100).execution(Execution::Serial).seed(42).build().unwrap();
+
+        let first = config.run().unwrap().into_summaries().remove(0);
+        let second = config.run().unwrap().into_summaries().remove(0);
+
+        assert_eq!(first.average_rolls(), second.average_rolls());
+        assert_eq!(first.std_dev_rolls(), second.std_dev_rolls());
+    }
+
+    #[test]
+    fn test_run() {
+        let results = SimulationConfig::builder()
+            .strategy(StrategyKind::Merge)
+            .initial_state(vec![0, 10, 0, 0, 0, 0])
+            .simulations(10)
+            .threads(2)
+            .block_size(3)
+            .histogram(true)
+            .build()
+            .unwrap()
+            .run()
+            .unwrap();
+
+        assert_eq!(results.parameters().initial_state, Some(vec![0, 10, 0, 0, 0, 0]));
+        assert_eq!(results.summary("merge").unwrap().average_rolls(), 0.0);
+        assert_eq!(results.summary("merge").unwrap().rolls_histogram(), Some(&[10][..]));
+    }
+
+    #[test]
+    fn test_run_batched() {
+        let results = SimulationConfig::builder().strategy(StrategyKind::Divide).initial_state(vec![0, 10, 0, 0, 0, 0]).simulations(10).threads(2).backend(Backend::Batched).build().unwrap().run().unwrap();
+
+        assert_eq!(results.summary("divide").unwrap().num_simulations(), 10);
+        assert_eq!(results.summary("divide").unwrap().average_rolls(), 0.0);
+    }
+
+    #[test]
+    fn test_run_into() {
+        // Every backend that plays on the CPU hands its games to the sink.
+
+        for backend in [Backend::Cpu, Backend::Batched] {
+            let config = SimulationConfig::builder().strategy(StrategyKind::Merge).simulations(100).backend(backend).build().unwrap();
+            let (results, totals) = config.run_into(crate::metrics::Totals::default(), &CancelToken::new(), ProgressHook::none()).unwrap();
+
+            assert_eq!(totals.num_games(), 100);
+            assert_eq!(totals.average_rolls(), results.summary("merge").unwrap().average_rolls());
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_round_trip() {
+        let config = SimulationConfig::builder().strategy(StrategyKind::Divide).initial_state(vec![0, 2, 0, 0, 0, 0]).build().unwrap();
+
+        let json = serde_json::to_string(&config).unwrap();
+        let parsed = serde_json::from_str::<SimulationConfig>(&json).unwrap();
+
+        assert_eq!(parsed.strategy(), "divide");
+        assert_eq!(parsed.initial_state(), Some(&[0, 2, 0, 0, 0, 0][..]));
+        assert!(json.contains("\"strategy\":\"divide\""));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_validates() {
+        assert_eq!(serde_json::from_str::<SimulationConfig>(r#"{ "num_dice": 12 }"#).unwrap().num_dice(), 12);
+        assert!(serde_json::from_str::<SimulationConfig>(r#"{ "num_sides": 0 }"#).is_err());
+    }
+}
\ No newline at end of file
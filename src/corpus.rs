@@ -0,0 +1,251 @@
+//! Regression checks over a stored corpus of seeded games (e.g., the outliers that a run flagged, or edge cases).
+//!
+//! A [`SeedCorpus`] pairs every game (a [`SeedCase`]) with the outcome that each strategy had when it was recorded.
+//! Checking the corpus plays every game again and reports each outcome that changed, which catches unintended changes
+//! of behavior when a strategy is refactored: a strategy that makes the same decisions from the same dice reaches the
+//! same outcome.
+//!
+//! The corpus is stored as text: a header line, followed by one line per outcome of the form
+//! "sides dice rng seed game rolls steps strategy", where the game is `-` for a game that is identified by its own
+//! seed (see [`SeedEntry::seed`](crate::seeds::SeedEntry::seed)).  Everything after a `#` on a line is a comment.
+
+use std::{fmt::Write as _, path::Path};
+
+use crate::{error::{Result, TenziError}, metrics::GameOutcome, rand::RngKind, testing::{self, DEFAULT_MAX_STEPS}, types::Num, SimulationConfig};
+
+/// The header that every corpus file starts with.
+const HEADER: &str = "tenzi-corpus";
+
+/// A seeded game: the game at an index of a seeded run, or the game that is rolled from its own seed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SeedCase {
+    pub num_sides: Num,
+    pub num_dice: Num,
+    pub rng: RngKind,
+    pub seed: u64,
+    /// The index of the game in the run with the seed, or `None` if the seed is the game's own.
+    pub game: Option<Num>,
+}
+
+/// The outcome that a strategy had in a game of the corpus.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CorpusEntry {
+    pub case: SeedCase,
+    pub strategy: String,
+    pub outcome: GameOutcome,
+}
+
+/// An outcome of the corpus that is not what it was recorded as.
+#[derive(Debug)]
+pub struct CorpusDiff {
+    /// The recorded outcome.
+    pub expected: CorpusEntry,
+    /// The outcome of the game now, or why it could not be played (e.g., a broken invariant).
+    pub actual: Result<GameOutcome>,
+}
+
+/// A corpus of seeded games, with the recorded outcome of each strategy in each.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SeedCorpus {
+    entries: Vec<CorpusEntry>,
+}
+
+impl SeedCase {
+    /// Plays the game with the given strategy (a spec of the default registry), checking the invariants after every
+    /// step (see [`testing::check_game`]).
+    pub fn play(&self, strategy: &str) -> Result<GameOutcome> {
+        let config = SimulationConfig::builder().sides(self.num_sides).dice(self.num_dice).strategy(strategy).seed(self.seed).rng(self.rng).build()?;
+
+        let mut game = match self.game {
+            Some(index) => config.game(index)?,
+            None => config.game_from_seed(self.seed),
+        };
+
+        let game = game.as_strategy_mut();
+        testing::check_game(game, DEFAULT_MAX_STEPS)?;
+
+        Ok(GameOutcome { num_rolls: game.num_rolls(), num_steps: game.num_steps() })
+    }
+}
+
+impl SeedCorpus {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns every recorded outcome, in the order they were recorded.
+    pub fn entries(&self) -> &[CorpusEntry] {
+        &self.entries
+    }
+
+    /// Returns every game of the corpus, in the order they were first recorded.
+    pub fn cases(&self) -> Vec<SeedCase> {
+        let mut cases = Vec::new();
+
+        for entry in &self.entries {
+            if !cases.contains(&entry.case) {
+                cases.push(entry.case);
+            }
+        }
+
+        cases
+    }
+
+    /// Plays the game with each of the given strategies, and records their outcomes (in place of any that were
+    /// recorded before).
+    pub fn record(&mut self, case: SeedCase, strategies: impl IntoIterator<Item = impl AsRef<str>>) -> Result<()> {
+        for strategy in strategies {
+            let strategy = strategy.as_ref();
+            let outcome = case.play(strategy)?;
+
+            match self.entries.iter_mut().find(|entry| entry.case == case && entry.strategy == strategy) {
+                Some(entry) => entry.outcome = outcome,
+                None => self.entries.push(CorpusEntry { case, strategy: strategy.to_string(), outcome }),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Plays every game of the corpus again, and records the outcomes (i.e., accepts every difference).
+    pub fn update(&mut self) -> Result<()> {
+        for entry in &mut self.entries {
+            entry.outcome = entry.case.play(&entry.strategy)?;
+        }
+
+        Ok(())
+    }
+
+    /// Plays every game of the corpus again, and returns each outcome that differs from the recorded one.
+    pub fn check(&self) -> Vec<CorpusDiff> {
+        self.entries.iter().filter_map(|entry| {
+            let actual = entry.case.play(&entry.strategy);
+
+            match &actual {
+                Ok(outcome) if *outcome == entry.outcome => None,
+                _ => Some(CorpusDiff { expected: entry.clone(), actual }),
+            }
+        }).collect()
+    }
+
+    /// Serializes the corpus to its text format (see the [module](self) docs).
+    pub fn to_text(&self) -> String {
+        let mut text = format!("{}\n# sides dice rng seed game rolls steps strategy\n", HEADER);
+
+        for CorpusEntry { case, strategy, outcome } in &self.entries {
+            let game = case.game.map_or("-".to_string(), |game| game.to_string());
+            writeln!(text, "{} {} {} {} {} {} {} {}", case.num_sides, case.num_dice, case.rng, case.seed, game, outcome.num_rolls, outcome.num_steps, strategy).unwrap();
+        }
+
+        text
+    }
+
+    /// Parses a corpus from its text format.
+    pub fn from_text(text: &str) -> Result<Self> {
+        let mut lines = text.lines().map(|line| line.split('#').next().unwrap().trim()).filter(|line| !line.is_empty());
+
+        if lines.next() != Some(HEADER) {
+            return Err(invalid("the corpus header is malformed"));
+        }
+
+        let entries = lines.map(|line| {
+            let [num_sides, num_dice, rng, seed, game, num_rolls, num_steps, strategy] = line.splitn(8, char::is_whitespace).collect::<Vec<_>>()[..] else {
+                return Err(invalid(format!("the corpus line `{}` is malformed", line)));
+            };
+
+            let case = SeedCase {
+                num_sides: parse(num_sides)?,
+                num_dice: parse(num_dice)?,
+                rng: rng.parse().map_err(|_| invalid(format!("`{}` is not an rng", rng)))?,
+                seed: parse(seed)?,
+                game: match game {
+                    "-" => None,
+                    game => Some(parse(game)?),
+                },
+            };
+
+            Ok(CorpusEntry { case, strategy: strategy.trim().to_string(), outcome: GameOutcome { num_rolls: parse(num_rolls)?, num_steps: parse(num_steps)? } })
+        }).collect::<Result<Vec<_>>>()?;
+
+        Ok(Self { entries })
+    }
+
+    /// Writes the corpus to a file.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        Ok(std::fs::write(path, self.to_text())?)
+    }
+
+    /// Reads a corpus from a file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        Self::from_text(&std::fs::read_to_string(path)?)
+    }
+}
+
+/// Parses a number from a corpus field.
+fn parse<T: std::str::FromStr>(s: &str) -> Result<T> {
+    s.parse().map_err(|_| invalid(format!("`{}` is not a number", s)))
+}
+
+/// Builds an invalid corpus error.
+fn invalid(reason: impl Into<String>) -> TenziError {
+    TenziError::InvalidCorpus(reason.into())
+}
+
+// Tests.
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn case(game: Option<Num>) -> SeedCase {
+        SeedCase { num_sides: 6, num_dice: 10, rng: RngKind::Std, seed: 42, game }
+    }
+
+    #[test]
+    fn test_corpus_round_trip() {
+        let mut corpus = SeedCorpus::new();
+
+        corpus.record(case(Some(7)), ["naive", "merge"]).unwrap();
+        corpus.record(case(None), ["merge"]).unwrap();
+        corpus.record(case(Some(7)), ["merge"]).unwrap();
+
+        assert_eq!(corpus.entries().len(), 3);
+        assert_eq!(corpus.cases(), vec![case(Some(7)), case(None)]);
+        assert_eq!(SeedCorpus::from_text(&corpus.to_text()).unwrap(), corpus);
+
+        // Games of a run are replayed exactly as the run played them.
+
+        let config = SimulationConfig::builder().strategy("merge").seed(42).build().unwrap();
+        let game = crate::trace::GameTrace::record("merge", config.game(7).unwrap());
+
+        assert_eq!(corpus.entries()[1].outcome.num_rolls, game.frames.last().unwrap().num_rolls);
+
+        assert!(matches!(SeedCorpus::from_text("tenzi-trace"), Err(TenziError::InvalidCorpus(_))));
+        assert!(matches!(SeedCorpus::from_text("tenzi-corpus\n6 10 std 42 7 10 1"), Err(TenziError::InvalidCorpus(_))));
+        assert!(matches!(SeedCorpus::from_text("tenzi-corpus\n6 10 mt 42 7 10 1 merge"), Err(TenziError::InvalidCorpus(_))));
+    }
+
+    #[test]
+    fn test_corpus_check() {
+        let mut corpus = SeedCorpus::new();
+        corpus.record(case(Some(3)), ["naive", "divide", "merge"]).unwrap();
+
+        assert!(corpus.check().is_empty());
+
+        // A changed outcome is reported (and accepted by an update).
+
+        let mut changed = corpus.clone();
+        changed.entries[0].outcome.num_rolls += 1;
+        changed.entries[2].outcome.num_steps += 1;
+
+        let diffs = changed.check();
+
+        assert_eq!(diffs.len(), 2);
+        assert_eq!(diffs[0].expected, changed.entries[0]);
+        assert_eq!(diffs[0].actual.as_ref().unwrap(), &corpus.entries[0].outcome);
+
+        changed.update().unwrap();
+        assert!(changed.check().is_empty());
+    }
+}
\ No newline at end of file
@@ -0,0 +1,103 @@
+//! Verifying that a seeded run plays the same games however it is scheduled, which is what makes its results (and any
+//! game of it) reproducible.
+//!
+//! [`verify`] plays a seeded configuration twice: once serially, on the calling thread, and once in parallel, on the
+//! configuration's threads (or the global pool).  The output of each run is serialized as the partial results of the
+//! whole run (see [`PartialResults::to_text`], with no duration, since that differs from run to run), followed by a line
+//! of NDJSON per game, in the order of their index (see [`SeedEntry::to_json`]).  The two outputs are compared line by
+//! line, so a divergence is reported where it first shows (e.g., at the first game that was played differently).
+//!
+//! [`SeedEntry::to_json`]: crate::seeds::SeedEntry::to_json
+
+use std::{fmt::Write as _, time::Duration};
+
+use crate::{cancel::CancelToken, error::{Result, TenziError}, metrics::Moments, monte_carlo::Execution, progress::ProgressHook, shard::{PartialResults, Shard}, SimulationConfig};
+
+/// The first line at which the outputs of the serial and the parallel run differ.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Divergence {
+    /// The number of the line (from 1).
+    pub line: usize,
+    /// The line of the serial run, or `None` if its output ended first.
+    pub serial: Option<String>,
+    /// The line of the parallel run, or `None` if its output ended first.
+    pub parallel: Option<String>,
+}
+
+/// Plays the seeded configuration serially and in parallel, and returns the first line at which their outputs differ
+/// (see the [module](self) docs), or `None` if they are identical.
+///
+/// Fails with [`TenziError::InvalidConfig`] if the configuration is not seeded, and with [`TenziError::Cancelled`] if
+/// the token is cancelled first.
+pub fn verify(config: &SimulationConfig, cancel: &CancelToken) -> Result<Option<Divergence>> {
+    if config.seed().is_none() {
+        return Err(TenziError::InvalidConfig("only a seeded run can be verified to be deterministic".to_string()));
+    }
+
+    let serial = output(&config.with_execution(Execution::Serial), cancel)?;
+    let parallel = output(&config.with_execution(Execution::Parallel), cancel)?;
+
+    Ok(diverge(&serial, &parallel))
+}
+
+/// Plays the (seeded) configuration, and returns its serialized output.
+fn output(config: &SimulationConfig, cancel: &CancelToken) -> Result<String> {
+    let sinks = (Moments::new(true), config.seed_log(None).expect("the configuration is seeded"));
+    let (_, (moments, log)) = config.run_into(sinks, cancel, ProgressHook::none())?;
+
+    if moments.num_games() != config.num_simulations() {
+        return Err(TenziError::Cancelled);
+    }
+
+    let mut text = PartialResults::new(Shard::new(0, 1)?, config.parameters(), config.strategy(), config.seed(), moments, Duration::ZERO).to_text();
+
+    for entry in log.into_entries() {
+        writeln!(text, "{}", entry.to_json()).unwrap();
+    }
+
+    Ok(text)
+}
+
+/// Returns the first line at which the outputs differ, if any.
+fn diverge(serial: &str, parallel: &str) -> Option<Divergence> {
+    let (mut serial, mut parallel) = (serial.lines(), parallel.lines());
+    let mut line = 0;
+
+    loop {
+        line += 1;
+
+        match (serial.next(), parallel.next()) {
+            (None, None) => return None,
+            (serial, parallel) if serial != parallel => return Some(Divergence { line, serial: serial.map(String::from), parallel: parallel.map(String::from) }),
+            _ => {}
+        }
+    }
+}
+
+// Tests.
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{rand::RngKind, simulation::StrategyKind};
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_verify() {
+        // A seeded run plays the same games serially and in parallel, with any generator and block size.
+
+        for rng in [RngKind::Std, RngKind::Philox] {
+            let config = SimulationConfig::builder().strategy(StrategyKind::Merge).simulations(1_000).threads(3).block_size(7).seed(42).rng(rng).build().unwrap();
+            assert_eq!(verify(&config, &CancelToken::new()).unwrap(), None);
+        }
+
+        let unseeded = SimulationConfig::builder().simulations(10).build().unwrap();
+        assert!(matches!(verify(&unseeded, &CancelToken::new()), Err(TenziError::InvalidConfig(_))));
+
+        // The first line that differs is reported, including where one output ends first.
+
+        assert_eq!(diverge("a\nb\nc\n", "a\nx\nc\n"), Some(Divergence { line: 2, serial: Some("b".to_string()), parallel: Some("x".to_string()) }));
+        assert_eq!(diverge("a\nb\n", "a\n"), Some(Divergence { line: 2, serial: Some("b".to_string()), parallel: None }));
+    }
+}
\ No newline at end of file
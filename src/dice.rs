@@ -0,0 +1,123 @@
+use alloc::boxed::Box;
+
+use crate::types::Num;
+
+/// A source of dice rolls, which every game holds (see [`Strategy::set_rng`](crate::simulation::Strategy::set_rng)).
+///
+/// With `std`, games roll with [`ThreadDice`](crate::rand::ThreadDice) unless told otherwise, and
+/// [`SeededDice`](crate::rand::SeededDice) plays reproducible games (and [`BufferedDice`](crate::rand::BufferedDice)
+/// draws from any other rng in blocks).  Every built-in source rolls without bias, except the explicitly biased
+/// [`ModuloDice`](crate::rand::ModuloDice).  Without `std`, there is no default source, so
+/// every game needs one before it is stepped.
+pub trait DiceRng: DiceRngClone + Send + Sync {
+    /// Rolls a die with the given number of sides, and returns the face rolled (from 1 to the number of sides).
+    fn roll(&mut self, num_sides: Num) -> Num;
+
+    /// Rolls the given number of dice, and adds each to the bucket of its face (i.e., at index `face - 1`).
+    ///
+    /// A game rolls its dice with this whenever it rolls at least as many dice as there are sides, so a source can
+    /// roll a large pool at once (e.g., [`ThreadDice`](crate::rand::ThreadDice) rolls huge pools in parallel).  The
+    /// default rolls them one at a time, in order.
+    fn roll_into(&mut self, num_sides: Num, num_dice: Num, buckets: &mut [Num]) {
+        for _ in 0..num_dice {
+            buckets[self.roll(num_sides) as usize - 1] += 1;
+        }
+    }
+
+    /// Returns a source of the same dice for the given block of a parallel run, which rolls a stream of its own rather
+    /// than the stream of this source, or `None` if every clone of this source already rolls its own dice.
+    ///
+    /// A clone of a source with a state (e.g., a seeded rng) continues from that state, so every worker that plays with
+    /// a clone would roll the same dice.  The default is `None`, for a source without a state of its own (e.g.,
+    /// [`ThreadDice`](crate::rand::ThreadDice), which rolls from the rng of whichever thread it is on).
+    fn split(&self, _index: u64) -> Option<Box<dyn DiceRng>> {
+        None
+    }
+}
+
+/// A helper trait that allows boxed dice sources to be cloned.
+///
+/// This is implemented for every source that is `Clone`, so it never needs to be implemented by hand.
+pub trait DiceRngClone {
+    /// Clones the source into a box.
+    fn clone_box(&self) -> Box<dyn DiceRng>;
+}
+
+impl<T: DiceRng + Clone + 'static> DiceRngClone for T {
+    fn clone_box(&self) -> Box<dyn DiceRng> {
+        Box::new(self.clone())
+    }
+}
+
+impl Clone for Box<dyn DiceRng> {
+    fn clone(&self) -> Self {
+        // The box is itself a source, so clone what it points to (rather than recursing into the box's own impl).
+        self.as_ref().clone_box()
+    }
+}
+
+impl DiceRng for Box<dyn DiceRng> {
+    fn roll(&mut self, num_sides: Num) -> Num {
+        self.as_mut().roll(num_sides)
+    }
+
+    fn roll_into(&mut self, num_sides: Num, num_dice: Num, buckets: &mut [Num]) {
+        self.as_mut().roll_into(num_sides, num_dice, buckets)
+    }
+
+    fn split(&self, index: u64) -> Option<Box<dyn DiceRng>> {
+        self.as_ref().split(index)
+    }
+}
+
+/// What a game's step rolls with: either its source, or the function handed to
+/// [`Strategy::step_with`](crate::simulation::Strategy::step_with) (see [`RollFn`]).
+pub(crate) trait Roll {
+    fn roll(&mut self, num_sides: Num) -> Num;
+
+    fn roll_into(&mut self, num_sides: Num, num_dice: Num, buckets: &mut [Num]);
+}
+
+impl<D: DiceRng + ?Sized> Roll for D {
+    fn roll(&mut self, num_sides: Num) -> Num {
+        DiceRng::roll(self, num_sides)
+    }
+
+    fn roll_into(&mut self, num_sides: Num, num_dice: Num, buckets: &mut [Num]) {
+        DiceRng::roll_into(self, num_sides, num_dice, buckets)
+    }
+}
+
+/// A function that rolls one die at a time, as a [`Roll`].
+pub(crate) struct RollFn<'a>(pub &'a mut dyn FnMut(Num) -> Num);
+
+impl Roll for RollFn<'_> {
+    fn roll(&mut self, num_sides: Num) -> Num {
+        (self.0)(num_sides)
+    }
+
+    fn roll_into(&mut self, num_sides: Num, num_dice: Num, buckets: &mut [Num]) {
+        for _ in 0..num_dice {
+            buckets[(self.0)(num_sides) as usize - 1] += 1;
+        }
+    }
+}
+
+/// The placeholder for a game without a dice source, which panics when rolled.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct NoDice;
+
+impl DiceRng for NoDice {
+    fn roll(&mut self, _num_sides: Num) -> Num {
+        panic!("the game has no dice source; set one with `Strategy::set_rng`")
+    }
+}
+
+/// Returns the dice source that new games are built with.
+pub(crate) fn default_rng() -> Box<dyn DiceRng> {
+    #[cfg(feature = "std")]
+    return Box::new(crate::rand::ThreadDice);
+
+    #[cfg(not(feature = "std"))]
+    return Box::new(NoDice);
+}
\ No newline at end of file
@@ -0,0 +1,88 @@
+//! The uncertainty of a distribution that was sampled by simulation (e.g., the histogram of the number of rolls), so
+//! that its charts can show a band around each bin rather than a bare line.
+//!
+//! Each bin of a histogram of `n` games is one cell of a multinomial sample, so its share of the games is a binomial
+//! proportion, and its interval is the Wilson score interval of that share (which, unlike the normal approximation,
+//! stays sensible for the rare games in the tails, and for the bins that no game fell in).  The intervals are
+//! per bin, and not simultaneous over the whole histogram.
+
+use crate::{calibrate, error::{Result, TenziError}, types::{Float, Num}};
+
+/// The estimated share of the games that fell in a bin, and its confidence interval.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BinEstimate {
+    /// The index of the bin (e.g., the number of rolls).
+    pub bin: Num,
+    /// The number of games that fell in the bin.
+    pub count: u64,
+    /// The share of the games that fell in the bin.
+    pub probability: Float,
+    /// The low end of the interval.
+    pub low: Float,
+    /// The high end of the interval.
+    pub high: Float,
+}
+
+/// Estimates the share of each bin of the histogram, with intervals at the given confidence level (e.g., `0.95`).
+///
+/// Fails with [`TenziError::InvalidConfig`] if the histogram is empty, or if the confidence level is not between 0 and
+/// 1.
+pub fn estimate_bins(counts: &[u64], confidence: Float) -> Result<Vec<BinEstimate>> {
+    let num_games = counts.iter().sum::<u64>();
+
+    if num_games == 0 {
+        return Err(TenziError::InvalidConfig("there are no games to estimate the distribution from".to_string()));
+    }
+
+    if !(confidence > 0.0 && confidence < 1.0) {
+        return Err(TenziError::InvalidConfig(format!("`{}` is not a confidence level between 0 and 1", confidence)));
+    }
+
+    let z = calibrate::z_score(confidence as f64);
+
+    Ok(counts.iter().zip(0..).map(|(&count, bin)| {
+        let (low, high) = wilson(count, num_games, z);
+
+        BinEstimate { bin, count, probability: (count as f64 / num_games as f64) as Float, low: low as Float, high: high as Float }
+    }).collect())
+}
+
+/// Returns the Wilson score interval of the proportion `count / n` at the given z-score, which never leaves [0, 1].
+pub(crate) fn wilson(count: u64, n: u64, z: f64) -> (f64, f64) {
+    let (p, n) = (count as f64 / n as f64, n as f64);
+
+    let center = (p + z * z / (2.0 * n)) / (1.0 + z * z / n);
+    let margin = z / (1.0 + z * z / n) * (p * (1.0 - p) / n + z * z / (4.0 * n * n)).sqrt();
+
+    ((center - margin).max(0.0), (center + margin).min(1.0))
+}
+
+// Tests.
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_estimate_bins() {
+        let estimates = estimate_bins(&[0, 100, 300, 100], 0.95).unwrap();
+
+        // Every interval holds its share, and an empty bin is not ruled out.
+
+        assert_eq!(estimates.iter().map(|estimate| estimate.bin).collect::<Vec<_>>(), vec![0, 1, 2, 3]);
+        assert_eq!(estimates[2].probability, 0.6);
+        assert!(estimates.iter().all(|estimate| estimate.low <= estimate.probability && estimate.probability <= estimate.high));
+        assert!(estimates[0].low == 0.0 && estimates[0].high > 0.0);
+        assert!((estimates[1].low - 0.1675).abs() < 1e-3 && (estimates[1].high - 0.2373).abs() < 1e-3);
+
+        // More games narrow the bands.
+
+        let more = estimate_bins(&[0, 1_000, 3_000, 1_000], 0.95).unwrap();
+
+        assert!(more[2].high - more[2].low < estimates[2].high - estimates[2].low);
+        assert!(matches!(estimate_bins(&[0, 0], 0.95), Err(TenziError::InvalidConfig(_))));
+        assert!(matches!(estimate_bins(&[1, 1], 0.0), Err(TenziError::InvalidConfig(_))));
+    }
+}
\ No newline at end of file
@@ -0,0 +1,132 @@
+use alloc::string::String;
+
+use crate::types::Num;
+
+/// The error type for everything that can go wrong in the simulator.
+#[derive(Debug, thiserror::Error)]
+pub enum TenziError {
+    /// The die must have at least one side.
+    #[error("the die must have at least one side")]
+    ZeroSides,
+
+    /// There must be at least one die.
+    #[error("there must be at least one die")]
+    ZeroDice,
+
+    /// There must be at least one simulation.
+    #[error("there must be at least one simulation")]
+    ZeroSimulations,
+
+    /// There must be at least one thread.
+    #[error("there must be at least one thread")]
+    ZeroThreads,
+
+    /// There is no strategy with the given name.
+    #[error("unknown strategy `{0}`")]
+    UnknownStrategy(String),
+
+    /// A strategy spec was rejected by its factory.
+    #[error("invalid strategy `{spec}`: {reason}")]
+    InvalidStrategy { spec: String, reason: String },
+
+    /// A bucket state does not fit the configuration.
+    #[error("invalid state: {0}")]
+    InvalidState(String),
+
+    /// A race player spec is malformed.
+    #[error("invalid player `{spec}`: {reason}")]
+    InvalidPlayer { spec: String, reason: String },
+
+    /// A race needs at least two players.
+    #[error("a race needs at least two players, but got {0}")]
+    TooFewPlayers(Num),
+
+    /// A game trace is malformed.
+    #[error("invalid trace: {0}")]
+    InvalidTrace(String),
+
+    /// A game snapshot is malformed.
+    #[error("invalid snapshot: {0}")]
+    InvalidSnapshot(String),
+
+    /// Recorded dice are malformed.
+    #[error("invalid dice: {0}")]
+    InvalidDice(String),
+
+    /// A record file is malformed.
+    #[error("invalid records: {0}")]
+    InvalidRecords(String),
+
+    /// A seed corpus is malformed.
+    #[error("invalid corpus: {0}")]
+    InvalidCorpus(String),
+
+    /// The partial results of a shard are malformed, or do not merge with the others (see [`shard`](crate::shard)).
+    #[error("invalid partial results: {0}")]
+    InvalidPartial(String),
+
+    /// Outcomes of a seed corpus differ from the ones that were recorded (see [`corpus`](crate::corpus)).
+    #[error("{0} outcomes of the corpus differ from the recorded ones")]
+    Regression(Num),
+
+    /// The serial and parallel runs of a seeded configuration differ (see [`determinism`](crate::determinism)).
+    #[error("the serial and parallel runs differ at line {0} of their output")]
+    Nondeterministic(usize),
+
+    /// Tests of a dice source failed (see [`battery`](crate::battery)).
+    #[error("{0} tests of the dice failed")]
+    FailedTests(Num),
+
+    /// A replayed game ran out of recorded dice before it was done.
+    #[error("the game ran out of dice after {0} rolls")]
+    DiceExhausted(Num),
+
+    /// A strategy broke one of the rules of the game (see [`testing`](crate::testing)).
+    #[error("invariant violated after step {step}: {reason}")]
+    InvariantViolation { step: Num, reason: String },
+
+    /// A serialized config could not be read.
+    #[error("invalid config: {0}")]
+    InvalidConfig(String),
+
+    /// A call through the C interface was malformed (e.g., a null, non-UTF-8, or out-of-range argument), or panicked.
+    #[error("invalid call: {0}")]
+    Ffi(String),
+
+    /// A server's queue of jobs is full (see [`server`](crate::server)).
+    #[cfg(feature = "server")]
+    #[error("the server is busy, with {0} jobs waiting to run")]
+    Busy(Num),
+
+    /// Jobs of a batch failed (see [`jobs`](crate::jobs)).
+    #[cfg(feature = "server")]
+    #[error("{0} jobs of the batch failed")]
+    FailedJobs(Num),
+
+    /// Results could not be published to a message queue (see [`publish`](crate::publish)).
+    #[cfg(feature = "std")]
+    #[error("unable to publish: {0}")]
+    Publish(String),
+
+    /// The simulation was cancelled before it finished.
+    #[error("the simulation was cancelled")]
+    Cancelled,
+
+    /// The thread pool could not be built.
+    #[cfg(feature = "std")]
+    #[error("unable to build the thread pool: {0}")]
+    ThreadPool(#[from] rayon::ThreadPoolBuildError),
+
+    /// The GPU backend could not find (or set up) a GPU to run on.
+    #[cfg(feature = "gpu")]
+    #[error("unable to use the gpu: {0}")]
+    Gpu(String),
+
+    /// An I/O error.
+    #[cfg(feature = "std")]
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+/// A result with a [`TenziError`].
+pub type Result<T, E = TenziError> = core::result::Result<T, E>;
\ No newline at end of file
@@ -0,0 +1,171 @@
+use alloc::vec::Vec;
+
+use crate::{observer::{GameView, Observer}, types::Num};
+
+/// A single thing that happened in a game, in the order that it happened.
+///
+/// Faces are numbered from 1.  Each step is some `Rolled` events (one per face that came up), followed by a `Kept`
+/// event for each face whose rolled dice were kept, and a `Zeroed` event for each face whose dice are re-rolled; the
+/// step that achieves a "tenzi" ends with `Done`.  With `serde`, each event serializes to a single line of NDJSON.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "event", rename_all = "lowercase"))]
+pub enum GameEvent {
+    /// The given number of dice came up with the face.
+    Rolled { face: Num, count: Num },
+    /// The policy kept the dice that were just rolled with the face.
+    Kept { face: Num },
+    /// The policy put the dice with the face back to be re-rolled.
+    Zeroed { face: Num },
+    /// A "tenzi" was achieved.
+    Done { steps: Num, rolls: Num },
+}
+
+/// A receiver of [`GameEvent`]s.
+///
+/// This is implemented for closures, and for a `Vec` that collects the events.
+pub trait EventSink: Send + Sync {
+    /// Receives the next event.
+    fn on_event(&mut self, event: GameEvent);
+}
+
+impl<F: FnMut(GameEvent) + Send + Sync> EventSink for F {
+    fn on_event(&mut self, event: GameEvent) {
+        self(event)
+    }
+}
+
+impl EventSink for Vec<GameEvent> {
+    fn on_event(&mut self, event: GameEvent) {
+        self.push(event);
+    }
+}
+
+/// An observer that turns the phases of a game into [`GameEvent`]s, and hands them to a sink.
+#[derive(Clone, Debug, Default)]
+pub struct EventObserver<S> {
+    sink: S,
+    kept: Vec<Num>,
+    rolled: Vec<Num>,
+}
+
+impl<S: EventSink> EventObserver<S> {
+    /// Returns an observer that hands every event to the sink.
+    pub fn new(sink: S) -> Self {
+        Self {
+            sink,
+            kept: Vec::new(),
+            rolled: Vec::new(),
+        }
+    }
+
+    /// Returns the sink.
+    pub fn sink(&self) -> &S {
+        &self.sink
+    }
+
+    /// Consumes the observer, and returns the sink.
+    pub fn into_sink(self) -> S {
+        self.sink
+    }
+}
+
+impl<S: EventSink> Observer for EventObserver<S> {
+    fn before_roll(&mut self, view: &GameView) {
+        self.kept.clear();
+        self.kept.extend_from_slice(view.buckets);
+    }
+
+    fn on_roll(&mut self, view: &GameView) {
+        for (face, (&rolled, &kept)) in (1..).zip(view.buckets.iter().zip(&self.kept)) {
+            if rolled > kept {
+                self.sink.on_event(GameEvent::Rolled { face, count: rolled - kept });
+            }
+        }
+
+        self.rolled.clear();
+        self.rolled.extend_from_slice(view.buckets);
+    }
+
+    fn on_keep(&mut self, view: &GameView) {
+        for (face, ((&now, &rolled), &kept)) in (1..).zip(view.buckets.iter().zip(&self.rolled).zip(&self.kept)) {
+            if now > kept {
+                self.sink.on_event(GameEvent::Kept { face });
+            }
+
+            if now < rolled {
+                self.sink.on_event(GameEvent::Zeroed { face });
+            }
+        }
+    }
+
+    fn on_done(&mut self, view: &GameView) {
+        self.sink.on_event(GameEvent::Done { steps: view.num_steps, rolls: view.num_rolls });
+    }
+}
+
+// Tests.
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+    use crate::simulation::{MergeSimulation, Strategy, Tracked};
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_events() {
+        // Roll 1, 1, and 2 with the merge strategy, which keeps the ones, and then roll the last die as a 1.
+
+        let mut dice = [1, 1, 2, 1].into_iter();
+        let mut sim = MergeSimulation::new(2, 3).with_observer(EventObserver::new(Vec::new()));
+
+        while !sim.done() {
+            sim.step_with(&mut |_| dice.next().unwrap());
+        }
+
+        assert_eq!(sim.into_observer().into_sink(), vec![
+            GameEvent::Rolled { face: 1, count: 2 },
+            GameEvent::Rolled { face: 2, count: 1 },
+            GameEvent::Kept { face: 1 },
+            GameEvent::Zeroed { face: 2 },
+            GameEvent::Rolled { face: 1, count: 1 },
+            GameEvent::Kept { face: 1 },
+            GameEvent::Done { steps: 2, rolls: 4 },
+        ]);
+    }
+
+    #[test]
+    fn test_events_replay_the_game() {
+        // The rolled dice add up to the number of rolls (rolled with a small LCG, so the test needs no `std`).
+
+        let mut state = 42u64;
+        let mut roll = |num_sides: Num| {
+            state = state.wrapping_mul(6_364_136_223_846_793_005).wrapping_add(1_442_695_040_888_963_407);
+            (state >> 33) as Num % num_sides + 1
+        };
+
+        let mut rolls = 0;
+        let mut sim = MergeSimulation::new(6, 10).with_observer(EventObserver::new(|event| {
+            if let GameEvent::Rolled { count, .. } = event {
+                rolls += count;
+            }
+        }));
+
+        while !sim.done() {
+            sim.step_with(&mut roll);
+        }
+
+        let num_rolls = sim.num_rolls();
+        drop(sim);
+
+        assert_eq!(rolls, num_rolls);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serialize() {
+        assert_eq!(serde_json::to_string(&GameEvent::Rolled { face: 3, count: 2 }).unwrap(), r#"{"event":"rolled","face":3,"count":2}"#);
+        assert_eq!(serde_json::from_str::<GameEvent>(r#"{"event":"done","steps":4,"rolls":30}"#).unwrap(), GameEvent::Done { steps: 4, rolls: 30 });
+    }
+}
\ No newline at end of file
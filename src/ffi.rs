@@ -0,0 +1,340 @@
+//! A C interface for configuring and running simulations, enabled by the `ffi` feature.
+//!
+//! The matching header is `include/tenzi_sim.h` (regenerate it with `cbindgen --config cbindgen.toml --output
+//! include/tenzi_sim.h` after changing this module), and a shared library is built with
+//! `cargo rustc --lib --release --features ffi --crate-type cdylib`.
+//!
+//! Every function that can fail returns [`TENZI_OK`] or [`TENZI_ERROR`], and the message of the last error on the
+//! calling thread is available from [`tenzi_last_error`].  A panic never unwinds into C: it is caught at the boundary,
+//! and reported as an error (or, for a function without a return code, as the last error alone).
+
+use std::{cell::RefCell, ffi::{c_char, CStr, CString}, panic::{self, AssertUnwindSafe}, ptr};
+
+use crate::{error::{Result, TenziError}, types::Num, MonteCarloBuilder, StrategySummary};
+
+/// The return code for success.
+pub const TENZI_OK: i32 = 0;
+
+/// The return code for failure; see [`tenzi_last_error`].
+pub const TENZI_ERROR: i32 = -1;
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// An opaque simulation configuration, created with [`tenzi_config_new`] and freed with [`tenzi_config_free`].
+pub struct TenziConfig {
+    builder: MonteCarloBuilder,
+}
+
+/// The statistics of a strategy, as a plain struct.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct TenziSummary {
+    pub num_simulations: u64,
+    pub average_rolls: f64,
+    pub std_dev_rolls: f64,
+    pub average_steps: f64,
+    pub std_dev_steps: f64,
+    pub duration_micros: u64,
+}
+
+impl From<&StrategySummary> for TenziSummary {
+    fn from(summary: &StrategySummary) -> Self {
+        Self {
+            num_simulations: summary.num_simulations() as u64,
+            average_rolls: summary.average_rolls() as f64,
+            std_dev_rolls: summary.std_dev_rolls() as f64,
+            average_steps: summary.average_steps() as f64,
+            std_dev_steps: summary.std_dev_steps() as f64,
+            duration_micros: summary.duration().as_micros() as u64,
+        }
+    }
+}
+
+// Configuration.
+
+/// Returns a new configuration with the defaults: 10 6-sided die, the naive strategy, and 10,000 simulations.
+#[no_mangle]
+pub extern "C" fn tenzi_config_new() -> *mut TenziConfig {
+    guard(ptr::null_mut(), || Box::into_raw(Box::new(TenziConfig { builder: MonteCarloBuilder::new() })))
+}
+
+/// Frees a configuration.
+///
+/// # Safety
+///
+/// The configuration must have come from [`tenzi_config_new`] (or be null), and must not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn tenzi_config_free(config: *mut TenziConfig) {
+    guard((), || {
+        if !config.is_null() {
+            drop(Box::from_raw(config));
+        }
+    })
+}
+
+/// Sets the number of sides on each die.
+///
+/// # Safety
+///
+/// The configuration must be valid.
+#[no_mangle]
+pub unsafe extern "C" fn tenzi_config_set_sides(config: *mut TenziConfig, num_sides: u64) -> i32 {
+    update(config, |builder| Ok(builder.sides(num(num_sides, "num_sides")?)))
+}
+
+/// Sets the number of dice.
+///
+/// # Safety
+///
+/// The configuration must be valid.
+#[no_mangle]
+pub unsafe extern "C" fn tenzi_config_set_dice(config: *mut TenziConfig, num_dice: u64) -> i32 {
+    update(config, |builder| Ok(builder.dice(num(num_dice, "num_dice")?)))
+}
+
+/// Sets the number of simulations.
+///
+/// # Safety
+///
+/// The configuration must be valid.
+#[no_mangle]
+pub unsafe extern "C" fn tenzi_config_set_simulations(config: *mut TenziConfig, num_simulations: u64) -> i32 {
+    update(config, |builder| Ok(builder.simulations(num(num_simulations, "num_simulations")?)))
+}
+
+/// Sets the number of threads to run the simulations on.
+///
+/// # Safety
+///
+/// The configuration must be valid.
+#[no_mangle]
+pub unsafe extern "C" fn tenzi_config_set_threads(config: *mut TenziConfig, num_threads: usize) -> i32 {
+    update(config, |builder| Ok(builder.threads(num_threads)))
+}
+
+/// Sets the strategy spec (e.g., "merge").
+///
+/// # Safety
+///
+/// The configuration must be valid, and the strategy must be a nul-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn tenzi_config_set_strategy(config: *mut TenziConfig, strategy: *const c_char) -> i32 {
+    update(config, |builder| {
+        let strategy = string(strategy, "strategy")?;
+        Ok(builder.strategy(strategy))
+    })
+}
+
+/// Sets the bucket state (i.e., the number of dice already kept for each face) that every game starts from.
+///
+/// # Safety
+///
+/// The configuration must be valid, and the state must point to `len` numbers.
+#[no_mangle]
+pub unsafe extern "C" fn tenzi_config_set_initial_state(config: *mut TenziConfig, state: *const u64, len: usize) -> i32 {
+    update(config, |builder| {
+        if state.is_null() {
+            return Err(null("state"));
+        }
+
+        let state = std::slice::from_raw_parts(state, len).iter().map(|&count| num(count, "state")).collect::<Result<_>>()?;
+        Ok(builder.initial_state(state))
+    })
+}
+
+// Running.
+
+/// Runs the simulations, and writes the summary to `out`.
+///
+/// # Safety
+///
+/// The configuration must be valid, and `out` must point to a writable summary.
+#[no_mangle]
+pub unsafe extern "C" fn tenzi_run(config: *const TenziConfig, out: *mut TenziSummary) -> i32 {
+    report(|| {
+        if out.is_null() {
+            return Err(null("out"));
+        }
+
+        let results = config.as_ref().ok_or_else(|| null("config"))?.builder.clone().build()?.run()?;
+        out.write(TenziSummary::from(&results.summaries()[0]));
+
+        Ok(())
+    })
+}
+
+/// Runs the simulations, and returns the full results as a JSON string, or null on failure.
+///
+/// # Safety
+///
+/// The configuration must be valid, and the string must be freed with [`tenzi_string_free`].
+#[no_mangle]
+pub unsafe extern "C" fn tenzi_run_json(config: *const TenziConfig) -> *mut c_char {
+    let mut json = ptr::null_mut();
+
+    report(|| {
+        let results = config.as_ref().ok_or_else(|| null("config"))?.builder.clone().build()?.run()?;
+        let text = serde_json::to_string(&results).map_err(|e| TenziError::Ffi(e.to_string()))?;
+
+        json = CString::new(text).map_err(|e| TenziError::Ffi(e.to_string()))?.into_raw();
+
+        Ok(())
+    });
+
+    json
+}
+
+/// Frees a string returned by this library.
+///
+/// # Safety
+///
+/// The string must have come from this library (or be null), and must not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn tenzi_string_free(string: *mut c_char) {
+    guard((), || {
+        if !string.is_null() {
+            drop(CString::from_raw(string));
+        }
+    })
+}
+
+/// Returns the message of the last error on the calling thread, or null if there has not been one.
+///
+/// The message is owned by the library, and is valid until the next failing call on the same thread.
+#[no_mangle]
+pub extern "C" fn tenzi_last_error() -> *const c_char {
+    LAST_ERROR.with(|e| e.borrow().as_ref().map_or(ptr::null(), |e| e.as_ptr()))
+}
+
+// Helpers.
+
+/// Updates the builder in a configuration.
+unsafe fn update(config: *mut TenziConfig, f: impl FnOnce(MonteCarloBuilder) -> Result<MonteCarloBuilder>) -> i32 {
+    report(|| {
+        let config = config.as_mut().ok_or_else(|| null("config"))?;
+        config.builder = f(std::mem::take(&mut config.builder))?;
+
+        Ok(())
+    })
+}
+
+/// Runs a fallible call, and records its error (if any) for [`tenzi_last_error`].
+fn report(f: impl FnOnce() -> Result<()>) -> i32 {
+    guard(TENZI_ERROR, || match f() {
+        Ok(()) => TENZI_OK,
+        Err(e) => {
+            record(&e);
+            TENZI_ERROR
+        }
+    })
+}
+
+/// Runs the body of an entry point, and returns the fallback if it panics (recording the panic for
+/// [`tenzi_last_error`]), since unwinding into C is undefined behavior.
+fn guard<T>(fallback: T, f: impl FnOnce() -> T) -> T {
+    panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or_else(|payload| {
+        let message = payload.downcast_ref::<&str>().copied().or_else(|| payload.downcast_ref::<String>().map(String::as_str)).unwrap_or("unknown panic");
+
+        record(&TenziError::Ffi(format!("the library panicked: {}", message)));
+        fallback
+    })
+}
+
+/// Records an error for [`tenzi_last_error`].
+fn record(error: &TenziError) {
+    let message = CString::new(error.to_string()).unwrap_or_default();
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+}
+
+/// Converts a count argument to the counter type, which fails rather than truncates if it does not fit (e.g., with the
+/// `num-u32` feature).
+fn num(value: u64, name: &str) -> Result<Num> {
+    Num::try_from(value).map_err(|_| TenziError::Ffi(format!("`{}` is out of range ({} is more than {})", name, value, Num::MAX)))
+}
+
+/// Reads a nul-terminated string argument.
+unsafe fn string<'a>(s: *const c_char, name: &str) -> Result<&'a str> {
+    if s.is_null() {
+        return Err(null(name));
+    }
+
+    CStr::from_ptr(s).to_str().map_err(|e| TenziError::Ffi(e.to_string()))
+}
+
+/// Builds the error for a null argument.
+fn null(name: &str) -> TenziError {
+    TenziError::Ffi(format!("`{}` is null", name))
+}
+
+// Tests.
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_run() {
+        unsafe {
+            let config = tenzi_config_new();
+            let state = [0, 10, 0, 0, 0, 0];
+
+            assert_eq!(tenzi_config_set_strategy(config, c"merge".as_ptr()), TENZI_OK);
+            assert_eq!(tenzi_config_set_initial_state(config, state.as_ptr(), state.len()), TENZI_OK);
+            assert_eq!(tenzi_config_set_simulations(config, 10), TENZI_OK);
+
+            let mut summary = TenziSummary::default();
+            assert_eq!(tenzi_run(config, &mut summary), TENZI_OK);
+
+            assert_eq!(summary.num_simulations, 10);
+            assert_eq!(summary.average_rolls, 0.0);
+
+            let json = tenzi_run_json(config);
+            assert!(CStr::from_ptr(json).to_str().unwrap().contains("\"strategy\":\"merge\""));
+
+            tenzi_string_free(json);
+            tenzi_config_free(config);
+        }
+    }
+
+    #[test]
+    fn test_errors() {
+        unsafe {
+            let config = tenzi_config_new();
+
+            assert_eq!(tenzi_config_set_sides(config, 0), TENZI_OK);
+            assert_eq!(tenzi_run(config, &mut TenziSummary::default()), TENZI_ERROR);
+            assert_eq!(CStr::from_ptr(tenzi_last_error()).to_str().unwrap(), "the die must have at least one side");
+
+            assert_eq!(tenzi_config_set_strategy(config, ptr::null()), TENZI_ERROR);
+            assert_eq!(CStr::from_ptr(tenzi_last_error()).to_str().unwrap(), "invalid call: `strategy` is null");
+
+            // A count that does not fit the counter type is an error, rather than truncated.
+
+            #[cfg(feature = "num-u32")]
+            {
+                assert_eq!(tenzi_config_set_dice(config, u64::from(u32::MAX) + 1), TENZI_ERROR);
+                assert!(CStr::from_ptr(tenzi_last_error()).to_str().unwrap().contains("`num_dice` is out of range"));
+            }
+
+            tenzi_config_free(config);
+        }
+
+        // A panic is caught at the boundary, and reported like an error.
+
+        assert_eq!(report(|| panic!("boom")), TENZI_ERROR);
+        assert_eq!(unsafe { CStr::from_ptr(tenzi_last_error()) }.to_str().unwrap(), "invalid call: the library panicked: boom");
+        assert_eq!(guard(7, || -> i32 { panic!("boom") }), 7);
+    }
+
+    #[test]
+    fn test_header() {
+        let header = include_str!("../include/tenzi_sim.h");
+
+        for function in ["tenzi_config_new", "tenzi_config_free", "tenzi_config_set_sides", "tenzi_config_set_dice", "tenzi_config_set_simulations", "tenzi_config_set_threads", "tenzi_config_set_strategy", "tenzi_config_set_initial_state", "tenzi_run", "tenzi_run_json", "tenzi_string_free", "tenzi_last_error"] {
+            assert!(header.contains(&format!("{}(", function)), "the header is missing `{}`", function);
+        }
+    }
+}
\ No newline at end of file
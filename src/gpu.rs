@@ -0,0 +1,291 @@
+//! An experimental backend that plays naive games in a compute shader (see `src/gpu.wgsl`), thousands per dispatch,
+//! and aggregates their outcomes on the CPU.
+//!
+//! The naive strategy only ever keeps the mode of the first roll, so after the first step a game is a count of the
+//! dice that match it, which is exactly the kind of independent, branch-light work that a GPU is built for.  The dice
+//! are rolled from a counter-based rng, so a seed plays the same games however they are dispatched, and
+//! [`play_on_cpu`] plays the same games as the shader (which is how the shader is tested).
+
+use std::time::Instant;
+
+use wgpu::util::DeviceExt;
+
+use crate::{cancel::CancelToken, error::{Result, TenziError}, metrics::{GameOutcome, MetricSink, Moments}, monte_carlo::Tally, progress::{Progress, ProgressHook}, results::StrategySummary, simulation::StrategyKind, types::Num};
+
+/// The most sides that a die can have, since every bucket is held by the invocation that plays the game.
+pub const MAX_SIDES: Num = 64;
+
+/// The number of games in each dispatch.
+const BATCH_SIZE: u32 = 1 << 16;
+
+/// The number of games that each workgroup plays (which must match the shader).
+const WORKGROUP_SIZE: u32 = 64;
+
+/// The size of the outcome of a game in the output buffer (i.e., the rolls and steps, as `u32`s).
+const OUTCOME_SIZE: u64 = 8;
+
+/// Runs an entire monte carlo simulation of the naive strategy on the GPU, like
+/// [`monte_carlo_cancellable`](crate::monte_carlo_cancellable), but with the games rolled from the given seed (or a
+/// random one).
+///
+/// Fails with [`TenziError::Gpu`] if there is no GPU to run on, and with [`TenziError::Cancelled`] only if the token
+/// is cancelled before any game is completed (otherwise, the summary covers the dispatches that were completed).
+pub fn monte_carlo_gpu(num_sides: Num, num_dice: Num, num_simulations: Num, histogram: bool, seed: Option<u64>, cancel: &CancelToken, progress: ProgressHook<impl Fn(&Progress) + Send + Sync>) -> Result<StrategySummary> {
+    check(num_sides, num_dice, num_simulations)?;
+
+    let start = Instant::now();
+    let seed = seed.unwrap_or_else(rand::random);
+    let gpu = Gpu::new(num_sides as u32, num_dice as u32, seed)?;
+
+    let mut moments = Moments::new(histogram);
+    let mut tally = Tally::default();
+
+    let mut first_game = 0;
+    while first_game < num_simulations as u32 && !cancel.is_cancelled() {
+        let num_games = BATCH_SIZE.min(num_simulations as u32 - first_game);
+
+        let before = tally.completed;
+
+        for outcome in gpu.play(first_game, num_games)? {
+            moments.record(&outcome);
+            tally = tally.add(&outcome);
+        }
+
+        first_game += num_games;
+
+        // Report once per dispatch, since that is when the outcomes arrive.
+
+        if progress.is_due(before, tally.completed) {
+            progress.report(&tally.progress(num_simulations));
+        }
+    }
+
+    progress.report(&tally.progress(num_simulations));
+
+    if moments.num_games() == 0 {
+        return Err(TenziError::Cancelled);
+    }
+
+    Ok(moments.summarize(StrategyKind::Naive.name(), start.elapsed()))
+}
+
+/// Ensures that a configuration fits the shader.
+pub(crate) fn check(num_sides: Num, num_dice: Num, num_simulations: Num) -> Result<()> {
+    if num_sides > MAX_SIDES {
+        return Err(TenziError::InvalidConfig(format!("the gpu backend supports dice with at most {MAX_SIDES} sides")));
+    }
+
+    if num_dice as u64 > u32::MAX as u64 || num_simulations as u64 > u32::MAX as u64 {
+        return Err(TenziError::InvalidConfig("the gpu backend counts dice and games with 32 bits".to_string()));
+    }
+
+    Ok(())
+}
+
+/// Plays the game with the given index, from the given seed, exactly as the shader does.
+pub fn play_on_cpu(seed: u64, game: u32, num_sides: u32, num_dice: u32) -> GameOutcome {
+    let mut rng = CounterRng { key: hash(seed as u32 ^ hash((seed >> 32) as u32 ^ hash(game))), counter: 0 };
+
+    // The first step rolls every die, and the mode (the last face with the most dice) is kept from then on.
+
+    let mut buckets = [0u32; MAX_SIDES as usize];
+    for _ in 0..num_dice {
+        buckets[rng.roll(num_sides) as usize] += 1;
+    }
+
+    let mode = (1..num_sides as usize).fold(0, |mode, k| if buckets[k] >= buckets[mode] { k } else { mode });
+
+    let mut kept = buckets[mode];
+    let mut outcome = GameOutcome { num_rolls: num_dice as Num, num_steps: 1 };
+
+    // Every later step only rolls the dice that are not kept, and keeps the ones that show the mode.
+
+    while kept < num_dice {
+        let num_to_roll = num_dice - kept;
+        kept += (0..num_to_roll).filter(|_| rng.roll(num_sides) as usize == mode).count() as u32;
+
+        outcome.num_rolls += num_to_roll as Num;
+        outcome.num_steps += 1;
+    }
+
+    outcome
+}
+
+/// The PCG hash (Jarzynski and Olano, 2020).
+fn hash(x: u32) -> u32 {
+    let state = x.wrapping_mul(747796405).wrapping_add(2891336453);
+    let word = ((state >> ((state >> 28) + 4)) ^ state).wrapping_mul(277803737);
+    (word >> 22) ^ word
+}
+
+/// The shader's counter-based rng: each draw hashes the game's key with the number of draws so far.
+struct CounterRng {
+    key: u32,
+    counter: u32,
+}
+
+impl CounterRng {
+    fn draw(&mut self) -> u32 {
+        self.counter = self.counter.wrapping_add(1);
+        hash(self.key ^ hash(self.counter))
+    }
+
+    /// Returns the index of a face, rejecting the draws that would bias the low faces.
+    fn roll(&mut self, num_sides: u32) -> u32 {
+        let threshold = num_sides.wrapping_neg() % num_sides;
+
+        let mut x = self.draw();
+        while x < threshold {
+            x = self.draw();
+        }
+
+        x % num_sides
+    }
+}
+
+/// A device with the shader, and the buffers that every dispatch reuses.
+struct Gpu {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    pipeline: wgpu::ComputePipeline,
+    bind_group: wgpu::BindGroup,
+    params: wgpu::Buffer,
+    outcomes: wgpu::Buffer,
+    staging: wgpu::Buffer,
+    num_sides: u32,
+    num_dice: u32,
+    seed: u64,
+}
+
+impl Gpu {
+    /// Sets up the first adapter that is available.
+    fn new(num_sides: u32, num_dice: u32, seed: u64) -> Result<Self> {
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor::default());
+        let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions::default())).map_err(|e| TenziError::Gpu(e.to_string()))?;
+        let (device, queue) = pollster::block_on(adapter.request_device(&wgpu::DeviceDescriptor::default())).map_err(|e| TenziError::Gpu(e.to_string()))?;
+
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("tenzi"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("gpu.wgsl").into()),
+        });
+
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("tenzi"),
+            layout: None,
+            module: &module,
+            entry_point: Some("main"),
+            compilation_options: Default::default(),
+            cache: None,
+        });
+
+        // The params are rewritten before every dispatch, and the outcomes are copied out to be read.
+
+        let params = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("params"),
+            contents: &[0; 24],
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let size = BATCH_SIZE as u64 * OUTCOME_SIZE;
+        let outcomes = device.create_buffer(&wgpu::BufferDescriptor { label: Some("outcomes"), size, usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC, mapped_at_creation: false });
+        let staging = device.create_buffer(&wgpu::BufferDescriptor { label: Some("staging"), size, usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST, mapped_at_creation: false });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("tenzi"),
+            layout: &pipeline.get_bind_group_layout(0),
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: params.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 1, resource: outcomes.as_entire_binding() },
+            ],
+        });
+
+        Ok(Self { device, queue, pipeline, bind_group, params, outcomes, staging, num_sides, num_dice, seed })
+    }
+
+    /// Plays the given number of games (at most [`BATCH_SIZE`]), starting from the given game index.
+    fn play(&self, first_game: u32, num_games: u32) -> Result<Vec<GameOutcome>> {
+        let params = [self.seed as u32, (self.seed >> 32) as u32, first_game, num_games, self.num_sides, self.num_dice];
+        self.queue.write_buffer(&self.params, 0, &params.iter().flat_map(|word| word.to_le_bytes()).collect::<Vec<_>>());
+
+        let size = num_games as u64 * OUTCOME_SIZE;
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor::default());
+            pass.set_pipeline(&self.pipeline);
+            pass.set_bind_group(0, &self.bind_group, &[]);
+            pass.dispatch_workgroups(num_games.div_ceil(WORKGROUP_SIZE), 1, 1);
+        }
+
+        encoder.copy_buffer_to_buffer(&self.outcomes, 0, &self.staging, 0, size);
+        self.queue.submit([encoder.finish()]);
+
+        // Wait for the outcomes, and read them out.
+
+        let slice = self.staging.slice(..size);
+        slice.map_async(wgpu::MapMode::Read, |_| {});
+        self.device.poll(wgpu::PollType::Wait).map_err(|e| TenziError::Gpu(e.to_string()))?;
+
+        let outcomes = slice
+            .get_mapped_range()
+            .chunks_exact(OUTCOME_SIZE as usize)
+            .map(|outcome| GameOutcome {
+                num_rolls: u32::from_le_bytes(outcome[0..4].try_into().unwrap()) as Num,
+                num_steps: u32::from_le_bytes(outcome[4..8].try_into().unwrap()) as Num,
+            })
+            .collect();
+
+        self.staging.unmap();
+
+        Ok(outcomes)
+    }
+}
+
+// Tests.
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{monte_carlo, simulation::{NaiveSimulation, SimulationType}, types::Float};
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_play_on_cpu_matches_naive() {
+        // The shader's games are the naive strategy's games, so their averages agree.
+
+        let num_games = 20_000;
+        let total_rolls = (0..num_games).map(|game| play_on_cpu(42, game, 6, 10).num_rolls).sum::<Num>();
+        let expected = monte_carlo(SimulationType::Naive(NaiveSimulation::new(6, 10)), num_games as Num).average_rolls();
+
+        assert!((total_rolls as Float / num_games as Float - expected).abs() / expected < 0.05);
+    }
+
+    #[test]
+    fn test_play_on_cpu_one_die() {
+        assert_eq!(play_on_cpu(42, 0, 6, 1), GameOutcome { num_rolls: 1, num_steps: 1 });
+        assert_eq!(play_on_cpu(42, 0, 1, 10), GameOutcome { num_rolls: 10, num_steps: 1 });
+    }
+
+    #[test]
+    fn test_check() {
+        assert!(check(64, 10, 1_000).is_ok());
+        assert!(matches!(check(65, 10, 1_000), Err(TenziError::InvalidConfig(_))));
+    }
+
+    #[test]
+    fn test_gpu_matches_cpu() {
+        // Plays the same games as the CPU, where there is a GPU to play them on.
+
+        let gpu = match Gpu::new(6, 10, 42) {
+            Ok(gpu) => gpu,
+            Err(TenziError::Gpu(_)) => return,
+            Err(e) => panic!("{e}"),
+        };
+
+        let outcomes = gpu.play(100, 1_000).unwrap();
+
+        for (game, outcome) in (100..).zip(outcomes) {
+            assert_eq!(outcome, play_on_cpu(42, game, 6, 10));
+        }
+    }
+}
\ No newline at end of file
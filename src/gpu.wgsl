@@ -0,0 +1,92 @@
+// Plays naive games of "tenzi", one per invocation (see `src/gpu.rs`, which mirrors this shader on the CPU).
+
+struct Params {
+    seed_lo: u32,
+    seed_hi: u32,
+    first_game: u32,
+    num_games: u32,
+    num_sides: u32,
+    num_dice: u32,
+}
+
+// The most sides that a die can have, since every bucket is held by the invocation.
+const MAX_SIDES: u32 = 64u;
+
+@group(0) @binding(0) var<uniform> params: Params;
+@group(0) @binding(1) var<storage, read_write> outcomes: array<vec2<u32>>;
+
+// The PCG hash (Jarzynski and Olano, 2020).
+fn hash(x: u32) -> u32 {
+    let state = x * 747796405u + 2891336453u;
+    let word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+    return (word >> 22u) ^ word;
+}
+
+// A counter-based rng: each draw hashes the game's key with the number of draws so far, so every game is independent
+// of every other (and of how the games are dispatched).
+struct Rng {
+    key: u32,
+    counter: u32,
+}
+
+fn draw(rng: ptr<function, Rng>) -> u32 {
+    (*rng).counter += 1u;
+    return hash((*rng).key ^ hash((*rng).counter));
+}
+
+// Returns the index of a face, rejecting the draws that would bias the low faces.
+fn roll(rng: ptr<function, Rng>, num_sides: u32) -> u32 {
+    let threshold = (0u - num_sides) % num_sides;
+
+    var x = draw(rng);
+    while (x < threshold) {
+        x = draw(rng);
+    }
+
+    return x % num_sides;
+}
+
+@compute @workgroup_size(64)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let index = id.x;
+    if (index >= params.num_games) {
+        return;
+    }
+
+    var rng = Rng(hash(params.seed_lo ^ hash(params.seed_hi ^ hash(params.first_game + index))), 0u);
+
+    // The first step rolls every die, and the mode (the last face with the most dice) is kept from then on.
+
+    var buckets: array<u32, MAX_SIDES>;
+    for (var i = 0u; i < params.num_dice; i++) {
+        buckets[roll(&rng, params.num_sides)] += 1u;
+    }
+
+    var mode = 0u;
+    for (var k = 1u; k < params.num_sides; k++) {
+        if (buckets[k] >= buckets[mode]) {
+            mode = k;
+        }
+    }
+
+    var kept = buckets[mode];
+    var rolls = params.num_dice;
+    var steps = 1u;
+
+    // Every later step only rolls the dice that are not kept, and keeps the ones that show the mode.
+
+    while (kept < params.num_dice) {
+        let num_to_roll = params.num_dice - kept;
+
+        for (var i = 0u; i < num_to_roll; i++) {
+            if (roll(&rng, params.num_sides) == mode) {
+                kept += 1u;
+            }
+        }
+
+        rolls += num_to_roll;
+        steps += 1u;
+    }
+
+    outcomes[index] = vec2<u32>(rolls, steps);
+}
\ No newline at end of file
@@ -0,0 +1,343 @@
+//! A gRPC service that mirrors the REST API of the [`server`], for clients that speak gRPC (see
+//! `proto/tenzi_sim.proto`, which ships with the crate, for the service definition).
+//!
+//! The service runs its jobs on a [`JobQueue`], which it can share with the REST API (see
+//! [`serve_jobs`](crate::server::serve_jobs)), so that a job that is submitted over one can be followed over the other.
+//! Errors map onto the usual status codes: an invalid job is `INVALID_ARGUMENT`, a full queue is `RESOURCE_EXHAUSTED`,
+//! a job that does not exist is `NOT_FOUND`, and the results of a job that is not done are `FAILED_PRECONDITION`.
+
+use std::{net::SocketAddr, sync::Arc};
+
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{Request, Response, Status};
+
+use crate::{config::MonteCarloBuilder, error::{Result, TenziError}, monte_carlo::Execution, results::RunResults, server::{self, JobQueue, JobSnapshot, JobSpec, SweepParameter, STREAM_INTERVAL}, types::{Float, Num}};
+
+/// The messages and the service of the definition, as generated by `tonic-build`.
+pub mod proto {
+    tonic::include_proto!("tenzi_sim");
+}
+
+use proto::{job_status, simulator_server::{Simulator, SimulatorServer}, submit_job_request::Kind};
+
+/// The reply of a call of the service.
+type Reply<T> = Result<Response<T>, Status>;
+
+/// The gRPC service, over a queue of jobs.
+pub struct SimulatorService {
+    jobs: Arc<JobQueue>,
+}
+
+impl SimulatorService {
+    /// Returns a service that runs its jobs on the given queue.
+    pub fn new(jobs: Arc<JobQueue>) -> Self {
+        Self { jobs }
+    }
+
+    /// Wraps the service in its server, to be added to a [`tonic::transport::Server`].
+    pub fn into_server(self) -> SimulatorServer<Self> {
+        SimulatorServer::new(self)
+    }
+
+    /// Returns the status of the job, or fails with `NOT_FOUND` if there is no such job.
+    #[allow(clippy::result_large_err)]
+    fn job_status(&self, id: u64) -> Result<proto::JobStatus, Status> {
+        self.jobs.status(id).map(|status| job_status(id, status)).ok_or_else(not_found)
+    }
+}
+
+#[tonic::async_trait]
+impl Simulator for SimulatorService {
+    type StreamJobStream = ReceiverStream<Result<proto::JobSnapshot, Status>>;
+
+    async fn list_strategies(&self, _request: Request<proto::ListStrategiesRequest>) -> Reply<proto::ListStrategiesResponse> {
+        Ok(Response::new(proto::ListStrategiesResponse { strategies: self.jobs.strategies().map(String::from).collect() }))
+    }
+
+    async fn submit_job(&self, request: Request<proto::SubmitJobRequest>) -> Reply<proto::SubmitJobResponse> {
+        let id = job_spec(request.into_inner()).and_then(|spec| self.jobs.submit(spec)).map_err(|e| match e {
+            TenziError::Busy(_) => Status::resource_exhausted(e.to_string()),
+            e => Status::invalid_argument(e.to_string()),
+        })?;
+
+        Ok(Response::new(proto::SubmitJobResponse { id }))
+    }
+
+    async fn list_jobs(&self, _request: Request<proto::ListJobsRequest>) -> Reply<proto::ListJobsResponse> {
+        let jobs = self.jobs.ids().into_iter().filter_map(|id| self.job_status(id).ok()).collect();
+
+        Ok(Response::new(proto::ListJobsResponse { jobs }))
+    }
+
+    async fn get_job(&self, request: Request<proto::JobRequest>) -> Reply<proto::JobStatus> {
+        Ok(Response::new(self.job_status(request.into_inner().id)?))
+    }
+
+    async fn stream_job(&self, request: Request<proto::JobRequest>) -> Reply<Self::StreamJobStream> {
+        let id = request.into_inner().id;
+        self.job_status(id)?;
+
+        let (sender, receiver) = tokio::sync::mpsc::channel(4);
+        let jobs = self.jobs.clone();
+
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(STREAM_INTERVAL);
+
+            loop {
+                interval.tick().await;
+
+                let Some(snapshot) = jobs.snapshot(id) else {
+                    let _ = sender.send(Err(Status::not_found("the job was cancelled"))).await;
+                    return;
+                };
+
+                let over = matches!(snapshot.status, server::JobStatus::Done | server::JobStatus::Failed { .. });
+
+                // Stop once the job is over, or the client hangs up.
+
+                if sender.send(Ok(job_snapshot(snapshot))).await.is_err() || over {
+                    return;
+                }
+            }
+        });
+
+        Ok(Response::new(ReceiverStream::new(receiver)))
+    }
+
+    async fn get_results(&self, request: Request<proto::JobRequest>) -> Reply<proto::JobResults> {
+        let id = request.into_inner().id;
+
+        match self.jobs.results(id) {
+            Some(results) => Ok(Response::new(proto::JobResults { results: results.iter().map(run_results).collect() })),
+            None => {
+                self.job_status(id)?;
+                Err(Status::failed_precondition("the job is not done"))
+            }
+        }
+    }
+
+    async fn cancel_job(&self, request: Request<proto::JobRequest>) -> Reply<proto::CancelJobResponse> {
+        match self.jobs.remove(request.into_inner().id) {
+            true => Ok(Response::new(proto::CancelJobResponse {})),
+            false => Err(not_found()),
+        }
+    }
+}
+
+/// Serves the service on the given address (e.g., "127.0.0.1:50051"), running its jobs on the queue, until the process
+/// exits.
+pub async fn serve(address: SocketAddr, jobs: Arc<JobQueue>) -> Result<()> {
+    let server = tonic::transport::Server::builder().add_service(SimulatorService::new(jobs).into_server());
+
+    server.serve(address).await.map_err(|e| TenziError::Io(std::io::Error::other(e)))
+}
+
+/// Converts a submitted job into its spec.
+fn job_spec(request: proto::SubmitJobRequest) -> Result<JobSpec> {
+    Ok(match request.kind.ok_or_else(|| TenziError::InvalidConfig("a job needs a kind".to_string()))? {
+        Kind::Simulate(job) => JobSpec::Simulate { config: config(job.config)? },
+        Kind::Compare(job) => JobSpec::Compare { config: config(job.config)?, strategies: job.strategies, adaptive: None },
+        Kind::Sweep(job) => {
+            let parameter = match proto::SweepParameter::try_from(job.parameter) {
+                Ok(proto::SweepParameter::Sides) => SweepParameter::Sides,
+                Ok(proto::SweepParameter::Dice) => SweepParameter::Dice,
+                Ok(proto::SweepParameter::Simulations) => SweepParameter::Simulations,
+                Err(_) => return Err(TenziError::InvalidConfig(format!("`{}` is not a sweep parameter", job.parameter))),
+            };
+
+            JobSpec::Sweep { config: config(job.config)?, parameter, values: job.values.into_iter().map(|value| value as Num).collect() }
+        }
+    })
+}
+
+/// Converts a configuration into a builder, where every missing field keeps its default.
+fn config(config: Option<proto::Config>) -> Result<MonteCarloBuilder> {
+    let config = config.unwrap_or_default();
+    let mut builder = MonteCarloBuilder::new();
+
+    if let Some(num_sides) = config.num_sides {
+        builder = builder.sides(num_sides as Num);
+    }
+
+    if let Some(num_dice) = config.num_dice {
+        builder = builder.dice(num_dice as Num);
+    }
+
+    if let Some(strategy) = config.strategy {
+        builder = builder.strategy(strategy);
+    }
+
+    if !config.initial_state.is_empty() {
+        builder = builder.initial_state(config.initial_state.into_iter().map(|face| face as Num).collect());
+    }
+
+    if let Some(num_simulations) = config.num_simulations {
+        builder = builder.simulations(num_simulations as Num);
+    }
+
+    if let Some(histogram) = config.histogram {
+        builder = builder.histogram(histogram);
+    }
+
+    if config.serial == Some(true) {
+        builder = builder.execution(Execution::Serial);
+    }
+
+    if let Some(block_size) = config.block_size {
+        builder = builder.block_size(block_size as Num);
+    }
+
+    if let Some(backend) = config.backend {
+        builder = builder.backend(backend.parse()?);
+    }
+
+    if let Some(seed) = config.seed {
+        builder = builder.seed(seed);
+    }
+
+    if let Some(rng) = config.rng {
+        builder = builder.rng(rng.parse()?);
+    }
+
+    if !config.weights.is_empty() {
+        builder = builder.weights(config.weights.into_iter().map(|weight| weight as Float).collect());
+    }
+
+    Ok(builder)
+}
+
+/// Converts the status of a job into its message.
+fn job_status(id: u64, status: server::JobStatus) -> proto::JobStatus {
+    let status = match status {
+        server::JobStatus::Queued { position } => job_status::Status::Queued(job_status::Queued { position: position as u64 }),
+        server::JobStatus::Running { run, runs, progress } => job_status::Status::Running(job_status::Running {
+            run: run as u64,
+            runs: runs as u64,
+            progress: Some(proto::Progress { completed: progress.completed as u64, total: progress.total as u64, total_rolls: progress.total_rolls as u64, total_steps: progress.total_steps as u64 }),
+        }),
+        server::JobStatus::Done => job_status::Status::Done(job_status::Done {}),
+        server::JobStatus::Failed { error } => job_status::Status::Failed(job_status::Failed { error }),
+    };
+
+    proto::JobStatus { id, status: Some(status) }
+}
+
+/// Converts a snapshot of a job into its message.
+fn job_snapshot(snapshot: JobSnapshot) -> proto::JobSnapshot {
+    proto::JobSnapshot {
+        job: Some(job_status(snapshot.id, snapshot.status)),
+        histogram: snapshot.histogram.unwrap_or_default(),
+        histogram_low: snapshot.bands.iter().flatten().map(|[low, _]| *low as f64).collect(),
+        histogram_high: snapshot.bands.iter().flatten().map(|[_, high]| *high as f64).collect(),
+        results: snapshot.results.iter().flatten().map(run_results).collect(),
+    }
+}
+
+/// Converts the results of a run into their message.
+fn run_results(results: &RunResults) -> proto::RunResults {
+    let parameters = results.parameters();
+
+    proto::RunResults {
+        parameters: Some(proto::RunParameters {
+            num_sides: parameters.num_sides as u64,
+            num_dice: parameters.num_dice as u64,
+            num_simulations: parameters.num_simulations as u64,
+            initial_state: parameters.initial_state.iter().flatten().map(|&face| face as u64).collect(),
+            rng: parameters.rng.clone(),
+        }),
+        summaries: results.summaries().iter().map(|summary| proto::StrategySummary {
+            strategy: summary.strategy().to_string(),
+            num_simulations: summary.num_simulations() as u64,
+            average_rolls: summary.average_rolls() as f64,
+            std_dev_rolls: summary.std_dev_rolls() as f64,
+            average_steps: summary.average_steps() as f64,
+            std_dev_steps: summary.std_dev_steps() as f64,
+            rolls_histogram: summary.rolls_histogram().into_iter().flatten().map(|&count| count as u64).collect(),
+            duration_seconds: summary.duration().as_secs_f64(),
+        }).collect(),
+    }
+}
+
+/// Returns the status for a job that does not exist.
+fn not_found() -> Status {
+    Status::not_found("no such job")
+}
+
+// Tests.
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::ServerOptions;
+    use pretty_assertions::assert_eq;
+    use tokio_stream::StreamExt;
+
+    fn block_on<F: std::future::Future>(future: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap().block_on(future)
+    }
+
+    fn simulate(config: proto::Config) -> Request<proto::SubmitJobRequest> {
+        Request::new(proto::SubmitJobRequest { kind: Some(Kind::Simulate(proto::SimulateJob { config: Some(config) })) })
+    }
+
+    #[test]
+    fn test_service() {
+        let service = SimulatorService::new(Arc::new(JobQueue::new(ServerOptions { num_threads: Some(2), ..ServerOptions::default() }).unwrap()));
+
+        block_on(async {
+            let strategies = service.list_strategies(Request::new(proto::ListStrategiesRequest {})).await.unwrap().into_inner().strategies;
+            assert_eq!(strategies[0], "naive");
+
+            let config = proto::Config { num_simulations: Some(1_000), seed: Some(42), histogram: Some(true), ..Default::default() };
+            let id = service.submit_job(simulate(config)).await.unwrap().into_inner().id;
+
+            // The stream follows the job until it is done, when its last snapshot carries the results.
+
+            let snapshots = service.stream_job(Request::new(proto::JobRequest { id })).await.unwrap().into_inner().collect::<Vec<_>>().await;
+            let last = snapshots.last().unwrap().as_ref().unwrap();
+
+            assert_eq!(last.job.as_ref().unwrap().status, Some(job_status::Status::Done(job_status::Done {})));
+            assert_eq!(last.results[0].summaries[0].num_simulations, 1_000);
+
+            let results = service.get_results(Request::new(proto::JobRequest { id })).await.unwrap().into_inner().results;
+
+            assert_eq!(results, last.results);
+            assert_eq!(results[0].summaries[0].rolls_histogram.iter().sum::<u64>(), 1_000);
+            assert_eq!(service.list_jobs(Request::new(proto::ListJobsRequest {})).await.unwrap().into_inner().jobs.len(), 1);
+
+            service.cancel_job(Request::new(proto::JobRequest { id })).await.unwrap();
+            assert_eq!(service.get_job(Request::new(proto::JobRequest { id })).await.unwrap_err().code(), tonic::Code::NotFound);
+        });
+    }
+
+    #[test]
+    fn test_service_rejected() {
+        let service = SimulatorService::new(Arc::new(JobQueue::new(ServerOptions { max_queued: 1, num_threads: Some(1), ..ServerOptions::default() }).unwrap()));
+
+        block_on(async {
+            let rejected = |config| async { service.submit_job(simulate(config)).await.unwrap_err().code() };
+
+            assert_eq!(rejected(proto::Config { num_dice: Some(0), ..Default::default() }).await, tonic::Code::InvalidArgument);
+            assert_eq!(rejected(proto::Config { rng: Some("mt".to_string()), ..Default::default() }).await, tonic::Code::InvalidArgument);
+            assert_eq!(service.submit_job(Request::new(proto::SubmitJobRequest { kind: None })).await.unwrap_err().code(), tonic::Code::InvalidArgument);
+
+            // A job that is not done has no results yet, and the queue turns jobs away once it is full.
+
+            let long = proto::Config { num_simulations: Some(1_000_000_000), ..Default::default() };
+            let running = service.submit_job(simulate(long.clone())).await.unwrap().into_inner().id;
+
+            while !matches!(service.jobs.status(running), Some(server::JobStatus::Running { .. })) {
+                tokio::task::yield_now().await;
+            }
+
+            let queued = service.submit_job(simulate(long.clone())).await.unwrap().into_inner().id;
+
+            assert_eq!(service.get_results(Request::new(proto::JobRequest { id: queued })).await.unwrap_err().code(), tonic::Code::FailedPrecondition);
+            assert_eq!(rejected(long).await, tonic::Code::ResourceExhausted);
+            assert_eq!(service.get_results(Request::new(proto::JobRequest { id: 7 })).await.unwrap_err().code(), tonic::Code::NotFound);
+
+            service.cancel_job(Request::new(proto::JobRequest { id: running })).await.unwrap();
+            service.cancel_job(Request::new(proto::JobRequest { id: queued })).await.unwrap();
+        });
+    }
+}
\ No newline at end of file
@@ -0,0 +1,693 @@
+//! Primitives over histograms of dice (i.e., a count for each face, like a game's buckets), for strategies to decide
+//! what to keep with.
+//!
+//! Faces are numbered from 1, so the face of the count at index `k` is `k + 1`.
+//!
+//! The helpers take a dense slice of counts, and most have a counterpart that is generic over [`Counts`], so that a
+//! sparse histogram (see [`Sparse`]) only costs the faces with dice, rather than every face (e.g., for 10 dice on a
+//! 100,000-sided die).  A histogram may also know its faces with dice as a [`FaceMask`], with which the helpers count
+//! and find them with bit operations rather than a scan.
+
+use alloc::vec::Vec;
+
+use super::types::Num;
+
+/// A histogram of dice, which the mode helpers are generic over: either dense (a slice, with a count for every face) or
+/// sparse (see [`Sparse`]).
+pub trait Counts {
+    /// Returns the number of faces.
+    fn num_faces(&self) -> usize;
+
+    /// Returns the count of the face at the given index.
+    fn count(&self, k: usize) -> Num;
+
+    /// Returns the indices and counts of the faces in ascending order, which include every face with dice (and may
+    /// skip faces without).
+    fn entries(&self) -> impl Iterator<Item = (usize, Num)> + Clone + '_;
+
+    /// Returns the faces with dice as a mask, if the histogram keeps track of them.
+    fn mask(&self) -> Option<FaceMask> {
+        None
+    }
+}
+
+impl Counts for [Num] {
+    fn num_faces(&self) -> usize {
+        self.len()
+    }
+
+    fn count(&self, k: usize) -> Num {
+        self[k]
+    }
+
+    fn entries(&self) -> impl Iterator<Item = (usize, Num)> + Clone + '_ {
+        self.iter().copied().enumerate()
+    }
+}
+
+/// A sparse histogram: the counts of a dense one, but only at the given indices (e.g., the faces with dice, which a
+/// game keeps track of as they are rolled), so that a scan costs the number of dice rather than the number of faces.
+#[derive(Clone, Copy, Debug)]
+pub struct Sparse<'a> {
+    counts: &'a [Num],
+    faces: &'a [usize],
+    mask: Option<FaceMask>,
+}
+
+impl<'a> Sparse<'a> {
+    /// Returns the histogram of the counts at the given indices, which must be in ascending order, and include every
+    /// count that is not zero.
+    pub fn new(counts: &'a [Num], faces: &'a [usize]) -> Self {
+        Self { counts, faces, mask: None }
+    }
+
+    /// Attaches the mask of the faces with dice (which must be exactly the counts that are not zero), if there is one.
+    pub fn with_mask(mut self, mask: Option<FaceMask>) -> Self {
+        self.mask = mask;
+        self
+    }
+}
+
+impl Counts for Sparse<'_> {
+    fn num_faces(&self) -> usize {
+        self.counts.len()
+    }
+
+    fn count(&self, k: usize) -> Num {
+        self.counts[k]
+    }
+
+    fn entries(&self) -> impl Iterator<Item = (usize, Num)> + Clone + '_ {
+        self.faces.iter().map(|&k| (k, self.counts[k]))
+    }
+
+    fn mask(&self) -> Option<FaceMask> {
+        self.mask
+    }
+}
+
+/// A set of face indices as the bits of a `u128`, for dice of up to [`FaceMask::MAX_FACES`] sides (i.e., every typical
+/// die), so that counting, finding, and iterating the faces with dice are bit operations.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct FaceMask(u128);
+
+impl FaceMask {
+    /// The number of faces that a mask can hold.
+    pub const MAX_FACES: usize = 128;
+
+    /// The mask without any faces.
+    pub const EMPTY: Self = Self(0);
+
+    /// Returns the mask of the faces with a count that is not zero, unless there are too many faces.
+    pub fn of(counts: &[Num]) -> Option<Self> {
+        (counts.len() <= Self::MAX_FACES).then(|| Self(counts.iter().enumerate().fold(0, |mask, (k, &count)| mask | ((count != 0) as u128) << k)))
+    }
+
+    /// Returns the mask of the given faces, unless any is too large.
+    pub fn from_faces(faces: impl IntoIterator<Item = usize>) -> Option<Self> {
+        let mut mask = Self::EMPTY;
+
+        for k in faces {
+            if k >= Self::MAX_FACES {
+                return None;
+            }
+
+            mask.insert(k);
+        }
+
+        Some(mask)
+    }
+
+    /// Adds the face at the given index, which must be less than [`FaceMask::MAX_FACES`].
+    pub fn insert(&mut self, k: usize) {
+        self.0 |= 1 << k;
+    }
+
+    /// Removes the face at the given index (if it is in the mask).
+    pub fn remove(&mut self, k: usize) {
+        if k < Self::MAX_FACES {
+            self.0 &= !(1 << k);
+        }
+    }
+
+    /// Returns whether the face at the given index is in the mask.
+    pub fn contains(&self, k: usize) -> bool {
+        k < Self::MAX_FACES && self.0 & (1 << k) != 0
+    }
+
+    /// Returns the number of faces.
+    pub fn len(&self) -> usize {
+        self.0.count_ones() as usize
+    }
+
+    /// Returns whether there are no faces.
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    /// Returns the smallest index, if any.
+    pub fn first(&self) -> Option<usize> {
+        (!self.is_empty()).then(|| self.0.trailing_zeros() as usize)
+    }
+
+    /// Returns the faces that are in this mask, but not the other.
+    pub fn difference(self, other: Self) -> Self {
+        Self(self.0 & !other.0)
+    }
+
+    /// Returns the faces that are in both masks.
+    pub fn intersection(self, other: Self) -> Self {
+        Self(self.0 & other.0)
+    }
+
+    /// Returns an iterator over the indices, in ascending order.
+    pub fn iter(&self) -> impl Iterator<Item = usize> {
+        let mut bits = self.0;
+
+        core::iter::from_fn(move || {
+            let k = (bits != 0).then(|| bits.trailing_zeros() as usize)?;
+            bits &= bits - 1;
+
+            Some(k)
+        })
+    }
+}
+
+/// The statistics of a histogram, computed in a single pass.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CountStats {
+    /// The sum of the counts (e.g., the number of dice).
+    pub total: Num,
+    /// The largest count.
+    pub max: Num,
+    /// The number of faces with the largest count.
+    pub max_occurrences: usize,
+    /// The smallest count that is not zero, if any.
+    pub min_nonzero: Option<Num>,
+    /// The number of faces with a count that is not zero.
+    pub nonzero: usize,
+}
+
+/// Returns the statistics of the counts.
+pub fn stats(counts: &[Num]) -> CountStats {
+    stats_of(counts.iter().copied())
+}
+
+fn stats_of(counts: impl Iterator<Item = Num>) -> CountStats {
+    let mut stats = CountStats::default();
+
+    for count in counts {
+        stats.add(count);
+    }
+
+    stats
+}
+
+impl CountStats {
+    fn add(&mut self, count: Num) {
+        self.total += count;
+
+        if count > self.max {
+            self.max = count;
+            self.max_occurrences = 1;
+        } else if count == self.max {
+            self.max_occurrences += 1;
+        }
+
+        if count > 0 {
+            self.nonzero += 1;
+            self.min_nonzero = Some(self.min_nonzero.map_or(count, |min| min.min(count)));
+        }
+    }
+}
+
+/// Everything that the strategies decide with, from a single pass over a histogram (see [`analyze`]).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Analysis {
+    /// The statistics of the counts.
+    pub stats: CountStats,
+    /// The mode, as [`mode_of`] finds it.
+    pub mode: Num,
+    /// The top two modes, as [`top_two_modes_of`] finds them.
+    pub top_two: (Num, Num),
+    /// The index of the first face with dice, if any.
+    pub first_nonzero: Option<usize>,
+    /// The faces with the smallest count that is not zero, unless any face with dice is too large for a mask.
+    pub min_nonzero_faces: Option<FaceMask>,
+}
+
+/// Returns the mode, the top two modes, the statistics, and the faces with the fewest dice, in a single pass.
+pub fn analyze<C: Counts + ?Sized>(counts: &C) -> Analysis {
+    let last = counts.num_faces().checked_sub(1).expect("there are no counts to analyze");
+
+    let mut stats = CountStats::default();
+    let (mut mode, mut mode_count) = (last, 0);
+    let (mut first_index, mut second_index) = (0, 0);
+    let (mut first, mut second) = (counts.count(0), 0);
+    let mut first_nonzero = None;
+    let mut min_nonzero_faces = Some(FaceMask::EMPTY);
+
+    for (k, count) in counts.entries() {
+        let min_nonzero = stats.min_nonzero;
+        stats.add(count);
+
+        if count == 0 {
+            continue;
+        }
+
+        first_nonzero.get_or_insert(k);
+
+        // The mode is the last face with the most dice (see `mode_of`).
+
+        if count >= mode_count {
+            (mode, mode_count) = (k, count);
+        }
+
+        // The top two modes start from the first face, and only move for strictly more dice (see `top_two_modes_of`).
+
+        if k != 0 {
+            if count > first {
+                (second, second_index) = (first, first_index);
+                (first, first_index) = (count, k);
+            } else if count > second {
+                (second, second_index) = (count, k);
+            }
+        }
+
+        // The faces with the fewest dice start over at every new minimum.
+
+        if k >= FaceMask::MAX_FACES {
+            min_nonzero_faces = None;
+        } else if let Some(faces) = &mut min_nonzero_faces {
+            if min_nonzero.is_none_or(|min| count < min) {
+                *faces = FaceMask::EMPTY;
+            }
+
+            if stats.min_nonzero == Some(count) {
+                faces.insert(k);
+            }
+        }
+    }
+
+    Analysis {
+        stats,
+        mode: mode as Num + 1,
+        top_two: (first_index as Num + 1, second_index as Num + 1),
+        first_nonzero,
+        min_nonzero_faces,
+    }
+}
+
+/// Returns the face with the largest count (the last such face, if there is a tie).
+pub fn mode_from_counts(counts: &[Num]) -> Num {
+    // Two passes, since finding the max is a plain reduction that vectorizes, and the last face with it is usually
+    // found well before the front.
+
+    let max = *counts.iter().max().expect("there are no counts to take the mode of");
+    counts.iter().rposition(|&count| count == max).unwrap() as Num + 1
+}
+
+/// Returns what [`mode_from_counts`] does, for any representation of the counts.
+pub fn mode_of<C: Counts + ?Sized>(counts: &C) -> Num {
+    // A face without dice is only the mode if no face has dice, in which case it is the last face.
+
+    let last = counts.num_faces().checked_sub(1).expect("there are no counts to take the mode of");
+    let (mode, _) = counts.entries().fold((last, 0), |(mode, max), (k, count)| match count > 0 && count >= max {
+        true => (k, count),
+        false => (mode, max),
+    });
+
+    mode as Num + 1
+}
+
+/// Returns every face with the largest count, in order, so that a tie can be broken deliberately (rather than by
+/// [`mode_from_counts`], which silently picks the last).
+pub fn modes_from_counts(counts: &[Num]) -> Vec<Num> {
+    let mut max = 0;
+    let mut modes = Vec::new();
+
+    for (i, &count) in counts.iter().enumerate() {
+        if count > max || modes.is_empty() {
+            max = count;
+            modes.clear();
+        }
+
+        if count == max {
+            modes.push(i as Num + 1);
+        }
+    }
+
+    modes
+}
+
+/// Returns the faces with the largest and second largest counts, which is [`top_k_modes`] for two faces without an
+/// allocation.
+pub fn top_two_modes_from_counts(counts: &[Num]) -> (Num, Num) {
+    top_two_modes_of(counts)
+}
+
+/// Returns what [`top_two_modes_from_counts`] does, for any representation of the counts.
+pub fn top_two_modes_of<C: Counts + ?Sized>(counts: &C) -> (Num, Num) {
+    // A face without dice never displaces either mode, so only the faces with dice (after the first face, which the
+    // scan starts from) need to be visited.
+
+    let (mut first_index, mut second_index) = (0, 0);
+    let (mut first, mut second) = (counts.count(0), 0);
+
+    for (i, count) in counts.entries().filter(|&(i, _)| i != 0) {
+        if count > first {
+            second = first;
+            second_index = first_index;
+            first = count;
+            first_index = i;
+        } else if count > second {
+            second = count;
+            second_index = i;
+        }
+    }
+
+    (first_index as Num + 1, second_index as Num + 1)
+}
+
+/// Returns the `k` faces with the largest counts, from the largest count down (where tied counts are in the order of
+/// their faces).
+///
+/// Returns every face if there are fewer than `k`.
+pub fn top_k_modes(counts: &[Num], k: usize) -> Vec<Num> {
+    let mut top = Vec::<(Num, usize)>::with_capacity(k + 1);
+
+    for (i, &count) in counts.iter().enumerate() {
+        // Insert after every count that is at least as large, so that ties keep the order of their faces.
+
+        let position = top.partition_point(|&(c, _)| c >= count);
+        if position < k {
+            top.insert(position, (count, i));
+            top.truncate(k);
+        }
+    }
+
+    top.into_iter().map(|(_, i)| i as Num + 1).collect()
+}
+
+/// Returns the faces with the smallest count that is not zero, which are the faces worth re-rolling.
+///
+/// There are none if only one face has dice, and if every face with dice is tied, the first is chosen so that a game
+/// can progress.
+pub fn anti_modes(counts: &[Num]) -> Vec<Num> {
+    let mut result = Vec::new();
+    anti_modes_into(counts, &mut result);
+    result
+}
+
+/// Writes the anti-modes (see [`anti_modes`]) into the given buffer in place of its contents, so that a caller that
+/// finds them every step can reuse one allocation.
+pub fn anti_modes_into(counts: &[Num], result: &mut Vec<Num>) {
+    anti_modes_of(counts, result);
+}
+
+/// Writes what [`anti_modes_into`] does into the given buffer, for any representation of the counts.
+pub fn anti_modes_of<C: Counts + ?Sized>(counts: &C, result: &mut Vec<Num>) {
+    result.clear();
+    extend_anti_modes(counts, result);
+}
+
+/// Appends the anti-modes (see [`anti_modes`]) to whatever collects them (e.g., a
+/// [`Scratch`](crate::scratch::Scratch)), after its current contents.
+pub fn extend_anti_modes<C: Counts + ?Sized>(counts: &C, result: &mut impl Extend<Num>) {
+    // With a mask, a single face with dice is found without a pass.
+    if counts.mask().is_some_and(|mask| mask.len() <= 1) {
+        return;
+    }
+
+    analyze(counts).extend_anti_modes(counts, result);
+}
+
+impl Analysis {
+    /// Appends the anti-modes (see [`anti_modes`]) of the counts that were analyzed, which only takes another pass over
+    /// them if the faces with the fewest dice did not fit in a mask.
+    pub fn extend_anti_modes<C: Counts + ?Sized>(&self, counts: &C, result: &mut impl Extend<Num>) {
+        let CountStats { max_occurrences, min_nonzero, nonzero, .. } = self.stats;
+
+        // If we have only one nonzero, then there are no antimodes.
+        if nonzero <= 1 {
+            return;
+        }
+
+        // If all nonzeroes are modes, then choose the first one to be an antinode so that the simulation can progress.
+        if max_occurrences == nonzero {
+            result.extend(self.first_nonzero.map(|k| k as Num + 1));
+            return;
+        }
+
+        match self.min_nonzero_faces {
+            Some(faces) => result.extend(faces.iter().map(|k| k as Num + 1)),
+            None => result.extend(counts.entries().filter(|&(_, count)| Some(count) == min_nonzero).map(|(k, _)| k as Num + 1)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "nightly")]
+    use std::hint::black_box;
+
+    #[cfg(feature = "nightly")]
+    use crate::rand::roll;
+
+    use super::*;
+    use alloc::{vec, vec::Vec};
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_mode_from_counts() {
+        let counts = vec![1, 2, 3, 4, 2, 3, 1, 1];
+        let expected = 4;
+
+        let result = mode_from_counts(&counts);
+
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn test_mode_from_counts_ties() {
+        // Every position of the max, with and without a tie at the front.
+
+        for len in 1..=25 {
+            for at in 0..len {
+                let mut counts = (0..len).map(|i| (i % 3) as Num).collect::<Vec<_>>();
+                counts[at] = 5;
+
+                assert_eq!(mode_from_counts(&counts), at as Num + 1);
+
+                counts[0] = 5;
+                assert_eq!(mode_from_counts(&counts), at as Num + 1);
+            }
+
+            assert_eq!(mode_from_counts(&vec![0; len]), len as Num);
+        }
+    }
+
+    #[test]
+    fn test_modes_from_counts() {
+        assert_eq!(modes_from_counts(&[1, 4, 3, 4, 2]), vec![2, 4]);
+        assert_eq!(modes_from_counts(&[0, 5, 0]), vec![2]);
+        assert_eq!(modes_from_counts(&[0, 0]), vec![1, 2]);
+        assert_eq!(*modes_from_counts(&[1, 4, 3, 4, 2]).last().unwrap(), mode_from_counts(&[1, 4, 3, 4, 2]));
+    }
+
+    #[test]
+    fn test_top_two_modes_from_counts() {
+        let counts = vec![1, 2, 3, 4, 2, 3, 1, 1];
+        let expected = (4, 3);
+
+        let result = top_two_modes_from_counts(&counts);
+
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn test_sparse() {
+        // Visiting only the faces with dice agrees with the full scans.
+
+        let cases: [&[Num]; 6] = [&[1, 2, 3, 4, 2, 3, 1, 1], &[0, 0, 4, 0, 4, 1], &[0, 0, 10, 0], &[0, 3, 0, 3], &[2, 0, 0, 1], &[0, 0, 0]];
+
+        for counts in cases {
+            let faces = (0..counts.len()).filter(|&k| counts[k] != 0).collect::<Vec<_>>();
+            let sparse = Sparse::new(counts, &faces);
+
+            assert_eq!(mode_of(&sparse), mode_from_counts(counts));
+            assert_eq!(mode_of(counts), mode_from_counts(counts));
+            assert_eq!(top_two_modes_of(&sparse), top_two_modes_from_counts(counts));
+
+            let mut result = Vec::new();
+            anti_modes_of(&sparse, &mut result);
+            assert_eq!(result, anti_modes(counts));
+
+            anti_modes_of(&sparse.with_mask(FaceMask::of(counts)), &mut result);
+            assert_eq!(result, anti_modes(counts));
+        }
+    }
+
+    #[test]
+    fn test_analyze() {
+        // A single pass agrees with each helper, including when the faces with dice are too large for a mask.
+
+        let mut wide = vec![0; 200];
+        wide[3] = 2;
+        wide[150] = 2;
+        wide[180] = 5;
+
+        let cases: [&[Num]; 7] = [&[1, 2, 3, 4, 2, 3, 1, 1], &[0, 0, 4, 0, 4, 1], &[0, 0, 10, 0], &[0, 3, 0, 3], &[2, 0, 0, 1], &[0, 0, 0], &wide];
+
+        for counts in cases {
+            let faces = (0..counts.len()).filter(|&k| counts[k] != 0).collect::<Vec<_>>();
+
+            assert_eq!(analyze(counts).stats, stats(counts));
+
+            for analysis in [analyze(counts), analyze(&Sparse::new(counts, &faces))] {
+                assert_eq!(analysis.mode, mode_of(counts));
+                assert_eq!(analysis.top_two, top_two_modes_from_counts(counts));
+                assert_eq!(analysis.first_nonzero, faces.first().copied());
+
+                let mut result = Vec::new();
+                analysis.extend_anti_modes(counts, &mut result);
+                assert_eq!(result, anti_modes(counts));
+            }
+        }
+
+        assert_eq!(analyze(wide.as_slice()).min_nonzero_faces, None);
+        assert_eq!(analyze(&[0, 2, 1, 1][..]).min_nonzero_faces, FaceMask::from_faces([2, 3]));
+    }
+
+    #[test]
+    fn test_face_mask() {
+        let mut mask = FaceMask::of(&[0, 2, 0, 1, 5]).unwrap();
+
+        assert_eq!(mask.iter().collect::<Vec<_>>(), vec![1, 3, 4]);
+        assert_eq!(mask.len(), 3);
+        assert_eq!(mask.first(), Some(1));
+        assert!(mask.contains(3) && !mask.contains(2) && !mask.contains(1_000));
+
+        mask.remove(1);
+        mask.insert(127);
+
+        assert_eq!(mask.iter().collect::<Vec<_>>(), vec![3, 4, 127]);
+        assert_eq!(mask.difference(FaceMask::from_faces([4]).unwrap()), FaceMask::from_faces([3, 127]).unwrap());
+        assert_eq!(mask.intersection(FaceMask::from_faces([4, 5]).unwrap()), FaceMask::from_faces([4]).unwrap());
+
+        // Masks only hold as many faces as a `u128` has bits.
+
+        assert_eq!(FaceMask::of(&[1; 129]), None);
+        assert_eq!(FaceMask::from_faces([128]), None);
+        assert_eq!(FaceMask::EMPTY.first(), None);
+    }
+
+    #[test]
+    fn test_top_k_modes() {
+        let counts = vec![1, 2, 3, 4, 2, 3, 1, 1];
+
+        assert_eq!(top_k_modes(&counts, 3), vec![4, 3, 6]);
+        assert_eq!(top_k_modes(&counts, 0), Vec::<Num>::new());
+        assert_eq!(top_k_modes(&[5, 1], 3), vec![1, 2]);
+
+        let (first, second) = top_two_modes_from_counts(&counts);
+        assert_eq!(top_k_modes(&counts, 2), vec![first, second]);
+    }
+
+    #[test]
+    fn test_stats() {
+        let counts = vec![3, 1, 0, 3, 2];
+        let expected = CountStats { total: 9, max: 3, max_occurrences: 2, min_nonzero: Some(1), nonzero: 4 };
+
+        assert_eq!(stats(&counts), expected);
+        assert_eq!(stats(&[0, 0]).min_nonzero, None);
+    }
+
+    #[test]
+    fn test_anti_modes() {
+        let counts = vec![3, 1, 1, 0, 2, 2, 1];
+        let expected = vec![2, 3, 7];
+
+        let result = anti_modes(&counts);
+
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn test_anti_modes_into() {
+        // The buffer is overwritten, rather than appended to.
+
+        let mut result = vec![9, 9, 9, 9];
+
+        anti_modes_into(&[3, 1, 1, 0, 2, 2, 1], &mut result);
+        assert_eq!(result, vec![2, 3, 7]);
+
+        anti_modes_into(&[0, 0, 10, 0], &mut result);
+        assert_eq!(result, Vec::<Num>::new());
+    }
+
+    #[test]
+    fn test_anti_modes_empty() {
+        let counts = vec![0, 0, 10, 0, 0, 0, 0];
+        let expected: Vec<Num> = vec![];
+
+        let result = anti_modes(&counts);
+
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn test_anti_modes_tied() {
+        let counts = vec![0, 0, 10, 10, 0, 0, 0];
+        let expected = vec![3];
+
+        let result = anti_modes(&counts);
+
+        assert_eq!(result, expected);
+    }
+
+    #[cfg(feature = "nightly")]
+    #[bench]
+    fn bench_mode_from_counts(b: &mut test::Bencher) {
+        let  size = 1_000;
+        let mut counts = Vec::with_capacity(size);
+        for _ in 0..size {
+            counts.push(roll(20));
+        }
+
+        b.iter(|| black_box(mode_from_counts(&counts)));
+    }
+
+    #[cfg(feature = "nightly")]
+    #[bench]
+    fn bench_top_two_modes_from_counts(b: &mut test::Bencher) {
+        let  size = 1_000;
+        let mut counts = Vec::with_capacity(size);
+        for _ in 0..size {
+            counts.push(roll(20));
+        }
+
+        b.iter(|| black_box(top_two_modes_from_counts(&counts)));
+    }
+
+    #[cfg(feature = "nightly")]
+    #[bench]
+    fn bench_anti_modes(b: &mut test::Bencher) {
+        let  size = 1_000;
+        let mut counts = Vec::with_capacity(size);
+        for _ in 0..size {
+            counts.push(roll(20));
+        }
+
+        b.iter(|| black_box(anti_modes(&counts)));
+    }
+
+    #[cfg(feature = "nightly")]
+    #[bench]
+    fn bench_analyze(b: &mut test::Bencher) {
+        let size = 1_000;
+        let counts = (0..size).map(|_| roll(20)).collect::<Vec<_>>();
+
+        b.iter(|| black_box(analyze(black_box(counts.as_slice()))));
+    }
+}
\ No newline at end of file
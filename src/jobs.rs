@@ -0,0 +1,186 @@
+//! A batch runner for jobs from a file (or stdin) of NDJSON job specifications, for experiment scripts that would
+//! rather not run a server.
+//!
+//! Each line is a job in the form that the server takes (see [`JobSpec`]), e.g.,
+//! `{ "kind": "sweep", "config": { "seed": 42 }, "parameter": "dice", "values": [5, 10, 20] }`, or just a configuration
+//! to simulate (in the serialized form of a [`MonteCarloBuilder`](crate::MonteCarloBuilder)), e.g.,
+//! `{ "num_dice": 20, "strategy": "divide" }`.  Either can carry a `"name"`, which is echoed back to tell the jobs apart.
+//! Blank lines are skipped.
+//!
+//! The jobs run a bounded number at a time (see [`BatchOptions`]), on a thread pool that they share, and a line is
+//! written for each job, in the order of the input (whatever the order in which the jobs finish):
+//! `{"line":3,"name":"big","results":[..]}`, where the results are a [`RunResults`] per run of the job, or
+//! `{"line":4,"error":".."}` for a job that is malformed or fails, which does not stop the other jobs.
+
+use std::{collections::BTreeMap, io::{BufRead, Write}, sync::{mpsc, Arc, Mutex}};
+
+use serde_json::{json, Value};
+
+use crate::{adaptive, cancel::CancelToken, error::{Result, TenziError}, progress::ProgressHook, registry::StrategyRegistry, results::RunResults, server::{self, Combine, JobSpec}};
+
+/// The limits of a batch.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BatchOptions {
+    /// The number of jobs that run at once (where `1` runs them one after another).
+    pub max_running: usize,
+    /// The number of threads that the jobs share, or `None` for one per logical core.
+    pub num_threads: Option<usize>,
+}
+
+/// The number of jobs that a batch ran, and how many of them failed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct BatchSummary {
+    pub num_jobs: usize,
+    pub num_failed: usize,
+}
+
+impl Default for BatchOptions {
+    fn default() -> Self {
+        Self { max_running: 1, num_threads: None }
+    }
+}
+
+/// Runs every job of the input (see the [module](self) docs), and writes a line for each to the output, until the input
+/// runs out, or the token is cancelled (when the jobs that are left fail with [`TenziError::Cancelled`]).
+///
+/// Fails if the input or the output does, and otherwise returns how many of the jobs failed.
+pub fn run_batch(input: impl BufRead + Send, mut output: impl Write, options: BatchOptions, cancel: &CancelToken) -> Result<BatchSummary> {
+    if options.max_running == 0 || options.num_threads == Some(0) {
+        return Err(TenziError::InvalidConfig("a batch needs room for at least one running job, and one thread".to_string()));
+    }
+
+    let pool = Arc::new(rayon::ThreadPoolBuilder::new().num_threads(options.num_threads.unwrap_or(0)).build()?);
+    let registry = StrategyRegistry::new();
+
+    // Each worker takes the next job from the input in turn, so the jobs start in order, and hands back its line of
+    // output under the index of the job.
+
+    let input = Mutex::new(input.lines().enumerate().filter(|(_, line)| !matches!(line, Ok(line) if line.trim().is_empty())).enumerate());
+    let (sender, receiver) = mpsc::channel::<Result<(usize, Value)>>();
+
+    std::thread::scope(|scope| {
+        for _ in 0..options.max_running {
+            let (input, sender, pool, registry) = (&input, sender.clone(), &pool, &registry);
+
+            scope.spawn(move || loop {
+                let Some((index, (number, line))) = input.lock().unwrap().next() else {
+                    return;
+                };
+
+                let message = line.map_err(TenziError::from).map(|line| (index, run_line(number + 1, &line, registry, pool, cancel)));
+
+                if sender.send(message).is_err() {
+                    return;
+                }
+            });
+        }
+
+        drop(sender);
+
+        // Write the lines in the order of the input, holding back those that finish before the jobs ahead of them.
+
+        let mut summary = BatchSummary::default();
+        let mut pending = BTreeMap::new();
+
+        for message in receiver {
+            let (index, line) = message?;
+            pending.insert(index, line);
+
+            while let Some(line) = pending.remove(&summary.num_jobs) {
+                summary.num_jobs += 1;
+                summary.num_failed += line.get("error").is_some() as usize;
+
+                writeln!(output, "{}", line)?;
+                output.flush()?;
+            }
+        }
+
+        Ok(summary)
+    })
+}
+
+/// Runs the job of a line of the input, and returns its line of output.
+fn run_line(number: usize, line: &str, registry: &StrategyRegistry, pool: &Arc<rayon::ThreadPool>, cancel: &CancelToken) -> Value {
+    let mut output = json!({ "line": number });
+
+    let results = serde_json::from_str::<Value>(line).map_err(|e| TenziError::InvalidConfig(e.to_string())).and_then(|mut job| {
+        if let Some(name) = job.as_object_mut().and_then(|job| job.remove("name")) {
+            output["name"] = name;
+        }
+
+        // A line without a kind is a configuration to simulate.
+
+        if job.get("kind").is_none() {
+            job = json!({ "kind": "simulate", "config": job });
+        }
+
+        let spec = serde_json::from_value::<JobSpec>(job).map_err(|e| TenziError::InvalidConfig(e.to_string()))?;
+
+        match spec.runs(registry, pool)? {
+            (runs, Combine::Adaptive(options)) => Ok(vec![adaptive::compare(&runs, options, cancel)?.into_results()]),
+            (runs, combine) => {
+                let results = runs.iter().map(|config| config.run_cancellable(cancel, ProgressHook::none())).collect::<Result<Vec<_>>>()?;
+                Ok(server::combine_results(results, combine))
+            }
+        }
+    });
+
+    match results {
+        Ok(results) => output["results"] = serde_json::to_value::<Vec<RunResults>>(results).unwrap(),
+        Err(e) => output["error"] = json!(e.to_string()),
+    }
+
+    output
+}
+
+// Tests.
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_batch() {
+        let input = [
+            r#"{ "name": "sweep", "kind": "sweep", "config": { "num_simulations": 100, "seed": 42 }, "parameter": "dice", "values": [2, 3] }"#,
+            "",
+            r#"{ "num_simulations": 1000, "strategy": "divide" }"#,
+            r#"{ "num_dice": 0 }"#,
+            "{",
+            r#"{ "kind": "compare", "config": { "num_simulations": 100 }, "strategies": ["naive", "merge"] }"#,
+            r#"{ "kind": "compare", "config": { "seed": 7 }, "strategies": ["naive", "divide"], "adaptive": { "initial_simulations": 500, "max_simulations": 2000 } }"#,
+        ]
+        .join("\n");
+
+        let mut output = Vec::new();
+        let summary = run_batch(input.as_bytes(), &mut output, BatchOptions { max_running: 3, num_threads: Some(2) }, &CancelToken::new()).unwrap();
+
+        assert_eq!(summary, BatchSummary { num_jobs: 6, num_failed: 2 });
+
+        // The lines come back in the order of the input, numbered by their line of it.
+
+        let lines = String::from_utf8(output).unwrap().lines().map(|line| serde_json::from_str::<Value>(line).unwrap()).collect::<Vec<_>>();
+
+        assert_eq!(lines.iter().map(|line| line["line"].as_u64().unwrap()).collect::<Vec<_>>(), vec![1, 3, 4, 5, 6, 7]);
+        assert_eq!(lines[0]["name"], "sweep");
+        assert_eq!(lines[0]["results"][1]["parameters"]["num_dice"], 3);
+        assert_eq!(lines[1]["results"][0]["summaries"][0]["strategy"], "divide");
+        assert_eq!(lines[1]["results"][0]["summaries"][0]["num_simulations"], 1000);
+        assert!(lines[2]["error"].is_string());
+        assert!(lines[3]["error"].is_string());
+        assert_eq!(lines[4]["results"][0]["summaries"].as_array().unwrap().len(), 2);
+        assert!(lines[5]["results"][0]["summaries"].as_array().unwrap().iter().all(|summary| summary["num_simulations"].as_u64().unwrap() <= 2000));
+
+        // A cancelled batch fails its jobs, but still writes a line for each.
+
+        let cancel = CancelToken::new();
+        cancel.cancel();
+
+        let mut output = Vec::new();
+        let summary = run_batch(input.as_bytes(), &mut output, BatchOptions::default(), &cancel).unwrap();
+
+        assert_eq!(summary, BatchSummary { num_jobs: 6, num_failed: 6 });
+        assert!(matches!(run_batch(input.as_bytes(), Vec::new(), BatchOptions { max_running: 0, num_threads: None }, &cancel), Err(TenziError::InvalidConfig(_))));
+    }
+}
\ No newline at end of file
@@ -0,0 +1,127 @@
+//! A simple monte carlo simulator for the game "tenzi".
+//!
+//! The main entry point is [`MonteCarloBuilder`], which validates a [`SimulationConfig`] that runs many games
+//! in parallel, and reports [`RunResults`] with the statistics of the number of rolls and steps it took to achieve a
+//! "tenzi".
+//!
+//! The simulation core (the games, strategies, and observers) only needs `alloc`, and builds without the `std`
+//! feature for embedded targets, where the caller supplies the dice with a [`DiceRng`](dice::DiceRng) (or rolls them
+//! with [`Strategy::step_with`](simulation::Strategy::step_with)).
+//! Everything that runs games in bulk (the monte carlo runner, races, and traces) needs `std`.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+// The benchmarks need the unstable `test` crate, so they are only built with the `nightly` feature.
+#![cfg_attr(feature = "nightly", feature(test))]
+// `Num` is switchable via features, so casts that are no-ops in one configuration are required in another.
+#![allow(clippy::unnecessary_cast)]
+
+extern crate alloc;
+
+#[cfg(feature = "nightly")]
+extern crate test;
+
+// Core.
+
+pub mod types;
+pub mod error;
+pub mod dice;
+pub mod histogram;
+pub mod scratch;
+pub mod rules;
+pub mod simulation;
+pub mod observer;
+pub mod event;
+
+// Runners.
+
+#[cfg(feature = "std")]
+pub mod cancel;
+#[cfg(feature = "std")]
+pub mod rand;
+#[cfg(feature = "std")]
+pub mod batch;
+#[cfg(feature = "std")]
+pub mod race;
+#[cfg(feature = "std")]
+pub mod tournament;
+#[cfg(feature = "std")]
+pub mod trace;
+#[cfg(feature = "std")]
+pub mod snapshot;
+#[cfg(feature = "std")]
+pub mod registry;
+#[cfg(feature = "std")]
+pub mod testing;
+#[cfg(feature = "std")]
+pub mod config;
+#[cfg(feature = "std")]
+pub mod results;
+#[cfg(feature = "std")]
+pub mod metrics;
+#[cfg(feature = "std")]
+pub mod memory;
+#[cfg(feature = "std")]
+pub mod records;
+#[cfg(feature = "std")]
+pub mod seeds;
+#[cfg(feature = "std")]
+pub mod sequence;
+#[cfg(feature = "std")]
+pub mod corpus;
+#[cfg(feature = "std")]
+pub mod calibrate;
+#[cfg(feature = "std")]
+pub mod distribution;
+#[cfg(feature = "std")]
+pub mod stats;
+#[cfg(feature = "std")]
+pub mod battery;
+#[cfg(feature = "std")]
+pub mod shard;
+#[cfg(feature = "std")]
+pub mod determinism;
+#[cfg(feature = "std")]
+pub mod adaptive;
+#[cfg(feature = "std")]
+pub mod optimize;
+#[cfg(feature = "std")]
+pub mod audit;
+#[cfg(feature = "std")]
+pub mod progress;
+#[cfg(feature = "async")]
+pub mod runtime;
+#[cfg(feature = "server")]
+pub mod server;
+#[cfg(feature = "server")]
+pub mod cluster;
+#[cfg(feature = "server")]
+pub mod jobs;
+#[cfg(feature = "server")]
+mod telemetry;
+#[cfg(feature = "std")]
+pub mod publish;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+#[cfg(feature = "gpu")]
+pub mod gpu;
+#[cfg(feature = "std")]
+mod monte_carlo;
+
+pub use error::TenziError;
+
+#[cfg(feature = "std")]
+pub use config::{MonteCarloBuilder, SimulationConfig};
+#[cfg(feature = "std")]
+pub use cancel::CancelToken;
+#[cfg(feature = "std")]
+pub use progress::{Progress, ProgressHook};
+#[cfg(feature = "std")]
+pub use registry::StrategyRegistry;
+#[cfg(feature = "std")]
+pub use monte_carlo::{monte_carlo, monte_carlo_blocks, monte_carlo_cancellable, monte_carlo_game, monte_carlo_in, monte_carlo_seeded, monte_carlo_serial, monte_carlo_throughput, monte_carlo_with, Backend, Execution};
+#[cfg(feature = "std")]
+pub use results::{RunResults, StrategySummary};
\ No newline at end of file
@@ -1,51 +1,1852 @@
-#![feature(portable_simd)]
-#![feature(once_cell_get_mut)]
-#![feature(test)]
+// `Num` is switchable via features, so casts that are no-ops in one configuration are required in another.
+#![allow(clippy::unnecessary_cast)]
 
-extern crate test;
+mod bench;
+mod repl;
+mod view;
 
-mod types;
-mod rand;
-mod mode;
-mod simulation;
+use std::{io::{BufWriter, IsTerminal, Write}, process::ExitCode};
 
-use std::sync::atomic::Ordering;
+use clap::{Parser, Subcommand};
+use colored::Colorize;
+use tenzi_sim::{audit, battery, determinism, memory::CountingAllocator, progress, publish, calibrate::{Calibration, FaceCounts}, corpus::{SeedCase, SeedCorpus}, distribution::BinEstimate, error::Result, metrics::Moments, race, rand::{self, RngKind}, records::{RecordFile, RecordWriter}, results::RunParameters, rules::RuleSet, seeds::SeedEntry, sequence::{DiceSequence, SequenceMode}, shard::{self, PartialResults, Shard}, snapshot::GameSnapshot, stats::{self, Fit, FittedDistribution}, testing, tournament::Tournament, trace, types::{Float, Num}, Backend, CancelToken, Execution, Progress, ProgressHook, RunResults, SimulationConfig, StrategyRegistry, TenziError};
+
+/// Counts the allocations, so that every run reports the peak of its heap.
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator::new();
+
+fn main() -> ExitCode {
+    ALLOCATOR.report_runs();
+
+    let args = Args::parse();
+
+    let result = match args.command {
+        Command::Simulate(args) => simulate(args),
+        Command::Compare(args) => compare(args),
+        Command::Sweep(args) => sweep(args),
+        Command::Optimize(args) => optimize(args),
+        Command::Merge(args) => merge(args),
+        Command::Analyze(AnalyzeArgs { command: AnalyzeCommand::State(args) }) => analyze_state(args),
+        Command::Analyze(AnalyzeArgs { command: AnalyzeCommand::Race(args) }) => analyze_race(args),
+        Command::Analyze(AnalyzeArgs { command: AnalyzeCommand::RaceSweep(args) }) => analyze_race_sweep(args),
+        Command::Analyze(AnalyzeArgs { command: AnalyzeCommand::Records(args) }) => analyze_records(args),
+        Command::Analyze(AnalyzeArgs { command: AnalyzeCommand::Decisions(args) }) => analyze_decisions(args),
+        Command::Stats(args) => stats(args),
+        Command::Tournament(args) => tournament(args),
+        Command::View(args) => view(args),
+        Command::Replay(args) => replay(args),
+        Command::Diff(args) => diff(args),
+        Command::ListStrategies => list_strategies(),
+        Command::Calibrate(args) => calibrate(args),
+        Command::RngTest(args) => rng_test(args),
+        #[cfg(feature = "server")]
+        Command::Serve(args) => serve(args),
+        #[cfg(feature = "server")]
+        Command::Batch(args) => batch(args),
+        Command::Check(args) => check(args),
+        Command::Corpus(CorpusArgs { command: CorpusCommand::Check(args) }) => corpus_check(args),
+        Command::Corpus(CorpusArgs { command: CorpusCommand::Add(args) }) => corpus_add(args),
+        Command::Corpus(CorpusArgs { command: CorpusCommand::Update(args) }) => corpus_update(args),
+        Command::Bench(args) => {
+            bench::bench(&args.only, std::time::Duration::from_millis(args.time_ms));
+            Ok(())
+        }
+        Command::Repl(args) => {
+            repl::Repl::new(args.sides, args.dice, args.simulations).run(std::io::stdin().lock());
+            Ok(())
+        }
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{} {}.", "error:".red().bold(), e);
+            exit_code(&e)
+        }
+    }
+}
+
+/// Maps an error to the process exit code: 2 for invalid input (like clap's usage errors), and 1 otherwise.
+fn exit_code(error: &TenziError) -> ExitCode {
+    match error {
+        TenziError::Cancelled | TenziError::Regression(_) | TenziError::Nondeterministic(_) | TenziError::FailedTests(_) | TenziError::ThreadPool(_) | TenziError::Io(_) => ExitCode::FAILURE,
+        #[cfg(feature = "gpu")]
+        TenziError::Gpu(_) => ExitCode::FAILURE,
+        #[cfg(feature = "server")]
+        TenziError::FailedJobs(_) => ExitCode::FAILURE,
+        TenziError::Publish(_) => ExitCode::FAILURE,
+        _ => ExitCode::from(2),
+    }
+}
+
+/// Runs the `simulate` command.
+fn simulate(args: SimulateArgs) -> Result<()> {
+    let mut builder = SimulationConfig::builder()
+        .sides(args.sides)
+        .dice(args.dice)
+        .strategy(&args.strategy)
+        .simulations(num_simulations(args.simulations)?);
+
+    if let Some(initial_state) = args.initial_state {
+        builder = builder.initial_state(initial_state);
+    }
+
+    if let Some(threads) = args.threads {
+        builder = builder.threads(threads);
+    }
+
+    if args.serial {
+        builder = builder.execution(Execution::Serial);
+    }
+
+    if let Some(block_size) = args.block_size {
+        builder = builder.block_size(block_size);
+    }
+
+    builder = builder.backend(args.backend);
+
+    match (args.seed, &args.log_seeds) {
+        (Some(seed), _) => builder = builder.seed(seed),
+        // A run whose seeds are logged needs a seed, so that its games can be replayed.
+        (None, Some(_)) => builder = builder.seed(::rand::random()),
+        (None, None) => {}
+    }
+
+    if let Some(rng) = args.rng {
+        builder = builder.rng(rng);
+    }
+
+    if let Some(weights) = args.weights {
+        builder = builder.weights(weights);
+    }
+
+    builder = builder.rules(house_rules(args.rules, args.rules_file.as_deref())?);
+
+    if args.distribution.is_some() {
+        builder = builder.histogram(true);
+    }
+
+    let config = builder.build()?;
+    let sequence = args.dice_file.as_ref().map(DiceSequence::load).transpose()?;
+
+    println!("Running {} \"tenzi\" monte carlo simulations with {} {}-sided die, and strategy: `{}`{}.", config.num_simulations().to_string().cyan(), config.num_dice().to_string().cyan(), config.num_sides().to_string().cyan(), config.strategy().cyan(), described(config.strategy()));
+
+    if let Some(initial_state) = config.initial_state() {
+        println!("Starting every game from the bucket state: {}.", format!("{:?}", initial_state).cyan());
+    }
+
+    if let Some(weights) = config.weights() {
+        println!("Rolling dice weighted by: {}.", format!("{:?}", weights).cyan());
+    }
+
+    if !config.rules().is_plain() {
+        println!("Playing by the house rules: {}.", config.rules().to_string().cyan());
+    }
+
+    match (&args.dice_file, &sequence) {
+        (Some(path), Some(sequence)) => println!("Rolling {} pre-generated dice from: {}.", sequence.faces().len().to_string().cyan(), path.display().to_string().cyan()),
+        _ => println!("Rolling dice with the rng: {}.", config.parameters().rng.cyan()),
+    }
+
+    if let Some(seed) = config.seed() {
+        println!("Seeding the run with: {}.", seed.to_string().cyan());
+    }
+
+    if args.verify_determinism {
+        return verify_determinism(&config);
+    }
+
+    // Publish snapshots of the run to a message queue, if asked to.
+
+    #[cfg(any(feature = "nats", feature = "kafka"))]
+    let snapshots = match (&args.publish, args.publish_every) {
+        (Some(url), Some(every)) => Some(publish::SnapshotPublisher::new(publish::connect(url)?, std::time::Duration::from_millis(every))),
+        _ => None,
+    };
+
+    // Append snapshots of the run to a file, if asked to.
+
+    let file_snapshots = match &args.snapshot_file {
+        Some(path) => Some(publish::SnapshotPublisher::new(publish::FilePublisher::append(path)?, args.snapshot_every)),
+        None => None,
+    };
+
+    // Draw a progress bar on a terminal, every percent of the games.
+
+    let draw = std::io::stderr().is_terminal();
+
+    let progress = ProgressHook::new(|progress: &Progress| {
+        if draw {
+            draw_progress(progress);
+        }
+
+        #[cfg(any(feature = "nats", feature = "kafka"))]
+        if let Some(snapshots) = &snapshots {
+            snapshots.report(progress);
+        }
+
+        if let Some(snapshots) = &file_snapshots {
+            snapshots.report(progress);
+        }
+    });
+
+    // Snapshots are taken when the run reports its progress, so report it more often for them on a long run.
+
+    let interval = match file_snapshots {
+        Some(_) => (config.num_simulations() / 100).clamp(1, progress::DEFAULT_INTERVAL),
+        None => (config.num_simulations() / 100).max(1),
+    };
+
+    let progress = progress.every(interval);
+
+    // Stop the run on Ctrl-C, and report the games that were completed.
+
+    let cancel = CancelToken::new();
+    let handler = cancel.clone();
+    ctrlc::set_handler(move || handler.cancel()).map_err(|e| TenziError::Io(std::io::Error::other(e)))?;
+
+    // Play only a shard of the games, if asked to, and leave their results to `merge`.
+
+    if let (Some(index), Some(count), Some(path)) = (args.shard_index, args.shard_count, &args.partial) {
+        let partial = config.run_shard(Shard::new(index, count)?, &cancel, progress)?;
+        partial.save(path)?;
+
+        println!("Wrote the partial results of the {} games of shard {} of {} to {} (see `merge`).", partial.num_games().to_string().cyan(), index.to_string().cyan(), count.to_string().cyan(), path.display().to_string().cyan());
+
+        return Ok(());
+    }
+
+    // Stream every game's outcome to the record file, if asked to, while the games are played.
+
+    let (writer, thread) = match &args.records {
+        Some(path) => {
+            let (writer, thread) = RecordWriter::spawn_binary(std::fs::File::create(path)?);
+            (Some(writer), Some(thread))
+        }
+        None => (None, None),
+    };
+
+    // Or publish them, if asked to.
+
+    #[cfg(any(feature = "nats", feature = "kafka"))]
+    let (writer, thread) = match (&args.publish, args.publish_every) {
+        (Some(url), None) => {
+            let (writer, thread) = publish::record_sink(publish::connect(url)?);
+            (Some(writer), Some(thread))
+        }
+        _ => (writer, thread),
+    };
+
+    // Log the seed of every game, if asked to, and otherwise the outliers of a seeded run on the CPU (whose games can
+    // be replayed).
+
+    let logs = match config.backend() {
+        Backend::Cpu => (args.log_seeds.as_ref().and(config.seed_log(None)), config.seed_log(Some(OUTLIERS))),
+        _ => (None, None),
+    };
+
+    let mode = match args.per_game {
+        true => SequenceMode::PerGame,
+        false => SequenceMode::Sequential,
+    };
+
+    let (results, (all, outliers)) = match (&sequence, writer, logs) {
+        (Some(sequence), _, _) => (config.run_sequence(sequence, mode, &cancel)?, (None, None)),
+        (None, None, (None, None)) => (config.run_until_cancelled(&cancel, progress)?, (None, None)),
+        (None, writer, logs) => {
+            let sinks = (writer, logs);
+            let (results, (writer, logs)) = config.run_into(sinks, &cancel, progress)?;
+
+            drop(writer);
+
+            if let Some(thread) = thread {
+                thread.finish()?;
+            }
+
+            (results, logs)
+        }
+    };
+
+    #[cfg(any(feature = "nats", feature = "kafka"))]
+    if let Some(snapshots) = snapshots {
+        println!("Published {} snapshots of the run.", snapshots.finish()?.to_string().cyan());
+    }
+
+    if let (Some(snapshots), Some(path)) = (file_snapshots, &args.snapshot_file) {
+        println!("Appended {} snapshots of the run to {}.", snapshots.finish()?.to_string().cyan(), path.display().to_string().cyan());
+    }
+
+    let summary = &results.summaries()[0];
+
+    match sequence {
+        Some(_) if !results.is_complete() => println!("{} only {} of the games had all of their dice, so the results are partial.", "Out of dice:".yellow().bold(), summary.num_simulations().to_string().cyan()),
+        None if !results.is_complete() => println!("{} only {} of the games were completed, so the results are partial.", "Interrupted:".yellow().bold(), summary.num_simulations().to_string().cyan()),
+        _ => {}
+    }
+
+    println!("Average rolls:            {:.8}.", summary.average_rolls().to_string().green());
+    println!("Standard deviation rolls: {:.8}.", summary.std_dev_rolls().to_string().yellow());
+    println!("Average steps:            {:.8}.", summary.average_steps().to_string().green());
+    println!("Standard deviation steps: {:.8}.", summary.std_dev_steps().to_string().yellow());
+    println!("Duration:                 {:.8}µs.", results.duration().as_micros().to_string().red());
+
+    let memory = summary.memory();
+    let peak = |bytes: Option<u64>| bytes.map_or("unknown".to_string(), byte_size);
+
+    println!("Peak memory:              {} resident, {} of heap in the run ({} of histogram, {} of buffers).", peak(memory.peak_rss).red(), peak(memory.peak_heap).red(), byte_size(memory.histogram_bytes).red(), byte_size(memory.buffer_bytes).red());
+
+    if let Some(path) = &args.distribution {
+        let estimates = summary.rolls_distribution(args.confidence)?;
+        write_distribution(path, &estimates)?;
+
+        println!("Wrote the distribution of the rolls of {} games to {}.", summary.num_simulations().to_string().cyan(), path.display().to_string().cyan());
+    }
+
+    if let (Some(all), Some(path)) = (all, &args.log_seeds) {
+        let entries = all.into_entries();
+        write_seeds(path, &entries)?;
+
+        println!("Logged the seeds of {} games to {}.", entries.len().to_string().cyan(), path.display().to_string().cyan());
+    }
+
+    if let Some(outliers) = outliers {
+        println!("Games with the most rolls:");
+
+        for entry in outliers.into_entries() {
+            println!("  Game {} took {} rolls (replay with `{}`).", entry.index.to_string().cyan(), entry.num_rolls.to_string().yellow(), replay_command(&config, &entry).dimmed());
+        }
+    }
+
+    Ok(())
+}
+
+/// Plays the seeded run serially and in parallel (see `--verify-determinism`), and reports the first line where their
+/// outputs differ.
+fn verify_determinism(config: &SimulationConfig) -> Result<()> {
+    let cancel = CancelToken::new();
+    let handler = cancel.clone();
+    ctrlc::set_handler(move || handler.cancel()).map_err(|e| TenziError::Io(std::io::Error::other(e)))?;
+
+    println!("Verifying that the run plays the same games serially and in parallel.");
+
+    let Some(divergence) = determinism::verify(config, &cancel)? else {
+        println!("The outputs of the {} games are identical.", config.num_simulations().to_string().green());
+        return Ok(());
+    };
+
+    let line = |line: Option<String>| line.unwrap_or_else(|| "(the end of the output)".to_string());
+
+    println!("The outputs differ at line {}:", divergence.line.to_string().red());
+    println!("  Serial:   {}", line(divergence.serial).yellow());
+    println!("  Parallel: {}", line(divergence.parallel).yellow());
+
+    Err(TenziError::Nondeterministic(divergence.line))
+}
+
+/// Runs the `compare` command.
+fn compare(args: CompareArgs) -> Result<()> {
+    use tenzi_sim::adaptive::{self, AdaptiveOptions};
+
+    let strategies = match args.strategies.is_empty() {
+        true => StrategyRegistry::new().plain_names().map(String::from).collect(),
+        false => args.strategies,
+    };
+
+    let configs = strategies.iter().map(|strategy| {
+        let builder = SimulationConfig::builder().sides(args.sides).dice(args.dice).strategy(strategy).simulations(args.simulations);
+
+        match args.seed {
+            Some(seed) => builder.seed(seed).build(),
+            None => builder.build(),
+        }
+    }).collect::<Result<Vec<_>>>()?;
+
+    println!("Comparing {} strategies with {} {}-sided die, with {} {} games each.", configs.len().to_string().cyan(), args.dice.to_string().cyan(), args.sides.to_string().cyan(), if args.adaptive { "at most" } else { "exactly" }, args.simulations.to_string().cyan());
+
+    // An equal budget is a comparison whose every strategy is settled after its one and only round.
+
+    let options = match args.adaptive {
+        true => AdaptiveOptions { initial_simulations: args.initial, max_simulations: args.simulations, confidence: args.confidence },
+        false => AdaptiveOptions { initial_simulations: args.simulations, max_simulations: args.simulations, confidence: args.confidence },
+    };
+
+    let cancel = CancelToken::new();
+    let handler = cancel.clone();
+    ctrlc::set_handler(move || handler.cancel()).map_err(|e| TenziError::Io(std::io::Error::other(e)))?;
+
+    let comparison = adaptive::compare(&configs, options, &cancel)?;
+    let summaries = comparison.results().summaries();
+    println!();
+
+    for (rank, index) in comparison.ranking().into_iter().enumerate() {
+        let summary = &summaries[index];
+        let settled = if comparison.is_settled(index) { "settled".green() } else { "uncertain".yellow() };
+
+        println!("{}. `{}`: {:.8} ± {:.8} rolls, over {} games ({}).", rank + 1, summary.strategy().cyan(), summary.average_rolls().to_string().green(), comparison.margin(index).to_string().yellow(), summary.num_simulations().to_string().cyan(), settled);
+    }
+
+    println!();
+    println!("Played {} games in {} rounds.", comparison.num_simulations().to_string().cyan(), comparison.num_rounds().to_string().cyan());
+
+    if args.adaptive {
+        println!("An equal budget would have played {} games.", (args.simulations * configs.len() as Num).to_string().cyan());
+    }
+
+    Ok(())
+}
+
+/// Runs the `sweep` command.
+fn sweep(args: SweepArgs) -> Result<()> {
+    use tenzi_sim::adaptive::{self, AdaptiveOptions};
+
+    let values = match args.values.is_empty() {
+        true => (1..=args.dice / 2 + 1).map(|value| value.to_string()).collect(),
+        false => args.values,
+    };
+
+    let configs = values.iter().map(|value| {
+        let builder = SimulationConfig::builder().sides(args.sides).dice(args.dice).strategy(format!("{}={}", args.strategy, value)).simulations(args.simulations);
+
+        match args.seed {
+            Some(seed) => builder.seed(seed).build(),
+            None => builder.build(),
+        }
+    }).collect::<Result<Vec<_>>>()?;
+
+    let parameter = StrategyRegistry::new().info(&args.strategy).and_then(|info| info.parameter.clone()).map_or("parameter".to_string(), |parameter| parameter.name);
+
+    println!("Sweeping `{}` over {} values of {}, with {} {}-sided die, with {} games each.", args.strategy.cyan(), values.len().to_string().cyan(), parameter.cyan(), args.dice.to_string().cyan(), args.sides.to_string().cyan(), args.simulations.to_string().cyan());
+
+    // Every value is settled after its one and only round, like a comparison with an equal budget.
+
+    let options = AdaptiveOptions { initial_simulations: args.simulations, max_simulations: args.simulations, confidence: args.confidence };
+
+    let cancel = CancelToken::new();
+    let handler = cancel.clone();
+    ctrlc::set_handler(move || handler.cancel()).map_err(|e| TenziError::Io(std::io::Error::other(e)))?;
+
+    let comparison = adaptive::compare(&configs, options, &cancel)?;
+    let summaries = comparison.results().summaries();
+    let best = comparison.ranking()[0];
+    println!();
+
+    for (index, (value, summary)) in values.iter().zip(summaries).enumerate() {
+        let marker = if index == best { format!(" {}", "<- best".green().bold()) } else { String::new() };
+
+        println!("{} = {}: {:.8} ± {:.8} rolls, {:.8} steps.{}", parameter, value.cyan(), summary.average_rolls().to_string().green(), comparison.margin(index).to_string().yellow(), summary.average_steps().to_string().cyan(), marker);
+    }
+
+    if let Some(path) = &args.csv {
+        let mut writer = BufWriter::new(std::fs::File::create(path)?);
+
+        writeln!(writer, "{},games,average_rolls,margin,std_dev_rolls,average_steps,std_dev_steps", parameter)?;
+
+        for (index, (value, summary)) in values.iter().zip(summaries).enumerate() {
+            writeln!(writer, "{},{},{},{},{},{},{}", value, summary.num_simulations(), summary.average_rolls(), comparison.margin(index), summary.std_dev_rolls(), summary.average_steps(), summary.std_dev_steps())?;
+        }
+
+        writer.flush()?;
+
+        println!();
+        println!("Wrote the curve to {}.", path.display().to_string().cyan());
+    }
+
+    Ok(())
+}
+
+/// Runs the `optimize` command.
+fn optimize(args: OptimizeArgs) -> Result<()> {
+    use tenzi_sim::optimize::{self, OptimizeOptions};
+
+    let seed = args.seed.unwrap_or_else(::rand::random);
+    let options = OptimizeOptions {
+        min: args.min,
+        max: args.max.unwrap_or(args.dice / 2 + 1),
+        population: args.population,
+        generations: args.generations,
+        simulations: args.simulations,
+        mutation_rate: args.mutation_rate,
+        finalists: args.finalists,
+        max_simulations: args.max_simulations,
+        confidence: args.confidence,
+        seed,
+    };
+
+    let parameter = StrategyRegistry::new().info(&args.strategy).and_then(|info| info.parameter.clone()).map_or("parameter".to_string(), |parameter| parameter.name);
+
+    println!("Optimizing `{}` over {} from {} to {}, with {} {}-sided die, with {} generations of {} values, on {} games each, and seed {}.", args.strategy.cyan(), parameter.cyan(), options.min.to_string().cyan(), options.max.to_string().cyan(), args.dice.to_string().cyan(), args.sides.to_string().cyan(), options.generations.to_string().cyan(), options.population.to_string().cyan(), options.simulations.to_string().cyan(), seed.to_string().cyan());
+
+    let cancel = CancelToken::new();
+    let handler = cancel.clone();
+    ctrlc::set_handler(move || handler.cancel()).map_err(|e| TenziError::Io(std::io::Error::other(e)))?;
+
+    let optimization = optimize::optimize(&args.strategy, args.sides, args.dice, &options, &cancel)?;
+    println!();
+
+    for (index, generation) in optimization.generations().iter().enumerate() {
+        let population = generation.population.iter().map(|value| value.to_string()).collect::<Vec<_>>().join(", ");
+
+        println!("Generation {}: {} = {} ({:.8} rolls), of [{}].", (index + 1).to_string().cyan(), parameter, generation.best.to_string().cyan(), generation.average_rolls.to_string().green(), population);
+    }
+
+    // The finalists are reported as they placed on the fresh games.
+
+    let comparison = optimization.comparison();
+    let summaries = comparison.results().summaries();
+    println!();
+
+    for index in comparison.ranking() {
+        let marker = if comparison.is_settled(index) { String::new() } else { format!(" {}", "(unsettled)".yellow()) };
+
+        println!("{} = {}: {:.8} ± {:.8} rolls, {:.8} steps, over {} games.{}", parameter, optimization.finalists()[index].to_string().cyan(), summaries[index].average_rolls().to_string().green(), comparison.margin(index).to_string().yellow(), summaries[index].average_steps().to_string().cyan(), summaries[index].num_simulations().to_string().cyan(), marker);
+    }
+
+    let best = optimization.best_index();
+    let confidence = format!("{}%", args.confidence * 100.0);
+    println!();
+
+    match optimization.is_settled() {
+        true => println!("Best: `{}={}`, which beats every other finalist at {} confidence.", args.strategy.green().bold(), optimization.best().to_string().green().bold(), confidence.cyan()),
+        false => println!("Best: `{}={}`, though it is not apart from every other finalist at {} confidence after {} games (raise `--max-simulations` to settle it).", args.strategy.green().bold(), optimization.best().to_string().green().bold(), confidence.cyan(), summaries[best].num_simulations().to_string().cyan()),
+    }
+
+    Ok(())
+}
+
+/// Merges the partial results of the shards of a run, and prints the statistics of the whole run like `simulate`.
+fn merge(args: MergeArgs) -> Result<()> {
+    let partials = args.paths.iter().map(PartialResults::load).collect::<Result<Vec<_>>>()?;
+    let results = shard::merge(partials)?;
+
+    let parameters = results.parameters();
+    let summary = &results.summaries()[0];
+
+    println!("Merged {} shards of {} \"tenzi\" monte carlo simulations with {} {}-sided die, and strategy: `{}`.", args.paths.len().to_string().cyan(), parameters.num_simulations.to_string().cyan(), parameters.num_dice.to_string().cyan(), parameters.num_sides.to_string().cyan(), summary.strategy().cyan());
+    println!("Average rolls:            {:.8}.", summary.average_rolls().to_string().green());
+    println!("Standard deviation rolls: {:.8}.", summary.std_dev_rolls().to_string().yellow());
+    println!("Average steps:            {:.8}.", summary.average_steps().to_string().green());
+    println!("Standard deviation steps: {:.8}.", summary.std_dev_steps().to_string().yellow());
+    println!("Duration (all shards):    {:.8}µs.", results.duration().as_micros().to_string().red());
+
+    Ok(())
+}
+
+/// The number of games with the most rolls that a seeded `simulate` run reports.
+const OUTLIERS: usize = 3;
+
+/// Writes the logged games to the seed file, as a line of NDJSON each (see [`SeedEntry::to_json`]).
+fn write_seeds(path: &std::path::Path, entries: &[SeedEntry]) -> Result<()> {
+    let mut writer = BufWriter::new(std::fs::File::create(path)?);
+
+    for entry in entries {
+        writeln!(writer, "{}", entry.to_json())?;
+    }
+
+    Ok(writer.flush()?)
+}
+
+/// Writes the distribution of the number of rolls to a CSV file, with a line per number of rolls.
+fn write_distribution(path: &std::path::Path, estimates: &[BinEstimate]) -> Result<()> {
+    let mut writer = BufWriter::new(std::fs::File::create(path)?);
+
+    writeln!(writer, "rolls,games,probability,low,high")?;
+
+    for estimate in estimates {
+        writeln!(writer, "{},{},{},{},{}", estimate.bin, estimate.count, estimate.probability, estimate.low, estimate.high)?;
+    }
+
+    Ok(writer.flush()?)
+}
+
+/// Returns the house rules of the `--rules` option, or of the `--rules-file` option (or the plain game, if neither is
+/// given).
+fn house_rules(rules: Option<RuleSet>, file: Option<&std::path::Path>) -> Result<RuleSet> {
+    match (rules, file) {
+        (Some(rules), _) => Ok(rules),
+        (None, Some(path)) => std::fs::read_to_string(path)?.parse(),
+        (None, None) => Ok(RuleSet::default()),
+    }
+}
+
+/// Saves the trace of a replayed game to the `--save-traces` directory (if given), as a file named for its strategy.
+fn save_trace(dir: Option<&std::path::Path>, trace: &trace::GameTrace) -> Result<()> {
+    let Some(dir) = dir else {
+        return Ok(());
+    };
+
+    std::fs::create_dir_all(dir)?;
+    trace.save(dir.join(format!("{}.trace", trace.strategy)))
+}
+
+/// Returns the `replay` command that plays a logged game of the run again: by the game's own seed, or by the run's
+/// seed and the game's index.
+fn replay_command(config: &SimulationConfig, entry: &SeedEntry) -> String {
+    let mut command = format!("tenzi_sim replay --sides {} --dice {} --strategy {}", config.num_sides(), config.num_dice(), config.strategy());
+
+    if let Some(state) = config.initial_state() {
+        command += &format!(" --initial-state {}", join(state));
+    }
+
+    if let Some(weights) = config.weights() {
+        command += &format!(" --weights {}", join(weights));
+    }
+
+    if !config.rules().is_plain() {
+        command += &format!(" --rules {}", config.rules());
+    }
+
+    if let Some(rng) = config.rng() {
+        command += &format!(" --rng {}", rng);
+    }
+
+    match entry.seed {
+        Some(seed) => command + &format!(" --seed {}", seed),
+        None => command + &format!(" --seed {} --game {}", config.seed().unwrap(), entry.index),
+    }
+}
+
+/// Joins values with commas, as the list arguments take them.
+fn join(values: &[impl ToString]) -> String {
+    values.iter().map(ToString::to_string).collect::<Vec<_>>().join(",")
+}
+
+/// Formats a number of bytes in the largest binary unit that keeps it at least one (e.g., "1.50 MiB").
+fn byte_size(bytes: u64) -> String {
+    let units = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let exponent = ((bytes.max(1) as f64).log2() as usize / 10).min(units.len() - 1);
+
+    match exponent {
+        0 => format!("{} B", bytes),
+        _ => format!("{:.2} {}", bytes as f64 / (1u64 << (10 * exponent)) as f64, units[exponent]),
+    }
+}
+
+/// Returns the number of simulations as the counter type, which fails if the build counts fewer.
+fn num_simulations(simulations: u64) -> Result<Num> {
+    Num::try_from(simulations).map_err(|_| TenziError::InvalidConfig(format!("this build counts at most {} simulations (build with the `num-u64` feature for more)", Num::MAX)))
+}
+
+/// Parses a duration as a number with a unit (e.g., "500ms", "10s", "5m" or "1h").
+fn parse_duration(s: &str) -> Result<std::time::Duration> {
+    let split = s.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(s.len());
+    let (value, unit) = s.split_at(split);
+
+    let seconds = match unit.trim() {
+        "ms" => 0.001,
+        "s" => 1.0,
+        "m" => 60.0,
+        "h" => 3600.0,
+        _ => return Err(TenziError::InvalidConfig(format!("`{}` is not a duration with a unit (e.g., `10s`)", s))),
+    };
+
+    match value.parse::<f64>() {
+        Ok(value) if value > 0.0 => Ok(std::time::Duration::from_secs_f64(value * seconds)),
+        _ => Err(TenziError::InvalidConfig(format!("`{}` is not a positive duration", s))),
+    }
+}
+
+/// Draws (or, once the run is done, clears) the progress bar of the `simulate` command on stderr.
+fn draw_progress(progress: &Progress) {
+    const WIDTH: usize = 40;
+
+    if progress.is_done() {
+        eprint!("\r{}\r", " ".repeat(WIDTH + 48));
+        return;
+    }
+
+    let filled = (progress.fraction() * WIDTH as Float) as usize;
+    let bar = format!("{}{}", "#".repeat(filled), " ".repeat(WIDTH - filled));
+
+    eprint!("\r[{}] {:>3}% (average rolls so far: {:.8})", bar.cyan(), (progress.fraction() * 100.0) as usize, progress.average_rolls().to_string().green());
+}
+
+/// Runs the `analyze state` command.
+fn analyze_state(args: AnalyzeStateArgs) -> Result<()> {
+    let num_sides = args.state.len() as Num;
+    let num_dice = args.dice;
+    let num_simulations = args.simulations;
+
+    let strategies = match args.strategy {
+        Some(strategy) => vec![strategy],
+        None => StrategyRegistry::new().plain_names().map(String::from).collect(),
+    };
+
+    println!("Analyzing the bucket state {} with {} {}-sided die, using {} conditional monte carlo simulations.", format!("{:?}", args.state).cyan(), num_dice.to_string().cyan(), num_sides.to_string().cyan(), num_simulations.to_string().cyan());
+
+    let summaries = strategies.into_iter().map(|strategy| {
+        SimulationConfig::builder()
+            .sides(num_sides)
+            .dice(num_dice)
+            .strategy(strategy)
+            .initial_state(args.state.clone())
+            .simulations(num_simulations)
+            .build()?
+            .run()
+            .map(RunResults::into_summaries)
+    }).collect::<Result<Vec<_>>>()?.into_iter().flatten().collect();
+
+    let results = RunResults::new(RunParameters { num_sides, num_dice, num_simulations, initial_state: Some(args.state), rng: rand::THREAD_RNG.to_string(), rules: RuleSet::default() }, summaries);
+
+    for summary in results.summaries() {
+        println!();
+        println!("Strategy: `{}`{}.", summary.strategy().cyan(), described(summary.strategy()));
+        println!("Expected remaining rolls: {:.8} ± {:.8}.", summary.average_rolls().to_string().green(), summary.std_err_rolls().to_string().yellow());
+        println!("Expected remaining steps: {:.8} ± {:.8}.", summary.average_steps().to_string().green(), summary.std_err_steps().to_string().yellow());
+    }
+
+    Ok(())
+}
+
+/// Runs the `list-strategies` command.
+fn list_strategies() -> Result<()> {
+    for info in StrategyRegistry::new().infos() {
+        println!("{}: {}", info.usage().cyan(), info.description);
+
+        if let Some(parameter) = &info.parameter {
+            println!("    {}: {}", parameter.name.yellow(), parameter.description);
+        }
+
+        if !info.fixed_sides.is_empty() {
+            println!("    Fixed-size buckets for {}-sided die.", info.fixed_sides.iter().map(|s| s.to_string()).collect::<Vec<_>>().join(", "));
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns the description of a strategy spec for a report header (i.e., " (description)"), or nothing if the
+/// strategy is not described.
+fn described(spec: &str) -> String {
+    let name = spec.split_once('=').map_or(spec, |(name, _)| name);
+
+    match StrategyRegistry::new().info(name) {
+        Some(info) if !info.description.is_empty() => format!(" ({})", info.description.trim_end_matches('.')),
+        _ => String::new(),
+    }
+}
+
+/// Returns the long help of a strategy argument, which lists the available strategies after the given summary (and
+/// before the given note about the default, if any).
+fn strategy_help(summary: &str, default: Option<&str>) -> String {
+    let options = StrategyRegistry::new().infos().map(|info| format!("  {}: {}", info.usage(), info.description)).collect::<Vec<_>>().join("\n");
+
+    match default {
+        Some(default) => format!("{}\n\nOptions are:\n{}\n\n{}", summary, options, default),
+        None => format!("{}\n\nOptions are:\n{}", summary, options),
+    }
+}
+
+/// Runs the `analyze race` command.
+fn analyze_race(args: AnalyzeRaceArgs) -> Result<()> {
+    let num_sides = args.sides;
+    let num_dice = args.dice;
+    let num_simulations = args.simulations;
+
+    let registry = StrategyRegistry::new();
+    let players = args.players.iter().map(|player| race::player_from_spec(&registry, player, num_sides, num_dice)).collect::<Result<Vec<_>>>()?;
+
+    if players.len() < 2 {
+        return Err(TenziError::TooFewPlayers(players.len() as Num));
+    }
+
+    println!("Racing {} players with {} {}-sided die, using {} coupled monte carlo simulations.", players.len().to_string().cyan(), num_dice.to_string().cyan(), num_sides.to_string().cyan(), num_simulations.to_string().cyan());
+    println!();
+
+    let output = match args.seed {
+        Some(seed) => race::race_seeded(&players, num_simulations, seed)?,
+        None => race::race(&players, num_simulations)?,
+    };
+
+    for (player, probability) in args.players.iter().zip(output.win_probabilities) {
+        println!("Player `{}` wins: {:.8}.", player.cyan(), probability.to_string().green());
+    }
+
+    println!("Tie:{}{:.8}.", " ".repeat(args.players.iter().map(|p| p.len()).max().unwrap() + 10), output.tie_probability.to_string().yellow());
+    println!("Expected turns to win: {:.8}.", output.expected_turns.to_string().cyan());
+
+    Ok(())
+}
+
+/// Runs the `analyze race-sweep` command.
+fn analyze_race_sweep(args: AnalyzeRaceSweepArgs) -> Result<()> {
+    let registry = StrategyRegistry::new();
+
+    let strategies = match args.strategies.is_empty() {
+        true => registry.plain_names().map(String::from).collect(),
+        false => args.strategies,
+    };
+
+    let against = args.against.as_deref().map_or("the same strategy".to_string(), |against| format!("`{}`", against));
+
+    println!("Racing each of {} strategies against tables of {} players of {}, with {} {}-sided die, using {} coupled monte carlo simulations each.", strategies.len().to_string().cyan(), format!("{:?}", args.players).cyan(), against, args.dice.to_string().cyan(), args.sides.to_string().cyan(), args.simulations.to_string().cyan());
+
+    let sizes = race::sweep_players(&registry, &strategies, args.against.as_deref(), &args.players, args.sides, args.dice, args.simulations, args.seed)?;
+
+    for size in sizes {
+        if size.num_players == args.players[0] {
+            println!();
+            println!("Strategy `{}`:", size.strategy.cyan());
+        }
+
+        println!("  {} players: wins {:.8} (a fair share is {:.8}), ties {:.8}, in {:.8} turns.", size.num_players, size.win_probability.to_string().green(), (1.0 / size.num_players as Float).to_string(), size.tie_probability.to_string().yellow(), size.expected_turns.to_string().cyan());
+    }
+
+    Ok(())
+}
+
+/// Runs the `tournament` command.
+fn tournament(args: TournamentArgs) -> Result<()> {
+    let registry = StrategyRegistry::new();
+
+    let strategies = match args.strategies.is_empty() {
+        true => registry.plain_names().map(String::from).collect(),
+        false => args.strategies,
+    };
+
+    let shares = match args.shares.is_empty() {
+        true => vec![1.0; strategies.len()],
+        false => args.shares,
+    };
+
+    println!("Racing each pair of {} strategies head to head with {} {}-sided die, using {} coupled monte carlo simulations each.", strategies.len().to_string().cyan(), args.dice.to_string().cyan(), args.sides.to_string().cyan(), args.simulations.to_string().cyan());
+    println!();
+
+    let tournament = Tournament::new(&registry, &strategies, args.sides, args.dice, args.simulations, args.seed)?;
+    let width = strategies.iter().map(|s| s.len()).max().unwrap();
+
+    for i in 0..strategies.len() {
+        for j in i + 1..strategies.len() {
+            println!("`{}` beats `{}` (counting a tie as half): {:.8}.", strategies[i].cyan(), strategies[j].cyan(), tournament.payoffs[i][j].to_string().green());
+        }
+    }
+
+    let evolution = tournament.evolve(&shares, args.generations, args.tolerance)?;
+
+    println!();
+
+    match evolution.converged {
+        true => println!("The population settled after {} generations:", (evolution.generations.len() - 1).to_string().cyan()),
+        false => println!("The population did not settle within {} generations, and ended at:", args.generations.to_string().yellow()),
+    }
+
+    let mut ranked = strategies.iter().zip(evolution.equilibrium()).collect::<Vec<_>>();
+    ranked.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+
+    for (strategy, share) in ranked {
+        println!("  `{}`{} {:.8}.", strategy.cyan(), " ".repeat(width - strategy.len()), share.to_string().green());
+    }
+
+    Ok(())
+}
+
+/// Runs the `analyze decisions` command.
+fn analyze_decisions(args: AnalyzeDecisionsArgs) -> Result<()> {
+    let strategies = match args.strategies.is_empty() {
+        true => StrategyRegistry::new().plain_names().map(String::from).collect(),
+        false => args.strategies,
+    };
+
+    println!("Auditing the decisions of {} strategies with {} {}-sided die, over {} games each.", strategies.len().to_string().cyan(), args.dice.to_string().cyan(), args.sides.to_string().cyan(), args.simulations.to_string().cyan());
+
+    for strategy in &strategies {
+        let builder = SimulationConfig::builder().sides(args.sides).dice(args.dice).strategy(strategy).simulations(args.simulations);
+
+        let config = match args.seed {
+            Some(seed) => builder.seed(seed).build()?,
+            None => builder.build()?,
+        };
+
+        let audit = audit::audit(&config);
+        let percent = |count: u64| format!("{:.2}%", audit.share(count) * 100.0);
+
+        println!();
+        println!("Strategy `{}`: {} steps, of which {} were decisions ({} rolled a \"tenzi\", and {} waited for the last die).", strategy.cyan(), audit.num_steps.to_string().cyan(), audit.decisions.to_string().cyan(), audit.single_face.to_string().cyan(), audit.waits.to_string().cyan());
+
+        for (faces, &count) in audit.kept_faces.iter().enumerate().filter(|(_, &count)| count != 0) {
+            println!("  Kept {} face(s):{}{}.", faces, " ".repeat(18), percent(count).green());
+        }
+
+        println!("  Kept a face that was not a mode: {}.", percent(audit.kept_non_mode).yellow());
+        println!("  Rolled every face tied:          {}.", percent(audit.tied_rolls).yellow());
+    }
+
+    Ok(())
+}
+
+/// Runs the `analyze records` command.
+fn analyze_records(args: AnalyzeRecordsArgs) -> Result<()> {
+    let file = RecordFile::open(&args.path)?;
+
+    println!("Analyzing {} recorded games from {}.", file.len().to_string().cyan(), args.path.display().to_string().cyan());
+
+    let moments = file.record_into(Moments::new(false))?;
+
+    println!("Average rolls:            {:.8}.", moments.average_rolls().to_string().green());
+    println!("Standard deviation rolls: {:.8}.", moments.std_dev_rolls().to_string().yellow());
+    println!("Average steps:            {:.8}.", moments.average_steps().to_string().green());
+    println!("Standard deviation steps: {:.8}.", moments.std_dev_steps().to_string().yellow());
+
+    Ok(())
+}
+
+/// Runs the `stats` command.
+fn stats(args: StatsArgs) -> Result<()> {
+    // Clap requires one of the two.
+
+    let path = args.path.or(args.input).unwrap();
+    let file = RecordFile::open(&path)?;
+
+    println!("Computing statistics of {} recorded games from {}.", file.len().to_string().cyan(), path.display().to_string().cyan());
+
+    let moments = file.record_into(Moments::new(true))?;
+    let histogram = moments.rolls_histogram().unwrap();
+
+    println!();
+    println!("Average rolls:            {:.8}.", moments.average_rolls().to_string().green());
+    println!("Standard deviation rolls: {:.8}.", moments.std_dev_rolls().to_string().yellow());
+
+    for &percent in &args.percentiles {
+        println!("Percentile {:>6}:        {} rolls.", percent, stats::percentile(histogram, percent)?.to_string().green());
+    }
+
+    for &min_rolls in &args.tail {
+        println!("At least {:>4} rolls:      {:.8}.", min_rolls, stats::tail_probability(histogram, min_rolls)?.to_string().yellow());
+    }
+
+    if let Some(fit) = args.fit {
+        let report = stats::fit(histogram, fit)?;
+
+        let parameters = match report.distribution {
+            FittedDistribution::NegativeBinomial { offset, r, p } => format!("offset {}, r {:.6}, p {:.6}", offset, r, p),
+            FittedDistribution::Poisson { offset, lambda } => format!("offset {}, lambda {:.6}", offset, lambda),
+        };
+
+        println!();
+        println!("Fit `{}`: {}.", fit.to_string().cyan(), parameters.green());
+        println!("Fit mean rolls:           {:.8}.", report.distribution.mean().to_string().green());
+        println!("Largest CDF gap (KS):     {:.8}.", report.ks_statistic.to_string().yellow());
+    }
+
+    Ok(())
+}
+
+/// Runs the `replay` command for a game of a seeded run, printing every step of the game.
+fn replay_seeded(args: ReplayArgs) -> Result<()> {
+    let seed = args.seed.expect("either a dice file or a seed is required");
+    let rules = house_rules(args.rules, args.rules_file.as_deref())?;
+    let strategies = match args.strategy {
+        Some(strategy) => vec![strategy],
+        None => StrategyRegistry::new().plain_names().map(String::from).collect(),
+    };
+
+    match args.game {
+        Some(index) => println!("Replaying game {} of the run with seed {}, with {} {}-sided die.", index.to_string().cyan(), seed.to_string().cyan(), args.dice.to_string().cyan(), args.sides.to_string().cyan()),
+        None => println!("Replaying the game with seed {}, with {} {}-sided die.", seed.to_string().cyan(), args.dice.to_string().cyan(), args.sides.to_string().cyan()),
+    }
+
+    for strategy in strategies {
+        let mut builder = SimulationConfig::builder()
+            .sides(args.sides)
+            .dice(args.dice)
+            .strategy(&strategy)
+            .seed(seed);
+
+        if let Some(initial_state) = &args.initial_state {
+            builder = builder.initial_state(initial_state.clone());
+        }
+
+        if let Some(rng) = args.rng {
+            builder = builder.rng(rng);
+        }
+
+        if let Some(weights) = &args.weights {
+            builder = builder.weights(weights.clone());
+        }
+
+        let config = builder.rules(rules).build()?;
+        let game = match args.game {
+            Some(index) => config.game(index)?,
+            None => config.game_from_seed(seed),
+        };
+
+        let trace = trace::GameTrace::record(&strategy, game);
+        let last = trace.frames.last().unwrap();
+
+        save_trace(args.save_traces.as_deref(), &trace)?;
+
+        println!();
+        println!("Strategy: `{}`.", strategy.cyan());
+
+        for frame in &trace.frames {
+            println!("  Step {:>3}: {:>5} rolls, buckets {}.", frame.num_steps, frame.num_rolls, format!("{:?}", frame.buckets).cyan());
+        }
+
+        println!("Tenzi after {} rolls and {} steps.", last.num_rolls.to_string().green(), last.num_steps.to_string().green());
+    }
+
+    Ok(())
+}
+
+/// Runs the `view` command.
+fn view(args: ViewArgs) -> Result<()> {
+    let trace = match (&args.trace, &args.resume) {
+        (Some(path), _) => trace::GameTrace::load(path)?,
+        (None, Some(path)) => {
+            let snapshot = GameSnapshot::load(path)?;
+            trace::GameTrace::record(&snapshot.strategy, snapshot.restore(&StrategyRegistry::new())?)
+        }
+        (None, None) => {
+            let mut builder = SimulationConfig::builder()
+                .sides(args.sides)
+                .dice(args.dice)
+                .strategy(&args.strategy);
+
+            if let Some(initial_state) = &args.initial_state {
+                builder = builder.initial_state(initial_state.clone());
+            }
+
+            let mut simulation = builder.build()?.simulation();
+
+            // Play up to the requested step, and save the game there, so the viewer starts from the saved state.
+
+            if let Some(path) = &args.save_snapshot {
+                simulation.steps().take(args.at_step as usize).for_each(drop);
+                GameSnapshot::take(&args.strategy, &simulation).save(path)?;
+            }
+
+            trace::GameTrace::record(&args.strategy, simulation)
+        }
+    };
+
+    if let Some(path) = &args.save {
+        trace.save(path)?;
+    }
+
+    Ok(view::view(&trace)?)
+}
+
+/// Runs the `replay` command.
+fn replay(args: ReplayArgs) -> Result<()> {
+    let Some(dice_file) = &args.dice_file else {
+        return replay_seeded(args);
+    };
+
+    let dice = trace::load_dice(dice_file)?;
+    let rules = house_rules(args.rules, args.rules_file.as_deref())?;
+
+    // Without a strategy, compare every strategy, where running out of dice is an outcome rather than an error.
+
+    let compare = args.strategy.is_none();
+    let strategies = match args.strategy {
+        Some(strategy) => vec![strategy],
+        None => StrategyRegistry::new().plain_names().map(String::from).collect(),
+    };
+
+    println!("Replaying {} recorded dice with {} {}-sided die.", dice.len().to_string().cyan(), args.dice.to_string().cyan(), args.sides.to_string().cyan());
+
+    for strategy in strategies {
+        let mut builder = SimulationConfig::builder()
+            .sides(args.sides)
+            .dice(args.dice)
+            .strategy(&strategy);
+
+        if let Some(initial_state) = &args.initial_state {
+            builder = builder.initial_state(initial_state.clone());
+        }
+
+        builder = builder.rules(rules);
+
+        println!();
+        println!("Strategy: `{}`.", strategy.cyan());
+
+        match trace::GameTrace::replay(&strategy, builder.build()?.simulation(), &dice) {
+            Ok(trace) => {
+                let last = trace.frames.last().unwrap();
+                let left_over = dice.len() - last.num_rolls as usize;
+
+                save_trace(args.save_traces.as_deref(), &trace)?;
+
+                println!("Tenzi after {} rolls and {} steps, with {} recorded dice left over.", last.num_rolls.to_string().green(), last.num_steps.to_string().green(), left_over.to_string().yellow());
+            }
+            Err(e @ TenziError::DiceExhausted(_)) if compare => println!("No tenzi: {}.", e.to_string().yellow()),
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(())
+}
+
+/// Runs the `diff` command.
+fn diff(args: DiffArgs) -> Result<()> {
+    let left = trace::GameTrace::load(&args.left)?;
+    let right = trace::GameTrace::load(&args.right)?;
+    let divergence = left.divergence(&right)?;
+
+    println!("Comparing `{}` ({}) to `{}` ({}), with {} {}-sided die.", left.strategy.cyan(), args.left.display(), right.strategy.cyan(), args.right.display(), left.num_dice.to_string().cyan(), left.num_sides.to_string().cyan());
+    println!();
+
+    // A frame is the state after a step's keep, so the dice that it leaves to re-roll are the decision of that step.
+
+    let describe = |frame: &trace::TraceFrame| {
+        let num_kept = frame.buckets.iter().sum::<Num>();
+        format!("Step {:>3}: {:>5} rolls, buckets {}, re-rolling {}.", frame.num_steps, frame.num_rolls, format!("{:?}", frame.buckets).cyan(), left.num_dice - num_kept)
+    };
+
+    let len = left.frames.len().max(right.frames.len());
+    let first = match (divergence, args.context) {
+        (Some(index), Some(context)) => index.saturating_sub(context as usize),
+        _ => 0,
+    };
+
+    if first > 0 {
+        println!("  ... ({} identical steps)", first);
+    }
+
+    for index in first..len {
+        match (left.frames.get(index), right.frames.get(index)) {
+            (Some(l), Some(r)) if l == r => println!("  {}", describe(l)),
+            (l, r) => {
+                let marker = if Some(index) == divergence { " <- first divergence".yellow().bold().to_string() } else { String::new() };
+
+                match l {
+                    Some(l) => println!("{} {}{}", "<".red(), describe(l), marker),
+                    None => println!("{} (done){}", "<".red(), marker),
+                }
+
+                match r {
+                    Some(r) => println!("{} {}", ">".green(), describe(r)),
+                    None => println!("{} (done)", ">".green()),
+                }
+            }
+        }
+    }
+
+    let (l, r) = (left.frames.last().unwrap(), right.frames.last().unwrap());
+
+    println!();
+
+    match divergence {
+        Some(index) => println!("The games diverge at step {}.", index.to_string().yellow()),
+        None => println!("The games are {}.", "identical".green()),
+    }
+
+    println!("`{}`: tenzi after {} rolls and {} steps.", left.strategy.cyan(), l.num_rolls.to_string().green(), l.num_steps.to_string().green());
+    println!("`{}`: tenzi after {} rolls and {} steps.", right.strategy.cyan(), r.num_rolls.to_string().green(), r.num_steps.to_string().green());
+
+    Ok(())
+}
+
+/// Runs the `check` command.
+fn check(args: CheckArgs) -> Result<()> {
+    let strategies = match args.strategy {
+        Some(strategy) => vec![strategy],
+        None => StrategyRegistry::new().plain_names().map(String::from).collect(),
+    };
+
+    println!("Checking every roll of {} {}-sided die, up to {} steps deep.", args.dice.to_string().cyan(), args.sides.to_string().cyan(), args.depth.to_string().cyan());
+
+    for strategy in strategies {
+        let simulation = SimulationConfig::builder().sides(args.sides).dice(args.dice).strategy(&strategy).build()?.simulation();
+        let report = testing::check_exhaustive(&simulation, args.depth)?;
+
+        println!();
+        println!("Strategy: `{}`{}.", strategy.cyan(), described(&strategy));
+        println!("Rolls checked: {}.", report.num_rolls_played.to_string().green());
+        println!("Probability of tenzi within {} steps: {:.8}.", args.depth, report.done_probability.to_string().green());
+        println!("Expected rolls (of those games): {:.8}.", report.expected_rolls.to_string().green());
+        println!("Expected steps (of those games): {:.8}.", report.expected_steps.to_string().green());
+    }
+
+    Ok(())
+}
+
+/// Runs the `calibrate` command.
+fn calibrate(args: CalibrateArgs) -> Result<()> {
+    let counts = match args.sides {
+        Some(sides) => FaceCounts::load(&args.path)?.with_sides(sides)?,
+        None => FaceCounts::load(&args.path)?,
+    };
+
+    let calibration = Calibration::new(&counts, args.confidence)?;
+    let fair = 1.0 / counts.num_sides() as Float;
+
+    println!("Calibrating a {}-sided die from {} rolls.", counts.num_sides().to_string().cyan(), calibration.num_rolls().to_string().cyan());
+    println!();
+
+    // Flag each face whose interval rules out a fair die.
+
+    for estimate in calibration.estimates() {
+        let probability = format!("{:.6}", estimate.probability);
+        let probability = if estimate.low > fair || estimate.high < fair { probability.yellow() } else { probability.green() };
+
+        println!("Face {}: {} rolls, p = {} ({:.0}% interval: {:.6} to {:.6}).", estimate.face, estimate.count, probability, args.confidence * 100.0, estimate.low, estimate.high);
+    }
+
+    println!();
+    println!("Chi-squared against a fair die: {} (with {} degrees of freedom).", format!("{:.4}", calibration.chi_squared()).yellow(), counts.num_sides() - 1);
+
+    if calibration.estimates().iter().any(|estimate| estimate.count == 0) {
+        println!("{} a face that never came up is never rolled by the weighted dice.", "warning:".yellow().bold());
+    }
+
+    let weights = calibration.weights().iter().map(|weight| format!("{:.6}", weight)).collect::<Vec<_>>().join(",");
+
+    println!("Weights: {}.", weights.green());
+    println!("Simulate with: `tenzi_sim simulate --sides {} --weights {}`.", counts.num_sides(), weights);
+
+    Ok(())
+}
+
+/// Runs the `rng-test` command.
+fn rng_test(args: RngTestArgs) -> Result<()> {
+    let seed = args.seed.unwrap_or_else(::rand::random);
+
+    // Without a generator, the dice are the thread-local rng's that every unseeded game rolls with.
+
+    let mut dice: Box<dyn tenzi_sim::dice::DiceRng> = match args.rng {
+        Some(RngKind::Os) if args.seed.is_some() => return Err(TenziError::InvalidConfig("the os rng cannot be seeded".to_string())),
+        Some(rng) => rng.dice(seed, None),
+        None => {
+            rand::seed_thread(seed);
+            Box::new(rand::ThreadDice)
+        }
+    };
+
+    let name = args.rng.map_or(rand::THREAD_RNG, |rng| rng.name());
+    let rolls = battery::roll_stream(dice.as_mut(), args.sides, args.rolls);
+    let battery = battery::run_battery(&rolls, args.sides)?;
+
+    println!("Testing {} rolls of a {}-sided die from the rng: {}.", args.rolls.to_string().cyan(), args.sides.to_string().cyan(), name.cyan());
+
+    if args.rng.is_none_or(|rng| rng.is_seedable()) {
+        println!("Seeding the rng with: {}.", seed.to_string().cyan());
+    }
+
+    println!();
+
+    for test in &battery {
+        let statistic = match test.degrees_of_freedom {
+            Some(degrees_of_freedom) => format!("chi-squared = {:.4} (with {} degrees of freedom)", test.statistic, degrees_of_freedom),
+            None => format!("z = {:.4}", test.statistic),
+        };
+
+        let verdict = if test.passed(args.alpha) { "PASS".green() } else { "FAIL".red() };
+
+        println!("{} {}: {}, p = {:.6}.", verdict, test.name, statistic, test.p_value);
+    }
+
+    match battery.iter().filter(|test| !test.passed(args.alpha)).count() {
+        0 => Ok(()),
+        num_failed => Err(TenziError::FailedTests(num_failed as Num)),
+    }
+}
+
+/// Runs the `batch` command.
+#[cfg(feature = "server")]
+fn batch(args: BatchArgs) -> Result<()> {
+    use std::io::BufReader;
+    use tenzi_sim::jobs::{self, BatchOptions};
+
+    let input: Box<dyn std::io::BufRead + Send> = match &args.input {
+        Some(path) if path.as_os_str() != "-" => Box::new(BufReader::new(std::fs::File::open(path)?)),
+        _ => Box::new(BufReader::new(std::io::stdin())),
+    };
+
+    let output: Box<dyn Write> = match &args.output {
+        Some(path) => Box::new(BufWriter::new(std::fs::File::create(path)?)),
+        None => Box::new(std::io::stdout().lock()),
+    };
+
+    // Stop the batch on Ctrl-C, failing the jobs that are left.
+
+    let cancel = CancelToken::new();
+    let handler = cancel.clone();
+    ctrlc::set_handler(move || handler.cancel()).map_err(|e| TenziError::Io(std::io::Error::other(e)))?;
+
+    let summary = jobs::run_batch(input, output, BatchOptions { max_running: args.jobs, num_threads: args.threads }, &cancel)?;
+
+    eprintln!("Ran {} jobs, of which {} failed.", summary.num_jobs.to_string().cyan(), summary.num_failed.to_string().cyan());
+
+    match summary.num_failed {
+        0 => Ok(()),
+        num_failed => Err(TenziError::FailedJobs(num_failed as Num)),
+    }
+}
+
+/// Runs the `serve` command.
+#[cfg(feature = "server")]
+fn serve(args: ServeArgs) -> Result<()> {
+    use std::sync::Arc;
+    use tenzi_sim::{cluster::{self, Coordinator}, server::{self, JobQueue, ServerOptions}};
+
+    let address = format!("{}:{}", args.host, args.port);
+    let options = ServerOptions { max_running: args.jobs, max_queued: args.queue, num_threads: args.threads };
+
+    let jobs = match args.role {
+        ServeRole::Local => JobQueue::new(options)?,
+        ServeRole::Coordinator => {
+            let coordinator = Coordinator::bind((args.host.as_str(), args.cluster_port), args.shard_size)?;
+
+            println!("Coordinating the workers that connect to {}, in shards of {} games.", coordinator.local_addr().to_string().cyan(), args.shard_size.to_string().cyan());
+
+            JobQueue::with_coordinator(options, Arc::new(coordinator))?
+        }
+        ServeRole::Worker => {
+            let coordinator = args.coordinator.expect("a worker requires a coordinator");
+
+            println!("Running the games of the coordinator at {}.", coordinator.cyan());
+
+            return cluster::work(coordinator.as_str(), args.threads);
+        }
+    };
+
+    let jobs = Arc::new(jobs);
+
+    println!("Serving the simulator (and its dashboard) on {}, running {} jobs at once (with up to {} waiting).", format!("http://{}", address).cyan(), args.jobs.to_string().cyan(), args.queue.to_string().cyan());
+
+    #[cfg(feature = "grpc")]
+    if let Some(port) = args.grpc_port {
+        let grpc_address: std::net::SocketAddr = format!("{}:{}", args.host, port).parse().map_err(|_| TenziError::InvalidConfig(format!("`{}` is not an address to serve gRPC on", args.host)))?;
+        let jobs = jobs.clone();
+
+        println!("Serving the gRPC service on {}.", grpc_address.to_string().cyan());
+
+        std::thread::spawn(move || {
+            let runtime = tokio::runtime::Runtime::new().expect("the gRPC runtime should start");
+
+            if let Err(e) = runtime.block_on(tenzi_sim::grpc::serve(grpc_address, jobs)) {
+                eprintln!("{} {}.", "error:".red().bold(), e);
+                std::process::exit(1);
+            }
+        });
+    }
+
+    server::serve_jobs(&address, jobs)
+}
+
+/// Runs the `corpus check` command.
+fn corpus_check(args: CorpusPathArgs) -> Result<()> {
+    let corpus = SeedCorpus::load(&args.path)?;
+
+    println!("Checking {} outcomes of {} seeded games.", corpus.entries().len().to_string().cyan(), corpus.cases().len().to_string().cyan());
+
+    let diffs = corpus.check();
+
+    for diff in &diffs {
+        let SeedCase { num_sides, num_dice, rng, seed, game } = diff.expected.case;
+        let game = game.map_or(format!("the game with the seed {}", seed), |game| format!("game {} of the run with the seed {}", game, seed));
+
+        println!();
+        println!("Strategy: `{}`, in {} ({} {}-sided die, with the rng: {}).", diff.expected.strategy.cyan(), game, num_dice, num_sides, rng);
+        println!("Expected: tenzi after {} rolls and {} steps.", diff.expected.outcome.num_rolls.to_string().green(), diff.expected.outcome.num_steps.to_string().green());
+
+        match &diff.actual {
+            Ok(outcome) => println!("Actual: tenzi after {} rolls and {} steps.", outcome.num_rolls.to_string().red(), outcome.num_steps.to_string().red()),
+            Err(e) => println!("Actual: {}.", e.to_string().red()),
+        }
+    }
+
+    match diffs.len() {
+        0 => {
+            println!("Every outcome matches.");
+            Ok(())
+        }
+        num_diffs => Err(TenziError::Regression(num_diffs as Num)),
+    }
+}
+
+/// Runs the `corpus add` command.
+fn corpus_add(args: CorpusAddArgs) -> Result<()> {
+    let mut corpus = match args.path.exists() {
+        true => SeedCorpus::load(&args.path)?,
+        false => SeedCorpus::new(),
+    };
+
+    let strategies = match args.strategy {
+        Some(strategy) => vec![strategy],
+        None => StrategyRegistry::new().plain_names().map(String::from).collect(),
+    };
+
+    let case = SeedCase { num_sides: args.sides, num_dice: args.dice, rng: args.rng, seed: args.seed, game: args.game };
+    corpus.record(case, &strategies)?;
+    corpus.save(&args.path)?;
+
+    println!("Recorded the outcomes of {} strategies in `{}`.", strategies.len().to_string().green(), args.path.display());
+
+    Ok(())
+}
+
+/// Runs the `corpus update` command.
+fn corpus_update(args: CorpusPathArgs) -> Result<()> {
+    let mut corpus = SeedCorpus::load(&args.path)?;
+    let num_diffs = corpus.check().len();
+
+    corpus.update()?;
+    corpus.save(&args.path)?;
+
+    println!("Updated {} of the {} outcomes in `{}`.", num_diffs.to_string().green(), corpus.entries().len(), args.path.display());
+
+    Ok(())
+}
+
+/// A monte carlo simulator for the game "tenzi".
+#[derive(Parser, Debug)]
+#[command(version, about, long_about)]
+struct Args {
+    #[command(subcommand)]
+    command: Command,
+}
+
+/// The commands supported by the simulator.
+#[derive(Subcommand, Debug)]
+// The arguments are parsed once, so the size of the largest command does not matter.
+#[allow(clippy::large_enum_variant)]
+enum Command {
+    /// Runs a monte carlo simulation of a strategy.
+    Simulate(SimulateArgs),
+
+    /// Ranks strategies by their average number of rolls, with an equal number of games each, or an adaptive number
+    /// that goes to the strategies whose rank is still uncertain.
+    Compare(CompareArgs),
+
+    /// Maps the performance curve of a family of strategies (e.g., `threshold=<t>`), by playing it with each value of
+    /// its parameter on an equal number of games.
+    Sweep(SweepArgs),
+
+    /// Searches the parameter of a family of strategies for the value that takes the fewest rolls, with a genetic
+    /// search on short runs of the same games, and compares the best values again to report the winner with confidence.
+    Optimize(OptimizeArgs),
+
+    /// Merges the partial results of the shards of a run (see `simulate --shard-index`) into the results of the whole
+    /// run.
+    Merge(MergeArgs),
+
+    /// Analyzes a game in progress.
+    Analyze(AnalyzeArgs),
+
+    /// Computes statistics of the number of rolls (percentiles, tail probabilities, and a fit distribution) from the
+    /// games in a record file (see `simulate --records`), without simulating them again.
+    Stats(StatsArgs),
+
+    /// Evolves a population of strategies that race each other, where the share of each strategy grows or shrinks
+    /// with how often it wins, and reports the mix that the population settles on.
+    Tournament(TournamentArgs),
+
+    /// Steps through a single game in a keyboard-driven viewer.
+    View(ViewArgs),
+
+    /// Starts an interactive session for exploring states and strategies.
+    Repl(ReplArgs),
+
+    /// Replays a physical game's dice through each strategy, to see what they would have done.
+    Replay(ReplayArgs),
+
+    /// Compares two recorded game traces (e.g., the same seed played by two strategies, or by two versions of one)
+    /// step by step, and highlights the first step where they diverge.
+    Diff(DiffArgs),
+
+    /// Lists the available strategies.
+    ListStrategies,
+
+    /// Estimates the probability of each face of a real die from a log of its rolls, and reports the weights to
+    /// simulate it with (see `simulate --weights`).
+    Calibrate(CalibrateArgs),
+
+    /// Runs a small battery of statistical tests (frequency, runs, serial correlation, and gaps) over the dice that a
+    /// generator rolls, and reports whether each passed.  Exits with a failure if any did not.
+    RngTest(RngTestArgs),
+
+    /// Serves a REST API that runs simulation, comparison, and sweep jobs for remote clients (see the `server`
+    /// module's docs for the endpoints), a dashboard at its root that follows a job live, and Prometheus metrics at
+    /// `/metrics`.
+    #[cfg(feature = "server")]
+    Serve(ServeArgs),
+
+    /// Runs a batch of jobs from a file of NDJSON job specifications (see the `jobs` module's docs), and writes a line
+    /// of results for each, in order.  Exits with a failure if any job failed.
+    #[cfg(feature = "server")]
+    Batch(BatchArgs),
+
+    /// Plays out every possible roll of a tiny game, checking that each strategy keeps the game's invariants.
+    Check(CheckArgs),
+
+    /// Checks a stored corpus of seeded games (e.g., flagged outliers and edge cases) against the outcomes that each
+    /// strategy had when they were recorded, to catch unintended changes of behavior.
+    Corpus(CorpusArgs),
+
+    /// Times the strategies, mode helpers, and dice sources at representative sizes, and prints a comparison table
+    /// for each (build with `--release` for meaningful numbers).
+    Bench(BenchArgs),
+}
+
+/// The arguments for the `simulate` command.
+#[derive(clap::Args, Debug)]
+struct SimulateArgs {
+    /// The number of sides on each die.
+    #[arg(short, long, default_value_t = 6)]
+    sides: Num,
+
+    /// The number of die to roll.
+    #[arg(short, long, default_value_t = 10)]
+    dice: Num,
+
+    /// The number of simulations to run (up to what the build counts, which is every `u64` on a 64-bit target, or
+    /// with the `num-u64` feature).
+    #[arg(short = 'm', long, default_value_t = 10_000)]
+    simulations: u64,
+
+    /// The strategy to use.
+    #[arg(short = 't', long, default_value = "naive", long_help = strategy_help("The strategy to use.", None))]
+    strategy: String,
+
+    /// The bucket state (i.e., the number of dice already kept for each face) to start every game from.
+    /// For example, "0,0,6,0,0,0" starts with six 3s kept.
+    #[arg(short, long, value_delimiter = ',')]
+    initial_state: Option<Vec<Num>>,
+
+    /// The number of threads to run the simulations on.
+    /// The default is one per logical core.
+    #[arg(short = 'j', long)]
+    threads: Option<usize>,
+
+    /// Plays the games one after another on a single thread, in order, rather than in parallel.
+    #[arg(long, conflicts_with = "threads")]
+    serial: bool,
+
+    /// The number of games in each block of work that is handed to a thread.
+    /// The default is tuned from the time that the first games take.
+    #[arg(long)]
+    block_size: Option<Num>,
 
-use clap::{arg, command, Parser};
-use colored::Colorize;
-use rayon::iter::{IntoParallelIterator, ParallelIterator};
-use simulation::{DivideSimulation, MergeSimulation, NaiveSimulation, SimulationType};
-use types::{AtomicNum, Float, Num};
+    /// Where to play the games: "cpu", "batched" (lockstep batches of games, for the built-in strategies), or the
+    /// experimental "gpu" (which only plays the naive strategy, and needs the `gpu` feature).
+    #[arg(long, default_value = "cpu")]
+    backend: Backend,
 
-fn main() {
-    let args = Args::parse();
+    /// The seed to roll the dice from, which makes the results exactly reproducible (on any number of threads).
+    #[arg(long)]
+    seed: Option<u64>,
 
-    let num_sides = args.sides;
-    let num_dice = args.dice;
-    let num_simulations = args.simulations;
+    /// The generator to roll the dice from: "xoshiro256++" (or "xoshiro"), "pcg64", "chacha8", or "std" (from fastest
+    /// to strongest, roughly), the counter-based "philox", or "os", which draws from the operating system's CSPRNG
+    /// (which is much slower, and cannot be seeded).  The default is the thread-local rng, or "std" for a seeded run.
+    #[arg(long)]
+    rng: Option<RngKind>,
 
-    let strategy = match args.strategy.as_str() {
-        "naive" => SimulationType::Naive(NaiveSimulation::new(num_sides, num_dice)),
-        "divide" => SimulationType::Divide(DivideSimulation::new(num_sides, num_dice)),
-        "merge" => SimulationType::Merge(MergeSimulation::new(num_sides, num_dice)),
-        _ => panic!("Invalid strategy"),
-    };
+    /// The weight of each face, to roll weighted dice rather than fair ones.
+    /// For example, "1,1,1,1,1,2" rolls twice as many 6s as any other face.
+    #[arg(long, value_delimiter = ',')]
+    weights: Option<Vec<Float>>,
+
+    /// The house rules to play by, separated by commas (e.g., "max-rerolls=5,lock-kept"), which every strategy is held
+    /// to: "max-rerolls=N" (at most N dice are re-rolled per step), "keep-new-die" (every roll keeps at least one more
+    /// die), "double-cost-after=N" (every die rolled after the Nth step counts as two rolls), and "lock-kept" (the kept
+    /// dice of the face with the most of them can never be re-rolled).
+    #[arg(long, conflicts_with = "rules_file")]
+    rules: Option<RuleSet>,
+
+    /// A file of house rules to play by (see `--rules`), one per line, where a "#" starts a comment.
+    #[arg(long)]
+    rules_file: Option<std::path::PathBuf>,
+
+    /// A file to stream the outcome of every game to, in the binary record format (see `analyze records`).
+    #[arg(long)]
+    records: Option<std::path::PathBuf>,
+
+    /// A file to log the index, seed, and outcome of every game to, as NDJSON, so that any game can be replayed (see
+    /// `replay --seed`).  An unseeded run is seeded with a random seed.
+    #[arg(long)]
+    log_seeds: Option<std::path::PathBuf>,
+
+    /// A file of pre-generated dice (e.g., from a physical dice-rolling machine) to play the games with, rather than
+    /// an rng: as text, with the faces separated by whitespace or commas and one game per line (a "#" starts a
+    /// comment), or, with a ".bin" extension, a byte per face where a zero byte ends a game.
+    /// The games roll the dice in order, one after another, until they run out.
+    #[arg(long, conflicts_with_all = ["seed", "rng", "weights", "records", "log_seeds"])]
+    dice_file: Option<std::path::PathBuf>,
+
+    /// Plays each game with the dice of its own line (or zero-terminated run of bytes) of the dice file, so the games
+    /// can be played in parallel.
+    #[arg(long, requires = "dice_file")]
+    per_game: bool,
+
+    /// Plays only the shard at this index (from 0) of the games, and writes its partial results to the `--partial`
+    /// file, to be merged with those of the other shards (see `merge`).  For example, the tasks of a Slurm or Kubernetes
+    /// array job can each run a shard of the same command, and a seeded run's shards play exactly the games of the
+    /// whole run.
+    #[arg(long, requires_all = ["shard_count", "partial"], conflicts_with_all = ["dice_file", "records", "log_seeds"])]
+    shard_index: Option<Num>,
+
+    /// The number of shards that the games are split into (see `--shard-index`).
+    #[arg(long, requires = "shard_index")]
+    shard_count: Option<Num>,
+
+    /// The file to write the partial results of the shard to (see `--shard-index`).
+    #[arg(long, requires = "shard_index")]
+    partial: Option<std::path::PathBuf>,
+
+    /// A file to write the distribution of the number of rolls to, as CSV: a line per number of rolls, with the
+    /// number of games that took it, their share of the games, and the interval of the share (for charting the
+    /// distribution with error bars).
+    #[arg(long, conflicts_with = "shard_index")]
+    distribution: Option<std::path::PathBuf>,
 
-    println!("Running {} \"tenzi\" monte carlo simulations with {} {}-sided die, and strategy: `{}`.", num_simulations.to_string().cyan(), num_dice.to_string().cyan(), num_sides.to_string().cyan(), args.strategy.to_string().cyan());
+    /// The confidence level of the intervals of the distribution (see `--distribution`).
+    #[arg(long, default_value_t = 0.95, requires = "distribution")]
+    confidence: Float,
 
-    let output = monte_carlo(strategy, num_simulations);
+    /// A message queue to publish the outcome of every game to as it is played (as a message of NDJSON each), like
+    /// "nats://127.0.0.1:4222/tenzi.games" or "kafka://broker:9092/tenzi-games".
+    #[cfg(any(feature = "nats", feature = "kafka"))]
+    #[arg(long, conflicts_with_all = ["records", "dice_file", "shard_index"])]
+    publish: Option<String>,
 
-    println!("Average rolls:            {:.8}.", output.average_rolls.to_string().green());
-    println!("Standard deviation rolls: {:.8}.", output.std_dev_rolls.to_string().yellow());
-    println!("Average steps:            {:.8}.", output.average_steps.to_string().green());
-    println!("Standard deviation steps: {:.8}.", output.std_dev_steps.to_string().yellow());
-    println!("Duration:                 {:.8}µs.", output.duration.as_micros().to_string().red());
+    /// Publishes a snapshot of the aggregates so far at most every this many milliseconds (as the run reports its
+    /// progress, every percent of the games), and once the run is done, rather than every game's outcome.
+    #[cfg(any(feature = "nats", feature = "kafka"))]
+    #[arg(long, requires = "publish")]
+    publish_every: Option<u64>,
+
+    /// A file to append a snapshot of the aggregates so far to during the run (as a line of NDJSON each, with the
+    /// averages, their standard errors and 95% intervals, and the counts), so that a plotter or a watchdog can follow a
+    /// long run without the TUI or the server.
+    #[arg(long, conflicts_with_all = ["dice_file", "shard_index"])]
+    snapshot_file: Option<std::path::PathBuf>,
+
+    /// Appends a snapshot at most this often (see `--snapshot-file`), like "500ms", "10s", "5m" or "1h", and once the
+    /// run is done.
+    #[arg(long, default_value = "10s", value_parser = parse_duration, requires = "snapshot_file")]
+    snapshot_every: std::time::Duration,
+
+    /// Plays the seeded run twice, serially and in parallel, and checks that the outputs of the two (i.e., the partial
+    /// results of the run, and the outcome of every game) are identical, or reports the first line where they differ.
+    #[arg(long, requires = "seed", conflicts_with_all = ["serial", "dice_file", "shard_index", "records", "log_seeds", "distribution", "snapshot_file"])]
+    verify_determinism: bool,
 }
 
-/// A monte carlo simulator for the game "tenzi".
-#[derive(Parser, Debug)]
-#[command(version, about, long_about)]
-struct Args {
+/// The arguments for the `compare` command.
+#[derive(clap::Args, Debug)]
+struct CompareArgs {
+    /// The number of sides on each die.
+    #[arg(short, long, default_value_t = 6)]
+    sides: Num,
+
+    /// The number of die to roll.
+    #[arg(short, long, default_value_t = 10)]
+    dice: Num,
+
+    /// The strategies to compare, separated by commas.
+    #[arg(short = 't', long, value_delimiter = ',', long_help = strategy_help("The strategies to compare, separated by commas.", Some("The default is to compare all of them.")))]
+    strategies: Vec<String>,
+
+    /// The number of games that each strategy plays, or (with `--adaptive`) the most that any strategy plays.
+    #[arg(short = 'm', long, default_value_t = 10_000)]
+    simulations: Num,
+
+    /// The seed to roll the dice from, which plays every strategy on the same games.
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// Plays rounds of games until the rank of every strategy is known at the confidence level (or it played its
+    /// games), where each round only plays the strategies whose rank is still uncertain, for twice as many games as
+    /// the last.
+    #[arg(long)]
+    adaptive: bool,
+
+    /// The number of games that each strategy plays in the first round of an adaptive comparison.
+    #[arg(long, default_value_t = 1_000, requires = "adaptive")]
+    initial: Num,
+
+    /// The confidence level of the intervals of the average number of rolls.
+    #[arg(long, default_value_t = 0.99)]
+    confidence: Float,
+}
+
+/// The arguments for the `sweep` command.
+#[derive(clap::Args, Debug)]
+struct SweepArgs {
+    /// The family of strategies to sweep, which takes a parameter (see `list-strategies`).
+    #[arg(short = 't', long, default_value = "threshold")]
+    strategy: String,
+
+    /// The values of the parameter to play the family with, separated by commas.
+    /// The default is every value from 1 to one more than half of the dice (i.e., every threshold, since any higher one
+    /// only ever keeps the mode).
+    #[arg(short, long, value_delimiter = ',')]
+    values: Vec<String>,
+
+    /// The number of sides on each die.
+    #[arg(short, long, default_value_t = 6)]
+    sides: Num,
+
+    /// The number of die to roll.
+    #[arg(short, long, default_value_t = 10)]
+    dice: Num,
+
+    /// The number of games that each value plays.
+    #[arg(short = 'm', long, default_value_t = 10_000)]
+    simulations: Num,
+
+    /// The seed to roll the dice from, which plays every value on the same games (so the curve is smoother than the
+    /// noise of independent runs).
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// The confidence level of the intervals of the average number of rolls.
+    #[arg(long, default_value_t = 0.95)]
+    confidence: Float,
+
+    /// A file to write the curve to, as CSV: a line per value, with its games, its average number of rolls (and the
+    /// margin of its interval), and its average number of steps.
+    #[arg(long)]
+    csv: Option<std::path::PathBuf>,
+}
+
+/// The arguments for the `optimize` command.
+#[derive(clap::Args, Debug)]
+struct OptimizeArgs {
+    /// The family of strategies to optimize, which takes a whole number as its parameter (see `list-strategies`).
+    #[arg(short = 't', long, default_value = "threshold")]
+    strategy: String,
+
+    /// The lowest value of the parameter to search.
+    #[arg(long, default_value_t = 1)]
+    min: Num,
+
+    /// The highest value of the parameter to search.
+    /// The default is one more than half of the dice (i.e., every threshold, since any higher one only ever keeps the
+    /// mode).
+    #[arg(long)]
+    max: Option<Num>,
+
+    /// The number of sides on each die.
+    #[arg(short, long, default_value_t = 6)]
+    sides: Num,
+
+    /// The number of die to roll.
+    #[arg(short, long, default_value_t = 10)]
+    dice: Num,
+
+    /// The number of values in each generation.
+    #[arg(short, long, default_value_t = 8)]
+    population: usize,
+
+    /// The number of generations.
+    #[arg(short, long, default_value_t = 10)]
+    generations: usize,
+
+    /// The number of games that each value plays in each generation.
+    #[arg(short = 'm', long, default_value_t = 2_000)]
+    simulations: Num,
+
+    /// The probability that a bred value mutates by a step.
+    #[arg(long, default_value_t = 0.3)]
+    mutation_rate: Float,
+
+    /// The number of the best values that are compared again on fresh games.
+    #[arg(long, default_value_t = 3)]
+    finalists: usize,
+
+    /// The most games that any finalist plays, as the comparison keeps playing the finalists that are not yet apart.
+    #[arg(long, default_value_t = 100_000)]
+    max_simulations: Num,
+
+    /// The seed that the search, and every game, is rolled from.
+    /// The default is a random seed, which is reported so that a search can be reproduced.
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// The confidence level of the intervals of the finalists.
+    #[arg(long, default_value_t = 0.95)]
+    confidence: Float,
+}
+
+/// The arguments for the `merge` command.
+#[derive(clap::Args, Debug)]
+struct MergeArgs {
+    /// The partial results files of every shard of the run (see `simulate --shard-index`).
+    #[arg(required = true)]
+    paths: Vec<std::path::PathBuf>,
+}
+
+/// The arguments for the `analyze` command.
+#[derive(clap::Args, Debug)]
+struct AnalyzeArgs {
+    #[command(subcommand)]
+    command: AnalyzeCommand,
+}
+
+/// The analyses supported by the `analyze` command.
+#[derive(Subcommand, Debug)]
+enum AnalyzeCommand {
+    /// Reports the expected remaining rolls and steps from a bucket state.
+    State(AnalyzeStateArgs),
+
+    /// Reports the probability that each player wins a race from their current matched counts.
+    Race(AnalyzeRaceArgs),
+
+    /// Reports how the probability of winning a race, and the time it takes, scale with the number of players at the
+    /// table, for each strategy.
+    RaceSweep(AnalyzeRaceSweepArgs),
+
+    /// Reports the statistics of the games in a record file (see `simulate --records`), streamed from the file.
+    Records(AnalyzeRecordsArgs),
+
+    /// Reports how often each class of decision comes up in the games of each strategy (e.g., how often divide keeps
+    /// two groups), which explains why the strategies differ.
+    Decisions(AnalyzeDecisionsArgs),
+}
+
+/// The arguments for the `analyze state` command.
+#[derive(clap::Args, Debug)]
+struct AnalyzeStateArgs {
+    /// The current bucket state (i.e., the number of dice kept for each face); the number of sides is its length.
+    /// For example, "0,0,6,0,0,0" is six 3s kept on six-sided die.
+    #[arg(short = 'b', long, value_delimiter = ',', required = true)]
+    state: Vec<Num>,
+
+    /// The number of die in the game.
+    #[arg(short, long, default_value_t = 10)]
+    dice: Num,
+
+    /// The number of conditional simulations to run.
+    #[arg(short = 'm', long, default_value_t = 10_000)]
+    simulations: Num,
+
+    /// The strategy to analyze.
+    #[arg(short = 't', long, long_help = strategy_help("The strategy to analyze.", Some("The default is to analyze all of them.")))]
+    strategy: Option<String>,
+}
+
+/// The arguments for the `analyze race` command.
+#[derive(clap::Args, Debug)]
+struct AnalyzeRaceArgs {
+    /// A player in the race, as "strategy:matched" (e.g., "merge:3" is a player using the merge
+    /// strategy who has already matched three dice).  Specify once per player.
+    #[arg(short, long = "player", required = true)]
+    players: Vec<String>,
+
+    /// The number of sides on each die.
+    #[arg(short, long, default_value_t = 6)]
+    sides: Num,
+
+    /// The number of die each player rolls.
+    #[arg(short, long, default_value_t = 10)]
+    dice: Num,
+
+    /// The number of races to simulate.
+    #[arg(short = 'm', long, default_value_t = 10_000)]
+    simulations: Num,
+
+    /// Rolls the dice of each player in each race from a stream of its own under the given seed, which makes the
+    /// results exactly reproducible.
+    #[arg(long)]
+    seed: Option<u64>,
+}
+
+/// The arguments for the `analyze decisions` command.
+#[derive(clap::Args, Debug)]
+struct AnalyzeDecisionsArgs {
     /// The number of sides on each die.
     #[arg(short, long, default_value_t = 6)]
     sides: Num,
@@ -54,82 +1855,464 @@ struct Args {
     #[arg(short, long, default_value_t = 10)]
     dice: Num,
 
-    /// The number of simulations to run.
+    /// The strategies to audit, separated by commas.
+    #[arg(short = 't', long, value_delimiter = ',', long_help = strategy_help("The strategies to audit, separated by commas.", Some("The default is to audit all of them.")))]
+    strategies: Vec<String>,
+
+    /// The number of games to audit each strategy over.
+    #[arg(short = 'm', long, default_value_t = 10_000)]
+    simulations: Num,
+
+    /// The seed to roll the dice from, which audits exactly the games of a seeded `simulate` run.
+    #[arg(long)]
+    seed: Option<u64>,
+}
+
+/// The arguments for the `analyze race-sweep` command.
+#[derive(clap::Args, Debug)]
+struct AnalyzeRaceSweepArgs {
+    /// The numbers of players at the table, separated by commas.
+    #[arg(short, long, value_delimiter = ',', default_value = "2,3,4,5,6,7,8")]
+    players: Vec<Num>,
+
+    /// The strategies to race, separated by commas.
+    #[arg(short = 't', long, value_delimiter = ',', long_help = strategy_help("The strategies to race, separated by commas.", Some("The default is to race all of them.")))]
+    strategies: Vec<String>,
+
+    /// The strategy that the rest of the table plays.
+    /// The default is the strategy that is raced.
+    #[arg(long)]
+    against: Option<String>,
+
+    /// The number of sides on each die.
+    #[arg(short, long, default_value_t = 6)]
+    sides: Num,
+
+    /// The number of die each player rolls.
+    #[arg(short, long, default_value_t = 10)]
+    dice: Num,
+
+    /// The number of races to simulate at each table size.
+    #[arg(short = 'm', long, default_value_t = 10_000)]
+    simulations: Num,
+
+    /// Rolls the dice of each player in each race from a stream of its own under the given seed, which makes the
+    /// results exactly reproducible.
+    #[arg(long)]
+    seed: Option<u64>,
+}
+
+/// The arguments for the `tournament` command.
+#[derive(clap::Args, Debug)]
+struct TournamentArgs {
+    /// The strategies in the population, separated by commas.
+    #[arg(short = 't', long, value_delimiter = ',', long_help = strategy_help("The strategies in the population, separated by commas.", Some("The default is all of them.")))]
+    strategies: Vec<String>,
+
+    /// The initial share of each strategy in the population, separated by commas (they need not add up to one).
+    /// The default is an equal share each.
+    #[arg(long, value_delimiter = ',')]
+    shares: Vec<Float>,
+
+    /// The number of sides on each die.
+    #[arg(short, long, default_value_t = 6)]
+    sides: Num,
+
+    /// The number of die each player rolls.
+    #[arg(short, long, default_value_t = 10)]
+    dice: Num,
+
+    /// The number of races to simulate for each pair of strategies.
     #[arg(short = 'm', long, default_value_t = 10_000)]
     simulations: Num,
 
+    /// The most generations to evolve the population for.
+    #[arg(short, long, default_value_t = 10_000)]
+    generations: Num,
+
+    /// The population has settled once no share changes by more than this in a generation.
+    #[arg(long, default_value_t = 1e-6)]
+    tolerance: Float,
+
+    /// Rolls the dice of each race from a stream of its own under the given seed, which makes the results exactly
+    /// reproducible.
+    #[arg(long)]
+    seed: Option<u64>,
+}
+
+/// The arguments for the `analyze records` command.
+#[derive(clap::Args, Debug)]
+struct AnalyzeRecordsArgs {
+    /// The record file to analyze.
+    path: std::path::PathBuf,
+}
+
+/// The arguments for the `stats` command.
+#[derive(clap::Args, Debug)]
+struct StatsArgs {
+    /// The record file to compute the statistics of (see `simulate --records`).
+    #[arg(required_unless_present = "input")]
+    path: Option<std::path::PathBuf>,
+
+    /// The record file, given as an option (the way this command used to take it).
+    #[arg(short = 'f', long, conflicts_with = "path", hide = true)]
+    input: Option<std::path::PathBuf>,
+
+    /// The percentiles of the number of rolls to report (e.g., "50,95").
+    #[arg(short, long, value_delimiter = ',', default_values_t = [50.0, 90.0, 99.0])]
+    percentiles: Vec<Float>,
+
+    /// The numbers of rolls to report the tail probability of (i.e., the share of the games that took at least that
+    /// many rolls).
+    #[arg(long, value_delimiter = ',')]
+    tail: Vec<Num>,
+
+    /// A distribution to fit the number of rolls with, by the method of moments: `nbinom` (negative binomial) or
+    /// `poisson`.
+    #[arg(long)]
+    fit: Option<Fit>,
+}
+
+/// The arguments for the `bench` command.
+#[derive(clap::Args, Debug)]
+struct BenchArgs {
+    /// A group of timings to run.  Specify once per group; the default is every group.
+    #[arg(long, value_enum)]
+    only: Vec<bench::Group>,
+
+    /// The time, in milliseconds, to spend on each timing.
+    #[arg(long, default_value_t = 100)]
+    time_ms: u64,
+}
+
+/// The arguments for the `view` command.
+#[derive(clap::Args, Debug)]
+struct ViewArgs {
+    /// A recorded game trace to load.
+    /// If omitted, a fresh game is played with the given parameters.
+    #[arg(short = 'f', long)]
+    trace: Option<std::path::PathBuf>,
+
+    /// A file to save the game trace to (e.g., to revisit a weird game later).
+    #[arg(short = 'o', long)]
+    save: Option<std::path::PathBuf>,
+
+    /// A game snapshot to resume, rather than playing a fresh game.
+    #[arg(short = 'r', long, conflicts_with = "trace")]
+    resume: Option<std::path::PathBuf>,
+
+    /// A file to save a snapshot of the fresh game to, after the step given by `--at-step`, so it can be resumed later.
+    #[arg(long, conflicts_with_all = ["trace", "resume"])]
+    save_snapshot: Option<std::path::PathBuf>,
+
+    /// The step to save the snapshot after.
+    #[arg(long, default_value_t = 0, requires = "save_snapshot")]
+    at_step: Num,
+
+    /// The number of sides on each die.
+    #[arg(short, long, default_value_t = 6)]
+    sides: Num,
+
+    /// The number of die to roll.
+    #[arg(short, long, default_value_t = 10)]
+    dice: Num,
+
     /// The strategy to use.
-    /// Options are "naive", "divide", and "merge".
-    /// The default is "naive".
-    #[arg(short = 't', long, default_value = "naive")]
+    #[arg(short = 't', long, default_value = "naive", long_help = strategy_help("The strategy to use.", None))]
     strategy: String,
+
+    /// The bucket state (i.e., the number of dice already kept for each face) to start the game from.
+    #[arg(short, long, value_delimiter = ',')]
+    initial_state: Option<Vec<Num>>,
 }
 
-/// The output of a monte carlo simulation.
-/// Contains the average number of rolls it took to achieve a "tenzi",
-/// and the standard deviation, and the clock time it took to run.
-struct MonteCarloOutput {
-    average_rolls: Float,
-    std_dev_rolls: Float,
-    average_steps: Float,
-    std_dev_steps: Float,
-    duration: std::time::Duration,
-}
-
-/// Runs an entire monte carlo simulation.
-/// Returns the average number of rolls it took to achieve a "tenzi", and
-/// the standard deviation, and the clock time it took to run.
-fn monte_carlo(strategy_type: SimulationType, num_simulations: Num) -> MonteCarloOutput {
-    let total_rolls = AtomicNum::new(0);
-    let total_squared_rolls = AtomicNum::new(0);
-    let total_steps = AtomicNum::new(0);
-    let total_squared_steps = AtomicNum::new(0);
-
-    let start = std::time::Instant::now();
-
-    (0..num_simulations).into_par_iter().map(|_| {
-        let (rolls, steps) = sim(strategy_type.clone());
-        (rolls, rolls * rolls, steps, steps * steps)
-    }).for_each(|(rolls, squared_rolls, steps, squared_steps)| {
-        total_rolls.fetch_add(rolls, Ordering::Relaxed);
-        total_squared_rolls.fetch_add(squared_rolls, Ordering::Relaxed);
-        total_steps.fetch_add(steps, Ordering::Relaxed);
-        total_squared_steps.fetch_add(squared_steps, Ordering::Relaxed);
-    });
+/// The arguments for the `replay` command.
+#[derive(clap::Args, Debug)]
+struct ReplayArgs {
+    /// A file with the dice that were rolled, in order, separated by whitespace or commas.
+    /// Everything after a "#" on a line is a comment.
+    #[arg(short = 'f', long, required_unless_present = "seed", conflicts_with = "seed")]
+    dice_file: Option<std::path::PathBuf>,
 
-    let total_rolls = total_rolls.load(Ordering::Relaxed);
-    let total_squared_rolls = total_squared_rolls.load(Ordering::Relaxed);
-    let total_steps = total_steps.load(Ordering::Relaxed);
-    let total_squared_steps = total_squared_steps.load(Ordering::Relaxed);
-    
-    let average_rolls = (total_rolls as Float) / (num_simulations as Float);
-    let variance_rolls = (total_squared_rolls as Float) / (num_simulations as Float) - (average_rolls * average_rolls as Float);
-    let std_dev_rolls = variance_rolls.sqrt();
+    /// The seed of a game to replay from a seeded run (as `simulate` reports it, or logs it with `--log-seeds`),
+    /// rather than the dice of a physical game.  With `--game`, this is the seed of the run instead.
+    #[arg(long)]
+    seed: Option<u64>,
 
-    let average_steps = (total_steps as Float) / (num_simulations as Float);
-    let variance_steps = (total_squared_steps as Float) / (num_simulations as Float) - (average_steps * average_steps as Float);
-    let std_dev_steps = variance_steps.sqrt();
+    /// The index of the game to replay in the run with the given seed.
+    #[arg(long, requires = "seed")]
+    game: Option<Num>,
 
+    /// The generator that the run rolled its dice from (see `simulate --rng`).
+    #[arg(long, requires = "seed")]
+    rng: Option<RngKind>,
 
-    let duration = start.elapsed();
+    /// The weight of each face, if the run rolled weighted dice (see `simulate --weights`).
+    #[arg(long, value_delimiter = ',', requires = "seed")]
+    weights: Option<Vec<Float>>,
 
-    MonteCarloOutput {
-        average_rolls,
-        std_dev_rolls,
-        average_steps,
-        std_dev_steps,
-        duration,
-    }
+    /// The house rules that the game was played by (see `simulate --rules`).
+    #[arg(long, conflicts_with = "rules_file")]
+    rules: Option<RuleSet>,
+
+    /// A file of house rules that the game was played by (see `simulate --rules-file`).
+    #[arg(long)]
+    rules_file: Option<std::path::PathBuf>,
+
+    /// The number of sides on each die.
+    #[arg(short, long, default_value_t = 6)]
+    sides: Num,
+
+    /// The number of die in the game.
+    #[arg(short, long, default_value_t = 10)]
+    dice: Num,
+
+    /// The strategy to replay.
+    #[arg(short = 't', long, long_help = strategy_help("The strategy to replay.", Some("The default is to replay all of them.")))]
+    strategy: Option<String>,
+
+    /// The bucket state (i.e., the number of dice already kept for each face) to start the game from.
+    #[arg(short, long, value_delimiter = ',')]
+    initial_state: Option<Vec<Num>>,
+
+    /// A directory to save the trace of each strategy's game to, as "<strategy>.trace" (e.g., to compare them with
+    /// `diff`).
+    #[arg(long)]
+    save_traces: Option<std::path::PathBuf>,
 }
 
-/// Returns the number of rolls it took to achieve a "tenzi".
-fn sim(mut simulation_type: SimulationType) -> (Num, Num) {
-    let strategy = simulation_type.as_strategy_mut();
+/// The arguments for the `diff` command.
+#[derive(clap::Args, Debug)]
+struct DiffArgs {
+    /// The first recorded game trace (see `view --save` and `replay --save-traces`).
+    left: std::path::PathBuf,
 
-    while !strategy.done() {
-        // Run a step.
-        strategy.step();
-    }
+    /// The second recorded game trace.
+    right: std::path::PathBuf,
+
+    /// The number of steps to show before the first divergence.
+    /// The default is to show every step.
+    #[arg(short = 'C', long)]
+    context: Option<Num>,
+}
+
+/// The arguments for the `check` command.
+#[derive(clap::Args, Debug)]
+struct CheckArgs {
+    /// The number of sides on each die.
+    #[arg(short, long, default_value_t = 3)]
+    sides: Num,
+
+    /// The number of die in the game.
+    /// Every roll is played out, so this should stay tiny.
+    #[arg(short, long, default_value_t = 3)]
+    dice: Num,
+
+    /// The number of steps to play out.
+    #[arg(long, default_value_t = 6)]
+    depth: Num,
+
+    /// The strategy to check.
+    #[arg(short = 't', long, long_help = strategy_help("The strategy to check.", Some("The default is to check all of them.")))]
+    strategy: Option<String>,
+}
+
+/// The arguments for the `calibrate` command.
+#[derive(clap::Args, Debug)]
+struct CalibrateArgs {
+    /// A file with the number of times each face came up, as a line of "face count" (or "face: count") per face.
+    /// A face that is listed more than once is summed, and everything after a "#" on a line is a comment.
+    path: std::path::PathBuf,
+
+    /// The number of sides on the die.
+    /// The default is the highest face in the file.
+    #[arg(short, long)]
+    sides: Option<Num>,
+
+    /// The confidence level of the intervals.
+    #[arg(short, long, default_value_t = 0.95)]
+    confidence: Float,
+}
+
+/// The arguments for the `rng-test` command.
+#[derive(clap::Args, Debug)]
+struct RngTestArgs {
+    /// The generator to test (see `simulate --rng`).
+    /// The default is the thread-local rng that unseeded runs roll with.
+    #[arg(long)]
+    rng: Option<RngKind>,
+
+    /// The number of sides on each die.
+    #[arg(short, long, default_value_t = 6)]
+    sides: Num,
+
+    /// The number of dice to roll.
+    #[arg(short = 'n', long, default_value_t = 1_000_000)]
+    rolls: usize,
+
+    /// The seed of the generator.
+    /// The default is a random seed, which is reported so that a failure can be reproduced.
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// The significance level that each test fails below.
+    #[arg(long, default_value_t = 0.001)]
+    alpha: Float,
+}
+
+/// The arguments for the `batch` command.
+#[cfg(feature = "server")]
+#[derive(clap::Args, Debug)]
+struct BatchArgs {
+    /// The file of jobs, with a JSON job specification (or configuration to simulate) per line.
+    /// The default (or "-") is stdin.
+    input: Option<std::path::PathBuf>,
+
+    /// The file to write a line of results per job to.
+    /// The default is stdout.
+    #[arg(short, long)]
+    output: Option<std::path::PathBuf>,
+
+    /// The number of jobs to run at once.
+    #[arg(long, default_value_t = 1)]
+    jobs: usize,
+
+    /// The number of threads that the jobs share.
+    /// The default is one per logical core.
+    #[arg(short = 'j', long)]
+    threads: Option<usize>,
+}
+
+/// The arguments for the `serve` command.
+#[cfg(feature = "server")]
+#[derive(clap::Args, Debug)]
+struct ServeArgs {
+    /// The port to listen on.
+    #[arg(short, long, default_value_t = 8080)]
+    port: u16,
+
+    /// The address to listen on (e.g., "0.0.0.0" to serve other machines).
+    #[arg(long, default_value = "127.0.0.1")]
+    host: String,
+
+    /// The number of jobs to run at once.
+    #[arg(long, default_value_t = 1)]
+    jobs: usize,
+
+    /// The number of jobs that can wait to run, past which submissions are turned away.
+    #[arg(long, default_value_t = 64)]
+    queue: usize,
+
+    /// The number of threads that the jobs share.
+    /// The default is one per logical core.
+    #[arg(short = 'j', long)]
+    threads: Option<usize>,
+
+    /// Also serves the gRPC service on this port, which shares the jobs of the REST API.
+    #[cfg(feature = "grpc")]
+    #[arg(long)]
+    grpc_port: Option<u16>,
+
+    /// Whether to run the jobs locally, to coordinate workers that run their games, or to be such a worker.
+    #[arg(long, value_enum, default_value_t = ServeRole::Local)]
+    role: ServeRole,
+
+    /// The port that a coordinator listens for workers on.
+    #[arg(long, default_value_t = 7070)]
+    cluster_port: u16,
+
+    /// The address of the coordinator that a worker connects to (e.g., "10.0.0.1:7070").
+    #[arg(long, required_if_eq("role", "worker"))]
+    coordinator: Option<String>,
+
+    /// The number of games in each shard that a coordinator hands to a worker.
+    #[arg(long, default_value_t = tenzi_sim::cluster::DEFAULT_SHARD_SIZE)]
+    shard_size: Num,
+}
+
+/// The roles of the `serve` command.
+#[cfg(feature = "server")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+enum ServeRole {
+    /// Serves the API, and runs its jobs.
+    Local,
+    /// Serves the API, and hands the games of its jobs out to the workers that connect to it.
+    Coordinator,
+    /// Connects to a coordinator, and runs the games that it hands out (without serving the API).
+    Worker,
+}
+
+/// The arguments for the `corpus` command.
+#[derive(clap::Args, Debug)]
+struct CorpusArgs {
+    #[command(subcommand)]
+    command: CorpusCommand,
+}
+
+/// The operations supported by the `corpus` command.
+#[derive(Subcommand, Debug)]
+enum CorpusCommand {
+    /// Plays every game of the corpus again, and reports each outcome that differs from the recorded one.
+    /// Exits with a failure if any do.
+    Check(CorpusPathArgs),
+
+    /// Records the outcomes of a seeded game in the corpus (which is created if it does not exist).
+    Add(CorpusAddArgs),
+
+    /// Plays every game of the corpus again, and records the outcomes (i.e., accepts every difference).
+    Update(CorpusPathArgs),
+}
+
+/// The arguments for the `corpus check` and `corpus update` commands.
+#[derive(clap::Args, Debug)]
+struct CorpusPathArgs {
+    /// The corpus file.
+    path: std::path::PathBuf,
+}
+
+/// The arguments for the `corpus add` command.
+#[derive(clap::Args, Debug)]
+struct CorpusAddArgs {
+    /// The corpus file.
+    path: std::path::PathBuf,
+
+    /// The seed of the game (as `simulate --log-seeds` logs it).  With `--game`, this is the seed of the run instead.
+    #[arg(long)]
+    seed: u64,
+
+    /// The index of the game in the run with the given seed.
+    #[arg(long)]
+    game: Option<Num>,
+
+    /// The generator that the game rolls its dice from (see `simulate --rng`).
+    #[arg(long, default_value_t = RngKind::Std)]
+    rng: RngKind,
+
+    /// The number of sides on each die.
+    #[arg(short, long, default_value_t = 6)]
+    sides: Num,
+
+    /// The number of die in the game.
+    #[arg(short, long, default_value_t = 10)]
+    dice: Num,
+
+    /// The strategy to record.
+    #[arg(short = 't', long, long_help = strategy_help("The strategy to record.", Some("The default is to record all of them.")))]
+    strategy: Option<String>,
+}
 
-    (strategy.num_rolls(), strategy.num_steps())
+/// The arguments for the `repl` command.
+#[derive(clap::Args, Debug)]
+struct ReplArgs {
+    /// The initial number of sides on each die.
+    #[arg(short, long, default_value_t = 6)]
+    sides: Num,
+
+    /// The initial number of die in the game.
+    #[arg(short, long, default_value_t = 10)]
+    dice: Num,
+
+    /// The initial number of simulations to run per query.
+    #[arg(short = 'm', long, default_value_t = 10_000)]
+    simulations: Num,
 }
\ No newline at end of file
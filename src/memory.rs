@@ -0,0 +1,206 @@
+//! Measuring what a run cost in memory, which is reported with its results (see
+//! [`StrategySummary::memory`](crate::StrategySummary::memory)).
+//!
+//! A run reports three things:
+//!
+//! * The peak resident set size of the process, as the OS tracks it (only on Linux, from `/proc/self/status`).  It is
+//!   the high-water mark of the whole process so far, not only of the run.
+//! * The peak of the heap during the run, above what it was when the run started, if the binary counts its
+//!   allocations with the [`CountingAllocator`] (as the CLI does).  Runs that overlap (e.g., the jobs of a server)
+//!   share the count, so each of them sees the peak of all of them.
+//! * The simulator's own major allocations: the histogram of the number of rolls, and the raw buffers of the run,
+//!   which are those of the sinks that the games were recorded into (see
+//!   [`MetricSink::heap_bytes`](crate::metrics::MetricSink::heap_bytes)), or the pre-generated dice that the games
+//!   were played with.
+
+use std::{alloc::{GlobalAlloc, Layout, System}, sync::{atomic::{AtomicUsize, Ordering}, OnceLock}};
+
+/// The allocator whose counts the runs report (see [`CountingAllocator::report_runs`]).
+static REPORTED: OnceLock<&'static CountingAllocator> = OnceLock::new();
+
+/// The memory that a run cost (see the [module](self) docs).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MemoryUsage {
+    /// The peak resident set size of the process, in bytes, if the OS reports it.
+    pub peak_rss: Option<u64>,
+    /// The peak of the heap during the run, in bytes above the heap when it started, if the allocations are counted.
+    pub peak_heap: Option<u64>,
+    /// The bytes of the histogram of the number of rolls.
+    pub histogram_bytes: u64,
+    /// The bytes of the raw buffers of the run (i.e., of its sinks, or of its pre-generated dice).
+    pub buffer_bytes: u64,
+}
+
+impl MemoryUsage {
+    /// Measures the memory of a run that started with the given heap (see [`start_run`]), and held the given bytes of
+    /// histogram and raw buffers.
+    pub(crate) fn measure(start: Option<u64>, histogram_bytes: u64, buffer_bytes: u64) -> Self {
+        Self {
+            peak_rss: peak_rss(),
+            peak_heap: start.zip(REPORTED.get()).map(|(start, allocator)| allocator.peak().saturating_sub(start)),
+            histogram_bytes,
+            buffer_bytes,
+        }
+    }
+}
+
+/// A global allocator that counts the bytes allocated through it (on top of the [`System`] allocator), so that runs
+/// can report the peak of their heap.
+///
+/// A binary installs it as its `#[global_allocator]` (in a static, from [`CountingAllocator::new`]), and calls
+/// [`CountingAllocator::report_runs`] on it at startup, as the CLI does.
+pub struct CountingAllocator {
+    current: AtomicUsize,
+    peak: AtomicUsize,
+}
+
+impl CountingAllocator {
+    /// Returns an allocator that has counted nothing yet.
+    pub const fn new() -> Self {
+        Self { current: AtomicUsize::new(0), peak: AtomicUsize::new(0) }
+    }
+
+    /// Has every run report the peak of the heap as this allocator counts it (which only the first call does).
+    pub fn report_runs(&'static self) {
+        let _ = REPORTED.set(self);
+    }
+
+    /// Returns the bytes currently allocated.
+    pub fn current(&self) -> u64 {
+        self.current.load(Ordering::Relaxed) as u64
+    }
+
+    /// Returns the most bytes allocated at once, since the peak was last reset.
+    pub fn peak(&self) -> u64 {
+        self.peak.load(Ordering::Relaxed) as u64
+    }
+
+    /// Resets the peak to the bytes currently allocated, and returns them.
+    pub fn reset_peak(&self) -> u64 {
+        let current = self.current.load(Ordering::Relaxed);
+        self.peak.store(current, Ordering::Relaxed);
+
+        current as u64
+    }
+
+    fn grow(&self, size: usize) {
+        let current = self.current.fetch_add(size, Ordering::Relaxed) + size;
+        self.peak.fetch_max(current, Ordering::Relaxed);
+    }
+
+    fn shrink(&self, size: usize) {
+        self.current.fetch_sub(size, Ordering::Relaxed);
+    }
+}
+
+impl Default for CountingAllocator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Safety: every call is forwarded to the system allocator, and only the counts are added.
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+
+        if !ptr.is_null() {
+            self.grow(layout.size());
+        }
+
+        ptr
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc_zeroed(layout);
+
+        if !ptr.is_null() {
+            self.grow(layout.size());
+        }
+
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        self.shrink(layout.size());
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = System.realloc(ptr, layout, new_size);
+
+        // A reallocation that moves holds both blocks for a moment, so count the new one before dropping the old.
+
+        if !new_ptr.is_null() {
+            self.grow(new_size);
+            self.shrink(layout.size());
+        }
+
+        new_ptr
+    }
+}
+
+/// Starts measuring the peak of the heap for a run: resets the peak of the reported allocator (see
+/// [`CountingAllocator::report_runs`]) to the current heap, and returns it, or `None` if no allocator is reported.
+pub fn start_run() -> Option<u64> {
+    REPORTED.get().map(|allocator| allocator.reset_peak())
+}
+
+/// Returns the peak resident set size of the process, in bytes, or `None` if the OS does not report it.
+pub fn peak_rss() -> Option<u64> {
+    #[cfg(target_os = "linux")]
+    {
+        let status = std::fs::read_to_string("/proc/self/status").ok()?;
+        let line = status.lines().find(|line| line.starts_with("VmHWM:"))?;
+        let kibibytes = line.trim_start_matches("VmHWM:").trim().trim_end_matches("kB").trim().parse::<u64>().ok()?;
+
+        Some(kibibytes * 1024)
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    {
+        None
+    }
+}
+
+// Tests.
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_counting_allocator() {
+        // The tests do not install an allocator, so drive one directly.
+
+        static ALLOCATOR: CountingAllocator = CountingAllocator::new();
+
+        let small = Layout::from_size_align(8, 8).unwrap();
+        let large = Layout::from_size_align(1 << 20, 8).unwrap();
+
+        unsafe {
+            let ptr = ALLOCATOR.alloc(small);
+            ALLOCATOR.dealloc(ptr, small);
+
+            assert_eq!((ALLOCATOR.current(), ALLOCATOR.peak()), (0, 8));
+            assert_eq!(ALLOCATOR.reset_peak(), 0);
+
+            let ptr = ALLOCATOR.alloc(large);
+            let ptr = ALLOCATOR.realloc(ptr, large, 2 << 20);
+            ALLOCATOR.dealloc(ptr, Layout::from_size_align(2 << 20, 8).unwrap());
+        }
+
+        assert_eq!((ALLOCATOR.current(), ALLOCATOR.peak()), (0, 3 << 20));
+
+        // Without a reported allocator, a run only reports what it knows of its own allocations.
+
+        let usage = MemoryUsage::measure(start_run(), 10, 20);
+
+        assert_eq!((usage.peak_heap, usage.histogram_bytes, usage.buffer_bytes), (None, 10, 20));
+
+        #[cfg(target_os = "linux")]
+        assert!(usage.peak_rss.unwrap() > 0);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,378 @@
+use crate::{memory::MemoryUsage, observer::GameView, results::StrategySummary, types::{Float, Num, Total}};
+
+/// The outcome of a single simulated game.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GameOutcome {
+    /// The number of rolls it took to achieve a "tenzi".
+    pub num_rolls: Num,
+    /// The number of steps it took to achieve a "tenzi".
+    pub num_steps: Num,
+}
+
+/// An accumulator that the monte carlo runner hands the outcome of every game (and, optionally, every step).
+///
+/// Sinks are mergeable rather than shared: each worker records into its own [`MetricSink::fork`] of the sink, and the
+/// forks are [`MetricSink::merge`]d back together once the games are done, so recording never needs a lock.  The
+/// built-in statistics are the [`Moments`] sink, and a pair of sinks records into both.
+pub trait MetricSink: Send + Sync {
+    /// Returns an empty sink with the same settings, for another worker to record into.
+    fn fork(&self) -> Self;
+
+    /// Records the outcome of a game.
+    fn record(&mut self, outcome: &GameOutcome);
+
+    /// Records the outcome of the game at the given index of the run, which the runners that know the index (e.g.,
+    /// every [`SimulationConfig`](crate::SimulationConfig) run on the CPU) call instead of [`MetricSink::record`].
+    fn record_game(&mut self, _index: Num, outcome: &GameOutcome) {
+        self.record(outcome);
+    }
+
+    /// Merges the sink of another worker into this one.
+    fn merge(&mut self, other: Self);
+
+    /// Called after every step of every game, before the game's outcome is recorded.
+    fn on_step(&mut self, _view: &GameView) {}
+
+    /// Returns the bytes that the sink holds on the heap (e.g., its buffers), which a run reports as what the sink
+    /// cost in memory (see [`MemoryUsage`](crate::memory::MemoryUsage)).
+    fn heap_bytes(&self) -> usize {
+        0
+    }
+}
+
+/// A sink that records nothing.
+impl MetricSink for () {
+    fn fork(&self) -> Self {}
+
+    fn record(&mut self, _outcome: &GameOutcome) {}
+
+    fn merge(&mut self, _other: Self) {}
+}
+
+/// A sink that is only there if it is needed (e.g., a record file that was asked for).
+impl<S: MetricSink> MetricSink for Option<S> {
+    fn fork(&self) -> Self {
+        self.as_ref().map(S::fork)
+    }
+
+    fn record(&mut self, outcome: &GameOutcome) {
+        if let Some(sink) = self {
+            sink.record(outcome);
+        }
+    }
+
+    fn record_game(&mut self, index: Num, outcome: &GameOutcome) {
+        if let Some(sink) = self {
+            sink.record_game(index, outcome);
+        }
+    }
+
+    fn merge(&mut self, other: Self) {
+        if let (Some(sink), Some(other)) = (self, other) {
+            sink.merge(other);
+        }
+    }
+
+    fn on_step(&mut self, view: &GameView) {
+        if let Some(sink) = self {
+            sink.on_step(view);
+        }
+    }
+
+    fn heap_bytes(&self) -> usize {
+        self.as_ref().map_or(0, S::heap_bytes)
+    }
+}
+
+impl<A: MetricSink, B: MetricSink> MetricSink for (A, B) {
+    fn fork(&self) -> Self {
+        (self.0.fork(), self.1.fork())
+    }
+
+    fn record(&mut self, outcome: &GameOutcome) {
+        self.0.record(outcome);
+        self.1.record(outcome);
+    }
+
+    fn record_game(&mut self, index: Num, outcome: &GameOutcome) {
+        self.0.record_game(index, outcome);
+        self.1.record_game(index, outcome);
+    }
+
+    fn merge(&mut self, other: Self) {
+        self.0.merge(other.0);
+        self.1.merge(other.1);
+    }
+
+    fn on_step(&mut self, view: &GameView) {
+        self.0.on_step(view);
+        self.1.on_step(view);
+    }
+
+    fn heap_bytes(&self) -> usize {
+        self.0.heap_bytes() + self.1.heap_bytes()
+    }
+}
+
+/// The built-in sink: the running sums that the mean and standard deviation of the number of rolls and steps are
+/// computed from (which are wide enough for a run of any length, see [`Total`]), and (optionally) the histogram of the
+/// number of rolls.
+///
+/// Like every sink, each worker records into its own fork, so the histogram's bins (one per number of rolls, which is
+/// grown as longer games are seen) are only ever touched by one worker, and are summed when the forks are merged.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Moments {
+    num_games: Num,
+    #[cfg_attr(feature = "serde", serde(with = "crate::types::serde_total"))]
+    total_rolls: Total,
+    #[cfg_attr(feature = "serde", serde(with = "crate::types::serde_total"))]
+    total_squared_rolls: Total,
+    #[cfg_attr(feature = "serde", serde(with = "crate::types::serde_total"))]
+    total_steps: Total,
+    #[cfg_attr(feature = "serde", serde(with = "crate::types::serde_total"))]
+    total_squared_steps: Total,
+    rolls_histogram: Option<Vec<Num>>,
+}
+
+impl Moments {
+    /// Returns empty moments, which also count the games by their number of rolls if asked to.
+    pub fn new(histogram: bool) -> Self {
+        Self {
+            rolls_histogram: histogram.then(Vec::new),
+            ..Self::default()
+        }
+    }
+
+    /// Returns the number of games recorded.
+    pub fn num_games(&self) -> Num {
+        self.num_games
+    }
+
+    /// Returns the average number of rolls.
+    pub fn average_rolls(&self) -> Float {
+        average(self.total_rolls, self.num_games)
+    }
+
+    /// Returns the standard deviation of the number of rolls.
+    pub fn std_dev_rolls(&self) -> Float {
+        std_dev(self.total_rolls, self.total_squared_rolls, self.num_games)
+    }
+
+    /// Returns the average number of steps.
+    pub fn average_steps(&self) -> Float {
+        average(self.total_steps, self.num_games)
+    }
+
+    /// Returns the standard deviation of the number of steps.
+    pub fn std_dev_steps(&self) -> Float {
+        std_dev(self.total_steps, self.total_squared_steps, self.num_games)
+    }
+
+    /// Returns the number of games that took each number of rolls, if it is recorded.
+    pub fn rolls_histogram(&self) -> Option<&[Num]> {
+        self.rolls_histogram.as_deref()
+    }
+
+    /// Returns the running sums: the number of games, and the totals of the rolls, the squared rolls, the steps, and the
+    /// squared steps.
+    pub(crate) fn sums(&self) -> [Total; 5] {
+        [self.num_games as Total, self.total_rolls, self.total_squared_rolls, self.total_steps, self.total_squared_steps]
+    }
+
+    /// Returns the moments with the given running sums (see [`Moments::sums`]) and histogram, or `None` if there are
+    /// more games than the counter type counts.
+    pub(crate) fn from_sums(sums: [Total; 5], rolls_histogram: Option<Vec<Num>>) -> Option<Self> {
+        let [num_games, total_rolls, total_squared_rolls, total_steps, total_squared_steps] = sums;
+
+        Some(Self { num_games: Num::try_from(num_games).ok()?, total_rolls, total_squared_rolls, total_steps, total_squared_steps, rolls_histogram })
+    }
+
+    /// Returns the progress of a run of the given number of games, of which these are the completed ones.
+    #[cfg(feature = "server")]
+    pub(crate) fn progress(&self, total: Num) -> crate::progress::Progress {
+        crate::progress::Progress { completed: self.num_games, total, total_rolls: self.total_rolls, total_steps: self.total_steps, total_squared_rolls: self.total_squared_rolls, total_squared_steps: self.total_squared_steps }
+    }
+
+    /// Summarizes the moments of the given strategy's games.
+    pub(crate) fn summarize(self, strategy: &str, duration: std::time::Duration) -> StrategySummary {
+        StrategySummary {
+            strategy: strategy.to_string(),
+            num_simulations: self.num_games,
+            average_rolls: self.average_rolls(),
+            std_dev_rolls: self.std_dev_rolls(),
+            average_steps: self.average_steps(),
+            std_dev_steps: self.std_dev_steps(),
+            rolls_histogram: self.rolls_histogram,
+            duration,
+            memory: MemoryUsage::default(),
+        }
+    }
+}
+
+impl MetricSink for Moments {
+    fn fork(&self) -> Self {
+        Self::new(self.rolls_histogram.is_some())
+    }
+
+    fn record(&mut self, outcome: &GameOutcome) {
+        let GameOutcome { num_rolls, num_steps } = *outcome;
+
+        let (num_rolls, num_steps) = (num_rolls as Total, num_steps as Total);
+
+        self.num_games += 1;
+        self.total_rolls += num_rolls;
+        self.total_squared_rolls += num_rolls * num_rolls;
+        self.total_steps += num_steps;
+        self.total_squared_steps += num_steps * num_steps;
+
+        if let Some(histogram) = &mut self.rolls_histogram {
+            if histogram.len() <= outcome.num_rolls as usize {
+                histogram.resize(outcome.num_rolls as usize + 1, 0);
+            }
+            histogram[outcome.num_rolls as usize] += 1;
+        }
+    }
+
+    fn merge(&mut self, other: Self) {
+        self.num_games += other.num_games;
+        self.total_rolls += other.total_rolls;
+        self.total_squared_rolls += other.total_squared_rolls;
+        self.total_steps += other.total_steps;
+        self.total_squared_steps += other.total_squared_steps;
+
+        if let (Some(histogram), Some(other)) = (&mut self.rolls_histogram, other.rolls_histogram) {
+            if histogram.len() < other.len() {
+                histogram.resize(other.len(), 0);
+            }
+            histogram.iter_mut().zip(other).for_each(|(count, other)| *count += other);
+        }
+    }
+
+    fn heap_bytes(&self) -> usize {
+        self.rolls_histogram.as_ref().map_or(0, |histogram| histogram.capacity() * core::mem::size_of::<Num>())
+    }
+}
+
+/// The leanest sink: only the number of games, and the total rolls and steps (so only their averages).
+///
+/// It records nothing per step, so with [`monte_carlo_throughput`](crate::monte_carlo_throughput), the games are
+/// played with no more bookkeeping than adding up their outcomes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Totals {
+    num_games: Num,
+    total_rolls: Total,
+    total_steps: Total,
+}
+
+impl Totals {
+    /// Returns the number of games recorded.
+    pub fn num_games(&self) -> Num {
+        self.num_games
+    }
+
+    /// Returns the average number of rolls.
+    pub fn average_rolls(&self) -> Float {
+        average(self.total_rolls, self.num_games)
+    }
+
+    /// Returns the average number of steps.
+    pub fn average_steps(&self) -> Float {
+        average(self.total_steps, self.num_games)
+    }
+}
+
+impl MetricSink for Totals {
+    fn fork(&self) -> Self {
+        Self::default()
+    }
+
+    fn record(&mut self, outcome: &GameOutcome) {
+        self.num_games += 1;
+        self.total_rolls += outcome.num_rolls as Total;
+        self.total_steps += outcome.num_steps as Total;
+    }
+
+    fn merge(&mut self, other: Self) {
+        self.num_games += other.num_games;
+        self.total_rolls += other.total_rolls;
+        self.total_steps += other.total_steps;
+    }
+}
+
+// Helpers.
+
+fn average(total: Total, count: Num) -> Float {
+    (total as Float) / (count as Float)
+}
+
+fn std_dev(total: Total, total_squared: Total, count: Num) -> Float {
+    let average = average(total, count);
+    let variance = (total_squared as Float) / (count as Float) - (average * average);
+
+    variance.sqrt()
+}
+
+// Tests.
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn outcome(num_rolls: Num, num_steps: Num) -> GameOutcome {
+        GameOutcome { num_rolls, num_steps }
+    }
+
+    #[test]
+    fn test_moments() {
+        let mut moments = Moments::new(true);
+        moments.record(&outcome(2, 1));
+        moments.record(&outcome(4, 3));
+
+        assert_eq!(moments.num_games(), 2);
+        assert_eq!(moments.average_rolls(), 3.0);
+        assert_eq!(moments.std_dev_rolls(), 1.0);
+        assert_eq!(moments.average_steps(), 2.0);
+        assert_eq!(moments.rolls_histogram(), Some(&[0, 0, 1, 0, 1][..]));
+    }
+
+    #[test]
+    fn test_moments_merge() {
+        let mut whole = Moments::new(true);
+        let mut left = whole.fork();
+        let mut right = whole.fork();
+
+        for (i, rolls) in [5, 3, 8, 3].into_iter().enumerate() {
+            whole.record(&outcome(rolls, 1));
+
+            match i % 2 {
+                0 => left.record(&outcome(rolls, 1)),
+                _ => right.record(&outcome(rolls, 1)),
+            }
+        }
+
+        left.merge(right);
+
+        assert_eq!(left, whole);
+    }
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_wide_totals() {
+        // Totals past a `u64` survive a message that is an internally tagged enum, like those of a cluster.
+
+        #[derive(serde::Serialize, serde::Deserialize)]
+        #[serde(tag = "type")]
+        enum Message {
+            Partial { moments: Moments },
+        }
+
+        let moments = Moments::from_sums([2, 7, Total::from(u64::MAX) * 3, 2, 2], None).unwrap();
+
+        let json = serde_json::to_string(&Message::Partial { moments: moments.clone() }).unwrap();
+        let Message::Partial { moments: parsed } = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(parsed, moments);
+        assert!(json.contains("\"total_rolls\":7,") && json.contains(&format!("\"{}\"", Total::from(u64::MAX) * 3)));
+    }
+}
\ No newline at end of file
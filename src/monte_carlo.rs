@@ -0,0 +1,722 @@
+use std::{num::NonZero, ops::Range, sync::Mutex, time::Duration};
+
+use rayon::{iter::{IntoParallelIterator, ParallelIterator}, ThreadPool};
+
+// `std::time::Instant` panics in the browser, so the clock comes from the JS runtime there.
+#[cfg(feature = "wasm")]
+use web_time::Instant;
+#[cfg(not(feature = "wasm"))]
+use std::time::Instant;
+
+use crate::{cancel::CancelToken, dice::DiceRng, error::{Result, TenziError}, metrics::{GameOutcome, MetricSink, Moments}, observer::GameView, progress::{Progress, ProgressHook}, rand::{self, SeededDice}, results::StrategySummary, simulation::{SimulationType, Strategy}, types::{Num, Total}};
+
+/// The number of games that are played on the calling thread, and timed, to tune the size of the blocks of work that
+/// are handed to the workers (unless the size is given).
+const CALIBRATION_GAMES: Num = 64;
+
+/// The time that a tuned block of games should take, which is long enough to amortize the scheduling of a block, and
+/// short enough to keep the workers balanced (and a cancelled run responsive).
+const BLOCK_TIME: Duration = Duration::from_micros(500);
+
+/// The most games in a tuned block.
+const MAX_BLOCK_SIZE: Num = 1 << 16;
+
+/// The number of blocks per worker that a tuned block size leaves, at least, so that the work can be balanced.
+const BLOCKS_PER_WORKER: Num = 4;
+
+/// The dice that each game of a seeded run is rolled with, by the game's index in the run (see
+/// [`monte_carlo_seeded`]).
+pub(crate) type GameDice<'a> = &'a (dyn Fn(Num) -> Box<dyn DiceRng> + Sync);
+
+/// Runs an entire monte carlo simulation.
+/// Returns the average number of rolls it took to achieve a "tenzi", and
+/// the standard deviation, and the clock time it took to run.
+pub fn monte_carlo(strategy_type: SimulationType, num_simulations: Num) -> StrategySummary {
+    monte_carlo_cancellable(strategy_type, num_simulations, false, &CancelToken::new(), ProgressHook::none()).expect("the token is never cancelled")
+}
+
+/// Runs an entire monte carlo simulation, like [`monte_carlo`], but on the given thread pool rather than the global one.
+pub fn monte_carlo_in(pool: &ThreadPool, strategy_type: SimulationType, num_simulations: Num) -> StrategySummary {
+    pool.install(|| monte_carlo(strategy_type, num_simulations))
+}
+
+/// How the games of a monte carlo simulation are scheduled.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum Execution {
+    /// The games are spread over a rayon thread pool.
+    #[default]
+    Parallel,
+    /// The games are played one after another on the calling thread, in order, by the same game.  With a seeded
+    /// dice source, the results are exactly reproducible (and easy to follow in a debugger).
+    Serial,
+}
+
+/// Where the games of a monte carlo simulation are played.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum Backend {
+    /// The games are played on the CPU (see [`Execution`]).
+    #[default]
+    Cpu,
+    /// The games are played on the CPU, in lockstep batches that vectorize across the games, which only plays the
+    /// built-in strategies (see [`batch`](crate::batch)).
+    Batched,
+    /// The games are played in a compute shader, which is experimental, only plays the naive strategy, and needs the
+    /// `gpu` feature (see the `gpu` module).
+    Gpu,
+}
+
+impl Backend {
+    /// Returns the name of the backend.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Backend::Cpu => "cpu",
+            Backend::Batched => "batched",
+            Backend::Gpu => "gpu",
+        }
+    }
+}
+
+impl core::fmt::Display for Backend {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl core::str::FromStr for Backend {
+    type Err = TenziError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "cpu" => Ok(Backend::Cpu),
+            "batched" => Ok(Backend::Batched),
+            "gpu" => Ok(Backend::Gpu),
+            _ => Err(TenziError::InvalidConfig(format!("unknown backend `{s}`"))),
+        }
+    }
+}
+
+/// Runs an entire monte carlo simulation, like [`monte_carlo`], but stops early with [`TenziError::Cancelled`]
+/// once the token is cancelled.
+///
+/// The progress hook is periodically handed the progress of the run, and once more when every game is done.
+/// The histogram of the number of rolls is only recorded when asked for, since every game has to update it.
+pub fn monte_carlo_cancellable(strategy_type: SimulationType, num_simulations: Num, histogram: bool, cancel: &CancelToken, progress: ProgressHook<impl Fn(&Progress) + Send + Sync>) -> Result<StrategySummary> {
+    run_to_completion(strategy_type, num_simulations, histogram, Execution::Parallel, cancel, progress)
+}
+
+/// Runs an entire monte carlo simulation, like [`monte_carlo_cancellable`], but with the games played serially
+/// (see [`Execution::Serial`]).
+pub fn monte_carlo_serial(strategy_type: SimulationType, num_simulations: Num, histogram: bool, cancel: &CancelToken, progress: ProgressHook<impl Fn(&Progress) + Send + Sync>) -> Result<StrategySummary> {
+    run_to_completion(strategy_type, num_simulations, histogram, Execution::Serial, cancel, progress)
+}
+
+/// Runs an entire monte carlo simulation, recording every game into the given sink (see [`MetricSink`]) rather than
+/// into the built-in statistics, and returns the sink once the games are done.
+///
+/// The sink is forked for every worker, and the forks are merged back into it at the end.  Once the token is
+/// cancelled, no new games are started, and the sink is returned with the games that were completed (so the aggregates
+/// of a cancelled run are partial, rather than lost).
+///
+/// The size of the blocks of games that are handed to the workers is tuned from the first games (see
+/// [`monte_carlo_blocks`] to set it).
+pub fn monte_carlo_with<S: MetricSink>(strategy_type: SimulationType, num_simulations: Num, execution: Execution, sink: S, cancel: &CancelToken, progress: ProgressHook<impl Fn(&Progress) + Send + Sync>) -> S {
+    monte_carlo_blocks(strategy_type, num_simulations, execution, None, sink, cancel, progress)
+}
+
+/// Runs an entire monte carlo simulation, like [`monte_carlo_with`], but hands the workers blocks of the given number
+/// of games (i.e., rayon's `with_min_len`, with a game that is reused across the block).
+///
+/// Larger blocks amortize the scheduling for very cheap games, and smaller blocks balance the workers (and respond to a
+/// cancellation sooner) for expensive ones.  Without a size, the first games are played on the calling thread and
+/// timed, and the blocks are sized to take about half a millisecond each (while still leaving every worker a few).
+/// A size is never zero, since a block has to hold a game.
+pub fn monte_carlo_blocks<S: MetricSink>(strategy_type: SimulationType, num_simulations: Num, execution: Execution, block_size: Option<NonZero<Num>>, sink: S, cancel: &CancelToken, progress: ProgressHook<impl Fn(&Progress) + Send + Sync>) -> S {
+    play_blocks(strategy_type, num_simulations, execution, block_size, None, sink, cancel, progress)
+}
+
+/// Runs an entire monte carlo simulation, like [`monte_carlo_with`], but rolls the dice of each game from its own seed,
+/// which is the game's substream of the given seed (see [`RngStream`](rand::RngStream)).
+///
+/// Every game is then played the same no matter which worker plays it, or when, so the results are identical for
+/// any number of threads (or serially), and from run to run.
+pub fn monte_carlo_seeded<S: MetricSink>(strategy_type: SimulationType, num_simulations: Num, execution: Execution, seed: u64, sink: S, cancel: &CancelToken, progress: ProgressHook<impl Fn(&Progress) + Send + Sync>) -> S {
+    let stream = rand::RngStream::new(seed);
+    let dice = |index: Num| -> Box<dyn DiceRng> { Box::new(SeededDice::new(stream.substream(index as u64).seed())) };
+
+    play_blocks(strategy_type, num_simulations, execution, None, Some(&dice), sink, cancel, progress)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn play_blocks<S: MetricSink>(strategy_type: SimulationType, num_simulations: Num, execution: Execution, block_size: Option<NonZero<Num>>, dice: Option<GameDice>, sink: S, cancel: &CancelToken, progress: ProgressHook<impl Fn(&Progress) + Send + Sync>) -> S {
+    // Match on the kind of strategy once, so that every game is played by a loop that is specialized to it.
+
+    match strategy_type {
+        SimulationType::Naive(game) => play_games(game, num_simulations, execution, block_size, dice, sink, cancel, progress),
+        SimulationType::Divide(game) => play_games(game, num_simulations, execution, block_size, dice, sink, cancel, progress),
+        SimulationType::Merge(game) => play_games(game, num_simulations, execution, block_size, dice, sink, cancel, progress),
+        SimulationType::Custom(game) => play_games(game, num_simulations, execution, block_size, dice, sink, cancel, progress),
+        SimulationType::Fixed(_, game) => play_games(game, num_simulations, execution, block_size, dice, sink, cancel, progress),
+    }
+}
+
+/// Runs an entire monte carlo simulation of the given game, like [`monte_carlo_blocks`], but with the game's own type
+/// rather than a [`SimulationType`], so that its steps are dispatched statically (and can be inlined into the loop).
+///
+/// A boxed strategy (e.g., [`SimulationType::Fixed`]) is also a strategy, and is played with dynamic dispatch.
+pub fn monte_carlo_game<G: Strategy + Clone, S: MetricSink>(game: G, num_simulations: Num, execution: Execution, block_size: Option<NonZero<Num>>, sink: S, cancel: &CancelToken, progress: ProgressHook<impl Fn(&Progress) + Send + Sync>) -> S {
+    play_games(game, num_simulations, execution, block_size, None, sink, cancel, progress)
+}
+
+/// Plays the games of [`monte_carlo_game`], handing each game its own dice first, if there are any.
+#[allow(clippy::too_many_arguments)]
+fn play_games<G: Strategy + Clone, S: MetricSink>(game: G, num_simulations: Num, execution: Execution, block_size: Option<NonZero<Num>>, dice: Option<GameDice>, mut sink: S, cancel: &CancelToken, progress: ProgressHook<impl Fn(&Progress) + Send + Sync>) -> S {
+    // The running totals for the progress reports are only shared once per block, rather than on every game.
+
+    let shared = Mutex::new(Tally::default());
+
+    // Play the games in blocks, so that a cancelled run skips whole blocks rather than every remaining game.
+
+    let play_block = |game: &mut G, sink: &mut S, games: Range<Num>| {
+        let tally = games.take_while(|_| !cancel.is_cancelled()).fold(Tally::default(), |tally, index| {
+            if let Some(dice) = dice {
+                game.set_rng(dice(index));
+            }
+
+            let outcome = sim(game, sink);
+            sink.record_game(index, &outcome);
+            tally.add(&outcome)
+        });
+
+        let (before, after) = {
+            let mut shared = shared.lock().unwrap();
+            let before = *shared;
+            *shared = shared.merge(tally);
+            (before, *shared)
+        };
+
+        if progress.is_due(before.completed, after.completed) {
+            progress.report(&after.progress(num_simulations));
+        }
+    };
+
+    // Unless the block size is given, time the first games (which count towards the run) to tune it.
+
+    let mut simulation = game.clone();
+    let mut recorded = sink.fork();
+
+    let (first, block_size) = match block_size {
+        Some(block_size) => (0, block_size.get()),
+        None => {
+            let num_games = CALIBRATION_GAMES.min(num_simulations);
+            let num_workers = match execution {
+                Execution::Parallel => rayon::current_num_threads() as Num,
+                Execution::Serial => 1,
+            };
+
+            let start = Instant::now();
+            play_block(&mut simulation, &mut recorded, 0..num_games);
+
+            (num_games, tuned_block_size(start.elapsed(), num_games, num_simulations - num_games, num_workers))
+        }
+    };
+
+    let num_blocks = (num_simulations - first).div_ceil(block_size);
+    let block = |index: Num| blocked(first, index, block_size, num_simulations);
+
+    match execution {
+        // Each worker clones the game once, and then resets it between games.  A clone of a stateful dice source would
+        // roll the dice of every other clone (and of the first games), so every block rolls its own substream of it.
+
+        Execution::Parallel => recorded.merge((0..num_blocks)
+            .into_par_iter()
+            .fold(|| (game.clone(), sink.fork()), |(mut simulation, mut sink), index| {
+                if let Some(rng) = game.split_rng(index as u64) {
+                    simulation.set_rng(rng);
+                }
+
+                play_block(&mut simulation, &mut sink, block(index));
+                (simulation, sink)
+            })
+            .map(|(_, sink)| sink)
+            .reduce(|| sink.fork(), |mut left, right| {
+                left.merge(right);
+                left
+            })),
+        Execution::Serial => (0..num_blocks).for_each(|index| play_block(&mut simulation, &mut recorded, block(index))),
+    }
+
+    progress.report(&shared.into_inner().unwrap().progress(num_simulations));
+
+    sink.merge(recorded);
+
+    sink
+}
+
+/// Runs an entire monte carlo simulation of the given game, in parallel, with as little bookkeeping as possible: there
+/// is no cancellation, no progress, and no timing, and the sink is the only thing that is kept per game (see
+/// [`Totals`](crate::metrics::Totals) for the leanest one).
+///
+/// Since the game and the sink are generic, whatever a sink does not record (e.g., a histogram, or the steps) is
+/// compiled out of the loop, so the throughput is that of the games themselves.  The games are still handed to the
+/// workers in blocks (a few per worker, of at most [`MAX_BLOCK_SIZE`] games), so that a run of billions of games is
+/// split into as many blocks as it needs, rather than into single games.
+pub fn monte_carlo_throughput<G: Strategy + Clone, S: MetricSink>(game: G, num_simulations: Num, mut sink: S) -> S {
+    let block_size = (num_simulations / (rayon::current_num_threads() as Num * BLOCKS_PER_WORKER)).clamp(1, MAX_BLOCK_SIZE);
+
+    let recorded = (0..num_simulations.div_ceil(block_size))
+        .into_par_iter()
+        .fold(|| (game.clone(), sink.fork()), |(mut simulation, mut sink), index| {
+            if let Some(rng) = game.split_rng(index as u64) {
+                simulation.set_rng(rng);
+            }
+
+            for _ in blocked(0, index, block_size, num_simulations) {
+                let outcome = sim(&mut simulation, &mut sink);
+                sink.record(&outcome);
+            }
+
+            (simulation, sink)
+        })
+        .map(|(_, sink)| sink)
+        .reduce(|| sink.fork(), |mut left, right| {
+            left.merge(right);
+            left
+        });
+
+    sink.merge(recorded);
+
+    sink
+}
+
+/// Runs an entire monte carlo simulation, and summarizes the games that were completed before the token was cancelled
+/// (which is every game, unless it was).
+///
+/// Fails with [`TenziError::Cancelled`] only if the token was cancelled before any game was completed.
+pub(crate) fn run(strategy_type: SimulationType, num_simulations: Num, histogram: bool, execution: Execution, block_size: Option<NonZero<Num>>, cancel: &CancelToken, progress: ProgressHook<impl Fn(&Progress) + Send + Sync>) -> Result<StrategySummary> {
+    run_with(strategy_type, num_simulations, histogram, execution, block_size, None, (), cancel, progress).map(|(summary, ())| summary)
+}
+
+/// Runs an entire monte carlo simulation like [`run`], but also records every game into the given sink (and, for a
+/// seeded run, rolls each game with its own dice).
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn run_with<S: MetricSink>(strategy_type: SimulationType, num_simulations: Num, histogram: bool, execution: Execution, block_size: Option<NonZero<Num>>, dice: Option<GameDice>, sink: S, cancel: &CancelToken, progress: ProgressHook<impl Fn(&Progress) + Send + Sync>) -> Result<(StrategySummary, S)> {
+    let start = Instant::now();
+    let name = strategy_type.name();
+
+    let (moments, sink) = play_blocks(strategy_type, num_simulations, execution, block_size, dice, (Moments::new(histogram), sink), cancel, progress);
+
+    if moments.num_games() == 0 && num_simulations != 0 {
+        return Err(TenziError::Cancelled);
+    }
+
+    Ok((moments.summarize(name, start.elapsed()), sink))
+}
+
+/// Runs an entire monte carlo simulation to completion, or fails with [`TenziError::Cancelled`] if it was cancelled.
+fn run_to_completion(strategy_type: SimulationType, num_simulations: Num, histogram: bool, execution: Execution, cancel: &CancelToken, progress: ProgressHook<impl Fn(&Progress) + Send + Sync>) -> Result<StrategySummary> {
+    let summary = run(strategy_type, num_simulations, histogram, execution, None, cancel, progress)?;
+
+    match summary.num_simulations() < num_simulations {
+        true => Err(TenziError::Cancelled),
+        false => Ok(summary),
+    }
+}
+
+/// Returns the number of games per block that takes about [`BLOCK_TIME`], given the time that the calibration games
+/// took, but small enough that the remaining games are spread over a few blocks per worker.
+fn tuned_block_size(elapsed: Duration, num_games: Num, num_remaining: Num, num_workers: Num) -> Num {
+    let per_game = (elapsed.as_nanos() / num_games.max(1) as u128).max(1);
+    let timed = (BLOCK_TIME.as_nanos() / per_game).min(MAX_BLOCK_SIZE as u128) as Num;
+    let balanced = num_remaining / (num_workers * BLOCKS_PER_WORKER);
+
+    timed.min(balanced).max(1)
+}
+
+/// Returns the games of the block at the given index, of the blocks of the given size that the games from the first one
+/// up to the given number are split into (where the last block may be shorter, and the end never overflows).
+fn blocked(first: Num, index: Num, block_size: Num, num_simulations: Num) -> Range<Num> {
+    let start = first + index * block_size;
+
+    start..num_simulations.min(start.saturating_add(block_size))
+}
+
+/// The running totals of a set of completed games.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct Tally {
+    pub(crate) completed: Num,
+    total_rolls: Total,
+    total_steps: Total,
+    total_squared_rolls: Total,
+    total_squared_steps: Total,
+}
+
+impl Tally {
+    /// Adds a completed game.
+    pub(crate) fn add(self, outcome: &GameOutcome) -> Self {
+        let (num_rolls, num_steps) = (outcome.num_rolls as Total, outcome.num_steps as Total);
+
+        Self {
+            completed: self.completed + 1,
+            total_rolls: self.total_rolls + num_rolls,
+            total_steps: self.total_steps + num_steps,
+            total_squared_rolls: self.total_squared_rolls + num_rolls * num_rolls,
+            total_squared_steps: self.total_squared_steps + num_steps * num_steps,
+        }
+    }
+
+    /// Combines the totals of two disjoint sets of games.
+    pub(crate) fn merge(self, other: Self) -> Self {
+        Self {
+            completed: self.completed + other.completed,
+            total_rolls: self.total_rolls + other.total_rolls,
+            total_steps: self.total_steps + other.total_steps,
+            total_squared_rolls: self.total_squared_rolls + other.total_squared_rolls,
+            total_squared_steps: self.total_squared_steps + other.total_squared_steps,
+        }
+    }
+
+    /// Returns the progress of a run of the given number of games.
+    pub(crate) fn progress(self, total: Num) -> Progress {
+        Progress { completed: self.completed, total, total_rolls: self.total_rolls, total_steps: self.total_steps, total_squared_rolls: self.total_squared_rolls, total_squared_steps: self.total_squared_steps }
+    }
+}
+
+/// Plays a game to completion, handing the sink every step, and returns its outcome.
+fn sim<G: Strategy + ?Sized>(strategy: &mut G, sink: &mut impl MetricSink) -> GameOutcome {
+    strategy.reset();
+
+    while !strategy.done() {
+        // Run a step.
+        strategy.step();
+        sink.on_step(&GameView::of(strategy));
+    }
+
+    GameOutcome {
+        num_rolls: strategy.num_rolls(),
+        num_steps: strategy.num_steps(),
+    }
+}
+
+// Tests.
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{metrics::Totals, rand::SeededDice, simulation::{NaiveSimulation, StrategyClone, StrategyKind}, types::Float};
+
+    #[test]
+    fn test_monte_carlo_finished_state() {
+        let strategy = SimulationType::Naive(NaiveSimulation::new(6, 10)).with_initial_state(&[0, 0, 10, 0, 0, 0]);
+
+        let output = monte_carlo(strategy, 100);
+
+        assert_eq!(output.strategy(), "naive");
+        assert_eq!(output.average_rolls(), 0.0);
+        assert_eq!(output.std_dev_rolls(), 0.0);
+        assert_eq!(output.average_steps(), 0.0);
+        assert_eq!(output.rolls_histogram(), None);
+    }
+
+    #[test]
+    fn test_monte_carlo_in() {
+        let pool = rayon::ThreadPoolBuilder::new().num_threads(2).build().unwrap();
+
+        let output = monte_carlo_in(&pool, SimulationType::Naive(NaiveSimulation::new(6, 10)).with_initial_state(&[0, 0, 10, 0, 0, 0]), 100);
+
+        assert_eq!(output.num_simulations(), 100);
+        assert_eq!(output.average_rolls(), 0.0);
+    }
+
+    #[test]
+    fn test_monte_carlo_reuses_games() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        use crate::simulation::{KeepPolicy, KeptBuckets, NaivePolicy};
+
+        static CLONES: AtomicUsize = AtomicUsize::new(0);
+
+        #[derive(Default)]
+        struct Counted(NaivePolicy);
+
+        impl Clone for Counted {
+            fn clone(&self) -> Self {
+                CLONES.fetch_add(1, Ordering::Relaxed);
+                Self(self.0.clone())
+            }
+        }
+
+        impl KeepPolicy for Counted {
+            fn keep(&mut self, buckets: &mut KeptBuckets, num_dice: Num) {
+                self.0.keep(buckets, num_dice)
+            }
+
+            fn reset(&mut self) {
+                self.0.reset()
+            }
+        }
+
+        // The games are reset between runs, so a worker only clones the game once, rather than once per game.
+
+        let pool = rayon::ThreadPoolBuilder::new().num_threads(4).build().unwrap();
+        let moments = pool.install(|| monte_carlo_blocks(SimulationType::custom(Counted::default(), 6, 10), 10_000, Execution::Parallel, NonZero::new(64), Moments::new(false), &CancelToken::new(), ProgressHook::none()));
+
+        assert_eq!(moments.num_games(), 10_000);
+        assert!(CLONES.load(Ordering::Relaxed) < 10_000 / 64);
+    }
+
+    #[test]
+    fn test_monte_carlo_histogram() {
+        let output = monte_carlo_cancellable(SimulationType::Naive(NaiveSimulation::new(6, 10)), 100, true, &CancelToken::new(), ProgressHook::none()).unwrap();
+        let histogram = output.rolls_histogram().unwrap();
+
+        let total_rolls = histogram.iter().enumerate().map(|(rolls, &count)| rolls as Num * count).sum::<Num>();
+
+        assert_eq!(histogram.iter().sum::<Num>(), 100);
+        assert_eq!(total_rolls as Float / 100.0, output.average_rolls());
+    }
+
+    #[test]
+    fn test_monte_carlo_serial() {
+        let run = || monte_carlo_serial(SimulationType::Naive(NaiveSimulation::new(6, 10)).with_rng(SeededDice::new(42)), 100, true, &CancelToken::new(), ProgressHook::none()).unwrap();
+
+        let (first, second) = (run(), run());
+
+        assert_eq!(first.average_rolls(), second.average_rolls());
+        assert_eq!(first.std_dev_steps(), second.std_dev_steps());
+        assert_eq!(first.rolls_histogram(), second.rolls_histogram());
+    }
+
+    #[test]
+    fn test_monte_carlo_with_sink() {
+        // Count the steps that leave the game done, alongside the built-in statistics.
+
+        #[derive(Default)]
+        struct DoneSteps(Num);
+
+        impl MetricSink for DoneSteps {
+            fn fork(&self) -> Self {
+                Self::default()
+            }
+
+            fn record(&mut self, _outcome: &GameOutcome) {}
+
+            fn merge(&mut self, other: Self) {
+                self.0 += other.0;
+            }
+
+            fn on_step(&mut self, view: &GameView) {
+                self.0 += view.done as Num;
+            }
+        }
+
+        let (moments, done_steps) = monte_carlo_with(SimulationType::Naive(NaiveSimulation::new(6, 10)), 500, Execution::Parallel, (Moments::new(false), DoneSteps::default()), &CancelToken::new(), ProgressHook::none());
+
+        assert_eq!(moments.num_games(), 500);
+        assert_eq!(done_steps.0, 500);
+    }
+
+    #[test]
+    fn test_monte_carlo_blocks() {
+        // Every game is played once, whatever the size of the blocks (including ones that do not divide the games).
+
+        for block_size in [NonZero::new(1), NonZero::new(7), NonZero::new(1_000), None] {
+            let moments = monte_carlo_blocks(SimulationType::Naive(NaiveSimulation::new(6, 10)), 500, Execution::Parallel, block_size, Moments::new(true), &CancelToken::new(), ProgressHook::none());
+
+            assert_eq!(moments.num_games(), 500);
+        }
+    }
+
+    #[test]
+    fn test_tuned_block_size() {
+        // Cheap games are blocked up to the time of a block, but never so much that a worker is left without blocks.
+
+        assert_eq!(tuned_block_size(Duration::from_micros(64), 64, 1_000_000, 8), 500);
+        assert_eq!(tuned_block_size(Duration::from_micros(64), 64, 1_000, 8), 31);
+        assert_eq!(tuned_block_size(Duration::ZERO, 64, Num::MAX, 1), MAX_BLOCK_SIZE);
+        assert_eq!(tuned_block_size(Duration::from_secs(1), 64, 1_000_000, 8), 1);
+        assert_eq!(tuned_block_size(Duration::ZERO, 0, 0, 8), 1);
+    }
+
+    #[test]
+    fn test_blocked() {
+        assert_eq!(blocked(64, 0, 100, 1_000), 64..164);
+        assert_eq!(blocked(64, 9, 100, 1_000), 964..1_000);
+
+        // The last block of a run of (nearly) as many games as can be counted ends with the run.
+
+        assert_eq!(blocked(0, Num::MAX / 100, 100, Num::MAX), Num::MAX / 100 * 100..Num::MAX);
+    }
+
+    #[test]
+    fn test_tally_totals() {
+        // The squares of long games add up past a `u64` (and the counter type), without overflowing the totals.
+
+        let long = GameOutcome { num_rolls: u32::MAX as Num, num_steps: 1 };
+        let progress = Tally::default().add(&long).add(&long).progress(2);
+
+        assert_eq!(progress.total_rolls, 2 * u32::MAX as Total);
+        assert_eq!(progress.total_squared_rolls, 2 * (u32::MAX as Total).pow(2));
+        assert_eq!(progress.std_err_rolls(), 0.0);
+    }
+
+    #[test]
+    fn test_monte_carlo_cancelled() {
+        let cancel = CancelToken::new();
+        cancel.cancel();
+
+        let result = monte_carlo_cancellable(SimulationType::Naive(NaiveSimulation::new(6, 10)), 100, false, &cancel, ProgressHook::none());
+
+        assert!(matches!(result, Err(TenziError::Cancelled)));
+    }
+
+    #[test]
+    fn test_monte_carlo_cancelled_partway() {
+        // Cancel after the 100th game, which leaves the games that were completed (and those already in flight).
+
+        let cancel = CancelToken::new();
+        let hook = ProgressHook::new(|_: &Progress| cancel.cancel()).every(100);
+
+        let summary = run(SimulationType::Naive(NaiveSimulation::new(6, 10)), 1_000_000, true, Execution::Parallel, None, &cancel, hook).unwrap();
+
+        assert!(summary.num_simulations() >= 100);
+        assert!(summary.num_simulations() < 1_000_000);
+        assert_eq!(summary.rolls_histogram().unwrap().iter().sum::<Num>(), summary.num_simulations());
+        assert!(summary.average_rolls() > 0.0);
+    }
+
+    #[test]
+    fn test_monte_carlo_progress() {
+        let reports = std::sync::Mutex::new(Vec::new());
+        let hook = ProgressHook::new(|progress: &Progress| reports.lock().unwrap().push(*progress)).every(500);
+
+        let output = monte_carlo_cancellable(SimulationType::Naive(NaiveSimulation::new(6, 10)), 2_000, false, &CancelToken::new(), hook).unwrap();
+
+        let mut reports = reports.into_inner().unwrap();
+        let last = reports.pop().unwrap();
+
+        // Every 500 games, and then once more at the end.
+
+        assert_eq!(reports.iter().map(|p| p.completed).max(), Some(2_000));
+        assert_eq!(reports.len(), 4);
+        assert!(last.is_done());
+        assert_eq!(last.average_rolls(), output.average_rolls());
+    }
+
+    #[test]
+    fn test_monte_carlo_game() {
+        // A game played through its own type, or through a box, gives the same results.
+
+        let boxed: Box<dyn StrategyClone> = Box::new(NaiveSimulation::new(6, 10).with_rng(SeededDice::new(42)));
+
+        let concrete = monte_carlo_game(NaiveSimulation::new(6, 10).with_rng(SeededDice::new(42)), 100, Execution::Serial, NonZero::new(10), Moments::new(true), &CancelToken::new(), ProgressHook::none());
+        let boxed = monte_carlo_game(boxed, 100, Execution::Serial, NonZero::new(10), Moments::new(true), &CancelToken::new(), ProgressHook::none());
+
+        assert_eq!(concrete.num_games(), 100);
+        assert_eq!(concrete.summarize("naive", Duration::ZERO).rolls_histogram(), boxed.summarize("naive", Duration::ZERO).rolls_histogram());
+    }
+
+    #[test]
+    fn test_monte_carlo_seeded() {
+        // Each game is rolled from its own seed, so the scheduling does not change the games.
+
+        let run = |execution| monte_carlo_seeded(SimulationType::new(StrategyKind::Divide, 6, 10), 500, execution, 42, Moments::new(true), &CancelToken::new(), ProgressHook::none());
+
+        assert_eq!(run(Execution::Parallel), run(Execution::Serial));
+        assert_eq!(run(Execution::Parallel).num_games(), 500);
+    }
+
+    #[test]
+    fn test_monte_carlo_stateful_rng() {
+        /// Records the buckets of every step of every game, so that a game that is played twice shows up.
+        #[derive(Default)]
+        struct Trajectories {
+            current: Vec<Num>,
+            games: Vec<Vec<Num>>,
+        }
+
+        impl MetricSink for Trajectories {
+            fn fork(&self) -> Self {
+                Self::default()
+            }
+
+            fn record(&mut self, _outcome: &GameOutcome) {
+                self.games.push(std::mem::take(&mut self.current));
+            }
+
+            fn merge(&mut self, other: Self) {
+                self.games.extend(other.games);
+            }
+
+            fn on_step(&mut self, view: &GameView) {
+                self.current.extend_from_slice(view.buckets);
+            }
+        }
+
+        // Every worker (and the first games, on the calling thread) plays with a clone of a seeded source, which must
+        // not roll the same games as the others.
+
+        let pool = rayon::ThreadPoolBuilder::new().num_threads(4).build().unwrap();
+        let game = NaiveSimulation::new(6, 10).with_rng(SeededDice::new(42));
+
+        let distinct = |sink: Trajectories| {
+            let mut games = sink.games;
+            games.sort_unstable();
+            games.dedup();
+            games.len()
+        };
+
+        let played = pool.install(|| monte_carlo_game(game.clone(), 1_000, Execution::Parallel, None, Trajectories::default(), &CancelToken::new(), ProgressHook::none()));
+        let blocked = pool.install(|| monte_carlo_game(game.clone(), 1_000, Execution::Parallel, NonZero::new(10), Trajectories::default(), &CancelToken::new(), ProgressHook::none()));
+        let thrown = pool.install(|| monte_carlo_throughput(game, 1_000, Trajectories::default()));
+
+        // A few games take the same course by chance, but a stream that is rolled twice repeats hundreds of them.
+
+        assert!(distinct(played) > 990);
+        assert!(distinct(blocked) > 990);
+        assert!(distinct(thrown) > 990);
+    }
+
+    #[test]
+    fn test_monte_carlo_throughput() {
+        let totals = monte_carlo_throughput(NaiveSimulation::new(6, 10), 1_000, Totals::default());
+        let moments = monte_carlo_throughput(NaiveSimulation::new(6, 10), 1_000, Moments::new(false));
+
+        assert_eq!(totals.num_games(), 1_000);
+        assert_eq!(moments.num_games(), 1_000);
+        assert!(totals.average_rolls() > 10.0);
+    }
+
+    // The lean loop against the usual one, both with the leanest sink.
+
+    #[cfg(feature = "nightly")]
+    #[bench]
+    fn bench_monte_carlo_throughput(b: &mut test::Bencher) {
+        b.iter(|| monte_carlo_throughput(NaiveSimulation::new(6, 10), 1_000, Totals::default()));
+    }
+
+    #[cfg(feature = "nightly")]
+    #[bench]
+    fn bench_monte_carlo_bookkept(b: &mut test::Bencher) {
+        b.iter(|| monte_carlo_game(NaiveSimulation::new(6, 10), 1_000, Execution::Parallel, None, Totals::default(), &CancelToken::new(), ProgressHook::none()));
+    }
+
+    // The static dispatch of a game's own type, against the dynamic dispatch of a boxed game.
+
+    #[cfg(feature = "nightly")]
+    #[bench]
+    fn bench_monte_carlo_static(b: &mut test::Bencher) {
+        b.iter(|| monte_carlo_game(NaiveSimulation::new(6, 10), 1_000, Execution::Serial, NonZero::new(100), Moments::new(false), &CancelToken::new(), ProgressHook::none()));
+    }
+
+    #[cfg(feature = "nightly")]
+    #[bench]
+    fn bench_monte_carlo_dynamic(b: &mut test::Bencher) {
+        let game: Box<dyn StrategyClone> = Box::new(NaiveSimulation::new(6, 10));
+
+        b.iter(|| monte_carlo_game(game.clone(), 1_000, Execution::Serial, NonZero::new(100), Moments::new(false), &CancelToken::new(), ProgressHook::none()));
+    }
+}
\ No newline at end of file
@@ -0,0 +1,52 @@
+use crate::{simulation::Strategy, types::Num};
+
+/// A read-only view of a game, handed to an [`Observer`] after each phase of a step.
+#[derive(Clone, Copy, Debug)]
+pub struct GameView<'a> {
+    /// The buckets (i.e., the number of dice for each face).
+    pub buckets: &'a [Num],
+    /// The number of dice in the game.
+    pub num_dice: Num,
+    /// The number of rolls so far.
+    pub num_rolls: Num,
+    /// The number of completed steps so far.
+    pub num_steps: Num,
+    /// Whether or not a "tenzi" has been achieved.
+    pub done: bool,
+}
+
+impl<'a> GameView<'a> {
+    /// Returns a view of the given game.
+    pub fn of<S: Strategy + ?Sized>(strategy: &'a S) -> Self {
+        Self {
+            buckets: strategy.buckets(),
+            num_dice: strategy.num_dice(),
+            num_rolls: strategy.num_rolls(),
+            num_steps: strategy.num_steps(),
+            done: strategy.done(),
+        }
+    }
+}
+
+/// An observer that can be attached to a game, and receives its state after each phase of a step.
+///
+/// Every method has a no-op default, so observers only implement the phases they care about.  The unit type is the
+/// default observer, and compiles away entirely.
+pub trait Observer: Send + Sync {
+    /// Called at the start of every step, before the dice are rolled.
+    /// The buckets hold only the kept dice.
+    fn before_roll(&mut self, _view: &GameView) {}
+
+    /// Called after the dice are rolled, but before the policy decides which to keep.
+    /// The buckets hold the kept dice plus the dice just rolled.
+    fn on_roll(&mut self, _view: &GameView) {}
+
+    /// Called after the policy has decided which dice to keep, and the step is complete.
+    /// The buckets hold only the kept dice.
+    fn on_keep(&mut self, _view: &GameView) {}
+
+    /// Called once, after the step that achieves a "tenzi".
+    fn on_done(&mut self, _view: &GameView) {}
+}
+
+impl Observer for () {}
\ No newline at end of file
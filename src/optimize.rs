@@ -0,0 +1,239 @@
+//! Searching the parameter space of a family of strategies (i.e., a parameterized strategy, such as `threshold=<t>`) for
+//! the value that takes the fewest rolls, with a simple genetic search, so that a family can be tuned without sweeping
+//! every value at full length.
+//!
+//! Every generation plays each value of its population on the same short run of games (i.e., common random numbers),
+//! which correlates their errors, so the values are ranked by how they differ rather than by the noise of independent
+//! runs.  The best value survives into the next generation, and the rest of it is bred from pairs of parents (each the
+//! better of two values drawn from the population), as a value between the two, which sometimes mutates by a step.
+//! Each generation plays games of its own, so a value that survives for long is played on more and more of them.
+//!
+//! Since the search ranks the values by the same games that it selects them with, the best of them look better than they
+//! are.  The values that did best over the whole search are then compared again on fresh games, with an adaptive
+//! budget (see [`adaptive::compare`]), which reports the best of them with a confidence interval, and whether it beats
+//! the others at the confidence level.
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::{adaptive::{self, AdaptiveComparison, AdaptiveOptions}, cancel::CancelToken, error::{Result, TenziError}, metrics::{MetricSink, Moments}, progress::ProgressHook, rand::RngStream, registry::StrategyRegistry, types::{Float, Num}, SimulationConfig};
+
+/// The search space and budget of a search (see [`optimize`]).
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct OptimizeOptions {
+    /// The lowest value of the parameter to search.
+    pub min: Num,
+    /// The highest value of the parameter to search.
+    pub max: Num,
+    /// The number of values in each generation.
+    pub population: usize,
+    /// The number of generations.
+    pub generations: usize,
+    /// The number of games that each value plays in each generation.
+    pub simulations: Num,
+    /// The probability that a bred value mutates by a step.
+    pub mutation_rate: Float,
+    /// The number of the best values that are compared again on fresh games.
+    pub finalists: usize,
+    /// The most games that any finalist plays.
+    pub max_simulations: Num,
+    /// The confidence level of the intervals of the finalists (e.g., `0.95`).
+    pub confidence: Float,
+    /// The seed that the search, and every game, is rolled from.
+    pub seed: u64,
+}
+
+impl Default for OptimizeOptions {
+    /// Searches the thresholds of 10 dice (i.e., every value of `threshold` that keeps a different set of faces).
+    fn default() -> Self {
+        Self { min: 1, max: 6, population: 8, generations: 10, simulations: 2_000, mutation_rate: 0.3, finalists: 3, max_simulations: 100_000, confidence: 0.95, seed: 0 }
+    }
+}
+
+/// A generation of a search.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Generation {
+    /// The values of the generation, some of which may repeat.
+    pub population: Vec<Num>,
+    /// The value that took the fewest rolls on the generation's games.
+    pub best: Num,
+    /// The average number of rolls of the best value on the generation's games.
+    pub average_rolls: Float,
+}
+
+/// The outcome of a search: its generations, and the comparison of its finalists.
+#[derive(Clone, Debug)]
+pub struct Optimization {
+    generations: Vec<Generation>,
+    evaluated: Vec<(Num, Moments)>,
+    finalists: Vec<Num>,
+    comparison: AdaptiveComparison,
+}
+
+impl Optimization {
+    /// Returns the generations of the search.
+    pub fn generations(&self) -> &[Generation] {
+        &self.generations
+    }
+
+    /// Returns every value that the search played, with the moments of all of its games, from the fewest average rolls
+    /// to the most.
+    pub fn evaluated(&self) -> &[(Num, Moments)] {
+        &self.evaluated
+    }
+
+    /// Returns the values that were compared again on fresh games, in the order of the comparison's summaries.
+    pub fn finalists(&self) -> &[Num] {
+        &self.finalists
+    }
+
+    /// Returns the comparison of the finalists.
+    pub fn comparison(&self) -> &AdaptiveComparison {
+        &self.comparison
+    }
+
+    /// Returns the index of the best finalist (i.e., the one with the fewest average rolls on the fresh games).
+    pub fn best_index(&self) -> usize {
+        self.comparison.ranking()[0]
+    }
+
+    /// Returns the best value of the parameter that the search found.
+    pub fn best(&self) -> Num {
+        self.finalists[self.best_index()]
+    }
+
+    /// Returns whether the best value beats every other finalist at the confidence level (rather than having run out of
+    /// games).
+    pub fn is_settled(&self) -> bool {
+        self.comparison.is_settled(self.best_index())
+    }
+}
+
+/// Searches the values of the family's parameter, which is a whole number, for the one that takes the fewest rolls with
+/// the given number of sides and dice (see the [module](self) docs).
+///
+/// Fails with [`TenziError::InvalidStrategy`] if the family does not take a parameter, or if a value of the parameter
+/// is not valid for the family, with [`TenziError::InvalidConfig`] if the options are out of range, and with
+/// [`TenziError::Cancelled`] if the token is cancelled first.
+pub fn optimize(family: &str, num_sides: Num, num_dice: Num, options: &OptimizeOptions, cancel: &CancelToken) -> Result<Optimization> {
+    let OptimizeOptions { min, max, population, generations, simulations, mutation_rate, finalists, max_simulations, confidence, seed } = *options;
+
+    match StrategyRegistry::new().info(family) {
+        Some(info) if info.parameter.is_some() => {}
+        _ => return Err(TenziError::InvalidStrategy { spec: family.to_string(), reason: "only a strategy that takes a parameter can be optimized (see `list-strategies`)".to_string() }),
+    }
+
+    if min > max || population < 2 || generations == 0 || simulations < 2 || !(0.0..=1.0).contains(&mutation_rate) || finalists == 0 || max_simulations < simulations {
+        return Err(TenziError::InvalidConfig("a search needs a range of values, a population of at least two, a generation, at least two games a value up to the finalists' cap, a mutation rate between 0 and 1, and a finalist".to_string()));
+    }
+
+    let config = |value: Num, seed: u64, num_simulations: Num| SimulationConfig::builder().sides(num_sides).dice(num_dice).strategy(format!("{}={}", family, value)).simulations(num_simulations).seed(seed).build();
+
+    // The breeding, every generation's games, and the finalists' games each roll from a substream of their own.
+
+    let mut stream = RngStream::new(seed);
+    let mut rng = StdRng::seed_from_u64(stream.split().seed());
+    let mut values = (0..population).map(|_| rng.gen_range(min..=max)).collect::<Vec<_>>();
+    let mut evaluated = std::collections::BTreeMap::<Num, Moments>::new();
+    let mut history = Vec::with_capacity(generations);
+
+    for generation in 0..generations {
+        let games = stream.split().seed();
+        let mut distinct = values.clone();
+        distinct.sort_unstable();
+        distinct.dedup();
+
+        let mut averages = std::collections::BTreeMap::new();
+
+        for &value in &distinct {
+            let (_, moments) = config(value, games, simulations)?.run_range(0, simulations, Moments::new(false), cancel, ProgressHook::none())?;
+
+            if moments.num_games() != simulations {
+                return Err(TenziError::Cancelled);
+            }
+
+            averages.insert(value, moments.average_rolls());
+            evaluated.entry(value).or_insert_with(|| Moments::new(false)).merge(moments);
+        }
+
+        let fitness = |value: &Num| averages[value];
+        let best = *distinct.iter().min_by(|a, b| fitness(a).total_cmp(&fitness(b))).unwrap();
+
+        history.push(Generation { population: values.clone(), best, average_rolls: averages[&best] });
+
+        if generation + 1 == generations {
+            break;
+        }
+
+        // The best value survives, and each of the rest is bred from the winners of two tournaments.
+
+        let select = |rng: &mut StdRng| {
+            let (a, b) = (values[rng.gen_range(0..population)], values[rng.gen_range(0..population)]);
+            if fitness(&a) <= fitness(&b) { a } else { b }
+        };
+
+        let mut next = vec![best];
+
+        while next.len() < population {
+            let (a, b) = (select(&mut rng), select(&mut rng));
+            let mut child = rng.gen_range(a.min(b)..=a.max(b));
+
+            if rng.gen_bool(mutation_rate as f64) {
+                let step = rng.gen_range(1..=((max - min) / 4).max(1));
+                child = if rng.gen_bool(0.5) { child.saturating_add(step).min(max) } else { child.saturating_sub(step).max(min) };
+            }
+
+            next.push(child);
+        }
+
+        values = next;
+    }
+
+    let mut evaluated = evaluated.into_iter().collect::<Vec<_>>();
+    evaluated.sort_by(|(_, a), (_, b)| a.average_rolls().total_cmp(&b.average_rolls()));
+
+    // The finalists play fresh games, so that their intervals are not biased by having been selected on the search's.
+
+    let finals = stream.split().seed();
+    let finalists = evaluated.iter().take(finalists).map(|(value, _)| *value).collect::<Vec<_>>();
+    let configs = finalists.iter().map(|&value| config(value, finals, max_simulations)).collect::<Result<Vec<_>>>()?;
+    let comparison = adaptive::compare(&configs, AdaptiveOptions { initial_simulations: simulations, max_simulations, confidence }, cancel)?;
+
+    Ok(Optimization { generations: history, evaluated, finalists, comparison })
+}
+
+// Tests.
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_optimize() {
+        // With 10 dice, a threshold of 3 takes over a roll fewer on average than any other.
+
+        let options = OptimizeOptions { population: 4, generations: 4, simulations: 500, max_simulations: 8_000, seed: 7, ..OptimizeOptions::default() };
+        let optimization = optimize("threshold", 6, 10, &options, &CancelToken::new()).unwrap();
+
+        assert_eq!(optimization.best(), 3);
+        assert_eq!(optimization.generations().len(), 4);
+        assert!(optimization.generations().iter().all(|generation| generation.population.len() == 4 && generation.population.contains(&generation.best)));
+        assert!(optimization.evaluated().windows(2).all(|pair| pair[0].1.average_rolls() <= pair[1].1.average_rolls()));
+        assert!(optimization.finalists().len() <= 3);
+
+        // The search is the same from run to run.
+
+        let again = optimize("threshold", 6, 10, &options, &CancelToken::new()).unwrap();
+
+        assert_eq!(again.generations(), optimization.generations());
+
+        // Only a family can be searched, over a valid range.
+
+        assert!(matches!(optimize("naive", 6, 10, &options, &CancelToken::new()), Err(TenziError::InvalidStrategy { .. })));
+        assert!(matches!(optimize("threshold", 6, 10, &OptimizeOptions { min: 4, max: 3, ..options }, &CancelToken::new()), Err(TenziError::InvalidConfig(_))));
+        assert!(matches!(optimize("threshold", 6, 10, &OptimizeOptions { min: 0, max: 0, ..options }, &CancelToken::new()), Err(TenziError::InvalidStrategy { .. })));
+    }
+}
\ No newline at end of file
@@ -0,0 +1,116 @@
+use crate::types::{Float, Num, Total};
+
+/// The default number of completed games between progress reports.
+pub const DEFAULT_INTERVAL: Num = 1_024;
+
+/// The progress of a monte carlo run, with the running totals that the aggregates so far are computed from.
+///
+/// The totals are read while the workers are still playing, so they are only consistent with each other once the run
+/// is done.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Progress {
+    /// The number of completed games.
+    pub completed: Num,
+    /// The number of games in the run.
+    pub total: Num,
+    /// The total number of rolls over the completed games.
+    #[cfg_attr(feature = "serde", serde(with = "crate::types::serde_total"))]
+    pub total_rolls: Total,
+    /// The total number of steps over the completed games.
+    #[cfg_attr(feature = "serde", serde(with = "crate::types::serde_total"))]
+    pub total_steps: Total,
+    /// The total of the squared number of rolls over the completed games.
+    #[cfg_attr(feature = "serde", serde(default, with = "crate::types::serde_total"))]
+    pub total_squared_rolls: Total,
+    /// The total of the squared number of steps over the completed games.
+    #[cfg_attr(feature = "serde", serde(default, with = "crate::types::serde_total"))]
+    pub total_squared_steps: Total,
+}
+
+impl Progress {
+    /// Returns the fraction of the games that are completed (from 0 to 1).
+    pub fn fraction(&self) -> Float {
+        (self.completed as Float) / (self.total as Float)
+    }
+
+    /// Returns whether or not every game is completed.
+    pub fn is_done(&self) -> bool {
+        self.completed == self.total
+    }
+
+    /// Returns the average number of rolls over the completed games so far.
+    pub fn average_rolls(&self) -> Float {
+        (self.total_rolls as Float) / (self.completed.max(1) as Float)
+    }
+
+    /// Returns the average number of steps over the completed games so far.
+    pub fn average_steps(&self) -> Float {
+        (self.total_steps as Float) / (self.completed.max(1) as Float)
+    }
+
+    /// Returns the standard error of the average number of rolls over the completed games so far.
+    pub fn std_err_rolls(&self) -> Float {
+        std_err(self.average_rolls(), self.total_squared_rolls, self.completed)
+    }
+
+    /// Returns the standard error of the average number of steps over the completed games so far.
+    pub fn std_err_steps(&self) -> Float {
+        std_err(self.average_steps(), self.total_squared_steps, self.completed)
+    }
+}
+
+/// Returns the standard error of an average from the total of the squares, which is zero without games.
+fn std_err(average: Float, total_squared: Total, count: Num) -> Float {
+    let count = count.max(1) as Float;
+    let variance = ((total_squared as Float) / count - average * average).max(0.0);
+
+    (variance / count).sqrt()
+}
+
+/// A callback that a monte carlo run hands its [`Progress`] every so many completed games (from whichever worker
+/// completes the block of games that passes the interval), and once more when the run is done.
+///
+/// This is the single hook that every progress display (and stream) is built on.
+#[derive(Clone, Debug)]
+pub struct ProgressHook<F> {
+    interval: Num,
+    callback: F,
+}
+
+impl<F: Fn(&Progress) + Send + Sync> ProgressHook<F> {
+    /// Returns a hook that calls the callback every [`DEFAULT_INTERVAL`] games.
+    pub fn new(callback: F) -> Self {
+        Self { interval: DEFAULT_INTERVAL, callback }
+    }
+
+    /// Calls the callback every `interval` completed games instead, or only when the run is done for 0.
+    ///
+    /// Games are counted a block at a time, so the reported number of completed games can overshoot the interval.
+    pub fn every(mut self, interval: Num) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Returns the number of completed games between calls.
+    pub fn interval(&self) -> Num {
+        self.interval
+    }
+
+    /// Returns whether or not going from `before` to `after` completed games passed an interval, so it is due a report.
+    pub(crate) fn is_due(&self, before: Num, after: Num) -> bool {
+        self.interval != 0 && after / self.interval > before / self.interval
+    }
+
+    /// Calls the callback.
+    pub(crate) fn report(&self, progress: &Progress) {
+        (self.callback)(progress)
+    }
+}
+
+impl ProgressHook<fn(&Progress)> {
+    /// Returns a hook that ignores the progress.
+    pub fn none() -> Self {
+        Self::new(|_| {})
+    }
+}
\ No newline at end of file
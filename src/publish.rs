@@ -0,0 +1,533 @@
+//! Publishing the results of a run to a message queue (or a file) as they are produced, so that a streaming pipeline
+//! downstream can consume them during the run rather than after it.
+//!
+//! A [`Publisher`] sends messages to a NATS subject (with the `nats` feature, see [`NatsPublisher`]) or a Kafka topic
+//! (with the `kafka` feature, see [`KafkaPublisher`]), and [`connect`] picks one from a URL, like
+//! `nats://127.0.0.1:4222/tenzi.games` or `kafka://broker1:9092,broker2:9092/tenzi-games`.  A [`FilePublisher`]
+//! appends the messages to a file instead, as lines of NDJSON, for a plotter or a watchdog that only reads files.  A
+//! run publishes either:
+//!
+//! * every game's outcome, as a message of NDJSON each (`{"rolls":14,"steps":3}`), through the [`RecordWriter`] that
+//!   [`record_sink`] returns, so the workers never wait on the network; or
+//! * periodic snapshots of the aggregates so far, from a [`SnapshotPublisher`] that is handed the run's progress:
+//!   `{"completed":2048,"total":10000,"total_rolls":..,"total_steps":..,"average_rolls":..,"average_steps":..,..}`,
+//!   which also has the standard errors of the averages, their 95% intervals, and the seconds since the first
+//!   snapshot.
+
+use std::{fs::File, io::Write, path::Path, sync::Mutex, time::{Duration, Instant}};
+
+use crate::{calibrate, error::{Result, TenziError}, progress::Progress, records::{RecordThread, RecordWriter}, types::{Float, Num}};
+
+/// A connection that sends messages to a subject (or topic) of a message queue.
+pub trait Publisher: Send {
+    /// Sends a message, which may be buffered until the next [`Publisher::flush`].
+    fn publish(&mut self, payload: &[u8]) -> Result<()>;
+
+    /// Waits until every message that was published so far has been handed to the message queue.
+    fn flush(&mut self) -> Result<()>;
+}
+
+impl<P: Publisher + ?Sized> Publisher for Box<P> {
+    fn publish(&mut self, payload: &[u8]) -> Result<()> {
+        (**self).publish(payload)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        (**self).flush()
+    }
+}
+
+/// Connects to the message queue of the URL (see the [module](self) docs), whose path is the subject (or topic) to
+/// publish to.
+///
+/// Fails with [`TenziError::Publish`] if the URL is malformed, or its scheme was not built in.
+pub fn connect(url: &str) -> Result<Box<dyn Publisher>> {
+    let Some((scheme, rest)) = url.split_once("://") else {
+        return Err(TenziError::Publish(format!("`{}` is not a URL of the form `scheme://address/subject`", url)));
+    };
+
+    let Some((address, subject)) = rest.split_once('/').filter(|(address, subject)| !address.is_empty() && !subject.is_empty()) else {
+        return Err(TenziError::Publish(format!("`{}` does not name both an address and a subject", url)));
+    };
+
+    match (scheme, address, subject) {
+        #[cfg(feature = "nats")]
+        ("nats", address, subject) => Ok(Box::new(NatsPublisher::connect(address, subject)?)),
+        #[cfg(feature = "kafka")]
+        ("kafka", address, subject) => Ok(Box::new(KafkaPublisher::connect(address, subject)?)),
+        (scheme, ..) => Err(TenziError::Publish(format!("the scheme `{}` is not supported by this build (build with the `nats` or `kafka` feature)", scheme))),
+    }
+}
+
+/// A publisher that appends every message to a file, as a line of its own.
+///
+/// Every message is written through when it is published, so a reader of the file sees it right away.
+pub struct FilePublisher {
+    file: File,
+}
+
+impl FilePublisher {
+    /// Opens the file at the given path to append to, creating it if it does not exist.
+    pub fn append(path: impl AsRef<Path>) -> Result<Self> {
+        Ok(Self { file: File::options().create(true).append(true).open(path)? })
+    }
+}
+
+impl Publisher for FilePublisher {
+    fn publish(&mut self, payload: &[u8]) -> Result<()> {
+        // A single write per line, so that a line is never split by another writer of the file.
+
+        let mut line = Vec::with_capacity(payload.len() + 1);
+        line.extend_from_slice(payload);
+        line.push(b'\n');
+
+        Ok(self.file.write_all(&line)?)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(self.file.flush()?)
+    }
+}
+
+/// Returns a sink that publishes every game's outcome as a message (see the [module](self) docs), along with the thread
+/// that publishes them, which [`RecordThread::finish`] waits for (and flushes) once the sink is dropped.
+pub fn record_sink(publisher: impl Publisher + 'static) -> (RecordWriter, RecordThread) {
+    RecordWriter::spawn(MessageWriter { publisher, line: Vec::new() })
+}
+
+/// A writer that publishes each line that is written to it as a message.
+struct MessageWriter<P> {
+    publisher: P,
+    line: Vec<u8>,
+}
+
+impl<P: Publisher> Write for MessageWriter<P> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.line.extend_from_slice(buf);
+
+        let mut start = 0;
+
+        while let Some(end) = self.line[start..].iter().position(|&byte| byte == b'\n') {
+            self.publisher.publish(&self.line[start..start + end]).map_err(std::io::Error::other)?;
+            start += end + 1;
+        }
+
+        self.line.drain(..start);
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.publisher.flush().map_err(std::io::Error::other)
+    }
+}
+
+/// Publishes a snapshot of the aggregates of a run from its progress (see [`SnapshotPublisher::report`]), at most once
+/// per interval, and once more when the run is done.
+pub struct SnapshotPublisher {
+    interval: Duration,
+    state: Mutex<SnapshotState>,
+}
+
+struct SnapshotState {
+    publisher: Box<dyn Publisher>,
+    first: Option<Instant>,
+    last: Option<Instant>,
+    done: bool,
+    num_published: Num,
+    error: Option<TenziError>,
+}
+
+impl SnapshotPublisher {
+    /// Returns a publisher of snapshots that are at least the interval apart.
+    pub fn new(publisher: impl Publisher + 'static, interval: Duration) -> Self {
+        Self { interval, state: Mutex::new(SnapshotState { publisher: Box::new(publisher), first: None, last: None, done: false, num_published: 0, error: None }) }
+    }
+
+    /// Publishes a snapshot of the progress, if the interval has passed since the last one (or the run is done).
+    ///
+    /// This is meant to be called from a [`ProgressHook`](crate::ProgressHook), so it cannot fail: the first error is
+    /// kept for [`SnapshotPublisher::finish`], and nothing more is published after it.
+    pub fn report(&self, progress: &Progress) {
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+
+        let due = match progress.is_done() {
+            true => !state.done,
+            false => state.last.is_none_or(|last| now - last >= self.interval),
+        };
+
+        if !due || state.error.is_some() {
+            return;
+        }
+
+        let z = calibrate::z_score(0.95) as Float;
+        let (average_rolls, std_err_rolls) = (progress.average_rolls(), progress.std_err_rolls());
+        let (average_steps, std_err_steps) = (progress.average_steps(), progress.std_err_steps());
+        let elapsed = now - *state.first.get_or_insert(now);
+
+        let snapshot = format!(
+            "{{\"completed\":{},\"total\":{},\"total_rolls\":{},\"total_steps\":{},\"average_rolls\":{},\"average_steps\":{},\"std_err_rolls\":{},\"std_err_steps\":{},\"rolls_interval\":[{},{}],\"steps_interval\":[{},{}],\"elapsed_secs\":{}}}",
+            progress.completed,
+            progress.total,
+            progress.total_rolls,
+            progress.total_steps,
+            average_rolls,
+            average_steps,
+            std_err_rolls,
+            std_err_steps,
+            average_rolls - z * std_err_rolls,
+            average_rolls + z * std_err_rolls,
+            average_steps - z * std_err_steps,
+            average_steps + z * std_err_steps,
+            elapsed.as_secs_f64()
+        );
+
+        match state.publisher.publish(snapshot.as_bytes()) {
+            Ok(()) => {
+                state.last = Some(now);
+                state.done = progress.is_done();
+                state.num_published += 1;
+            }
+            Err(e) => state.error = Some(e),
+        }
+    }
+
+    /// Flushes the snapshots, and returns the number that were published, or the first error.
+    pub fn finish(self) -> Result<Num> {
+        let mut state = self.state.into_inner().unwrap();
+
+        if let Some(error) = state.error {
+            return Err(error);
+        }
+
+        state.publisher.flush()?;
+
+        Ok(state.num_published)
+    }
+}
+
+#[cfg(feature = "nats")]
+pub use nats::NatsPublisher;
+
+#[cfg(feature = "kafka")]
+pub use kafka::KafkaPublisher;
+
+/// A publisher that speaks the core NATS protocol (which is text over TCP) directly.
+#[cfg(feature = "nats")]
+mod nats {
+    use std::{io::{BufRead, BufReader, BufWriter, Write}, net::{Shutdown, TcpStream, ToSocketAddrs}, sync::{Arc, Condvar, Mutex}, time::Duration};
+
+    use super::Publisher;
+    use crate::error::{Result, TenziError};
+
+    /// How long a flush waits for the server to acknowledge the messages.
+    const FLUSH_TIMEOUT: Duration = Duration::from_secs(10);
+
+    /// A publisher to a subject of a NATS server.
+    ///
+    /// A thread answers the server's pings (so that it does not drop a quiet connection), and a flush waits for the
+    /// server to answer a ping of its own, which it only does once it has processed every message before it.
+    pub struct NatsPublisher {
+        subject: String,
+        writer: Arc<Mutex<BufWriter<TcpStream>>>,
+        state: Arc<(Mutex<NatsState>, Condvar)>,
+        stream: TcpStream,
+    }
+
+    #[derive(Default)]
+    struct NatsState {
+        pongs: u64,
+        error: Option<String>,
+    }
+
+    impl NatsPublisher {
+        /// Connects to the NATS server at the address (e.g., "127.0.0.1:4222"), to publish to the subject.
+        pub fn connect(address: impl ToSocketAddrs, subject: &str) -> Result<Self> {
+            if subject.is_empty() || subject.contains(char::is_whitespace) {
+                return Err(TenziError::Publish(format!("`{}` is not a valid subject", subject)));
+            }
+
+            let stream = TcpStream::connect(address)?;
+            let mut reader = BufReader::new(stream.try_clone()?);
+            let mut line = String::new();
+
+            reader.read_line(&mut line)?;
+
+            if !line.starts_with("INFO") {
+                return Err(TenziError::Publish(format!("the server greeted with `{}` rather than its info", line.trim())));
+            }
+
+            let mut writer = BufWriter::new(stream.try_clone()?);
+            writer.write_all(b"CONNECT {\"verbose\":false,\"pedantic\":false,\"name\":\"tenzi_sim\"}\r\n")?;
+            writer.flush()?;
+
+            let writer = Arc::new(Mutex::new(writer));
+            let state = Arc::new((Mutex::new(NatsState::default()), Condvar::new()));
+
+            let (thread_writer, thread_state) = (writer.clone(), state.clone());
+            std::thread::spawn(move || read(reader, &thread_writer, &thread_state));
+
+            Ok(Self { subject: subject.to_string(), writer, state, stream })
+        }
+
+        /// Returns the error that the connection failed with, if it did.
+        fn error(&self) -> Result<()> {
+            match &self.state.0.lock().unwrap().error {
+                Some(error) => Err(TenziError::Publish(error.clone())),
+                None => Ok(()),
+            }
+        }
+    }
+
+    impl Publisher for NatsPublisher {
+        fn publish(&mut self, payload: &[u8]) -> Result<()> {
+            self.error()?;
+
+            let mut writer = self.writer.lock().unwrap();
+            write!(writer, "PUB {} {}\r\n", self.subject, payload.len())?;
+            writer.write_all(payload)?;
+            writer.write_all(b"\r\n")?;
+
+            Ok(())
+        }
+
+        fn flush(&mut self) -> Result<()> {
+            let (state, acknowledged) = &*self.state;
+            let expected = state.lock().unwrap().pongs + 1;
+
+            {
+                let mut writer = self.writer.lock().unwrap();
+                writer.write_all(b"PING\r\n")?;
+                writer.flush()?;
+            }
+
+            let (state, timeout) = acknowledged.wait_timeout_while(state.lock().unwrap(), FLUSH_TIMEOUT, |state| state.pongs < expected && state.error.is_none()).unwrap();
+
+            match &state.error {
+                Some(error) => Err(TenziError::Publish(error.clone())),
+                None if timeout.timed_out() => Err(TenziError::Publish("the server did not acknowledge the messages in time".to_string())),
+                None => Ok(()),
+            }
+        }
+    }
+
+    impl Drop for NatsPublisher {
+        fn drop(&mut self) {
+            // Shutting the socket down stops the reader thread.
+
+            let _ = self.stream.shutdown(Shutdown::Both);
+        }
+    }
+
+    /// Reads the messages of the server until the connection closes: answering its pings, counting its pongs, and
+    /// keeping its first error.
+    fn read(mut reader: BufReader<TcpStream>, writer: &Mutex<BufWriter<TcpStream>>, state: &(Mutex<NatsState>, Condvar)) {
+        let mut line = String::new();
+
+        let error = loop {
+            line.clear();
+
+            match reader.read_line(&mut line) {
+                Ok(0) => break "the server closed the connection".to_string(),
+                Err(e) => break e.to_string(),
+                Ok(_) => {}
+            }
+
+            match line.trim_end() {
+                "PING" => {
+                    let mut writer = writer.lock().unwrap();
+
+                    if let Err(e) = writer.write_all(b"PONG\r\n").and_then(|_| writer.flush()) {
+                        break e.to_string();
+                    }
+                }
+                "PONG" => {
+                    state.0.lock().unwrap().pongs += 1;
+                    state.1.notify_all();
+                }
+                error if error.starts_with("-ERR") => break format!("the server failed with: {}", error.trim_start_matches("-ERR").trim()),
+                _ => {}
+            }
+        };
+
+        state.0.lock().unwrap().error.get_or_insert(error);
+        state.1.notify_all();
+    }
+}
+
+/// A publisher to Kafka, through librdkafka.
+#[cfg(feature = "kafka")]
+mod kafka {
+    use std::time::Duration;
+
+    use rdkafka::{error::{KafkaError, RDKafkaErrorCode}, producer::{BaseProducer, BaseRecord, Producer}, ClientConfig};
+
+    use super::Publisher;
+    use crate::error::{Result, TenziError};
+
+    /// How long a flush waits for the brokers to take the messages.
+    const FLUSH_TIMEOUT: Duration = Duration::from_secs(30);
+
+    /// A publisher to a topic of a Kafka cluster.
+    pub struct KafkaPublisher {
+        topic: String,
+        producer: BaseProducer,
+    }
+
+    impl KafkaPublisher {
+        /// Connects to the Kafka cluster of the brokers (e.g., "broker1:9092,broker2:9092"), to publish to the topic.
+        pub fn connect(brokers: &str, topic: &str) -> Result<Self> {
+            let producer = ClientConfig::new().set("bootstrap.servers", brokers).create().map_err(|e: KafkaError| TenziError::Publish(e.to_string()))?;
+
+            Ok(Self { topic: topic.to_string(), producer })
+        }
+    }
+
+    impl Publisher for KafkaPublisher {
+        fn publish(&mut self, payload: &[u8]) -> Result<()> {
+            // While the producer's queue is full, serve its deliveries until there is room.
+
+            loop {
+                match self.producer.send(BaseRecord::<(), [u8]>::to(&self.topic).payload(payload)) {
+                    Ok(()) => break,
+                    Err((KafkaError::MessageProduction(RDKafkaErrorCode::QueueFull), _)) => self.producer.poll(Duration::from_millis(100)),
+                    Err((e, _)) => return Err(TenziError::Publish(e.to_string())),
+                }
+            }
+
+            self.producer.poll(Duration::ZERO);
+
+            Ok(())
+        }
+
+        fn flush(&mut self) -> Result<()> {
+            self.producer.flush(FLUSH_TIMEOUT).map_err(|e| TenziError::Publish(e.to_string()))
+        }
+    }
+}
+
+// Tests.
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CancelToken, ProgressHook, SimulationConfig};
+    use pretty_assertions::assert_eq;
+    #[cfg(feature = "nats")]
+    use std::{io::{BufRead, BufReader, Read}, net::TcpListener};
+
+    /// Serves a single NATS client: greets it, answers its pings, and returns the payloads that it published once it
+    /// hangs up.
+    #[cfg(feature = "nats")]
+    fn serve_once(listener: TcpListener) -> std::thread::JoinHandle<Vec<String>> {
+        std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut writer = stream.try_clone().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut payloads = Vec::new();
+
+            writer.write_all(b"INFO {\"server_id\":\"test\"}\r\n").unwrap();
+
+            let mut line = String::new();
+
+            while reader.read_line(&mut line).unwrap_or(0) > 0 {
+                let command = line.trim_end().split(' ').collect::<Vec<_>>();
+
+                match command[..] {
+                    ["PING"] => writer.write_all(b"PONG\r\n").unwrap(),
+                    ["PUB", "tenzi.games", length] => {
+                        let mut payload = vec![0; length.parse::<usize>().unwrap() + 2];
+                        reader.read_exact(&mut payload).unwrap();
+                        payloads.push(String::from_utf8(payload[..payload.len() - 2].to_vec()).unwrap());
+                    }
+                    _ => {}
+                }
+
+                line.clear();
+            }
+
+            payloads
+        })
+    }
+
+    #[cfg(feature = "nats")]
+    #[test]
+    fn test_publish_records() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = serve_once(listener);
+
+        let config = SimulationConfig::builder().simulations(500).seed(42).build().unwrap();
+        let (sink, thread) = record_sink(connect(&format!("nats://{}/tenzi.games", address)).unwrap());
+        let (results, sink) = config.run_into(sink, &CancelToken::new(), ProgressHook::none()).unwrap();
+
+        drop(sink);
+
+        assert_eq!(thread.finish().unwrap(), 500);
+
+        // Every game was published as a message of its own, and the messages add up to the results.
+
+        let payloads = server.join().unwrap();
+        let rolls = payloads.iter().map(|payload| payload.split(['"', ':', ',']).nth(3).unwrap().parse::<Num>().unwrap()).sum::<Num>();
+
+        assert_eq!(payloads.len(), 500);
+        assert_eq!(rolls as f64 / 500.0, results.summaries()[0].average_rolls() as f64);
+    }
+
+    #[cfg(feature = "nats")]
+    #[test]
+    fn test_publish_snapshots() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = serve_once(listener);
+
+        // With an interval that never passes during the run, only the first and the last snapshots are published.
+
+        let snapshots = SnapshotPublisher::new(NatsPublisher::connect(address, "tenzi.games").unwrap(), Duration::from_secs(3600));
+        let config = SimulationConfig::builder().simulations(10_000).build().unwrap();
+
+        config.run_cancellable(&CancelToken::new(), ProgressHook::new(|progress: &Progress| snapshots.report(progress)).every(1_000)).unwrap();
+
+        assert_eq!(snapshots.finish().unwrap(), 2);
+
+        let payloads = server.join().unwrap();
+
+        assert_eq!(payloads.len(), 2);
+        assert!(payloads[1].starts_with("{\"completed\":10000,\"total\":10000,"));
+        assert!(matches!(connect("nats://127.0.0.1:4222"), Err(TenziError::Publish(_))));
+        assert!(matches!(connect("amqp://127.0.0.1:5672/tenzi"), Err(TenziError::Publish(_))));
+    }
+    #[test]
+    fn test_file_snapshots() {
+        let path = std::env::temp_dir().join(format!("tenzi_sim_snapshots_{}.ndjson", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        // Every snapshot is published, and a second run appends to the snapshots of the first.
+
+        let config = SimulationConfig::builder().simulations(4_000).seed(7).build().unwrap();
+
+        for _ in 0..2 {
+            let snapshots = SnapshotPublisher::new(FilePublisher::append(&path).unwrap(), Duration::ZERO);
+            config.run_cancellable(&CancelToken::new(), ProgressHook::new(|progress: &Progress| snapshots.report(progress)).every(1_000)).unwrap();
+
+            assert!(snapshots.finish().unwrap() >= 2);
+        }
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        let last = contents.lines().last().unwrap();
+
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(contents.lines().filter(|line| line.starts_with("{\"completed\":4000,\"total\":4000,")).count(), 2);
+        assert!(contents.lines().all(|line| line.starts_with('{') && line.ends_with('}')));
+
+        // The interval of the average holds it, a couple of standard errors on each side.
+
+        let field = |name: &str| last.split(&format!("\"{}\":", name)).nth(1).unwrap().split([',', ']', '}']).next().unwrap().trim_start_matches('[').parse::<f64>().unwrap();
+        let (average, std_err, low) = (field("average_rolls"), field("std_err_rolls"), field("rolls_interval"));
+
+        assert!(std_err > 0.0 && std_err < 1.0);
+        assert!((average - low - 1.96 * std_err).abs() < 1e-2);
+    }
+}
\ No newline at end of file
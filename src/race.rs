@@ -0,0 +1,274 @@
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
+use crate::{error::{Result, TenziError}, rand::{RngStream, SeededDice}, registry::StrategyRegistry, simulation::SimulationType, types::{Float, Num}};
+
+/// The output of a race simulation.
+/// Contains the probability that each player wins the race outright, the probability that
+/// the race ends in a tie (i.e., more than one player achieves a "tenzi" on the same step), and
+/// the average number of turns that it takes someone to win (or tie).
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RaceOutput {
+    pub win_probabilities: Vec<Float>,
+    pub tie_probability: Float,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub expected_turns: Float,
+}
+
+/// The races of a strategy at a table of a given size, from a sweep over the number of players (see
+/// [`sweep_players`]).
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TableSize {
+    pub strategy: String,
+    pub num_players: Num,
+    /// The probability that the player of the strategy wins outright.
+    pub win_probability: Float,
+    pub tie_probability: Float,
+    /// The average number of turns that it takes someone to win (or tie).
+    pub expected_turns: Float,
+}
+
+/// Runs a monte carlo simulation of a race between the given players, where each player is a
+/// simulation (possibly started from a mid-game state).
+///
+/// The games are coupled: every player steps once per "turn", and the first player(s) to achieve
+/// a "tenzi" end the race.
+pub fn race(players: &[SimulationType], num_simulations: Num) -> Result<RaceOutput> {
+    race_with(players, num_simulations, None)
+}
+
+/// Runs a monte carlo simulation of a race, like [`race`], but rolls the dice of each player in each race from its own
+/// stream, which is the player's substream of the race's substream of the given seed (see [`RngStream`]).
+///
+/// Every race is then run the same no matter which worker runs it, so the output is identical for any number of
+/// threads, and from run to run.
+pub fn race_seeded(players: &[SimulationType], num_simulations: Num, seed: u64) -> Result<RaceOutput> {
+    race_with(players, num_simulations, Some(seed))
+}
+
+fn race_with(players: &[SimulationType], num_simulations: Num, seed: Option<u64>) -> Result<RaceOutput> {
+    if players.len() < 2 {
+        return Err(TenziError::TooFewPlayers(players.len() as Num));
+    }
+
+    if num_simulations == 0 {
+        return Err(TenziError::ZeroSimulations);
+    }
+
+    // Each worker counts the outcomes of its own races, and the counts are merged at the end, so the workers never
+    // contend over shared counters.
+
+    let (wins, ties, turns) = (0..num_simulations)
+        .into_par_iter()
+        .fold(|| (players.to_vec(), vec![0; players.len()], 0, 0u64), |(mut players, mut wins, mut ties, turns), index| {
+            if let Some(seed) = seed {
+                let stream = RngStream::new(seed).substream(index as u64);
+
+                for (player, k) in players.iter_mut().zip(0..) {
+                    player.as_strategy_mut().set_rng(Box::new(SeededDice::new(stream.substream(k).seed())));
+                }
+            }
+
+            let (winner, num_turns) = race_once(&mut players);
+
+            match winner {
+                Some(k) => wins[k] += 1,
+                None => ties += 1,
+            }
+
+            (players, wins, ties, turns + num_turns as u64)
+        })
+        .map(|(_, wins, ties, turns)| (wins, ties, turns))
+        .reduce(|| (vec![0; players.len()], 0, 0), |(mut wins, ties, turns), (other_wins, other_ties, other_turns)| {
+            wins.iter_mut().zip(other_wins).for_each(|(count, other)| *count += other);
+            (wins, ties + other_ties, turns + other_turns)
+        });
+
+    let win_probabilities = wins.iter().map(|&w: &Num| w as Float / num_simulations as Float).collect();
+    let tie_probability = ties as Float / num_simulations as Float;
+    let expected_turns = (turns as f64 / num_simulations as f64) as Float;
+
+    Ok(RaceOutput {
+        win_probabilities,
+        tie_probability,
+        expected_turns,
+    })
+}
+
+/// Sweeps the number of players at the table: for each strategy, and each number of players, races one player of the
+/// strategy against the rest of the table, who all play the opponent strategy (or the same strategy, if there is none),
+/// so that the output tells how the chance of winning, and the time it takes, scale with the size of the table.
+///
+/// Every race is seeded from the given seed (if any), like [`race_seeded`], so that each table size is reproducible.
+#[allow(clippy::too_many_arguments)]
+pub fn sweep_players(registry: &StrategyRegistry, strategies: &[String], opponent: Option<&str>, num_players: &[Num], num_sides: Num, num_dice: Num, num_simulations: Num, seed: Option<u64>) -> Result<Vec<TableSize>> {
+    let mut sizes = Vec::new();
+
+    for strategy in strategies {
+        let player = registry.build(strategy, num_sides, num_dice)?;
+        let opponent = registry.build(opponent.unwrap_or(strategy), num_sides, num_dice)?;
+
+        for &count in num_players {
+            let players = std::iter::once(player.clone()).chain(std::iter::repeat_n(opponent.clone(), (count as usize).saturating_sub(1))).collect::<Vec<_>>();
+            let output = race_with(&players, num_simulations, seed)?;
+
+            sizes.push(TableSize {
+                strategy: strategy.clone(),
+                num_players: count,
+                win_probability: output.win_probabilities[0],
+                tie_probability: output.tie_probability,
+                expected_turns: output.expected_turns,
+            });
+        }
+    }
+
+    Ok(sizes)
+}
+
+/// Builds a race player from a spec of the form "strategy:matched" (e.g., "merge:3"), where "matched" is
+/// the number of dice the player has already matched.  The matched count may be omitted for a fresh game.
+/// The strategy is looked up in the given registry.
+pub fn player_from_spec(registry: &StrategyRegistry, spec: &str, num_sides: Num, num_dice: Num) -> Result<SimulationType> {
+    let invalid = |reason: String| TenziError::InvalidPlayer { spec: spec.to_string(), reason };
+
+    let (name, matched) = match spec.split_once(':') {
+        Some((name, matched)) => (name, matched.parse::<Num>().map_err(|_| invalid(format!("matched count `{}` is not a number", matched)))?),
+        None => (spec, 0),
+    };
+
+    if matched > num_dice {
+        return Err(invalid(format!("more dice matched than the {} available", num_dice)));
+    }
+
+    // The face that the matched dice show does not matter, so just put them on the first face.
+
+    let mut state = vec![0; num_sides as usize];
+    state[0] = matched;
+
+    let player = registry.build(name, num_sides, num_dice)?;
+
+    Ok(player.with_initial_state(&state))
+}
+
+/// Runs a single race, and returns the index of the winner (or `None` for a tie), and the number of turns it took.
+fn race_once(players: &mut [SimulationType]) -> (Option<usize>, Num) {
+    for player in players.iter_mut() {
+        player.as_strategy_mut().reset();
+    }
+
+    let mut turns = 0;
+
+    loop {
+        // Check if anyone has finished (including before the first step, for players that start with a "tenzi").
+
+        let mut winners = players.iter().enumerate().filter(|(_, p)| p.as_strategy().done()).map(|(k, _)| k);
+
+        match (winners.next(), winners.next()) {
+            (Some(k), None) => return (Some(k), turns),
+            (Some(_), Some(_)) => return (None, turns),
+            _ => {}
+        }
+
+        // Everyone takes a turn.
+
+        for player in players.iter_mut() {
+            player.as_strategy_mut().step();
+        }
+
+        turns += 1;
+    }
+}
+
+// Tests.
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::{MergeSimulation, NaiveSimulation};
+
+    #[test]
+    fn test_player_from_spec() {
+        let registry = StrategyRegistry::new();
+
+        assert!(!player_from_spec(&registry, "merge:3", 6, 10).unwrap().as_strategy().done());
+        assert!(player_from_spec(&registry, "merge:10", 6, 10).unwrap().as_strategy().done());
+        assert!(player_from_spec(&registry, "naive", 6, 10).is_ok());
+        assert!(player_from_spec(&registry, "naive:11", 6, 10).is_err());
+        assert!(player_from_spec(&registry, "naive:x", 6, 10).is_err());
+        assert!(player_from_spec(&registry, "bogus:1", 6, 10).is_err());
+    }
+
+    #[test]
+    fn test_race_too_few_players() {
+        let players = vec![SimulationType::Naive(NaiveSimulation::new(6, 10))];
+
+        assert!(matches!(race(&players, 100), Err(TenziError::TooFewPlayers(1))));
+    }
+
+    #[test]
+    fn test_race_finished_player_wins() {
+        let players = vec![
+            SimulationType::Naive(NaiveSimulation::new(6, 10)),
+            SimulationType::Merge(MergeSimulation::new(6, 10)).with_initial_state(&[10, 0, 0, 0, 0, 0]),
+        ];
+
+        let output = race(&players, 100).unwrap();
+
+        assert_eq!(output.win_probabilities, vec![0.0, 1.0]);
+        assert_eq!(output.tie_probability, 0.0);
+        assert_eq!(output.expected_turns, 0.0);
+    }
+
+    #[test]
+    fn test_race_probabilities_sum_to_one() {
+        let players = vec![
+            SimulationType::Naive(NaiveSimulation::new(6, 10)),
+            SimulationType::Naive(NaiveSimulation::new(6, 10)).with_initial_state(&[5, 0, 0, 0, 0, 0]),
+        ];
+
+        let output = race(&players, 1_000).unwrap();
+        let total = output.win_probabilities.iter().sum::<Float>() + output.tie_probability;
+
+        assert!((total - 1.0).abs() < 1e-6);
+        assert!(output.win_probabilities[1] > output.win_probabilities[0]);
+    }
+
+    #[test]
+    fn test_race_seeded() {
+        let players = vec![SimulationType::Naive(NaiveSimulation::new(6, 10)), SimulationType::Merge(MergeSimulation::new(6, 10))];
+
+        // A seeded race is reproduced exactly, even on a pool with another number of threads.
+
+        let output = race_seeded(&players, 1_000, 42).unwrap();
+        let pool = rayon::ThreadPoolBuilder::new().num_threads(3).build().unwrap();
+        let other = pool.install(|| race_seeded(&players, 1_000, 42)).unwrap();
+
+        assert_eq!(output.win_probabilities, other.win_probabilities);
+        assert_eq!(output.tie_probability, other.tie_probability);
+        assert_ne!(race_seeded(&players, 1_000, 43).unwrap().win_probabilities, output.win_probabilities);
+    }
+
+    #[test]
+    fn test_sweep_players() {
+        let registry = StrategyRegistry::new();
+        let strategies = ["naive".to_string(), "merge".to_string()];
+        let sizes = sweep_players(&registry, &strategies, None, &[2, 4], 6, 10, 2_000, Some(42)).unwrap();
+
+        assert_eq!(sizes.iter().map(|size| (size.strategy.as_str(), size.num_players)).collect::<Vec<_>>(), vec![("naive", 2), ("naive", 4), ("merge", 2), ("merge", 4)]);
+
+        // A bigger table is harder to win, and quicker to end.
+
+        for pair in sizes.chunks(2) {
+            assert!(pair[1].win_probability < pair[0].win_probability);
+            assert!(pair[1].expected_turns < pair[0].expected_turns);
+        }
+
+        // A race is won in turns rather than rolls, and merge takes more of them, so against a table of naive players, a
+        // merge player wins less than its share.
+
+        let against = sweep_players(&registry, &strategies[1..], Some("naive"), &[4], 6, 10, 2_000, Some(42)).unwrap();
+
+        assert!(against[0].win_probability < 0.25);
+        assert!(matches!(sweep_players(&registry, &strategies, None, &[1], 6, 10, 100, None), Err(TenziError::TooFewPlayers(1))));
+    }
+}
\ No newline at end of file
@@ -1,28 +1,693 @@
-use rand::Rng;
+use std::{cell::RefCell, sync::Arc};
 
-use crate::types::Num;
+use rand::{rngs::{OsRng, SmallRng, StdRng}, Rng, RngCore, SeedableRng};
 
+use rand_chacha::ChaCha8Rng;
+use rand_pcg::Pcg64;
+use rand_xoshiro::Xoshiro256PlusPlus;
+
+use crate::{dice::DiceRng, error::{Result, TenziError}, types::{Float, Num}};
+
+/// The name of the rng that [`ThreadDice`] rolls with, as it is recorded in the results of a run (see
+/// [`RunParameters`](crate::results::RunParameters)).
+pub const THREAD_RNG: &str = "small";
+
+/// The number of random words that a [`BufferedDice`] draws at once.
+const BUFFER_SIZE: usize = 64;
+
+/// The number of dice from which [`ThreadDice`] rolls a pool in parallel, rather than on the game's thread.
+pub const PARALLEL_ROLL_THRESHOLD: Num = 1 << 16;
+
+/// The number of dice that each parallel task of [`ThreadDice`] rolls.
+const ROLL_CHUNK: Num = 1 << 14;
+
+thread_local! {
+    /// The rng that every die is rolled with on this thread, which is seeded once (per worker) rather than on every
+    /// roll, since rolling is the hottest path in a run.
+    static RNG: RefCell<SmallRng> = RefCell::new(SmallRng::from_entropy());
+}
+
+/// Rolls a die with the thread-local rng.
 pub fn roll(num_sides: Num) -> Num {
-    1 + (get_num() % num_sides)
+    RNG.with(|rng| {
+        let mut rng = rng.borrow_mut();
+        face(|| rng.gen::<u64>(), num_sides)
+    })
 }
 
-#[cfg(not(test))]
-fn get_num() -> Num {
-    rand::thread_rng().gen::<Num>()
+/// Reseeds the calling thread's rng (e.g., from a master seed, mixed with the worker index), so that the dice it rolls
+/// with [`roll`] are reproducible.
+pub fn seed_thread(seed: u64) {
+    RNG.with(|rng| *rng.borrow_mut() = SmallRng::seed_from_u64(seed));
 }
 
-#[cfg(test)]
-fn get_num() -> Num {
-    TEST_RNG.with_borrow_mut(|r| r.gen::<Num>())
+/// Rolls dice with the thread-local rng, which is the default for every game.
+///
+/// A pool of at least [`PARALLEL_ROLL_THRESHOLD`] dice (e.g., the first steps of a game with a million dice) is rolled
+/// in parallel, in chunks that each count their faces into their own buckets, which are summed afterwards.  Each chunk
+/// rolls from its own substream of a stream that is keyed by the game's thread (see [`RngStream`]), rather than from
+/// the rng of whichever worker runs it, so those rolls are reproduced by [`seed_thread`] too.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ThreadDice;
+
+impl DiceRng for ThreadDice {
+    fn roll(&mut self, num_sides: Num) -> Num {
+        roll(num_sides)
+    }
+
+    fn roll_into(&mut self, num_sides: Num, num_dice: Num, buckets: &mut [Num]) {
+        use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
+        if num_dice < PARALLEL_ROLL_THRESHOLD {
+            return roll_serially(num_sides, num_dice, buckets);
+        }
+
+        let stream = RngStream::new(RNG.with(|rng| rng.borrow_mut().next_u64()));
+
+        let counts = (0..num_dice.div_ceil(ROLL_CHUNK))
+            .into_par_iter()
+            .fold(|| vec![0; buckets.len()], |mut counts, chunk| {
+                let mut rng = SmallRng::seed_from_u64(stream.substream(chunk as u64).seed());
+
+                for _ in 0..ROLL_CHUNK.min(num_dice - chunk * ROLL_CHUNK) {
+                    counts[face(|| rng.next_u64(), num_sides) as usize - 1] += 1;
+                }
+
+                counts
+            })
+            .reduce_with(|mut left, right| {
+                left.iter_mut().zip(right).for_each(|(left, right)| *left += right);
+                left
+            })
+            .unwrap();
+
+        buckets.iter_mut().zip(counts).for_each(|(bucket, count)| *bucket += count);
+    }
 }
 
-#[cfg(test)]
-thread_local! {
-    static TEST_RNG: std::cell::RefCell<rand::rngs::StdRng> = std::cell::RefCell::new(rand::SeedableRng::seed_from_u64(42));
+/// Rolls dice into the buckets with the thread-local rng, borrowing it once for the whole pool.
+fn roll_serially(num_sides: Num, num_dice: Num, buckets: &mut [Num]) {
+    RNG.with(|rng| {
+        let mut rng = rng.borrow_mut();
+
+        for _ in 0..num_dice {
+            buckets[face(|| rng.gen::<u64>(), num_sides) as usize - 1] += 1;
+        }
+    })
+}
+
+/// Rolls dice with a seeded rng, so the same seed always plays the same games.
+///
+/// Clones continue from the state they were cloned at, so every clone rolls the same dice (which is why every block of a
+/// parallel run rolls its own split of it instead; see [`DiceRng::split`]).
+#[derive(Clone, Debug)]
+pub struct SeededDice<R = StdRng> {
+    rng: R,
+}
+
+impl SeededDice {
+    /// Returns a source that rolls the dice determined by the given seed.
+    pub fn new(seed: u64) -> Self {
+        Self::from_rng(StdRng::seed_from_u64(seed))
+    }
+}
+
+impl<R: RngCore> SeededDice<R> {
+    /// Returns a source that rolls the dice drawn from the given (seeded) rng.
+    pub fn from_rng(rng: R) -> Self {
+        Self { rng }
+    }
+}
+
+impl<R: RngCore + SeedableRng + Clone + Send + Sync + 'static> DiceRng for SeededDice<R> {
+    fn roll(&mut self, num_sides: Num) -> Num {
+        face(|| self.rng.gen::<u64>(), num_sides)
+    }
+
+    fn split(&self, index: u64) -> Option<Box<dyn DiceRng>> {
+        Some(Box::new(SeededDice::from_rng(R::seed_from_u64(split_seed(self.rng.clone().next_u64(), index)))))
+    }
+}
+
+/// Rolls dice from a block of random words that is filled all at once, and refilled when it runs out.
+///
+/// Late in a game only a few dice are rolled per step, so this amortizes the cost of calling into the rng across
+/// steps (and games).  That only pays off for rngs that are costly per call: the thread-local [`SmallRng`] is cheaper
+/// to call directly (see `bench_buffered_dice`), so [`roll`] does not buffer.
+///
+/// Each die takes one word, so a seeded rng rolls the same faces buffered or not.
+#[derive(Clone, Debug)]
+pub struct BufferedDice<R = SmallRng> {
+    rng: R,
+    buffer: [u64; BUFFER_SIZE],
+    next: usize,
+}
+
+impl<R: RngCore> BufferedDice<R> {
+    /// Returns a source that rolls the dice drawn from the given rng.
+    pub fn new(rng: R) -> Self {
+        Self { rng, buffer: [0; BUFFER_SIZE], next: BUFFER_SIZE }
+    }
+
+    /// Returns the next random word, refilling the buffer if it is exhausted.
+    fn draw(&mut self) -> u64 {
+        if self.next == BUFFER_SIZE {
+            self.rng.fill(&mut self.buffer[..]);
+            self.next = 0;
+        }
+
+        let draw = self.buffer[self.next];
+        self.next += 1;
+
+        draw
+    }
+}
+
+impl<R: RngCore + SeedableRng + Clone + Send + Sync + 'static> DiceRng for BufferedDice<R> {
+    fn roll(&mut self, num_sides: Num) -> Num {
+        face(|| self.draw(), num_sides)
+    }
+
+    fn split(&self, index: u64) -> Option<Box<dyn DiceRng>> {
+        Some(Box::new(BufferedDice::new(R::seed_from_u64(split_seed(self.clone().draw(), index)))))
+    }
+}
+
+/// Rolls dice by reducing each random word modulo the number of sides, which is the fast path that the dice were
+/// once rolled with.
+///
+/// This is slightly biased towards the low faces for a number of sides that does not divide 2^64 (i.e., that is not
+/// a power of two), so it is only an explicit opt-in (e.g., to reproduce old results).
+#[derive(Clone, Debug)]
+pub struct ModuloDice<R = SmallRng> {
+    rng: R,
+}
+
+impl<R: RngCore> ModuloDice<R> {
+    /// Returns a source that rolls the dice drawn from the given rng.
+    pub fn new(rng: R) -> Self {
+        Self { rng }
+    }
+}
+
+impl<R: RngCore + SeedableRng + Clone + Send + Sync + 'static> DiceRng for ModuloDice<R> {
+    fn roll(&mut self, num_sides: Num) -> Num {
+        modulo_face(self.rng.next_u64(), num_sides)
+    }
+
+    fn split(&self, index: u64) -> Option<Box<dyn DiceRng>> {
+        Some(Box::new(ModuloDice::new(R::seed_from_u64(split_seed(self.rng.clone().next_u64(), index)))))
+    }
+}
+
+/// A precomputed table (with Vose's alias method) that draws the faces of a die with the given weights from a single
+/// random word, which is two table lookups with no division or rejection loop.
+///
+/// The word picks a column with a widening multiply, as the high word of `draw * num_sides`, and the low word of the
+/// product picks between the column's face and its alias.  Picking the column that way is biased by at most
+/// `num_sides / 2^64` (which is why the uniform dice reject instead), so the table is for weighted dice, or for uniform
+/// ones where that bias is of no concern.
+///
+/// A table is built once per run, and shared read-only by every worker's [`AliasDice`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AliasTable {
+    /// The probability of keeping each column's own face, as a fraction of 2^64.
+    thresholds: Vec<u64>,
+    /// The face that each column falls back to (from 1).
+    aliases: Vec<Num>,
+}
+
+impl AliasTable {
+    /// Builds the table of a die with a face per weight (from 1), whose weights must be finite, not negative, and not
+    /// all zero.
+    pub fn new(weights: &[Float]) -> Result<Self> {
+        if weights.is_empty() {
+            return Err(TenziError::InvalidDice("a weighted die needs at least one face".to_string()));
+        }
+
+        if let Some(weight) = weights.iter().find(|weight| !weight.is_finite() || **weight < 0.0) {
+            return Err(TenziError::InvalidDice(format!("`{}` is not a valid weight", weight)));
+        }
+
+        let total = weights.iter().map(|&weight| weight as f64).sum::<f64>();
+
+        if total <= 0.0 {
+            return Err(TenziError::InvalidDice("a weighted die needs a face with some weight".to_string()));
+        }
+
+        // Scale the weights so that they average one, and pair each column that is short of one with a column that has
+        // more than enough, until every column is full.
+
+        let num_sides = weights.len();
+        let mut scaled = weights.iter().map(|&weight| weight as f64 * num_sides as f64 / total).collect::<Vec<_>>();
+        let mut thresholds = vec![u64::MAX; num_sides];
+        let mut aliases = (1..=num_sides as Num).collect::<Vec<_>>();
+
+        let (mut small, mut large): (Vec<_>, Vec<_>) = (0..num_sides).partition(|&k| scaled[k] < 1.0);
+
+        while let (Some(&short), Some(&long)) = (small.last(), large.last()) {
+            small.pop();
+
+            thresholds[short] = (scaled[short] * u64::MAX as f64) as u64;
+            aliases[short] = long as Num + 1;
+            scaled[long] -= 1.0 - scaled[short];
+
+            if scaled[long] < 1.0 {
+                large.pop();
+                small.push(long);
+            }
+        }
+
+        // Whatever is left is full, up to rounding.
+
+        Ok(Self { thresholds, aliases })
+    }
+
+    /// Builds the table of a fair die (see the bias of [`AliasTable`]).
+    pub fn uniform(num_sides: Num) -> Result<Self> {
+        Self::new(&vec![1.0; num_sides as usize])
+    }
+
+    /// Returns the number of sides of the die.
+    pub fn num_sides(&self) -> Num {
+        self.thresholds.len() as Num
+    }
+
+    /// Returns the face that the given random word draws.
+    pub fn sample(&self, draw: u64) -> Num {
+        let product = draw as u128 * self.thresholds.len() as u128;
+        let column = (product >> 64) as usize;
+
+        // A full column is its own alias, so it needs no special case.
+
+        if (product as u64) < self.thresholds[column] {
+            column as Num + 1
+        } else {
+            self.aliases[column]
+        }
+    }
+}
+
+/// Rolls a weighted die (see [`AliasTable`]), from the thread-local rng (so that every worker rolls its own dice), or
+/// from a seed.
+///
+/// Clones share the table, so handing a clone to every worker costs no more than the rng.
+#[derive(Clone, Debug)]
+pub struct AliasDice<R = StdRng> {
+    table: Arc<AliasTable>,
+    rng: Option<R>,
+}
+
+impl AliasDice {
+    /// Returns a source that rolls the die of the table with the thread-local rng.
+    pub fn new(table: Arc<AliasTable>) -> Self {
+        Self { table, rng: None }
+    }
+
+    /// Returns a source that rolls the die of the table from the given seed.
+    pub fn seeded(table: Arc<AliasTable>, seed: u64) -> Self {
+        Self::from_rng(table, StdRng::seed_from_u64(seed))
+    }
+}
+
+impl<R: RngCore> AliasDice<R> {
+    /// Returns a source that rolls the die of the table from the given (seeded) rng.
+    pub fn from_rng(table: Arc<AliasTable>, rng: R) -> Self {
+        Self { table, rng: Some(rng) }
+    }
+
+    /// Returns the table that the die is rolled from.
+    pub fn table(&self) -> &AliasTable {
+        &self.table
+    }
+}
+
+impl<R: RngCore + SeedableRng + Clone + Send + Sync + 'static> DiceRng for AliasDice<R> {
+    fn roll(&mut self, num_sides: Num) -> Num {
+        assert_eq!(num_sides, self.table.num_sides(), "the weighted die must have as many sides as the game");
+
+        let draw = match &mut self.rng {
+            Some(rng) => rng.next_u64(),
+            None => RNG.with(|rng| rng.borrow_mut().next_u64()),
+        };
+
+        self.table.sample(draw)
+    }
+
+    fn split(&self, index: u64) -> Option<Box<dyn DiceRng>> {
+        let rng = self.rng.as_ref()?;
+
+        Some(Box::new(AliasDice::from_rng(self.table.clone(), R::seed_from_u64(split_seed(rng.clone().next_u64(), index)))))
+    }
+}
+
+/// Rolls dice from the operating system's CSPRNG (see [`RngKind::Os`]), a block of words at a time (as with
+/// [`BufferedDice`]), from the weighted die of the table if there is one.
+///
+/// Every word is fresh from the operating system, so clones never roll the same dice, and there is nothing to split.
+#[derive(Clone, Debug)]
+pub struct OsDice {
+    words: BufferedDice<OsRng>,
+    table: Option<Arc<AliasTable>>,
+}
+
+impl OsDice {
+    /// Returns a source that rolls fair dice, or the die of the table if there is one.
+    pub fn new(table: Option<Arc<AliasTable>>) -> Self {
+        Self { words: BufferedDice::new(OsRng), table }
+    }
+}
+
+impl DiceRng for OsDice {
+    fn roll(&mut self, num_sides: Num) -> Num {
+        match &self.table {
+            Some(table) => {
+                assert_eq!(num_sides, table.num_sides(), "the weighted die must have as many sides as the game");
+                table.sample(self.words.draw())
+            }
+            None => face(|| self.words.draw(), num_sides),
+        }
+    }
+}
+
+/// The generators that a run can roll its dice from, which trade statistical quality against speed.
+///
+/// Each generator rolls every game from that game's own seed (see [`game_seed`]), or, for the counter-based
+/// [`Philox`], from the run's seed and the game's index, so any of them plays the same games on any number of threads.
+/// Without a generator, an unseeded run rolls from the thread-local [`SmallRng`] (see [`THREAD_RNG`]), and a seeded one
+/// from [`StdRng`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum RngKind {
+    /// Xoshiro256++, which is small and fast (and is what [`SmallRng`] is on 64-bit platforms).
+    #[cfg_attr(feature = "serde", serde(rename = "xoshiro256++"))]
+    Xoshiro256PlusPlus,
+    /// PCG64 (i.e., PCG XSL RR 128/64), which is small and fast, with a larger state.
+    #[cfg_attr(feature = "serde", serde(rename = "pcg64"))]
+    Pcg64,
+    /// ChaCha with 8 rounds, which is a cryptographically strong (and slower) stream cipher.
+    #[cfg_attr(feature = "serde", serde(rename = "chacha8"))]
+    ChaCha8,
+    /// The [`StdRng`] of the `rand` crate (currently ChaCha with 12 rounds), which is what a seeded run rolls from by
+    /// default.
+    #[cfg_attr(feature = "serde", serde(rename = "std"))]
+    Std,
+    /// Philox4x32-10 (see [`Philox`]), which is counter-based, so any draw of any game can be computed directly.
+    #[cfg_attr(feature = "serde", serde(rename = "philox"))]
+    Philox,
+    /// The operating system's CSPRNG (through `getrandom`), for when no artifact of a pseudo-random generator can be
+    /// ruled out.  It is much slower than every other generator (even though its words are drawn a block at a time,
+    /// as with [`BufferedDice`]), and it cannot be seeded, so its runs are never reproducible.
+    #[cfg_attr(feature = "serde", serde(rename = "os"))]
+    Os,
+}
+
+impl RngKind {
+    /// Every generator.
+    pub const ALL: [RngKind; 6] = [RngKind::Xoshiro256PlusPlus, RngKind::Pcg64, RngKind::ChaCha8, RngKind::Std, RngKind::Philox, RngKind::Os];
+
+    /// Returns the name of the generator.
+    pub fn name(&self) -> &'static str {
+        match self {
+            RngKind::Xoshiro256PlusPlus => "xoshiro256++",
+            RngKind::Pcg64 => "pcg64",
+            RngKind::ChaCha8 => "chacha8",
+            RngKind::Std => "std",
+            RngKind::Philox => "philox",
+            RngKind::Os => "os",
+        }
+    }
+
+    /// Returns whether the generator rolls the same dice from the same seed (i.e., every generator but [`RngKind::Os`]).
+    pub fn is_seedable(&self) -> bool {
+        *self != RngKind::Os
+    }
+
+    /// Returns a source that rolls the dice determined by the given seed with this generator (which [`RngKind::Os`]
+    /// ignores), from the weighted die of the table if there is one.
+    pub fn dice(&self, seed: u64, table: Option<&Arc<AliasTable>>) -> Box<dyn DiceRng> {
+        match self {
+            RngKind::Xoshiro256PlusPlus => rng_dice(Xoshiro256PlusPlus::seed_from_u64(seed), table),
+            RngKind::Pcg64 => rng_dice(Pcg64::seed_from_u64(seed), table),
+            RngKind::ChaCha8 => rng_dice(ChaCha8Rng::seed_from_u64(seed), table),
+            RngKind::Std => rng_dice(StdRng::seed_from_u64(seed), table),
+            RngKind::Philox => rng_dice(Philox::new(seed, 0), table),
+            RngKind::Os => Box::new(OsDice::new(table.cloned())),
+        }
+    }
+
+    /// Returns a source that rolls the dice of the game at the given index of a run with the given seed: the counter-
+    /// based [`Philox`] is keyed on the seed and counts from the index, and every other generator is seeded with
+    /// [`game_seed`].
+    pub fn game_dice(&self, seed: u64, index: u64, table: Option<&Arc<AliasTable>>) -> Box<dyn DiceRng> {
+        match self.game_seed(seed, index) {
+            Some(game_seed) => self.dice(game_seed, table),
+            None => rng_dice(Philox::new(seed, index), table),
+        }
+    }
+
+    /// Returns the seed that this generator rolls the game at the given index of a run with the given seed from
+    /// (i.e., that [`RngKind::dice`] rolls the same game from), or `None` for [`Philox`], which has no seed per game.
+    pub fn game_seed(&self, seed: u64, index: u64) -> Option<u64> {
+        match self {
+            RngKind::Philox => None,
+            _ => Some(game_seed(seed, index)),
+        }
+    }
+}
+
+impl core::fmt::Display for RngKind {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl core::str::FromStr for RngKind {
+    type Err = TenziError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        // Xoshiro256++ is also known by its family's name.
+
+        if s == "xoshiro" {
+            return Ok(RngKind::Xoshiro256PlusPlus);
+        }
+
+        RngKind::ALL.into_iter().find(|kind| kind.name() == s).ok_or_else(|| TenziError::InvalidConfig(format!("unknown rng `{s}`")))
+    }
+}
+
+/// Returns a source that rolls the dice drawn from the given generator.
+fn rng_dice<R: RngCore + SeedableRng + Clone + Send + Sync + 'static>(rng: R, table: Option<&Arc<AliasTable>>) -> Box<dyn DiceRng> {
+    match table {
+        Some(table) => Box::new(AliasDice::from_rng(table.clone(), rng)),
+        None => Box::new(SeededDice::from_rng(rng)),
+    }
+}
+
+/// The multipliers of the Philox4x32 rounds.
+const PHILOX_M: [u32; 2] = [0xD251_1F53, 0xCD9E_8D57];
+
+/// The constants that the Philox4x32 key is bumped by after every round (the golden ratio, and the square root of 3).
+const PHILOX_W: [u32; 2] = [0x9E37_79B9, 0xBB67_AE85];
+
+/// The Philox4x32-10 counter-based generator (Salmon et al., 2011), keyed on a seed and a stream.
+///
+/// Each draw is a pure function of the key (i.e., the run's seed), the stream (i.e., the game's index), and the
+/// draw's index within the stream, so any draw is computed directly (see [`Philox::word`]) rather than by stepping
+/// through the ones before it, and a stream needs no state beyond its position.  That is what lets the games of a
+/// run be replayed by their index, or split into shards, with no stored rng state.  The rounds only need 32-bit
+/// multiplies, so the same stream can be reproduced in a shader.
+///
+/// Each block of the counter (i.e., one run of the ten rounds) yields two words, so the generator caches the block
+/// that its position is in.
+#[derive(Clone, Debug)]
+pub struct Philox {
+    key: [u32; 2],
+    stream: u64,
+    position: u64,
+    block: Option<(u64, [u64; 2])>,
+}
+
+impl Philox {
+    /// Returns the generator of the given stream under the given seed, at its first draw.
+    pub fn new(seed: u64, stream: u64) -> Self {
+        Self { key: [seed as u32, (seed >> 32) as u32], stream, position: 0, block: None }
+    }
+
+    /// Returns the word at the given index of the stream, without moving the generator.
+    pub fn word(&self, index: u64) -> u64 {
+        self.block(index / 2)[(index % 2) as usize]
+    }
+
+    /// Returns the index of the next word that the generator draws.
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// Moves the generator to the given index of its stream, in constant time.
+    pub fn seek(&mut self, index: u64) {
+        self.position = index;
+    }
+
+    /// Returns the two words of the given block of the stream, which is the ten Philox4x32 rounds over the counter
+    /// `(block, stream)`.
+    fn block(&self, block: u64) -> [u64; 2] {
+        let mut counter = [block as u32, (block >> 32) as u32, self.stream as u32, (self.stream >> 32) as u32];
+        let mut key = self.key;
+
+        for round in 0..10 {
+            if round > 0 {
+                key = [key[0].wrapping_add(PHILOX_W[0]), key[1].wrapping_add(PHILOX_W[1])];
+            }
+
+            let first = PHILOX_M[0] as u64 * counter[0] as u64;
+            let second = PHILOX_M[1] as u64 * counter[2] as u64;
+
+            counter = [(second >> 32) as u32 ^ counter[1] ^ key[0], second as u32, (first >> 32) as u32 ^ counter[3] ^ key[1], first as u32];
+        }
+
+        [counter[0] as u64 | (counter[1] as u64) << 32, counter[2] as u64 | (counter[3] as u64) << 32]
+    }
+}
+
+impl RngCore for Philox {
+    fn next_u32(&mut self) -> u32 {
+        self.next_u64() as u32
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let index = self.position;
+        self.position += 1;
+
+        let block = match self.block {
+            Some((block, words)) if block == index / 2 => words,
+            _ => {
+                let words = self.block(index / 2);
+                self.block = Some((index / 2, words));
+                words
+            }
+        };
+
+        block[(index % 2) as usize]
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for chunk in dest.chunks_mut(8) {
+            chunk.copy_from_slice(&self.next_u64().to_le_bytes()[..chunk.len()]);
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> core::result::Result<(), rand::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+impl SeedableRng for Philox {
+    type Seed = [u8; 8];
+
+    /// Returns the first stream under the seed.
+    fn from_seed(seed: Self::Seed) -> Self {
+        Self::new(u64::from_le_bytes(seed), 0)
+    }
+
+    /// Returns the first stream under the seed, which keys the generator directly (rather than through another rng).
+    fn seed_from_u64(seed: u64) -> Self {
+        Self::new(seed, 0)
+    }
+}
+
+/// A stream of random words under a seed, which splits into substreams for the nested parallel parts of a run (e.g.,
+/// the games of a run, the players of each race, or the chunks of a huge roll), so that each part rolls from a stream
+/// of its own that only depends on the master seed and the part's path of indices (rather than on which worker runs
+/// it, or when).
+///
+/// A substream's seed is the output of a SplitMix64 stream under its parent's seed, at the substream's index.  The
+/// output is a bijection of the index, so the substreams of a stream never share a seed, and a generator that is seeded
+/// from each (see [`RngStream::dice`]) rolls an independent stream in practice.  (The GPU backend keys its games with
+/// the shader's own 32-bit hash instead, since a shader has no 64-bit integers.)
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct RngStream {
+    seed: u64,
+    next: u64,
+}
+
+impl RngStream {
+    /// Returns the stream under the given (master) seed.
+    pub fn new(seed: u64) -> Self {
+        Self { seed, next: 0 }
+    }
+
+    /// Returns the seed of the stream.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Returns the substream at the given index (e.g., of a game in a run), which is the same however many times
+    /// (and in whichever order) the substreams are taken.
+    pub fn substream(&self, index: u64) -> Self {
+        Self::new(game_seed(self.seed, index))
+    }
+
+    /// Returns the next substream that has not been split off yet (i.e., the substreams at 0, 1, 2, and so on).
+    pub fn split(&mut self) -> Self {
+        self.next += 1;
+        self.substream(self.next - 1)
+    }
+
+    /// Returns a source that rolls the dice of the stream with the given generator, from the weighted die of the table
+    /// if there is one (see [`RngKind::dice`]).
+    pub fn dice(&self, rng: RngKind, table: Option<&Arc<AliasTable>>) -> Box<dyn DiceRng> {
+        rng.dice(self.seed, table)
+    }
+}
+
+/// Returns the seed of the game at the given index of a run with the given seed (i.e., of the run's substream at the
+/// index; see [`RngStream::substream`]), so every game gets an independent seed that only depends on the run's seed
+/// and the index.
+pub fn game_seed(seed: u64, index: u64) -> u64 {
+    let mut z = seed.wrapping_add(index.wrapping_add(1).wrapping_mul(0x9E37_79B9_7F4A_7C15));
+
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+
+    z ^ (z >> 31)
+}
+
+/// Returns the seed of the given block of a parallel run that is played with a stateful source (see
+/// [`DiceRng::split`]), whose next word is the given one: the block's substream of a stream under that word.
+fn split_seed(word: u64, index: u64) -> u64 {
+    RngStream::new(word).substream(index).seed()
+}
+
+/// Returns a face drawn uniformly from the random words, with Lemire's widening multiply: the face is the high word
+/// of `draw * num_sides`, and the rare draws whose low word falls in the biased remainder are rejected.
+fn face(mut draw: impl FnMut() -> u64, num_sides: Num) -> Num {
+    // Always draw 64 bits, so that the same seed produces the same rolls regardless of the counter type.
+
+    let range = num_sides as u64;
+    let mut product = draw() as u128 * range as u128;
+
+    if (product as u64) < range {
+        let threshold = range.wrapping_neg() % range;
+
+        while (product as u64) < threshold {
+            product = draw() as u128 * range as u128;
+        }
+    }
+
+    1 + (product >> 64) as Num
+}
+
+fn modulo_face(draw: u64, num_sides: Num) -> Num {
+    1 + (draw % num_sides as u64) as Num
 }
 
 #[cfg(test)]
 mod tests {
+    #[cfg(feature = "nightly")]
+    use std::hint::black_box;
+
     use super::*;
     use pretty_assertions::assert_eq;
 
@@ -45,8 +710,328 @@ mod tests {
     #[test]
     fn test_seed() {
         let num_sides = 1000;
-        
-        assert_eq!(roll(num_sides), 523);
-        assert_eq!(roll(num_sides), 190);
+        let mut dice = SeededDice::new(42);
+
+        assert_eq!(dice.roll(num_sides), 527);
+        assert_eq!(dice.roll(num_sides), 543);
+    }
+
+    #[test]
+    fn test_modulo_dice() {
+        // The biased path rolls what the seeded dice used to.
+
+        let num_sides = 1000;
+        let mut dice = ModuloDice::new(StdRng::seed_from_u64(42));
+
+        assert_eq!(dice.roll(num_sides), 523);
+        assert_eq!(dice.roll(num_sides), 190);
+    }
+
+    #[test]
+    fn test_face_rejects_biased_draws() {
+        // 2^64 is one more than a multiple of 3, so a zero draw is the one word in the biased remainder.
+
+        let mut words = [0, u64::MAX].into_iter();
+
+        assert_eq!(face(|| words.next().unwrap(), 3), 3);
+        assert_eq!(words.next(), None);
+
+        let mut words = [1].into_iter();
+        assert_eq!(face(|| words.next().unwrap(), 3), 1);
+    }
+
+    #[test]
+    fn test_buffered_dice() {
+        // Past the end of the first buffer, the rolls are the same as drawing one word at a time.
+
+        let num_sides = 1000;
+        let mut buffered = BufferedDice::new(SmallRng::seed_from_u64(42));
+        let mut unbuffered = SmallRng::seed_from_u64(42);
+
+        for _ in 0..BUFFER_SIZE * 2 + 1 {
+            assert_eq!(buffered.roll(num_sides), face(|| unbuffered.gen::<u64>(), num_sides));
+        }
+    }
+
+    #[test]
+    fn test_alias_table() {
+        // Every face comes up in proportion to its weight, and a face without weight never does.
+
+        let table = AliasTable::new(&[1.0, 0.0, 3.0, 4.0]).unwrap();
+        let mut rng = SmallRng::seed_from_u64(42);
+        let mut counts = [0; 4];
+
+        for _ in 0..80_000 {
+            counts[table.sample(rng.next_u64()) as usize - 1] += 1;
+        }
+
+        assert_eq!(counts[1], 0);
+        assert!(counts.iter().zip([0.125, 0.0, 0.375, 0.5]).all(|(&count, expected)| (count as f64 / 80_000.0 - expected).abs() < 0.01));
+
+        // The extreme words land on the first and last columns.
+
+        let uniform = AliasTable::uniform(6).unwrap();
+
+        assert_eq!(uniform.num_sides(), 6);
+        assert_eq!(uniform.sample(0), 1);
+        assert_eq!(uniform.sample(u64::MAX), 6);
+    }
+
+    #[test]
+    fn test_alias_table_invalid() {
+        assert!(matches!(AliasTable::new(&[]), Err(TenziError::InvalidDice(_))));
+        assert!(matches!(AliasTable::new(&[1.0, -1.0]), Err(TenziError::InvalidDice(_))));
+        assert!(matches!(AliasTable::new(&[1.0, Float::NAN]), Err(TenziError::InvalidDice(_))));
+        assert!(matches!(AliasTable::new(&[0.0, 0.0]), Err(TenziError::InvalidDice(_))));
+    }
+
+    #[test]
+    fn test_alias_dice() {
+        // Clones share the table, and a seed rolls the same faces.
+
+        let table = Arc::new(AliasTable::new(&[0.0, 1.0, 1.0]).unwrap());
+        let mut dice = AliasDice::seeded(table.clone(), 42);
+        let mut clone = dice.clone();
+
+        let rolls = (0..100).map(|_| dice.roll(3)).collect::<Vec<_>>();
+
+        assert!(rolls.iter().all(|&face| face == 2 || face == 3));
+        assert_eq!(rolls, (0..100).map(|_| clone.roll(3)).collect::<Vec<_>>());
+        assert_eq!(Arc::strong_count(&table), 3);
+
+        let mut thread = AliasDice::new(table);
+        assert!((0..100).all(|_| thread.roll(3) != 1));
+    }
+
+    #[test]
+    fn test_thread_dice_roll_into() {
+        // Both a pool rolled on this thread and one rolled in parallel count every die.
+
+        for num_dice in [1_000, PARALLEL_ROLL_THRESHOLD * 3 + 7] {
+            let mut buckets = vec![1; 6];
+            ThreadDice.roll_into(6, num_dice, &mut buckets);
+
+            assert_eq!(buckets.iter().sum::<Num>(), num_dice + 6);
+            assert!(buckets.iter().all(|&count| count > 1));
+        }
+    }
+
+    #[test]
+    fn test_game_seed() {
+        // The first output of SplitMix64 seeded with zero.
+
+        assert_eq!(game_seed(0, 0), 0xE220_A839_7B1D_CDAF);
+        assert_ne!(game_seed(42, 1), game_seed(42, 2));
+        assert_ne!(game_seed(42, 1), game_seed(43, 1));
+    }
+
+    #[test]
+    fn test_rng_kind() {
+        for kind in RngKind::ALL {
+            assert_eq!(kind.name().parse::<RngKind>().unwrap(), kind);
+        }
+
+        assert_eq!("xoshiro".parse::<RngKind>().unwrap(), RngKind::Xoshiro256PlusPlus);
+        assert!(matches!("mt19937".parse::<RngKind>(), Err(TenziError::InvalidConfig(_))));
+
+        // The standard generator rolls what the seeded dice do, and every generator rolls its own dice from a seed.
+
+        let num_sides = 1000;
+        let rolls = |kind: RngKind| {
+            let mut dice = kind.dice(42, None);
+            (0..8).map(|_| dice.roll(num_sides)).collect::<Vec<_>>()
+        };
+
+        let mut seeded = SeededDice::new(42);
+        assert_eq!(rolls(RngKind::Std), (0..8).map(|_| seeded.roll(num_sides)).collect::<Vec<_>>());
+
+        let seedable = RngKind::ALL.into_iter().filter(RngKind::is_seedable).collect::<Vec<_>>();
+
+        for (i, &first) in seedable.iter().enumerate() {
+            assert_eq!(rolls(first), rolls(first));
+
+            for &second in &seedable[i + 1..] {
+                assert_ne!(rolls(first), rolls(second));
+            }
+        }
+
+        // A generator rolls weighted dice from the table.
+
+        let table = Arc::new(AliasTable::new(&[0.0, 1.0]).unwrap());
+        let mut dice = RngKind::Pcg64.dice(42, Some(&table));
+
+        assert!((0..100).all(|_| dice.roll(2) == 2));
+
+        // The operating system's rng rolls fair faces (weighted or not), regardless of the seed.
+
+        let mut dice = RngKind::Os.dice(42, None);
+        assert!((0..100).all(|_| (1..=6).contains(&dice.roll(6))));
+
+        let mut dice = RngKind::Os.dice(42, Some(&table));
+        assert!((0..100).all(|_| dice.roll(2) == 2));
+    }
+
+    #[test]
+    fn test_philox() {
+        // The known answers of Philox4x32-10 (from the Random123 distribution), with the counter `(block, stream)`.
+
+        assert_eq!(Philox::new(0, 0).block(0), [0xE169_C58D_6627_E8D5, 0x9B00_DBD8_BC57_AC4C]);
+        assert_eq!(Philox::new(u64::MAX, u64::MAX).block(u64::MAX), [0x41C8_3B0E_408F_276D, 0x6D54_51FD_A20B_C7C6]);
+        assert_eq!(Philox::new(0x299F_31D0_A409_3822, 0x0370_7344_1319_8A2E).block(0x85A3_08D3_243F_6A88), [0x94FD_CCEB_D16C_FE09, 0x2412_6EA1_5001_E420]);
+
+        // Every draw can be computed (or sought) directly, and each stream is its own.
+
+        let mut rng = Philox::new(42, 7);
+        let words = (0..5).map(|_| rng.next_u64()).collect::<Vec<_>>();
+
+        assert_eq!(rng.position(), 5);
+        assert_eq!((0..5).map(|index| rng.word(index)).collect::<Vec<_>>(), words);
+
+        rng.seek(3);
+        assert_eq!(rng.next_u64(), words[3]);
+
+        assert_ne!(Philox::new(42, 8).word(0), words[0]);
+        assert_ne!(Philox::new(43, 7).word(0), words[0]);
+        assert_eq!(Philox::seed_from_u64(42).word(0), Philox::new(42, 0).word(0));
+    }
+
+    #[test]
+    fn test_seed_thread() {
+        let num_sides = 1000;
+
+        seed_thread(42);
+        let first = [roll(num_sides), roll(num_sides)];
+
+        seed_thread(42);
+        let second = [roll(num_sides), roll(num_sides)];
+
+        assert_eq!(first, second);
+
+        // A pool that is rolled in parallel is reproduced too, however its chunks are scheduled.
+
+        let num_dice = 3 * PARALLEL_ROLL_THRESHOLD + 1;
+        let mut pools = [vec![0; 6], vec![0; 6]];
+
+        for pool in &mut pools {
+            seed_thread(42);
+            ThreadDice.roll_into(6, num_dice, pool);
+        }
+
+        assert_eq!(pools[0], pools[1]);
+        assert_eq!(pools[0].iter().sum::<Num>(), num_dice);
+    }
+
+    #[test]
+    fn test_rng_stream() {
+        let mut stream = RngStream::new(42);
+        let substreams = [stream.split(), stream.split(), stream.split()];
+
+        // The substreams are the games of a seeded run, and nest by their path of indices.
+
+        assert_eq!(substreams, [0, 1, 2].map(|index| RngStream::new(42).substream(index)));
+        assert_eq!(substreams[1].seed(), game_seed(42, 1));
+        assert_eq!(substreams[1].substream(3), RngStream::new(game_seed(42, 1)).substream(3));
+        assert_ne!(substreams[0].substream(1), substreams[1].substream(0));
+
+        let mut dice = substreams[2].dice(RngKind::Pcg64, None);
+        let mut game = RngKind::Pcg64.game_dice(42, 2, None);
+
+        assert_eq!((0..10).map(|_| dice.roll(6)).collect::<Vec<_>>(), (0..10).map(|_| game.roll(6)).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_split() {
+        // A split rolls from the parent's generator, seeded with the block's seed under the parent's next word.
+
+        let rolls = |mut dice: Box<dyn DiceRng>| (0..10).map(|_| dice.roll(6)).collect::<Vec<_>>();
+        let seed = |index| split_seed(Pcg64::seed_from_u64(42).next_u64(), index);
+        let table = Arc::new(AliasTable::uniform(6).unwrap());
+
+        for index in [0, 3] {
+            assert_eq!(rolls(SeededDice::from_rng(Pcg64::seed_from_u64(42)).split(index).unwrap()), rolls(Box::new(SeededDice::from_rng(Pcg64::seed_from_u64(seed(index))))));
+            assert_eq!(rolls(BufferedDice::new(Pcg64::seed_from_u64(42)).split(index).unwrap()), rolls(Box::new(BufferedDice::new(Pcg64::seed_from_u64(seed(index))))));
+            assert_eq!(rolls(ModuloDice::new(Pcg64::seed_from_u64(42)).split(index).unwrap()), rolls(Box::new(ModuloDice::new(Pcg64::seed_from_u64(seed(index))))));
+            assert_eq!(rolls(AliasDice::from_rng(table.clone(), Pcg64::seed_from_u64(42)).split(index).unwrap()), rolls(Box::new(AliasDice::from_rng(table.clone(), Pcg64::seed_from_u64(seed(index))))));
+        }
+
+        assert_ne!(rolls(SeededDice::from_rng(Pcg64::seed_from_u64(42)).split(0).unwrap()), rolls(Box::new(SeededDice::new(seed(0)))));
+
+        // A source without a state of its own has nothing to split.
+
+        assert!(AliasDice::new(table.clone()).split(0).is_none());
+        assert!(OsDice::new(None).split(0).is_none());
+        assert!(ThreadDice.split(0).is_none());
+    }
+
+    #[cfg(feature = "nightly")]
+    #[bench]
+    fn bench_roll(b: &mut test::Bencher) {
+        b.iter(|| {
+            for _ in 0..1_000 {
+                black_box(roll(black_box(6)));
+            }
+        });
+    }
+
+    #[cfg(feature = "nightly")]
+    #[bench]
+    fn bench_buffered_dice(b: &mut test::Bencher) {
+        let mut dice = BufferedDice::new(SmallRng::seed_from_u64(42));
+
+        b.iter(|| {
+            for _ in 0..1_000 {
+                black_box(dice.roll(black_box(6)));
+            }
+        });
+    }
+
+    #[cfg(feature = "nightly")]
+    #[bench]
+    fn bench_unbuffered_dice(b: &mut test::Bencher) {
+        let mut rng = SmallRng::seed_from_u64(42);
+
+        b.iter(|| {
+            for _ in 0..1_000 {
+                black_box(face(|| rng.gen::<u64>(), black_box(6)));
+            }
+        });
+    }
+
+    #[cfg(feature = "nightly")]
+    #[bench]
+    fn bench_thread_dice_roll_into(b: &mut test::Bencher) {
+        let mut buckets = vec![0; 6];
+        b.iter(|| ThreadDice.roll_into(6, black_box(1_000_000), &mut buckets));
+    }
+
+    #[cfg(feature = "nightly")]
+    #[bench]
+    fn bench_thread_dice_roll_serially(b: &mut test::Bencher) {
+        let mut buckets = vec![0; 6];
+        b.iter(|| roll_serially(6, black_box(1_000_000), &mut buckets));
+    }
+
+    #[cfg(feature = "nightly")]
+    #[bench]
+    fn bench_alias_dice(b: &mut test::Bencher) {
+        let mut dice = AliasDice::seeded(Arc::new(AliasTable::uniform(6).unwrap()), 42);
+
+        b.iter(|| {
+            for _ in 0..1_000 {
+                black_box(dice.roll(black_box(6)));
+            }
+        });
+    }
+
+    // The baseline that `roll` is measured against: fetching the thread rng on every roll.
+
+    #[cfg(feature = "nightly")]
+    #[bench]
+    fn bench_roll_thread_rng(b: &mut test::Bencher) {
+        b.iter(|| {
+            for _ in 0..1_000 {
+                black_box(face(|| rand::thread_rng().gen::<u64>(), black_box(6)));
+            }
+        });
     }
 }
\ No newline at end of file
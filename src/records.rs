@@ -0,0 +1,347 @@
+//! Streaming the outcome of every game (e.g., to a file) as the games are played, without holding them in memory.
+//!
+//! The workers hand their records to a dedicated writer thread over a bounded channel: the I/O never runs on (or
+//! locks) the rayon workers, and if the writer falls behind, the workers wait for it rather than queueing records
+//! without bound.
+//!
+//! The records are written either as NDJSON, or (for runs too large for text) in a binary format: an 8-byte magic
+//! ([`MAGIC`]), followed by each record as its number of rolls and steps (each a little-endian `u64`).  The records
+//! have a fixed width, so the file is its own index (record `i` is at `MAGIC.len() + i * RECORD_SIZE`), and a run that
+//! is cut short leaves a valid file of the records written so far.  With the `mmap` feature, a [`RecordFile`] reads
+//! one through a memory map, so the statistics of a larger-than-memory file are streamed from it.
+
+use std::{io::{BufWriter, Write}, sync::mpsc::{self, SyncSender}, thread::{self, JoinHandle}};
+
+use crate::{error::Result, metrics::{GameOutcome, MetricSink}, types::Num};
+
+/// The magic (and version) at the start of a binary record file.
+pub const MAGIC: &[u8; 8] = b"TENZIRC1";
+
+/// The size of each record in a binary record file.
+pub const RECORD_SIZE: usize = 16;
+
+/// The number of records that each worker collects before handing them to the writer, which amortizes the channel.
+const CHUNK_SIZE: usize = 1024;
+
+/// The number of chunks that can wait for the writer, after which the workers block until it catches up (so at most
+/// this many chunks, plus one per worker, are ever held in memory).
+const CHANNEL_CHUNKS: usize = 64;
+
+/// A sink that streams every game's outcome to a writer thread (see [`RecordWriter::spawn`]), which writes each as a
+/// line of NDJSON: `{"rolls":14,"steps":3}`.
+///
+/// The records are in the order that the writer receives them, which is not the order of the games once they are
+/// spread over workers.  Pair it with another sink (e.g., `(Moments, RecordWriter)`) to also keep statistics.
+pub struct RecordWriter {
+    sender: SyncSender<Vec<GameOutcome>>,
+    chunk: Vec<GameOutcome>,
+}
+
+/// The writer thread of a [`RecordWriter`].
+pub struct RecordThread {
+    thread: JoinHandle<std::io::Result<Num>>,
+}
+
+impl RecordWriter {
+    /// Spawns a thread that writes the records to the given writer (which is buffered), and returns the sink that
+    /// feeds it, along with the thread.
+    ///
+    /// Once the sink (and every fork of it) is dropped, [`RecordThread::finish`] waits for the last records.
+    pub fn spawn(writer: impl Write + Send + 'static) -> (Self, RecordThread) {
+        Self::spawn_with(writer, b"", |writer, GameOutcome { num_rolls, num_steps }| writeln!(writer, "{{\"rolls\":{},\"steps\":{}}}", num_rolls, num_steps))
+    }
+
+    /// Like [`RecordWriter::spawn`], but writes the records in the binary format (see the [module](self) docs).
+    pub fn spawn_binary(writer: impl Write + Send + 'static) -> (Self, RecordThread) {
+        Self::spawn_with(writer, MAGIC, |writer, GameOutcome { num_rolls, num_steps }| {
+            writer.write_all(&(num_rolls as u64).to_le_bytes())?;
+            writer.write_all(&(num_steps as u64).to_le_bytes())
+        })
+    }
+
+    fn spawn_with<W: Write + Send + 'static>(writer: W, header: &'static [u8], write: fn(&mut BufWriter<W>, GameOutcome) -> std::io::Result<()>) -> (Self, RecordThread) {
+        let (sender, receiver) = mpsc::sync_channel::<Vec<GameOutcome>>(CHANNEL_CHUNKS);
+
+        let thread = thread::spawn(move || {
+            let mut writer = BufWriter::new(writer);
+            let mut num_records = 0;
+
+            writer.write_all(header)?;
+
+            for chunk in receiver {
+                for outcome in chunk {
+                    write(&mut writer, outcome)?;
+                    num_records += 1;
+                }
+            }
+
+            writer.flush()?;
+
+            Ok(num_records)
+        });
+
+        (Self::with_sender(sender), RecordThread { thread })
+    }
+
+    fn with_sender(sender: SyncSender<Vec<GameOutcome>>) -> Self {
+        Self { sender, chunk: Vec::with_capacity(CHUNK_SIZE) }
+    }
+
+    /// Hands the collected records to the writer, waiting for room in the channel if it is full.
+    fn send(&mut self) {
+        if self.chunk.is_empty() {
+            return;
+        }
+
+        let chunk = std::mem::replace(&mut self.chunk, Vec::with_capacity(CHUNK_SIZE));
+
+        // If the writer failed, it has stopped receiving, and its error is reported by `RecordThread::finish`.
+
+        let _ = self.sender.send(chunk);
+    }
+}
+
+impl MetricSink for RecordWriter {
+    fn fork(&self) -> Self {
+        Self::with_sender(self.sender.clone())
+    }
+
+    fn record(&mut self, outcome: &GameOutcome) {
+        self.chunk.push(*outcome);
+
+        if self.chunk.len() == CHUNK_SIZE {
+            self.send();
+        }
+    }
+
+    fn merge(&mut self, other: Self) {
+        // The other fork's records go straight to the writer (as it is dropped).
+
+        drop(other);
+    }
+
+    fn heap_bytes(&self) -> usize {
+        // The most records that can wait for the writer, besides the chunk that each worker is collecting.
+
+        CHANNEL_CHUNKS * CHUNK_SIZE * std::mem::size_of::<GameOutcome>()
+    }
+}
+
+impl Drop for RecordWriter {
+    fn drop(&mut self) {
+        self.send();
+    }
+}
+
+impl RecordThread {
+    /// Waits for the writer to write every record, which requires the sink (and every fork of it) to be dropped, and
+    /// returns the number of records written.
+    pub fn finish(self) -> Result<Num> {
+        match self.thread.join() {
+            Ok(written) => Ok(written?),
+            Err(panic) => std::panic::resume_unwind(panic),
+        }
+    }
+}
+
+/// A binary record file (see the [module](self) docs), read through a memory map, so that only the pages that are
+/// read are ever loaded (and the OS can drop them again).
+#[cfg(feature = "mmap")]
+pub struct RecordFile {
+    map: memmap2::Mmap,
+}
+
+#[cfg(feature = "mmap")]
+impl RecordFile {
+    /// Opens the binary record file at the given path.
+    ///
+    /// A partial record at the end (i.e., from a run that was cut short mid-write) is ignored.
+    pub fn open(path: impl AsRef<std::path::Path>) -> Result<Self> {
+        let file = std::fs::File::open(path)?;
+
+        // Safety: the map is only ever read, and a file that is changed while it is mapped is the caller's to avoid
+        // (as with any memory-mapped file).
+        let map = unsafe { memmap2::Mmap::map(&file)? };
+
+        if !map.starts_with(MAGIC) {
+            return Err(crate::error::TenziError::InvalidRecords("the file does not start with the record magic".to_string()));
+        }
+
+        Ok(Self { map })
+    }
+
+    /// Returns the number of records.
+    pub fn len(&self) -> usize {
+        (self.map.len() - MAGIC.len()) / RECORD_SIZE
+    }
+
+    /// Returns whether there are no records.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the record at the given index, which fails with [`TenziError::InvalidRecords`](crate::error::TenziError::InvalidRecords)
+    /// if its counts do not fit the counter type (e.g., with the `num-u32` feature).
+    pub fn get(&self, index: usize) -> Option<Result<GameOutcome>> {
+        (index < self.len()).then(|| decode(&self.records()[index * RECORD_SIZE..(index + 1) * RECORD_SIZE]))
+    }
+
+    /// Returns an iterator over the records, in order (each of which fails like [`RecordFile::get`]).
+    pub fn iter(&self) -> impl Iterator<Item = Result<GameOutcome>> + '_ {
+        self.records().chunks_exact(RECORD_SIZE).map(decode)
+    }
+
+    /// Records every record into the given sink, in parallel (with a fork of the sink per worker), and returns it.
+    ///
+    /// Fails like [`RecordFile::get`] if any record does not fit the counter type.
+    pub fn record_into<S: MetricSink>(&self, mut sink: S) -> Result<S> {
+        use rayon::{iter::ParallelIterator, slice::ParallelSlice};
+
+        let recorded = self.records()
+            .par_chunks_exact(RECORD_SIZE)
+            .try_fold(|| sink.fork(), |mut sink, record| -> Result<S> {
+                sink.record(&decode(record)?);
+                Ok(sink)
+            })
+            .try_reduce(|| sink.fork(), |mut left, right| {
+                left.merge(right);
+                Ok(left)
+            })?;
+
+        sink.merge(recorded);
+
+        Ok(sink)
+    }
+
+    fn records(&self) -> &[u8] {
+        &self.map[MAGIC.len()..MAGIC.len() + self.len() * RECORD_SIZE]
+    }
+}
+
+/// Decodes a record, whose counts are stored as 64 bits, which fails rather than truncates if they do not fit the
+/// counter type.
+#[cfg(feature = "mmap")]
+fn decode(record: &[u8]) -> Result<GameOutcome> {
+    let (rolls, steps) = record.split_at(RECORD_SIZE / 2);
+
+    let count = |bytes: &[u8], name: &str| {
+        let count = u64::from_le_bytes(bytes.try_into().unwrap());
+        Num::try_from(count).map_err(|_| crate::error::TenziError::InvalidRecords(format!("a record has {} {}, which is more than this build counts (build with the `num-u64` feature)", count, name)))
+    };
+
+    Ok(GameOutcome {
+        num_rolls: count(rolls, "rolls")?,
+        num_steps: count(steps, "steps")?,
+    })
+}
+
+// Tests.
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use pretty_assertions::assert_eq;
+    use crate::{cancel::CancelToken, error::TenziError, metrics::Moments, monte_carlo_with, progress::ProgressHook, simulation::{SimulationType, StrategyKind}, types::Float, Execution};
+
+    /// A writer into a buffer that the test keeps a handle to.
+    #[derive(Clone, Default)]
+    struct Shared(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_record_writer() {
+        let buffer = Shared::default();
+        let (writer, thread) = RecordWriter::spawn(buffer.clone());
+
+        let (moments, writer) = monte_carlo_with(SimulationType::fast(StrategyKind::Naive, 6, 10), 5_000, Execution::Parallel, (Moments::new(false), writer), &CancelToken::new(), ProgressHook::none());
+        drop(writer);
+
+        assert_eq!(thread.finish().unwrap(), 5_000);
+
+        // Every game is a line, and the lines add up to the same statistics.
+
+        let text = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let rolls = text.lines().map(|line| line.strip_prefix("{\"rolls\":").unwrap().split(',').next().unwrap().parse::<Num>().unwrap()).collect::<Vec<_>>();
+
+        assert_eq!(rolls.len(), 5_000);
+        assert!((rolls.iter().sum::<Num>() as Float / 5_000.0 - moments.average_rolls()).abs() < 1e-3);
+    }
+
+    #[cfg(feature = "mmap")]
+    #[test]
+    fn test_record_file() {
+        let path = std::env::temp_dir().join(format!("tenzi_sim_records_{}.bin", std::process::id()));
+        let (writer, thread) = RecordWriter::spawn_binary(std::fs::File::create(&path).unwrap());
+
+        let (moments, writer) = monte_carlo_with(SimulationType::fast(StrategyKind::Divide, 6, 10), 5_000, Execution::Parallel, (Moments::new(true), writer), &CancelToken::new(), ProgressHook::none());
+        drop(writer);
+
+        assert_eq!(thread.finish().unwrap(), 5_000);
+
+        // A partial record at the end is ignored.
+
+        std::fs::OpenOptions::new().append(true).open(&path).unwrap().write_all(&[1, 2, 3]).unwrap();
+
+        let file = RecordFile::open(&path).unwrap();
+        let streamed = file.record_into(Moments::new(true)).unwrap();
+
+        assert_eq!(file.len(), 5_000);
+        assert_eq!(file.iter().count(), 5_000);
+        assert_eq!(file.get(4_999).unwrap().unwrap(), file.iter().last().unwrap().unwrap());
+        assert!(file.get(5_000).is_none());
+        assert_eq!(streamed.rolls_histogram(), moments.rolls_histogram());
+        assert_eq!(streamed.num_games(), 5_000);
+
+        // A count that does not fit the counter type is an error, rather than truncated.
+
+        #[cfg(feature = "num-u32")]
+        {
+            let record = [&MAGIC[..], &u64::MAX.to_le_bytes(), &1u64.to_le_bytes()].concat();
+            std::fs::write(&path, record).unwrap();
+
+            assert!(matches!(RecordFile::open(&path).unwrap().record_into(Moments::new(false)), Err(TenziError::InvalidRecords(_))));
+        }
+
+        std::fs::write(&path, b"{\"rolls\":1}").unwrap();
+        assert!(matches!(RecordFile::open(&path), Err(TenziError::InvalidRecords(_))));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_record_writer_fails() {
+        struct Failing;
+
+        impl Write for Failing {
+            fn write(&mut self, _buf: &[u8]) -> std::io::Result<usize> {
+                Err(std::io::Error::other("disk full"))
+            }
+
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let (mut writer, thread) = RecordWriter::spawn(Failing);
+
+        // Enough records to overflow the buffer, and to outlast the writer.
+
+        for _ in 0..(CHUNK_SIZE * (CHANNEL_CHUNKS + 16)) {
+            writer.record(&GameOutcome { num_rolls: 10, num_steps: 1 });
+        }
+
+        drop(writer);
+
+        assert!(matches!(thread.finish(), Err(TenziError::Io(_))));
+    }
+}
\ No newline at end of file
@@ -0,0 +1,294 @@
+use std::sync::Arc;
+
+use crate::{error::{Result, TenziError}, simulation::{ParameterSchema, SimulationType, StrategyKind, StrategyMeta, ThresholdPolicy}, types::Num};
+
+/// The name of the built-in family of strategies that keep every face with at least a threshold of dice (see
+/// [`ThresholdPolicy`]), which is specified as `threshold=<t>`.
+pub const THRESHOLD: &str = "threshold";
+
+/// A factory that builds a strategy from the number of sides and dice.
+type PlainFactory = dyn Fn(Num, Num) -> SimulationType + Send + Sync;
+
+/// A factory that builds a strategy from a parameter, and the number of sides and dice.
+type ParameterizedFactory = dyn Fn(&str, Num, Num) -> Result<SimulationType> + Send + Sync;
+
+/// A factory for a named strategy.
+#[derive(Clone)]
+enum Factory {
+    Plain(Arc<PlainFactory>),
+    Parameterized(Arc<ParameterizedFactory>),
+}
+
+/// The metadata of a registered strategy (see [`StrategyMeta`]).
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StrategyInfo {
+    pub name: String,
+    pub description: String,
+    pub parameter: Option<ParameterSchema>,
+    pub fixed_sides: Vec<Num>,
+}
+
+impl StrategyInfo {
+    /// Copies the metadata of a strategy.
+    pub fn of(meta: &dyn StrategyMeta) -> Self {
+        Self {
+            name: meta.name().to_string(),
+            description: meta.description().to_string(),
+            parameter: meta.parameter().cloned(),
+            fixed_sides: meta.fixed_sides().to_vec(),
+        }
+    }
+
+    /// Returns the spec that the strategy is specified by (e.g., "name", or `name=<parameter>`).
+    pub fn usage(&self) -> String {
+        match &self.parameter {
+            Some(parameter) => format!("{}=<{}>", self.name, parameter.name),
+            None => self.name.clone(),
+        }
+    }
+
+    fn undescribed(name: String, parameter: Option<ParameterSchema>) -> Self {
+        Self { name, description: String::new(), parameter, fixed_sides: Vec::new() }
+    }
+}
+
+impl StrategyMeta for StrategyInfo {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn parameter(&self) -> Option<&ParameterSchema> {
+        self.parameter.as_ref()
+    }
+
+    fn fixed_sides(&self) -> &[Num] {
+        &self.fixed_sides
+    }
+}
+
+/// A registry of named strategy factories, which is how strategies are looked up by name.
+///
+/// Strategies are specified as either "name", or "name=parameter" for parameterized factories (e.g., a family of
+/// strategies that differ by a threshold).  The default registry holds the built-in strategies, which are built with
+/// fixed-size buckets for common numbers of sides (see [`SimulationType::fast`]), and the [`THRESHOLD`] family.
+#[derive(Clone)]
+pub struct StrategyRegistry {
+    factories: Vec<(StrategyInfo, Factory)>,
+}
+
+impl Default for StrategyRegistry {
+    fn default() -> Self {
+        let mut registry = Self::empty();
+
+        for kind in StrategyKind::ALL {
+            registry.register(kind.name(), move |num_sides, num_dice| SimulationType::fast(kind, num_sides, num_dice));
+            registry.describe(kind.meta()).expect("the strategy was just registered");
+        }
+
+        registry.register_parameterized(THRESHOLD, |parameter, num_sides, num_dice| {
+            let invalid = |reason: String| TenziError::InvalidStrategy { spec: format!("{}={}", THRESHOLD, parameter), reason };
+            let threshold = parameter.parse::<Num>().map_err(|_| invalid("the threshold is not a number".to_string()))?;
+            let policy = ThresholdPolicy::new(threshold).map_err(|e| invalid(e.to_string()))?;
+
+            Ok(SimulationType::custom(policy, num_sides, num_dice))
+        });
+
+        let threshold = StrategyInfo {
+            name: THRESHOLD.to_string(),
+            description: "Keep every face with at least t dice (and always the most), and roll the rest.".to_string(),
+            parameter: Some(ParameterSchema::new("t", "The fewest dice that a face is kept with.")),
+            fixed_sides: Vec::new(),
+        };
+
+        registry.describe(&threshold).expect("the strategy was just registered");
+
+        registry
+    }
+}
+
+impl std::fmt::Debug for StrategyRegistry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_set().entries(self.names()).finish()
+    }
+}
+
+impl StrategyRegistry {
+    /// Returns a registry with the built-in strategies.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns a registry with no strategies at all.
+    pub fn empty() -> Self {
+        Self { factories: Vec::new() }
+    }
+
+    /// Registers a strategy under the given name, replacing any strategy already registered under it.
+    pub fn register(&mut self, name: impl Into<String>, factory: impl Fn(Num, Num) -> SimulationType + Send + Sync + 'static) -> &mut Self {
+        self.insert(StrategyInfo::undescribed(name.into(), None), Factory::Plain(Arc::new(factory)))
+    }
+
+    /// Registers a parameterized strategy under the given name, replacing any strategy already registered under it.
+    ///
+    /// The factory is handed the parameter from a "name=parameter" spec, and may reject it.
+    pub fn register_parameterized(&mut self, name: impl Into<String>, factory: impl Fn(&str, Num, Num) -> Result<SimulationType> + Send + Sync + 'static) -> &mut Self {
+        let parameter = ParameterSchema::new("parameter", "");
+        self.insert(StrategyInfo::undescribed(name.into(), Some(parameter)), Factory::Parameterized(Arc::new(factory)))
+    }
+
+    /// Sets the metadata of the strategy registered under the metadata's name.
+    ///
+    /// Fails if no strategy is registered under the name, or if the metadata disagrees with the factory about whether
+    /// or not the strategy takes a parameter.
+    pub fn describe(&mut self, meta: &dyn StrategyMeta) -> Result<&mut Self> {
+        let Some((info, factory)) = self.factories.iter_mut().find(|(info, _)| info.name == meta.name()) else {
+            return Err(TenziError::UnknownStrategy(meta.name().to_string()));
+        };
+
+        if meta.parameter().is_some() != matches!(factory, Factory::Parameterized(_)) {
+            return Err(TenziError::InvalidStrategy { spec: meta.name().to_string(), reason: "the metadata does not match the factory's parameter".to_string() });
+        }
+
+        *info = StrategyInfo::of(meta);
+
+        Ok(self)
+    }
+
+    /// Returns the metadata of the strategy registered under the given name.
+    pub fn info(&self, name: &str) -> Option<&StrategyInfo> {
+        self.factories.iter().find(|(info, _)| info.name == name).map(|(info, _)| info)
+    }
+
+    /// Returns the metadata of the registered strategies, in the order they were registered.
+    pub fn infos(&self) -> impl Iterator<Item = &StrategyInfo> {
+        self.factories.iter().map(|(info, _)| info)
+    }
+
+    /// Returns whether or not a strategy is registered under the given name.
+    pub fn contains(&self, name: &str) -> bool {
+        self.info(name).is_some()
+    }
+
+    /// Returns the names of the registered strategies, in the order they were registered.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.factories.iter().map(|(info, _)| info.name.as_str())
+    }
+
+    /// Returns the names of the registered strategies that are built from their name alone (i.e., that take no
+    /// parameter), in the order they were registered, which is what "every strategy" means to a comparison.
+    pub fn plain_names(&self) -> impl Iterator<Item = &str> {
+        self.factories.iter().filter(|(_, factory)| matches!(factory, Factory::Plain(_))).map(|(info, _)| info.name.as_str())
+    }
+
+    /// Builds a fresh simulation from a strategy spec (i.e., "name", or "name=parameter").
+    pub fn build(&self, spec: &str, num_sides: Num, num_dice: Num) -> Result<SimulationType> {
+        let (name, parameter) = match spec.split_once('=') {
+            Some((name, parameter)) => (name, Some(parameter)),
+            None => (spec, None),
+        };
+
+        let Some((info, factory)) = self.factories.iter().find(|(info, _)| info.name == name) else {
+            return Err(TenziError::UnknownStrategy(name.to_string()));
+        };
+
+        match (factory, parameter) {
+            (Factory::Plain(factory), None) => Ok(factory(num_sides, num_dice)),
+            (Factory::Parameterized(factory), Some(parameter)) => factory(parameter, num_sides, num_dice),
+            (Factory::Plain(_), Some(_)) => Err(TenziError::InvalidStrategy { spec: spec.to_string(), reason: "the strategy does not take a parameter".to_string() }),
+            (Factory::Parameterized(_), None) => Err(TenziError::InvalidStrategy { spec: spec.to_string(), reason: format!("the strategy is specified as `{}`", info.usage()) }),
+        }
+    }
+
+    fn insert(&mut self, info: StrategyInfo, factory: Factory) -> &mut Self {
+        match self.factories.iter_mut().find(|(existing, _)| existing.name == info.name) {
+            Some(existing) => *existing = (info, factory),
+            None => self.factories.push((info, factory)),
+        }
+        self
+    }
+}
+
+// Tests.
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::{KeepPolicy, KeptBuckets};
+    use pretty_assertions::assert_eq;
+
+    /// Keeps every die that shows at least the given face.
+    #[derive(Clone)]
+    struct AtLeast(Num);
+
+    impl KeepPolicy for AtLeast {
+        fn keep(&mut self, buckets: &mut KeptBuckets, _num_dice: Num) {
+            buckets.retain(|k, _| k as Num + 1 >= self.0);
+        }
+    }
+
+    #[test]
+    fn test_builtins() {
+        let registry = StrategyRegistry::new();
+
+        assert_eq!(registry.names().collect::<Vec<_>>(), vec!["naive", "divide", "merge", "threshold"]);
+        assert_eq!(registry.plain_names().collect::<Vec<_>>(), vec!["naive", "divide", "merge"]);
+        assert_eq!(registry.build("merge", 6, 10).unwrap().name(), "merge");
+        assert!(matches!(registry.build("bogus", 6, 10), Err(TenziError::UnknownStrategy(_))));
+        assert!(matches!(registry.build("merge=3", 6, 10), Err(TenziError::InvalidStrategy { .. })));
+
+        // The threshold family is built from its threshold.
+
+        assert_eq!(registry.build("threshold=3", 6, 10).unwrap().name(), "custom");
+        assert_eq!(registry.info(THRESHOLD).unwrap().usage(), "threshold=<t>");
+
+        for malformed in ["threshold", "threshold=0", "threshold=x"] {
+            assert!(matches!(registry.build(malformed, 6, 10), Err(TenziError::InvalidStrategy { .. })), "{}", malformed);
+        }
+    }
+
+    #[test]
+    fn test_builtin_infos() {
+        let registry = StrategyRegistry::new();
+        let merge = registry.info("merge").unwrap();
+
+        assert_eq!(merge.description, StrategyKind::Merge.meta().description());
+        assert_eq!(merge.usage(), "merge");
+        assert_eq!(merge.fixed_sides, crate::simulation::FIXED_SIDES.to_vec());
+        assert!(registry.infos().all(|info| !info.description.is_empty()));
+        assert!(registry.infos().all(|info| info.parameter.as_ref().is_none_or(|parameter| !parameter.description.is_empty())));
+    }
+
+    #[test]
+    fn test_register_parameterized() {
+        let mut registry = StrategyRegistry::empty();
+        registry.register_parameterized("at-least", |parameter, num_sides, num_dice| {
+            let face = parameter.parse::<Num>().map_err(|_| TenziError::InvalidStrategy { spec: format!("at-least={}", parameter), reason: "the face is not a number".to_string() })?;
+            Ok(SimulationType::custom(AtLeast(face), num_sides, num_dice))
+        });
+
+        assert!(registry.contains("at-least"));
+        assert_eq!(registry.build("at-least=5", 6, 10).unwrap().name(), "custom");
+        assert!(matches!(registry.build("at-least", 6, 10), Err(TenziError::InvalidStrategy { .. })));
+        assert!(matches!(registry.build("at-least=x", 6, 10), Err(TenziError::InvalidStrategy { .. })));
+
+        // Describe the strategy, including its parameter.
+
+        let info = StrategyInfo {
+            name: "at-least".to_string(),
+            description: "Keep every die that shows at least the given face.".to_string(),
+            parameter: Some(ParameterSchema::new("face", "The lowest face to keep.")),
+            fixed_sides: Vec::new(),
+        };
+
+        registry.describe(&info).unwrap();
+
+        assert_eq!(registry.info("at-least").unwrap().usage(), "at-least=<face>");
+        assert!(matches!(registry.build("at-least", 6, 10), Err(TenziError::InvalidStrategy { reason, .. }) if reason.contains("at-least=<face>")));
+        assert!(matches!(registry.describe(StrategyKind::Naive.meta()), Err(TenziError::UnknownStrategy(_))));
+    }
+}
\ No newline at end of file
@@ -0,0 +1,225 @@
+use std::io::{BufRead, Write};
+
+use colored::Colorize;
+
+use tenzi_sim::{race, simulation, types::Num, SimulationConfig, StrategyRegistry};
+
+/// The help text for the REPL.
+const HELP: &str = "\
+Commands:
+  show                        Shows the current parameters and bucket state.
+  set sides <n>               Sets the number of sides on each die (clears the bucket state).
+  set dice <n>                Sets the number of die in the game.
+  set sims <n>                Sets the number of simulations to run per query.
+  set strategy <name>         Sets the strategy to query (see `strategies`).
+  strategies                  Lists the available strategies.
+  state <counts>              Sets the bucket state (e.g., `state 0,0,6,0,0,0`).
+  state clear                 Clears the bucket state.
+  bucket <face> <count>       Sets the number of dice kept for a single face.
+  run                         Runs a conditional simulation of the strategy from the bucket state.
+  compare                     Runs a conditional simulation of every strategy from the bucket state.
+  race <player> <player> ...  Races players given as \"strategy:matched\" (e.g., `race naive:3 merge:5`).
+  help                        Shows this message.
+  quit                        Exits the REPL.";
+
+/// An interactive session for exploring states and strategies.
+/// Holds the parameters and the bucket state between commands.
+pub struct Repl {
+    sides: Num,
+    dice: Num,
+    simulations: Num,
+    strategy: String,
+    state: Vec<Num>,
+    registry: StrategyRegistry,
+}
+
+impl Repl {
+    pub fn new(sides: Num, dice: Num, simulations: Num) -> Self {
+        Self {
+            sides,
+            dice,
+            simulations,
+            strategy: "naive".to_string(),
+            state: vec![0; sides as usize],
+            registry: StrategyRegistry::new(),
+        }
+    }
+
+    /// Reads commands from the input until it is exhausted, or the user quits.
+    pub fn run(&mut self, input: impl BufRead) {
+        println!("Tenzi REPL.  Type `help` for a list of commands.");
+
+        let mut lines = input.lines();
+
+        loop {
+            print!("{} ", ">".cyan());
+            std::io::stdout().flush().unwrap();
+
+            let Some(Ok(line)) = lines.next() else {
+                break;
+            };
+
+            match self.execute(&line) {
+                Ok(true) => {}
+                Ok(false) => break,
+                Err(e) => println!("{} {}", "Error:".red(), e),
+            }
+        }
+    }
+
+    /// Executes a single command, and returns whether or not the session should continue.
+    pub fn execute(&mut self, line: &str) -> Result<bool, String> {
+        let words = line.split_whitespace().collect::<Vec<_>>();
+
+        match words.as_slice() {
+            [] => {}
+            ["help"] => println!("{}", HELP),
+            ["strategies"] => {
+                for info in self.registry.infos() {
+                    println!("`{}`: {}", info.usage().cyan(), info.description);
+                }
+            }
+            ["quit"] | ["exit"] => return Ok(false),
+            ["show"] => self.show(),
+            ["set", "sides", n] => {
+                self.sides = parse_num(n)?;
+                self.state = vec![0; self.sides as usize];
+            }
+            ["set", "dice", n] => {
+                let dice = parse_num(n)?;
+                simulation::check_state(&self.state, self.sides, dice).map_err(|e| e.to_string())?;
+                self.dice = dice;
+            }
+            ["set", "sims", n] => {
+                let simulations = parse_num(n)?;
+                if simulations == 0 {
+                    return Err("there must be at least one simulation".to_string());
+                }
+                self.simulations = simulations;
+            }
+            ["set", "strategy", name] => {
+                self.registry.build(name, self.sides, self.dice).map_err(|e| e.to_string())?;
+                self.strategy = name.to_string();
+            }
+            ["state", "clear"] => self.state = vec![0; self.sides as usize],
+            ["state", counts] => {
+                let state = counts.split(',').map(parse_num).collect::<Result<Vec<_>, _>>()?;
+                simulation::check_state(&state, self.sides, self.dice).map_err(|e| e.to_string())?;
+                self.state = state;
+            }
+            ["bucket", face, count] => {
+                let face = parse_num(face)?;
+                if face == 0 || face > self.sides {
+                    return Err(format!("face must be between 1 and {}", self.sides));
+                }
+
+                let mut state = self.state.clone();
+                state[face as usize - 1] = parse_num(count)?;
+                simulation::check_state(&state, self.sides, self.dice).map_err(|e| e.to_string())?;
+                self.state = state;
+            }
+            ["run"] => self.simulate(&self.strategy)?,
+            ["compare"] => {
+                for name in self.registry.plain_names() {
+                    self.simulate(name)?;
+                }
+            }
+            ["race", players @ ..] if players.len() >= 2 => {
+                let racers = players.iter().map(|p| race::player_from_spec(&self.registry, p, self.sides, self.dice)).collect::<Result<Vec<_>, _>>().map_err(|e| e.to_string())?;
+                let output = race::race(&racers, self.simulations).map_err(|e| e.to_string())?;
+
+                for (player, probability) in players.iter().zip(output.win_probabilities) {
+                    println!("Player `{}` wins: {:.8}.", player.cyan(), probability.to_string().green());
+                }
+                println!("Tie: {:.8}.", output.tie_probability.to_string().yellow());
+            }
+            _ => return Err(format!("unrecognized command `{}`; type `help` for a list of commands", line.trim())),
+        }
+
+        Ok(true)
+    }
+
+    /// Prints the current parameters and bucket state.
+    fn show(&self) {
+        println!("Sides:       {}.", self.sides.to_string().cyan());
+        println!("Dice:        {}.", self.dice.to_string().cyan());
+        println!("Simulations: {}.", self.simulations.to_string().cyan());
+        println!("Strategy:    `{}`.", self.strategy.cyan());
+        println!("State:       {}.", format!("{:?}", self.state).cyan());
+    }
+
+    /// Runs a conditional simulation of the given strategy from the current bucket state.
+    fn simulate(&self, name: &str) -> Result<(), String> {
+        let config = SimulationConfig::builder()
+            .sides(self.sides)
+            .dice(self.dice)
+            .strategy(name)
+            .registry(self.registry.clone())
+            .initial_state(self.state.clone())
+            .simulations(self.simulations)
+            .build()
+            .map_err(|e| e.to_string())?;
+
+        let results = config.run().map_err(|e| e.to_string())?;
+
+        for summary in results.summaries() {
+            println!("`{}`: {:.8} ± {:.8} remaining rolls, {:.8} remaining steps.", summary.strategy().cyan(), summary.average_rolls().to_string().green(), summary.std_err_rolls().to_string().yellow(), summary.average_steps().to_string().green());
+        }
+
+        Ok(())
+    }
+}
+
+/// Parses a number from a command argument.
+fn parse_num(s: &str) -> Result<Num, String> {
+    s.parse::<Num>().map_err(|_| format!("`{}` is not a number", s))
+}
+
+// Tests.
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_set_parameters() {
+        let mut repl = Repl::new(6, 10, 100);
+
+        repl.execute("set sides 8").unwrap();
+        repl.execute("set dice 12").unwrap();
+        repl.execute("set sims 50").unwrap();
+        repl.execute("set strategy merge").unwrap();
+
+        assert_eq!(repl.sides, 8);
+        assert_eq!(repl.dice, 12);
+        assert_eq!(repl.simulations, 50);
+        assert_eq!(repl.strategy, "merge");
+        assert_eq!(repl.state, vec![0; 8]);
+    }
+
+    #[test]
+    fn test_modify_state() {
+        let mut repl = Repl::new(6, 10, 100);
+
+        repl.execute("state 0,0,6,0,0,0").unwrap();
+        repl.execute("bucket 1 2").unwrap();
+        assert_eq!(repl.state, vec![2, 0, 6, 0, 0, 0]);
+
+        repl.execute("state clear").unwrap();
+        assert_eq!(repl.state, vec![0; 6]);
+    }
+
+    #[test]
+    fn test_invalid_commands() {
+        let mut repl = Repl::new(6, 10, 100);
+
+        assert!(repl.execute("state 0,0,6").is_err());
+        assert!(repl.execute("bucket 7 1").is_err());
+        assert!(repl.execute("bucket 1 11").is_err());
+        assert!(repl.execute("set strategy bogus").is_err());
+        assert!(repl.execute("race naive").is_err());
+        assert!(repl.execute("frobnicate").is_err());
+        assert_eq!(repl.execute("quit"), Ok(false));
+    }
+}
\ No newline at end of file
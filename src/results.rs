@@ -0,0 +1,186 @@
+use std::time::Duration;
+
+use crate::{distribution::{self, BinEstimate}, error::{Result, TenziError}, memory::MemoryUsage, rules::RuleSet, types::{Float, Num}};
+
+/// The results of a monte carlo run: the parameters it was run with, and a summary for each strategy.
+///
+/// Every output of the simulator (the CLI, the REPL, and the library API) is a view over this type.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RunResults {
+    parameters: RunParameters,
+    summaries: Vec<StrategySummary>,
+}
+
+impl RunResults {
+    /// Collects the summaries of strategies that were run with the same parameters.
+    pub fn new(parameters: RunParameters, summaries: Vec<StrategySummary>) -> Self {
+        Self { parameters, summaries }
+    }
+
+    /// Returns the parameters that every strategy was run with.
+    pub fn parameters(&self) -> &RunParameters {
+        &self.parameters
+    }
+
+    /// Returns the summary of each strategy, in the order they were run.
+    pub fn summaries(&self) -> &[StrategySummary] {
+        &self.summaries
+    }
+
+    /// Returns the summary of the strategy with the given name, if it was run.
+    pub fn summary(&self, strategy: &str) -> Option<&StrategySummary> {
+        self.summaries.iter().find(|s| s.strategy == strategy)
+    }
+
+    /// Returns the summaries, consuming the results.
+    pub fn into_summaries(self) -> Vec<StrategySummary> {
+        self.summaries
+    }
+
+    /// Returns whether or not every strategy ran every simulation (i.e., the run was not cancelled part way).
+    pub fn is_complete(&self) -> bool {
+        self.summaries.iter().all(|s| s.num_simulations == self.parameters.num_simulations)
+    }
+
+    /// Returns the clock time it took to run every strategy.
+    pub fn duration(&self) -> Duration {
+        self.summaries.iter().map(|s| s.duration).sum()
+    }
+}
+
+/// The parameters of a monte carlo run.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RunParameters {
+    pub num_sides: Num,
+    pub num_dice: Num,
+    pub num_simulations: Num,
+    pub initial_state: Option<Vec<Num>>,
+    /// The name of the rng that the dice were rolled with (e.g., "chacha8"), so that results can be traced back to
+    /// their generator.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub rng: String,
+    /// The house rules that the games were played by.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub rules: RuleSet,
+}
+
+/// The statistics of the number of rolls and steps it took a strategy to achieve a "tenzi".
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StrategySummary {
+    pub(crate) strategy: String,
+    pub(crate) num_simulations: Num,
+    pub(crate) average_rolls: Float,
+    pub(crate) std_dev_rolls: Float,
+    pub(crate) average_steps: Float,
+    pub(crate) std_dev_steps: Float,
+    pub(crate) rolls_histogram: Option<Vec<Num>>,
+    pub(crate) duration: Duration,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub(crate) memory: MemoryUsage,
+}
+
+impl StrategySummary {
+    /// Returns the name of the strategy.
+    pub fn strategy(&self) -> &str {
+        &self.strategy
+    }
+
+    /// Returns the number of games that were simulated.
+    pub fn num_simulations(&self) -> Num {
+        self.num_simulations
+    }
+
+    /// Returns the average number of rolls.
+    pub fn average_rolls(&self) -> Float {
+        self.average_rolls
+    }
+
+    /// Returns the standard deviation of the number of rolls.
+    pub fn std_dev_rolls(&self) -> Float {
+        self.std_dev_rolls
+    }
+
+    /// Returns the standard error of the average number of rolls.
+    pub fn std_err_rolls(&self) -> Float {
+        self.std_dev_rolls / (self.num_simulations as Float).sqrt()
+    }
+
+    /// Returns the average number of steps.
+    pub fn average_steps(&self) -> Float {
+        self.average_steps
+    }
+
+    /// Returns the standard deviation of the number of steps.
+    pub fn std_dev_steps(&self) -> Float {
+        self.std_dev_steps
+    }
+
+    /// Returns the standard error of the average number of steps.
+    pub fn std_err_steps(&self) -> Float {
+        self.std_dev_steps / (self.num_simulations as Float).sqrt()
+    }
+
+    /// Returns the number of games that took each number of rolls (i.e., index `k` is the number of games that took
+    /// `k` rolls), if it was recorded.
+    pub fn rolls_histogram(&self) -> Option<&[Num]> {
+        self.rolls_histogram.as_deref()
+    }
+
+    /// Estimates the share of the games that took each number of rolls, with intervals at the given confidence level
+    /// (see [`distribution::estimate_bins`]).
+    ///
+    /// Fails with [`TenziError::InvalidConfig`] if the histogram was not recorded, or if the confidence level is not
+    /// between 0 and 1.
+    pub fn rolls_distribution(&self, confidence: Float) -> Result<Vec<BinEstimate>> {
+        let histogram = self.rolls_histogram().ok_or_else(|| TenziError::InvalidConfig("the histogram of rolls was not recorded".to_string()))?;
+
+        distribution::estimate_bins(&histogram.iter().map(|&count| count as u64).collect::<Vec<_>>(), confidence)
+    }
+
+    /// Returns the clock time it took to run the simulations.
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+
+    /// Returns what the simulations cost in memory (see [`memory`](crate::memory)).
+    pub fn memory(&self) -> &MemoryUsage {
+        &self.memory
+    }
+}
+
+// Tests.
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn summary(strategy: &str, millis: u64) -> StrategySummary {
+        StrategySummary {
+            strategy: strategy.to_string(),
+            num_simulations: 100,
+            average_rolls: 40.0,
+            std_dev_rolls: 10.0,
+            average_steps: 8.0,
+            std_dev_steps: 2.0,
+            rolls_histogram: None,
+            duration: Duration::from_millis(millis),
+            memory: MemoryUsage::default(),
+        }
+    }
+
+    #[test]
+    fn test_results_views() {
+        let parameters = RunParameters { num_sides: 6, num_dice: 10, num_simulations: 100, initial_state: None, rng: "std".to_string(), rules: RuleSet::default() };
+        let results = RunResults::new(parameters, vec![summary("naive", 2), summary("merge", 3)]);
+
+        assert_eq!(results.duration(), Duration::from_millis(5));
+        assert_eq!(results.summary("merge").unwrap().std_err_rolls(), 1.0);
+        assert_eq!(results.summary("merge").unwrap().std_err_steps(), 0.2);
+        assert!(matches!(results.summaries()[0].rolls_distribution(0.95), Err(TenziError::InvalidConfig(_))));
+        assert!(results.summary("divide").is_none());
+    }
+}
\ No newline at end of file
@@ -0,0 +1,172 @@
+//! House rules, which modify the core game for every strategy alike.
+//!
+//! A [`RuleSet`] composes any of the modifiers below, and the [`Game`](crate::simulation::Game) engine enforces them
+//! after every keep, whatever the policy decided (see [`Strategy::set_rules`](crate::simulation::Strategy::set_rules)):
+//!
+//! * `max-rerolls=N`: at most `N` dice may be re-rolled per step.
+//! * `keep-new-die`: every roll of at least two dice must keep at least one more die than before (i.e., a roll can
+//!   never be re-rolled whole).
+//! * `double-cost-after=N`: from the step after the `N`th on, every die rolled counts as two rolls.
+//! * `lock-kept`: the face with the most kept dice is locked the first time that any die is kept, and from then on, no
+//!   die that shows it can be re-rolled.
+//!
+//! A rule set is written as its rules, separated by commas or lines (e.g., "max-rerolls=5,lock-kept"), where a "#"
+//! starts a comment, which is how it is given on the command line and in a rules file.
+
+use alloc::{format, string::{String, ToString}, vec::Vec};
+
+use crate::{error::{Result, TenziError}, types::Num};
+
+/// A set of house rules (see the [module](self) docs), where the default is the plain game.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct RuleSet {
+    /// The most dice that may be re-rolled per step.
+    pub max_rerolls: Option<Num>,
+    /// Whether every roll of at least two dice must keep at least one more die.
+    pub keep_new_die: bool,
+    /// The number of steps after which every die rolled counts as two rolls.
+    pub double_cost_after: Option<Num>,
+    /// Whether the face with the most kept dice is locked, so that no die that shows it is re-rolled.
+    pub lock_kept: bool,
+}
+
+impl RuleSet {
+    /// Returns whether these are the rules of the plain game (i.e., no rule is set).
+    pub fn is_plain(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Ensures that every game under these rules can end.
+    pub fn check(&self) -> Result<()> {
+        if self.max_rerolls == Some(0) {
+            return Err(TenziError::InvalidConfig("at least one die must be re-rolled per step, or the game never ends".to_string()));
+        }
+
+        Ok(())
+    }
+
+    /// Returns the most dice that may be re-rolled after a roll of the given number of dice, if the rules limit them.
+    pub fn max_rerolls_after(&self, num_rolled: Num) -> Option<Num> {
+        let keep_new_die = (self.keep_new_die && num_rolled >= 2).then(|| num_rolled - 1);
+
+        match (self.max_rerolls, keep_new_die) {
+            (Some(max_rerolls), Some(keep_new_die)) => Some(max_rerolls.min(keep_new_die)),
+            (max_rerolls, keep_new_die) => max_rerolls.or(keep_new_die),
+        }
+    }
+
+    /// Returns the number of rolls that each die rolled on the step after the given number of steps counts as.
+    pub fn cost(&self, num_steps: Num) -> Num {
+        match self.double_cost_after {
+            Some(after) if num_steps >= after => 2,
+            _ => 1,
+        }
+    }
+}
+
+impl core::fmt::Display for RuleSet {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let mut rules = Vec::new();
+
+        if let Some(max_rerolls) = self.max_rerolls {
+            rules.push(format!("max-rerolls={}", max_rerolls));
+        }
+
+        if self.keep_new_die {
+            rules.push("keep-new-die".to_string());
+        }
+
+        if let Some(after) = self.double_cost_after {
+            rules.push(format!("double-cost-after={}", after));
+        }
+
+        if self.lock_kept {
+            rules.push("lock-kept".to_string());
+        }
+
+        f.write_str(&rules.join(","))
+    }
+}
+
+impl core::str::FromStr for RuleSet {
+    type Err = TenziError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut rules = Self::default();
+
+        let entries = s.lines().map(|line| line.split('#').next().unwrap()).flat_map(|line| line.split(',')).map(str::trim).filter(|entry| !entry.is_empty());
+
+        for entry in entries {
+            let (name, value) = match entry.split_once('=') {
+                Some((name, value)) => (name.trim(), Some(value.trim())),
+                None => (entry, None),
+            };
+
+            let number = |value: Option<&str>| -> Result<Num> {
+                let value = value.ok_or_else(|| invalid(format!("the rule `{}` needs a value (e.g., `{}=5`)", name, name)))?;
+                value.parse().map_err(|_| invalid(format!("`{}` is not a number", value)))
+            };
+
+            let flag = |value: Option<&str>| match value {
+                Some(_) => Err(invalid(format!("the rule `{}` does not take a value", name))),
+                None => Ok(true),
+            };
+
+            match name {
+                "max-rerolls" => rules.max_rerolls = Some(number(value)?),
+                "keep-new-die" => rules.keep_new_die = flag(value)?,
+                "double-cost-after" => rules.double_cost_after = Some(number(value)?),
+                "lock-kept" => rules.lock_kept = flag(value)?,
+                _ => return Err(invalid(format!("unknown rule `{}`", name))),
+            }
+        }
+
+        rules.check()?;
+
+        Ok(rules)
+    }
+}
+
+/// Returns an error for malformed rules.
+fn invalid(reason: String) -> TenziError {
+    TenziError::InvalidConfig(reason)
+}
+
+// Tests.
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_parse() {
+        let rules = "max-rerolls=5, lock-kept\n# Expensive after a while.\ndouble-cost-after = 10 # (steps)\n".parse::<RuleSet>().unwrap();
+
+        assert_eq!(rules, RuleSet { max_rerolls: Some(5), keep_new_die: false, double_cost_after: Some(10), lock_kept: true });
+        assert_eq!(rules.to_string(), "max-rerolls=5,double-cost-after=10,lock-kept");
+        assert_eq!(rules.to_string().parse::<RuleSet>().unwrap(), rules);
+
+        assert!("".parse::<RuleSet>().unwrap().is_plain());
+        assert_eq!(RuleSet::default().to_string(), "");
+
+        for malformed in ["bogus", "max-rerolls", "max-rerolls=x", "lock-kept=1", "max-rerolls=0"] {
+            assert!(matches!(malformed.parse::<RuleSet>(), Err(TenziError::InvalidConfig(_))), "{}", malformed);
+        }
+    }
+
+    #[test]
+    fn test_limits() {
+        let rules = RuleSet { max_rerolls: Some(3), keep_new_die: true, double_cost_after: Some(2), ..RuleSet::default() };
+
+        assert_eq!(rules.max_rerolls_after(10), Some(3));
+        assert_eq!(rules.max_rerolls_after(3), Some(2));
+        assert_eq!(rules.max_rerolls_after(1), Some(3));
+        assert_eq!(RuleSet::default().max_rerolls_after(10), None);
+
+        assert_eq!((rules.cost(0), rules.cost(1), rules.cost(2), rules.cost(7)), (1, 1, 2, 2));
+        assert_eq!(RuleSet::default().cost(100), 1);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,81 @@
+//! An async wrapper for embedding the simulator in a tokio runtime.
+//!
+//! The simulations run on rayon, so they are moved onto tokio's blocking pool rather than the async workers.
+
+use std::future::Future;
+
+use tokio::sync::watch;
+
+use crate::{cancel::CancelToken, error::{Result, TenziError}, progress::{Progress, ProgressHook}, results::RunResults, types::Num, SimulationConfig};
+
+/// Runs the monte carlo simulation without blocking the async runtime.
+///
+/// Cancelling the token stops the simulation early with [`TenziError::Cancelled`].
+pub async fn run_monte_carlo(config: SimulationConfig, cancel: CancelToken) -> Result<RunResults> {
+    spawn(move || config.run_cancellable(&cancel, ProgressHook::none())).await
+}
+
+/// Runs the monte carlo simulation without blocking the async runtime, like [`run_monte_carlo`], while streaming
+/// its progress to the returned receiver every `interval` completed games (see [`ProgressHook::every`]).
+pub fn run_monte_carlo_with_progress(config: SimulationConfig, cancel: CancelToken, interval: Num) -> (watch::Receiver<Progress>, impl Future<Output = Result<RunResults>>) {
+    let (sender, receiver) = watch::channel(Progress::default());
+
+    let hook = ProgressHook::new(move |progress: &Progress| {
+        sender.send_replace(*progress);
+    });
+
+    let future = spawn(move || config.run_cancellable(&cancel, hook.every(interval)));
+
+    (receiver, future)
+}
+
+/// Moves a simulation onto the blocking pool, and resumes any panic on the awaiting task.
+async fn spawn(f: impl FnOnce() -> Result<RunResults> + Send + 'static) -> Result<RunResults> {
+    match tokio::task::spawn_blocking(f).await {
+        Ok(result) => result,
+        Err(e) if e.is_panic() => std::panic::resume_unwind(e.into_panic()),
+        Err(_) => Err(TenziError::Cancelled),
+    }
+}
+
+// Tests.
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn block_on<F: Future>(future: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(future)
+    }
+
+    #[test]
+    fn test_run_monte_carlo() {
+        let config = SimulationConfig::builder().initial_state(vec![0, 10, 0, 0, 0, 0]).simulations(10).build().unwrap();
+
+        let results = block_on(run_monte_carlo(config, CancelToken::new())).unwrap();
+
+        assert_eq!(results.summaries()[0].average_rolls(), 0.0);
+    }
+
+    #[test]
+    fn test_run_monte_carlo_cancelled() {
+        let cancel = CancelToken::new();
+        cancel.cancel();
+
+        let result = block_on(run_monte_carlo(SimulationConfig::builder().build().unwrap(), cancel));
+
+        assert!(matches!(result, Err(TenziError::Cancelled)));
+    }
+
+    #[test]
+    fn test_run_monte_carlo_with_progress() {
+        let config = SimulationConfig::builder().simulations(2_000).build().unwrap();
+
+        let (progress, future) = run_monte_carlo_with_progress(config, CancelToken::new(), 100);
+        block_on(future).unwrap();
+
+        assert_eq!(progress.borrow().completed, 2_000);
+        assert!(progress.borrow().is_done());
+    }
+}
\ No newline at end of file
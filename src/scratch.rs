@@ -0,0 +1,128 @@
+//! A scratch arena for the temporaries of a game's decisions (e.g., the anti-modes that a policy re-rolls).
+//!
+//! A [`Scratch`] is a bump allocator over a single buffer: each temporary is appended to it and handed back as a
+//! [`Span`], and the whole arena is reset at once (by the [`Game`](crate::simulation::Game) before every keep).  Once
+//! the buffer has grown to fit the largest decision, a game never allocates for its temporaries again, and (as every
+//! game owns its arena) the workers never share one.
+
+use alloc::vec::Vec;
+
+use crate::types::Num;
+
+/// A bump arena of numbers (e.g., counts or faces), which is reset as a whole.
+#[derive(Clone, Debug, Default)]
+pub struct Scratch {
+    values: Vec<Num>,
+}
+
+/// A slice of a [`Scratch`], which is valid until the arena is reset.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Span {
+    start: usize,
+    end: usize,
+}
+
+impl Scratch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Allocates a slice of the given length, filled with zeroes.
+    pub fn alloc(&mut self, len: usize) -> Span {
+        let start = self.values.len();
+        self.values.resize(start + len, 0);
+
+        Span { start, end: self.values.len() }
+    }
+
+    /// Allocates a slice of the given values.
+    pub fn collect(&mut self, values: impl IntoIterator<Item = Num>) -> Span {
+        self.collect_with(|scratch| scratch.extend(values))
+    }
+
+    /// Allocates a slice of whatever the given function appends to the arena (through its [`Extend`] impl), for the
+    /// helpers that write into a buffer (e.g., [`histogram::extend_anti_modes`](crate::histogram::extend_anti_modes)).
+    pub fn collect_with(&mut self, append: impl FnOnce(&mut Self)) -> Span {
+        let start = self.values.len();
+        append(self);
+
+        Span { start, end: self.values.len() }
+    }
+
+    /// Returns the values of a slice.
+    pub fn get(&self, span: Span) -> &[Num] {
+        &self.values[span.start..span.end]
+    }
+
+    /// Returns the values of a slice, to be changed in place.
+    pub fn get_mut(&mut self, span: Span) -> &mut [Num] {
+        &mut self.values[span.start..span.end]
+    }
+
+    /// Returns the number of values that are allocated.
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Returns whether nothing is allocated.
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Frees every slice at once, while keeping the buffer for the next ones.
+    pub fn reset(&mut self) {
+        self.values.clear();
+    }
+}
+
+impl Extend<Num> for Scratch {
+    fn extend<I: IntoIterator<Item = Num>>(&mut self, values: I) {
+        self.values.extend(values);
+    }
+}
+
+impl Span {
+    /// Returns the number of values in the slice.
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    /// Returns whether the slice is empty.
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+}
+
+// Tests.
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_scratch() {
+        let mut scratch = Scratch::new();
+
+        let zeroes = scratch.alloc(3);
+        let faces = scratch.collect([4, 2]);
+        let appended = scratch.collect_with(|scratch| scratch.extend([7]));
+
+        scratch.get_mut(zeroes)[1] = 9;
+
+        assert_eq!(scratch.get(zeroes), &[0, 9, 0]);
+        assert_eq!(scratch.get(faces), &[4, 2]);
+        assert_eq!(scratch.get(appended), &[7]);
+        assert_eq!(scratch.len(), 6);
+        assert_eq!(faces.len(), 2);
+
+        // A reset frees every slice, but keeps the buffer.
+
+        let capacity = scratch.values.capacity();
+        scratch.reset();
+
+        assert!(scratch.is_empty());
+        assert!(scratch.collect_with(|_| {}).is_empty());
+        assert_eq!(scratch.values.capacity(), capacity);
+    }
+}
\ No newline at end of file
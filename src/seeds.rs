@@ -0,0 +1,166 @@
+//! Logging the seed of every game of a seeded run (or only of its outliers), so that any game can be replayed exactly.
+//!
+//! A seeded run rolls each game from the run's seed and the game's index (see [`RngKind::game_dice`]), so a game is
+//! identified by its index, and (for every generator but the counter-based [`Philox`](crate::rand::Philox)) by the
+//! seed that is derived from it.  A [`SeedLog`] records either every game, or only the ones that took the most rolls,
+//! and [`SimulationConfig::game`](crate::SimulationConfig::game) plays any of them again.
+
+use std::cmp::Reverse;
+
+use crate::{metrics::{GameOutcome, MetricSink}, rand::RngKind, types::Num};
+
+/// A sink that logs the index and seed of the games of a seeded run, along with their outcomes.
+///
+/// Games are only logged when the runner hands over their index (see [`MetricSink::record_game`]).
+#[derive(Clone, Debug)]
+pub struct SeedLog {
+    seed: u64,
+    rng: RngKind,
+    limit: Option<usize>,
+    entries: Vec<SeedEntry>,
+}
+
+/// A logged game of a seeded run.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SeedEntry {
+    /// The index of the game in the run.
+    pub index: Num,
+    /// The seed that the game was rolled from, or `None` if the generator rolls the game from the run's seed instead
+    /// (see [`RngKind::game_seed`]).
+    pub seed: Option<u64>,
+    /// The number of rolls it took to achieve a "tenzi".
+    pub num_rolls: Num,
+    /// The number of steps it took to achieve a "tenzi".
+    pub num_steps: Num,
+}
+
+impl SeedEntry {
+    /// Returns the entry as a line of JSON: `{"game":7,"seed":123,"rolls":14,"steps":3}` (where the seed is `null` if the
+    /// game is only identified by its index).
+    pub fn to_json(&self) -> String {
+        let seed = self.seed.map_or("null".to_string(), |seed| seed.to_string());
+        format!("{{\"game\":{},\"seed\":{},\"rolls\":{},\"steps\":{}}}", self.index, seed, self.num_rolls, self.num_steps)
+    }
+}
+
+impl SeedLog {
+    /// Returns a log of every game of a run with the given seed and generator.
+    ///
+    /// Every game is held in memory, so this is meant for runs that are small enough to also write out.
+    pub fn all(seed: u64, rng: RngKind) -> Self {
+        Self { seed, rng, limit: None, entries: Vec::new() }
+    }
+
+    /// Returns a log of the given number of games that took the most rolls (i.e., the outliers) in a run with the
+    /// given seed and generator.
+    pub fn most_rolls(seed: u64, rng: RngKind, limit: usize) -> Self {
+        Self { seed, rng, limit: Some(limit), entries: Vec::new() }
+    }
+
+    /// Returns the seed of the run.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Returns the generator of the run.
+    pub fn rng(&self) -> RngKind {
+        self.rng
+    }
+
+    /// Returns the logged games, consuming the log: every game in the order of their index, or the outliers from the
+    /// most rolls to the fewest (and then by index).
+    pub fn into_entries(mut self) -> Vec<SeedEntry> {
+        self.trim();
+        self.entries
+    }
+
+    /// Sorts the entries, and drops every outlier past the limit.
+    fn trim(&mut self) {
+        match self.limit {
+            Some(limit) => {
+                self.entries.sort_unstable_by_key(|entry| (Reverse(entry.num_rolls), entry.index));
+                self.entries.truncate(limit);
+            }
+            None => self.entries.sort_unstable_by_key(|entry| entry.index),
+        }
+    }
+}
+
+impl MetricSink for SeedLog {
+    fn fork(&self) -> Self {
+        Self { entries: Vec::new(), ..*self }
+    }
+
+    fn record(&mut self, _outcome: &GameOutcome) {}
+
+    fn record_game(&mut self, index: Num, outcome: &GameOutcome) {
+        self.entries.push(SeedEntry { index, seed: self.rng.game_seed(self.seed, index as u64), num_rolls: outcome.num_rolls, num_steps: outcome.num_steps });
+
+        // Only trim the outliers once they have doubled, so that the sort is amortized over the games.
+
+        if self.limit.is_some_and(|limit| self.entries.len() > 2 * limit.max(1)) {
+            self.trim();
+        }
+    }
+
+    fn merge(&mut self, other: Self) {
+        self.entries.extend(other.entries);
+
+        if self.limit.is_some() {
+            self.trim();
+        }
+    }
+
+    fn heap_bytes(&self) -> usize {
+        self.entries.capacity() * std::mem::size_of::<SeedEntry>()
+    }
+}
+
+// Tests.
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn outcome(num_rolls: Num) -> GameOutcome {
+        GameOutcome { num_rolls, num_steps: 1 }
+    }
+
+    #[test]
+    fn test_seed_log() {
+        let mut all = SeedLog::all(42, RngKind::Std);
+        let mut worst = SeedLog::most_rolls(42, RngKind::Std, 2);
+        let mut forked = worst.fork();
+
+        for (index, rolls) in [(0, 12), (3, 40), (1, 40), (2, 10), (4, 25)] {
+            all.record_game(index, &outcome(rolls));
+
+            match index % 2 {
+                0 => worst.record_game(index, &outcome(rolls)),
+                _ => forked.record_game(index, &outcome(rolls)),
+            }
+        }
+
+        worst.merge(forked);
+
+        // The outliers are ordered from the most rolls, and the seeds are the games' own.
+
+        let worst = worst.into_entries();
+
+        assert_eq!(worst.iter().map(|entry| entry.index).collect::<Vec<_>>(), vec![1, 3]);
+        assert_eq!(worst[0].seed, Some(crate::rand::game_seed(42, 1)));
+        assert_eq!(all.into_entries().iter().map(|entry| entry.index).collect::<Vec<_>>(), vec![0, 1, 2, 3, 4]);
+
+        // A game of the counter-based generator has no seed of its own.
+
+        let mut log = SeedLog::all(42, RngKind::Philox);
+        log.record_game(7, &outcome(10));
+
+        let entries = log.into_entries();
+
+        assert_eq!(entries[0].seed, None);
+        assert_eq!(entries[0].to_json(), r#"{"game":7,"seed":null,"rolls":10,"steps":1}"#);
+    }
+}
\ No newline at end of file
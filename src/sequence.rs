@@ -0,0 +1,227 @@
+//! Playing games with pre-generated dice (e.g., the output of a physical dice-rolling machine), rather than an rng.
+//!
+//! A [`DiceSequence`] is read from a text file (the faces separated by commas or whitespace, with one game per line,
+//! as in the dice files that [`trace::parse_dice`] reads), or from a binary file (a byte per face, where a zero byte
+//! ends a game).  The games consume the dice either as one stream, in order ([`SequenceMode::Sequential`]), or each
+//! from its own line ([`SequenceMode::PerGame`]).
+//!
+//! A game is only recorded if all of its dice are there: a game whose dice run out part way is dropped, so that no
+//! outcome ever mixes in dice from anywhere else.
+
+use std::path::Path;
+
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
+use crate::{cancel::CancelToken, error::{Result, TenziError}, metrics::{GameOutcome, MetricSink}, observer::GameView, simulation::{SimulationType, Strategy}, trace, types::Num};
+
+/// The name that the results of a run with pre-generated dice record as their rng.
+pub const SEQUENCE_RNG: &str = "sequence";
+
+/// Dice that were rolled ahead of time, split into the dice of each game.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DiceSequence {
+    faces: Vec<Num>,
+    ends: Vec<usize>,
+}
+
+/// How the games of a run consume a [`DiceSequence`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "kebab-case"))]
+pub enum SequenceMode {
+    /// The games roll every die in order, one game after another (so the games are played serially), until the
+    /// dice run out.
+    #[default]
+    Sequential,
+    /// The game at each index rolls the dice of the game at that index of the sequence (so the games can be played
+    /// in parallel), and a game whose dice run out is dropped.
+    PerGame,
+}
+
+impl DiceSequence {
+    /// Returns a sequence of the given dice of each game.
+    pub fn new(games: impl IntoIterator<Item = Vec<Num>>) -> Self {
+        let mut sequence = Self::default();
+
+        for game in games {
+            sequence.faces.extend(game);
+            sequence.ends.push(sequence.faces.len());
+        }
+
+        sequence
+    }
+
+    /// Parses a sequence from text, where each line with dice is a game (see [`trace::parse_dice`]).
+    pub fn parse_text(text: &str) -> Result<Self> {
+        let games = text.lines().map(trace::parse_dice).filter(|game| !game.as_ref().is_ok_and(Vec::is_empty));
+
+        Ok(Self::new(games.collect::<Result<Vec<_>>>()?))
+    }
+
+    /// Reads a sequence from bytes, where each byte is a face and a zero byte ends a game.
+    pub fn from_bytes(bytes: &[u8]) -> Self {
+        let games = bytes.split(|&byte| byte == 0).filter(|game| !game.is_empty());
+
+        Self::new(games.map(|game| game.iter().map(|&face| face as Num).collect()))
+    }
+
+    /// Reads a sequence from a file: in the binary format if it has a `.bin` extension, and as text otherwise.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+
+        match path.extension().is_some_and(|extension| extension == "bin") {
+            true => Ok(Self::from_bytes(&std::fs::read(path)?)),
+            false => Self::parse_text(&std::fs::read_to_string(path)?),
+        }
+    }
+
+    /// Returns the bytes that the sequence holds on the heap.
+    pub fn heap_bytes(&self) -> usize {
+        self.faces.capacity() * std::mem::size_of::<Num>() + self.ends.capacity() * std::mem::size_of::<usize>()
+    }
+
+    /// Returns every die of the sequence, in order.
+    pub fn faces(&self) -> &[Num] {
+        &self.faces
+    }
+
+    /// Returns the number of games that the sequence is split into.
+    pub fn num_games(&self) -> usize {
+        self.ends.len()
+    }
+
+    /// Returns the dice of the game at the given index.
+    pub fn game(&self, index: usize) -> Option<&[Num]> {
+        let end = *self.ends.get(index)?;
+        let start = index.checked_sub(1).map_or(0, |previous| self.ends[previous]);
+
+        Some(&self.faces[start..end])
+    }
+
+    /// Ensures that every die is a face of a die with the given number of sides.
+    pub fn check(&self, num_sides: Num) -> Result<()> {
+        match self.faces.iter().find(|&&face| face == 0 || face > num_sides) {
+            Some(face) => Err(TenziError::InvalidDice(format!("`{}` is not a face of a {}-sided die", face, num_sides))),
+            None => Ok(()),
+        }
+    }
+
+    /// Plays up to the given number of games of the simulation with the dice, in the given mode, and records each
+    /// into the sink, which is returned.  The games stop early once the dice run out, or the token is cancelled.
+    pub fn play<S: MetricSink>(&self, simulation: &SimulationType, mode: SequenceMode, num_simulations: Num, mut sink: S, cancel: &CancelToken) -> S {
+        match mode {
+            SequenceMode::Sequential => {
+                let mut game = simulation.clone();
+                let mut dice = self.faces();
+
+                for index in 0..num_simulations {
+                    if cancel.is_cancelled() {
+                        break;
+                    }
+
+                    match play_game(game.as_strategy_mut(), &mut dice, &mut sink) {
+                        Some(outcome) => sink.record_game(index, &outcome),
+                        None => break,
+                    }
+                }
+            }
+            SequenceMode::PerGame => {
+                let num_games = num_simulations.min(self.num_games() as Num);
+                let recorded = (0..num_games)
+                    .into_par_iter()
+                    .fold(|| (simulation.clone(), sink.fork()), |(mut game, mut sink), index| {
+                        let mut dice = self.game(index as usize).unwrap();
+
+                        if cancel.is_cancelled() {
+                            return (game, sink);
+                        }
+
+                        if let Some(outcome) = play_game(game.as_strategy_mut(), &mut dice, &mut sink) {
+                            sink.record_game(index, &outcome);
+                        }
+
+                        (game, sink)
+                    })
+                    .map(|(_, sink)| sink)
+                    .reduce(|| sink.fork(), |mut left, right| {
+                        left.merge(right);
+                        left
+                    });
+
+                sink.merge(recorded);
+            }
+        }
+
+        sink
+    }
+}
+
+/// Plays a game from the start with the given dice (consuming the ones it rolls), or returns `None` if they run out
+/// before the game is done.
+fn play_game(game: &mut dyn Strategy, dice: &mut &[Num], sink: &mut impl MetricSink) -> Option<GameOutcome> {
+    game.reset();
+
+    while !game.done() {
+        // Check that the whole roll is available up front, so that the game is never left mid-roll.
+
+        let (roll, rest) = dice.split_at_checked(game.num_to_roll() as usize)?;
+        let mut faces = roll.iter().copied();
+
+        game.step_with(&mut |_| faces.next().unwrap());
+        sink.on_step(&GameView::of(game));
+
+        *dice = rest;
+    }
+
+    Some(GameOutcome { num_rolls: game.num_rolls(), num_steps: game.num_steps() })
+}
+
+// Tests.
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{metrics::Moments, simulation::NaiveSimulation};
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_parse_sequence() {
+        let text = "# rolled by the machine\n1, 2 3\n\n4 # a short game\n";
+        let sequence = DiceSequence::parse_text(text).unwrap();
+
+        assert_eq!(sequence, DiceSequence::new([vec![1, 2, 3], vec![4]]));
+        assert_eq!(sequence.game(1), Some(&[4][..]));
+        assert_eq!(sequence.game(2), None);
+        assert_eq!(DiceSequence::from_bytes(&[1, 2, 3, 0, 4, 0, 0]), sequence);
+
+        assert!(matches!(DiceSequence::parse_text("1 x"), Err(TenziError::InvalidDice(_))));
+        assert!(matches!(sequence.check(3), Err(TenziError::InvalidDice(_))));
+        assert!(sequence.check(4).is_ok());
+    }
+
+    #[test]
+    fn test_play_sequence() {
+        // Two 2-sided dice: a game is over after a pair, and otherwise keeps the 2 and rolls the other die until it
+        // shows a 2.
+
+        let simulation = SimulationType::Naive(NaiveSimulation::new(2, 2));
+        let sequence = DiceSequence::new([vec![1, 1], vec![1, 2, 2, 1], vec![2, 1, 1]]);
+
+        // In order, the games are [1, 1] and [1, 2, 2], and the third runs out of dice part way (after [1, 2, 2, 1, 1]).
+
+        let moments = sequence.play(&simulation, SequenceMode::Sequential, 10, Moments::new(true), &CancelToken::new());
+
+        assert_eq!(moments.num_games(), 2);
+        assert_eq!(moments.rolls_histogram(), Some(&[0, 0, 1, 1][..]));
+
+        // Each game rolls its own dice: the second leaves a die over, and the third runs out of dice part way.
+
+        let moments = sequence.play(&simulation, SequenceMode::PerGame, 10, Moments::new(true), &CancelToken::new());
+
+        assert_eq!(moments.num_games(), 2);
+        assert_eq!(moments.rolls_histogram(), Some(&[0, 0, 1, 1][..]));
+
+        let moments = sequence.play(&simulation, SequenceMode::Sequential, 1, Moments::new(false), &CancelToken::new());
+        assert_eq!(moments.num_games(), 1);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,768 @@
+//! An HTTP server that runs simulation jobs for remote clients (e.g., a team that shares one machine), over a small
+//! REST API of JSON:
+//!
+//! * `GET /strategies` lists the strategies.
+//! * `POST /jobs` submits a job (see [`JobSpec`]), and returns its id.
+//! * `GET /jobs` lists the status of every job, and `GET /jobs/{id}` the status of one (see [`JobStatus`]).
+//! * `GET /jobs/{id}/results` returns the results of a finished job, as a [`RunResults`] per run.
+//! * `DELETE /jobs/{id}` cancels a job (if it is still queued or running), and forgets it.
+//! * `GET /metrics` reports the games, jobs, and workers of the server in the Prometheus text format (see
+//!   [`JobQueue::metrics`]).
+//!
+//! The server also serves a small dashboard at `GET /`, which submits jobs and follows one live, so a run can be
+//! watched by anyone with its URL (e.g., `http://host:8080/#3` for job 3).  The dashboard follows a job over the
+//! WebSocket at `GET /jobs/{id}/stream`, which sends a [`JobSnapshot`] a few times a second: the job's status and
+//! progress, and the histogram of the number of rolls of the run so far, until the job is done (when the last snapshot
+//! carries its results), fails, or is removed.  The histogram comes with a 95% band around each bin (see
+//! [`distribution::estimate_bins`]), so the chart shows how far the run is from settling.
+//!
+//! Jobs wait in a queue of a bounded length, and a fixed number of them run at once, on a thread pool that every job
+//! shares (see [`ServerOptions`]), so the server never takes on more work than it was sized for.  Since the pool is the
+//! server's, a job's configuration cannot choose its own threads.
+//!
+//! A queue can also run its jobs on the workers of a [`Coordinator`] (see [`JobQueue::with_coordinator`]), in which case
+//! the games are played on other machines, and the streams of its jobs have no live histogram.
+
+use std::{collections::{BTreeMap, VecDeque}, sync::{atomic::{AtomicU64, Ordering}, Arc, Condvar, Mutex}, time::Duration};
+
+use rayon::ThreadPool;
+use serde_json::{json, Value};
+
+use crate::{adaptive::{self, AdaptiveOptions}, cancel::CancelToken, cluster::Coordinator, config::MonteCarloBuilder, distribution, error::{Result, TenziError}, metrics::{GameOutcome, MetricSink}, monte_carlo::Backend, progress::{Progress, ProgressHook}, registry::StrategyRegistry, results::RunResults, telemetry::{QueueGauges, ServerMetrics}, types::{Float, Num}, SimulationConfig};
+
+/// The dashboard, which is served at `GET /`.
+const DASHBOARD: &str = include_str!("dashboard.html");
+
+/// How often a stream sends a snapshot of its job.
+pub(crate) const STREAM_INTERVAL: Duration = Duration::from_millis(250);
+
+/// The number of bins of a live histogram, where the last bin also counts every longer game.
+const HISTOGRAM_BINS: usize = 256;
+
+/// The limits of a server.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ServerOptions {
+    /// The number of jobs that run at once.
+    pub max_running: usize,
+    /// The number of jobs that can wait to run, past which submissions are turned away.
+    pub max_queued: usize,
+    /// The number of threads that the jobs share, or `None` for one per logical core.
+    pub num_threads: Option<usize>,
+}
+
+impl Default for ServerOptions {
+    fn default() -> Self {
+        Self { max_running: 1, max_queued: 64, num_threads: None }
+    }
+}
+
+/// A job, as it is submitted: a configuration (in the serialized form of a [`MonteCarloBuilder`], where every field
+/// is optional), and what to run with it.
+#[derive(Clone, Debug, serde::Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum JobSpec {
+    /// Runs the configuration.
+    Simulate {
+        #[serde(default)]
+        config: MonteCarloBuilder,
+    },
+    /// Runs the configuration with each of the strategies (or every strategy, if there are none), into one set of
+    /// results.  With `adaptive` options, the strategies play an adaptive number of games each (see [`adaptive`]),
+    /// rather than the configuration's, on the server's own threads (even if it has a coordinator).
+    Compare {
+        #[serde(default)]
+        config: MonteCarloBuilder,
+        #[serde(default)]
+        strategies: Vec<String>,
+        #[serde(default)]
+        adaptive: Option<AdaptiveOptions>,
+    },
+    /// Runs the configuration with each of the values of a parameter, into a set of results each.
+    Sweep {
+        #[serde(default)]
+        config: MonteCarloBuilder,
+        parameter: SweepParameter,
+        values: Vec<Num>,
+    },
+}
+
+/// A parameter that a [`JobSpec::Sweep`] varies.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SweepParameter {
+    Sides,
+    Dice,
+    Simulations,
+}
+
+/// The status of a job.
+#[derive(Clone, Debug, PartialEq, serde::Serialize)]
+#[serde(tag = "status", rename_all = "lowercase")]
+pub enum JobStatus {
+    /// The job is waiting to run, behind the given number of jobs.
+    Queued { position: usize },
+    /// The job is running the run at the given index (of the given number of runs), with the given progress.
+    Running { run: usize, runs: usize, progress: Progress },
+    /// The job is done, and its results can be fetched.
+    Done,
+    /// The job failed with the given error.
+    Failed { error: String },
+}
+
+/// A snapshot of a job, as its stream sends it.
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct JobSnapshot {
+    pub id: u64,
+    #[serde(flatten)]
+    pub status: JobStatus,
+    /// The number of games that took each number of rolls (up to the longest game) in the run so far, while the job is
+    /// running.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub histogram: Option<Vec<u64>>,
+    /// The 95% interval of each bin (in games, like the bins) of the histogram that the dashboard charts: the live
+    /// histogram while the job is running, or the histogram of rolls of a job that is done with a single strategy.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bands: Option<Vec<[Float; 2]>>,
+    /// The results of the job, once it is done.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub results: Option<Vec<RunResults>>,
+}
+
+/// The jobs of a server, and the workers that run them.
+///
+/// Dropping the queue cancels every job, and stops the workers.
+pub struct JobQueue {
+    shared: Arc<Shared>,
+    pool: Arc<ThreadPool>,
+    registry: StrategyRegistry,
+}
+
+/// The state that the queue shares with its workers.
+struct Shared {
+    inner: Mutex<Inner>,
+    ready: Condvar,
+    max_queued: usize,
+    coordinator: Option<Arc<Coordinator>>,
+    metrics: ServerMetrics,
+}
+
+struct Inner {
+    jobs: BTreeMap<u64, Job>,
+    queue: VecDeque<u64>,
+    next_id: u64,
+    shutdown: bool,
+}
+
+/// The histogram of the number of rolls of a running job, which its workers count into directly (rather than into
+/// forks of their own, like every other sink), so that it can be read while the run goes on.
+#[derive(Clone)]
+struct LiveHistogram {
+    bins: Arc<[AtomicU64]>,
+}
+
+struct Job {
+    runs: Vec<SimulationConfig>,
+    num_runs: usize,
+    combine: Combine,
+    cancel: CancelToken,
+    state: JobState,
+}
+
+enum JobState {
+    Queued,
+    Running { run: usize, progress: Progress, histogram: LiveHistogram },
+    Done(Vec<RunResults>),
+    Failed(String),
+}
+
+/// How the runs of a job are played, and their results combined.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Combine {
+    /// Each run is played, into a set of results each.
+    Separately,
+    /// Each run is played, into one set of results.
+    Compare,
+    /// The runs are compared with an adaptive budget, into one set of results.
+    Adaptive(AdaptiveOptions),
+}
+
+impl JobSpec {
+    /// Validates the job, and returns the configuration of each of its runs (on the given pool), and how they are
+    /// combined.
+    pub(crate) fn runs(self, registry: &StrategyRegistry, pool: &Arc<ThreadPool>) -> Result<(Vec<SimulationConfig>, Combine)> {
+        let (builders, combine) = match self {
+            JobSpec::Simulate { config } => (vec![config], Combine::Separately),
+            JobSpec::Compare { config, strategies, adaptive } => {
+                let strategies = match strategies.is_empty() {
+                    true => registry.plain_names().map(String::from).collect(),
+                    false => strategies,
+                };
+
+                (strategies.into_iter().map(|strategy| config.clone().strategy(strategy)).collect(), adaptive.map_or(Combine::Compare, Combine::Adaptive))
+            }
+            JobSpec::Sweep { config, parameter, values } => {
+                let builders = values.into_iter().map(|value| match parameter {
+                    SweepParameter::Sides => config.clone().sides(value),
+                    SweepParameter::Dice => config.clone().dice(value),
+                    SweepParameter::Simulations => config.clone().simulations(value),
+                });
+
+                (builders.collect(), Combine::Separately)
+            }
+        };
+
+        if builders.is_empty() {
+            return Err(TenziError::InvalidConfig("a job needs at least one run".to_string()));
+        }
+
+        Ok((builders.into_iter().map(|builder| builder.pool(pool.clone()).build()).collect::<Result<Vec<_>>>()?, combine))
+    }
+}
+
+impl JobQueue {
+    /// Returns an empty queue, with its workers waiting for jobs.
+    pub fn new(options: ServerOptions) -> Result<Self> {
+        Self::start(options, None)
+    }
+
+    /// Returns an empty queue like [`JobQueue::new`], but which runs the games of its jobs on the workers of the
+    /// coordinator.
+    pub fn with_coordinator(options: ServerOptions, coordinator: Arc<Coordinator>) -> Result<Self> {
+        Self::start(options, Some(coordinator))
+    }
+
+    fn start(options: ServerOptions, coordinator: Option<Arc<Coordinator>>) -> Result<Self> {
+        if options.max_running == 0 || options.max_queued == 0 || options.num_threads == Some(0) {
+            return Err(TenziError::InvalidConfig("a server needs room for at least one running job, one queued job, and one thread".to_string()));
+        }
+
+        let pool = rayon::ThreadPoolBuilder::new().num_threads(options.num_threads.unwrap_or(0)).build()?;
+
+        let shared = Arc::new(Shared {
+            inner: Mutex::new(Inner { jobs: BTreeMap::new(), queue: VecDeque::new(), next_id: 1, shutdown: false }),
+            ready: Condvar::new(),
+            max_queued: options.max_queued,
+            coordinator,
+            metrics: ServerMetrics::new(options.max_running),
+        });
+
+        for _ in 0..options.max_running {
+            let shared = shared.clone();
+            std::thread::spawn(move || work(&shared));
+        }
+
+        Ok(Self { shared, pool: Arc::new(pool), registry: StrategyRegistry::new() })
+    }
+
+    /// Validates the job, and queues it to run.  Returns the id of the job.
+    ///
+    /// Fails with [`TenziError::Busy`] if the queue is full, or with the error of the first invalid configuration.
+    pub fn submit(&self, spec: JobSpec) -> Result<u64> {
+        let (runs, combine) = spec.runs(&self.registry, &self.pool)?;
+
+        let mut inner = self.shared.inner.lock().unwrap();
+
+        if inner.queue.len() >= self.shared.max_queued {
+            return Err(TenziError::Busy(inner.queue.len() as Num));
+        }
+
+        let id = inner.next_id;
+        inner.next_id += 1;
+        inner.jobs.insert(id, Job { num_runs: runs.len(), runs, combine, cancel: CancelToken::new(), state: JobState::Queued });
+        inner.queue.push_back(id);
+
+        self.shared.ready.notify_one();
+
+        Ok(id)
+    }
+
+    /// Returns the status of the job, or `None` if there is no such job.
+    pub fn status(&self, id: u64) -> Option<JobStatus> {
+        let inner = self.shared.inner.lock().unwrap();
+        let job = inner.jobs.get(&id)?;
+
+        Some(match &job.state {
+            JobState::Queued => JobStatus::Queued { position: inner.queue.iter().position(|&queued| queued == id).unwrap() },
+            JobState::Running { run, progress, .. } => JobStatus::Running { run: *run, runs: job.num_runs, progress: *progress },
+            JobState::Done(_) => JobStatus::Done,
+            JobState::Failed(error) => JobStatus::Failed { error: error.clone() },
+        })
+    }
+
+    /// Returns the names of the strategies that jobs can run.
+    pub fn strategies(&self) -> impl Iterator<Item = &str> {
+        self.registry.names()
+    }
+
+    /// Returns the ids of every job, in the order they were submitted.
+    pub fn ids(&self) -> Vec<u64> {
+        self.shared.inner.lock().unwrap().jobs.keys().copied().collect()
+    }
+
+    /// Returns the results of the job, or `None` if there is no such job, or it is not done.
+    pub fn results(&self, id: u64) -> Option<Vec<RunResults>> {
+        match &self.shared.inner.lock().unwrap().jobs.get(&id)?.state {
+            JobState::Done(results) => Some(results.clone()),
+            _ => None,
+        }
+    }
+
+    /// Returns a snapshot of the job for its stream, or `None` if there is no such job.
+    pub fn snapshot(&self, id: u64) -> Option<JobSnapshot> {
+        let status = self.status(id)?;
+
+        let (histogram, results) = match &self.shared.inner.lock().unwrap().jobs.get(&id)?.state {
+            JobState::Running { histogram, .. } => (Some(histogram.snapshot()), None),
+            JobState::Done(results) => (None, Some(results.clone())),
+            _ => (None, None),
+        };
+
+        let bands = match (&histogram, results.as_deref()) {
+            (Some(histogram), _) => histogram_bands(histogram),
+            (None, Some([run])) => match run.summaries() {
+                [summary] => summary.rolls_histogram().and_then(|histogram| histogram_bands(&histogram.iter().map(|&count| count as u64).collect::<Vec<_>>())),
+                _ => None,
+            },
+            _ => None,
+        };
+
+        Some(JobSnapshot { id, status, histogram, bands, results })
+    }
+
+    /// Cancels the job (if it is still queued or running), and forgets it.  Returns whether there was such a job.
+    pub fn remove(&self, id: u64) -> bool {
+        let mut inner = self.shared.inner.lock().unwrap();
+        inner.queue.retain(|&queued| queued != id);
+
+        match inner.jobs.remove(&id) {
+            Some(job) => {
+                job.cancel.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Returns the metrics of the queue in the Prometheus text format: the games that its jobs played (and their rolls,
+    /// and the time they took) by strategy, the jobs that finished (and how long they ran), and how busy its workers
+    /// are.
+    pub fn metrics(&self) -> String {
+        let num_queued = self.shared.inner.lock().unwrap().queue.len();
+        let idle_workers = self.shared.coordinator.as_ref().map(|coordinator| coordinator.idle_workers());
+
+        self.shared.metrics.render(QueueGauges { num_queued, idle_workers })
+    }
+
+    /// Handles a request of the REST API (see the [module](self) docs), and returns the status code and the JSON body
+    /// of the response.
+    pub fn handle(&self, method: &str, url: &str, body: &str) -> (u16, Value) {
+        let path = url.split('?').next().unwrap();
+        let segments = path.split('/').filter(|segment| !segment.is_empty()).collect::<Vec<_>>();
+        let id = segments.get(1).and_then(|id| id.parse::<u64>().ok());
+
+        match (method, &segments[..], id) {
+            ("GET", ["strategies"], _) => (200, json!(self.strategies().collect::<Vec<_>>())),
+            ("POST", ["jobs"], _) => match serde_json::from_str::<JobSpec>(body).map_err(|e| TenziError::InvalidConfig(e.to_string())).and_then(|spec| self.submit(spec)) {
+                Ok(id) => (202, json!({ "id": id })),
+                Err(e @ TenziError::Busy(_)) => (429, error(e)),
+                Err(e) => (400, error(e)),
+            },
+            ("GET", ["jobs"], _) => (200, Value::Array(self.ids().into_iter().filter_map(|id| self.status_json(id)).collect())),
+            ("GET", ["jobs", _], Some(id)) => match self.status_json(id) {
+                Some(status) => (200, status),
+                None => not_found(),
+            },
+            ("GET", ["jobs", _, "results"], Some(id)) => match (self.results(id), self.status(id)) {
+                (Some(results), _) => (200, serde_json::to_value(results).unwrap()),
+                (None, Some(status)) => (409, json!({ "error": "the job is not done", "job": status })),
+                (None, None) => not_found(),
+            },
+            ("DELETE", ["jobs", _], Some(id)) => match self.remove(id) {
+                true => (200, json!({ "id": id })),
+                false => not_found(),
+            },
+            _ => not_found(),
+        }
+    }
+
+    /// Returns the status of the job as JSON, along with its id.
+    fn status_json(&self, id: u64) -> Option<Value> {
+        let mut status = serde_json::to_value(self.status(id)?).unwrap();
+        status["id"] = json!(id);
+
+        Some(status)
+    }
+}
+
+impl Drop for JobQueue {
+    fn drop(&mut self) {
+        let mut inner = self.shared.inner.lock().unwrap();
+        inner.shutdown = true;
+        inner.jobs.values().for_each(|job| job.cancel.cancel());
+
+        self.shared.ready.notify_all();
+    }
+}
+
+impl Shared {
+    /// Sets the state of the job, unless it has been removed.
+    fn update(&self, id: u64, state: JobState) {
+        if let Some(job) = self.inner.lock().unwrap().jobs.get_mut(&id) {
+            job.state = state;
+        }
+    }
+}
+
+impl LiveHistogram {
+    fn new() -> Self {
+        Self { bins: (0..HISTOGRAM_BINS).map(|_| AtomicU64::new(0)).collect() }
+    }
+
+    /// Returns the number of games that took each number of rolls so far, up to the longest game.
+    fn snapshot(&self) -> Vec<u64> {
+        let mut counts = self.bins.iter().map(|bin| bin.load(Ordering::Relaxed)).collect::<Vec<_>>();
+
+        while counts.last() == Some(&0) {
+            counts.pop();
+        }
+
+        counts
+    }
+}
+
+/// Returns the 95% interval of each bin of the histogram, in games, or `None` if it has no games.
+fn histogram_bands(counts: &[u64]) -> Option<Vec<[Float; 2]>> {
+    let num_games = counts.iter().sum::<u64>() as Float;
+
+    Some(distribution::estimate_bins(counts, 0.95).ok()?.iter().map(|estimate| [estimate.low * num_games, estimate.high * num_games]).collect())
+}
+
+/// Every fork shares the same bins, so there is nothing to merge.
+impl MetricSink for LiveHistogram {
+    fn fork(&self) -> Self {
+        self.clone()
+    }
+
+    fn record(&mut self, outcome: &GameOutcome) {
+        self.bins[(outcome.num_rolls as usize).min(HISTOGRAM_BINS - 1)].fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn merge(&mut self, _other: Self) {}
+
+    fn heap_bytes(&self) -> usize {
+        self.bins.len() * std::mem::size_of::<AtomicU64>()
+    }
+}
+
+/// Serves the REST API and the dashboard on the given address (e.g., "127.0.0.1:8080") until the process exits.
+pub fn serve(address: &str, options: ServerOptions) -> Result<()> {
+    serve_jobs(address, Arc::new(JobQueue::new(options)?))
+}
+
+/// Serves the REST API and the dashboard like [`serve`], but runs the jobs on the given queue (e.g., one that is
+/// shared with the gRPC service).
+pub fn serve_jobs(address: &str, jobs: Arc<JobQueue>) -> Result<()> {
+    let server = tiny_http::Server::http(address).map_err(|e| TenziError::Io(std::io::Error::other(e)))?;
+    let content_type = tiny_http::Header::from_bytes("Content-Type", "application/json").unwrap();
+
+    for mut request in server.incoming_requests() {
+        let path = request.url().split('?').next().unwrap().to_string();
+        let segments = path.split('/').filter(|segment| !segment.is_empty()).collect::<Vec<_>>();
+
+        if request.method() == &tiny_http::Method::Get {
+            if segments.is_empty() {
+                let html = tiny_http::Header::from_bytes("Content-Type", "text/html; charset=utf-8").unwrap();
+                let _ = request.respond(tiny_http::Response::from_string(DASHBOARD).with_header(html));
+                continue;
+            }
+
+            if segments == ["metrics"] {
+                let text = tiny_http::Header::from_bytes("Content-Type", "text/plain; version=0.0.4").unwrap();
+                let _ = request.respond(tiny_http::Response::from_string(jobs.metrics()).with_header(text));
+                continue;
+            }
+
+            if let (["jobs", id, "stream"], Some(key)) = (&segments[..], websocket_key(&request)) {
+                if let Ok(id) = id.parse::<u64>() {
+                    let accept = tiny_http::Header::from_bytes("Sec-WebSocket-Accept", tungstenite::handshake::derive_accept_key(key.as_bytes())).unwrap();
+                    let socket = request.upgrade("websocket", tiny_http::Response::empty(101).with_header(accept));
+                    let jobs = jobs.clone();
+
+                    std::thread::spawn(move || stream(&jobs, id, tungstenite::WebSocket::from_raw_socket(socket, tungstenite::protocol::Role::Server, None)));
+                    continue;
+                }
+            }
+        }
+
+        let mut body = String::new();
+
+        let (status, body) = match request.as_reader().read_to_string(&mut body) {
+            Ok(_) => jobs.handle(request.method().as_str(), request.url(), &body),
+            Err(e) => (400, error(e)),
+        };
+
+        // A client that hangs up before the response is no concern of the server's.
+
+        let response = tiny_http::Response::from_string(body.to_string()).with_status_code(status).with_header(content_type.clone());
+        let _ = request.respond(response);
+    }
+
+    Ok(())
+}
+
+/// Returns the key of a WebSocket handshake, or `None` if the request is not one.
+fn websocket_key(request: &tiny_http::Request) -> Option<String> {
+    let header = |name: &'static str| request.headers().iter().find(|header| header.field.equiv(name)).map(|header| header.value.to_string());
+
+    header("Upgrade").filter(|upgrade| upgrade.eq_ignore_ascii_case("websocket")).and(header("Sec-WebSocket-Key"))
+}
+
+/// Sends snapshots of the job over the socket until the job is over (or the client hangs up).
+fn stream<S: std::io::Read + std::io::Write>(jobs: &JobQueue, id: u64, mut socket: tungstenite::WebSocket<S>) {
+    loop {
+        let snapshot = jobs.snapshot(id);
+        let over = snapshot.as_ref().is_none_or(|snapshot| matches!(snapshot.status, JobStatus::Done | JobStatus::Failed { .. }));
+        let message = snapshot.map_or_else(|| error("not found"), |snapshot| serde_json::to_value(snapshot).unwrap());
+
+        if socket.send(tungstenite::Message::Text(message.to_string())).is_err() {
+            return;
+        }
+
+        if over {
+            let _ = socket.close(None);
+            let _ = socket.flush();
+            return;
+        }
+
+        std::thread::sleep(STREAM_INTERVAL);
+    }
+}
+
+/// Runs jobs from the queue until it is dropped.
+fn work(shared: &Shared) {
+    loop {
+        let (id, runs, combine, cancel) = {
+            let mut inner = shared.inner.lock().unwrap();
+
+            loop {
+                if inner.shutdown {
+                    return;
+                }
+
+                if let Some(id) = inner.queue.pop_front() {
+                    let job = inner.jobs.get_mut(&id).unwrap();
+                    job.state = JobState::Running { run: 0, progress: Progress::default(), histogram: LiveHistogram::new() };
+
+                    break (id, std::mem::take(&mut job.runs), job.combine, job.cancel.clone());
+                }
+
+                inner = shared.ready.wait(inner).unwrap();
+            }
+        };
+
+        shared.metrics.job_started(id);
+
+        let (state, outcome) = match run_job(shared, id, &runs, combine, &cancel) {
+            Ok(results) => (JobState::Done(results), "done"),
+            Err(e @ TenziError::Cancelled) => (JobState::Failed(e.to_string()), "cancelled"),
+            Err(e) => (JobState::Failed(e.to_string()), "failed"),
+        };
+
+        shared.metrics.job_finished(id, outcome);
+
+        shared.update(id, state);
+    }
+}
+
+/// Runs every run of a job, and returns their results (combined into one, for a comparison).
+fn run_job(shared: &Shared, id: u64, runs: &[SimulationConfig], combine: Combine, cancel: &CancelToken) -> Result<Vec<RunResults>> {
+    if let Combine::Adaptive(options) = combine {
+        let results = adaptive::compare(runs, options, cancel)?.into_results();
+        shared.metrics.record_run(&results);
+
+        return Ok(vec![results]);
+    }
+
+    let mut results = Vec::with_capacity(runs.len());
+
+    for (run, config) in runs.iter().enumerate() {
+        let histogram = LiveHistogram::new();
+        shared.update(id, JobState::Running { run, progress: Progress::default(), histogram: histogram.clone() });
+
+        let hook = ProgressHook::new(|progress: &Progress| shared.update(id, JobState::Running { run, progress: *progress, histogram: histogram.clone() }));
+
+        // The GPU backend does not hand back its games, so its runs are followed without a histogram (as are the runs of
+        // a coordinator, whose games are played elsewhere).
+
+        let run = match (&shared.coordinator, config.backend()) {
+            (Some(coordinator), _) => coordinator.run(config, cancel, hook)?,
+            (None, Backend::Gpu) => config.run_cancellable(cancel, hook)?,
+            (None, _) => config.run_into(histogram.clone(), cancel, hook)?.0,
+        };
+
+        shared.metrics.record_run(&run);
+
+        if !run.is_complete() {
+            return Err(TenziError::Cancelled);
+        }
+
+        results.push(run);
+    }
+
+    Ok(combine_results(results, combine))
+}
+
+/// Combines the results of the runs of a comparison into one set of results, and returns those of any other job as
+/// they are.
+pub(crate) fn combine_results(results: Vec<RunResults>, combine: Combine) -> Vec<RunResults> {
+    match combine != Combine::Separately {
+        true => {
+            let parameters = results[0].parameters().clone();
+            vec![RunResults::new(parameters, results.into_iter().flat_map(RunResults::into_summaries).collect())]
+        }
+        false => results,
+    }
+}
+
+/// Returns the body of an error response.
+fn error(error: impl ToString) -> Value {
+    json!({ "error": error.to_string() })
+}
+
+/// Returns the response for a job or route that does not exist.
+fn not_found() -> (u16, Value) {
+    (404, error("not found"))
+}
+
+// Tests.
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    /// Polls the job until it is no longer queued or running.
+    fn wait(jobs: &JobQueue, id: u64) -> JobStatus {
+        loop {
+            match jobs.status(id).unwrap() {
+                JobStatus::Queued { .. } | JobStatus::Running { .. } => std::thread::sleep(std::time::Duration::from_millis(5)),
+                status => return status,
+            }
+        }
+    }
+
+    #[test]
+    fn test_jobs() {
+        let jobs = JobQueue::new(ServerOptions { max_running: 2, num_threads: Some(2), ..ServerOptions::default() }).unwrap();
+
+        let (status, body) = jobs.handle("POST", "/jobs", r#"{ "kind": "compare", "config": { "num_simulations": 100 }, "strategies": ["naive", "merge"] }"#);
+        assert_eq!(status, 202);
+
+        let compare = body["id"].as_u64().unwrap();
+
+        let (_, body) = jobs.handle("POST", "/jobs/", r#"{ "kind": "sweep", "config": { "num_simulations": 100, "seed": 42 }, "parameter": "dice", "values": [2, 3, 4] }"#);
+        let sweep = body["id"].as_u64().unwrap();
+
+        assert_eq!(wait(&jobs, compare), JobStatus::Done);
+        assert_eq!(wait(&jobs, sweep), JobStatus::Done);
+
+        // A comparison is one set of results, and a sweep is a set per value.
+
+        let (status, body) = jobs.handle("GET", &format!("/jobs/{}/results", compare), "");
+
+        assert_eq!(status, 200);
+        assert_eq!(body[0]["summaries"].as_array().unwrap().len(), 2);
+
+        let results = jobs.results(sweep).unwrap();
+
+        assert_eq!(results.iter().map(|results| results.parameters().num_dice).collect::<Vec<_>>(), vec![2, 3, 4]);
+        assert_eq!(jobs.handle("GET", &format!("/jobs/{}", sweep), "").1, json!({ "id": sweep, "status": "done" }));
+        assert_eq!(jobs.handle("GET", "/jobs", "").1.as_array().unwrap().len(), 2);
+
+        assert_eq!(jobs.handle("DELETE", &format!("/jobs/{}", sweep), "").0, 200);
+        assert_eq!(jobs.handle("GET", &format!("/jobs/{}", sweep), "").0, 404);
+        assert_eq!(jobs.handle("GET", "/strategies", "").1[0], "naive");
+        assert!(jobs.metrics().contains("tenzi_simulations_total{strategy=\"merge\"} 100\n"));
+        assert!(jobs.metrics().contains("tenzi_jobs_total{outcome=\"done\"} 2\n"));
+    }
+
+    #[test]
+    fn test_snapshots() {
+        let jobs = JobQueue::new(ServerOptions { max_running: 1, num_threads: Some(2), ..ServerOptions::default() }).unwrap();
+        let id = jobs.handle("POST", "/jobs", r#"{ "kind": "simulate", "config": { "num_simulations": 1000000000 } }"#).1["id"].as_u64().unwrap();
+
+        // While the job runs, its snapshots count its games into the histogram as they finish.
+
+        let (histogram, bands) = loop {
+            match jobs.snapshot(id).unwrap() {
+                JobSnapshot { status: JobStatus::Running { .. }, histogram: Some(histogram), bands: Some(bands), .. } if !histogram.is_empty() => break (histogram, bands),
+                _ => std::thread::sleep(std::time::Duration::from_millis(5)),
+            }
+        };
+
+        // Each bin comes with a band around its count.
+
+        assert!(histogram.iter().sum::<u64>() > 0);
+        assert_eq!(bands.len(), histogram.len());
+        assert!(histogram.iter().zip(&bands).all(|(&count, &[low, high])| low <= count as Float + 1e-3 && count as Float - 1e-3 <= high));
+        assert!(jobs.remove(id));
+        assert!(jobs.snapshot(id).is_none());
+
+        // Once it is done, its snapshot carries its results.
+
+        let id = jobs.submit(JobSpec::Simulate { config: MonteCarloBuilder::default().simulations(100) }).unwrap();
+        assert_eq!(wait(&jobs, id), JobStatus::Done);
+
+        let snapshot = serde_json::to_value(jobs.snapshot(id).unwrap()).unwrap();
+
+        assert_eq!(snapshot["status"], "done");
+        assert_eq!(snapshot["results"][0]["summaries"][0]["num_simulations"], 100);
+        assert!(DASHBOARD.contains("/stream"));
+    }
+
+    #[test]
+    fn test_jobs_distributed() {
+        let coordinator = Arc::new(Coordinator::bind("127.0.0.1:0", 250).unwrap());
+        let address = coordinator.local_addr();
+
+        std::thread::spawn(move || crate::cluster::work(address, Some(1)));
+
+        let jobs = JobQueue::with_coordinator(ServerOptions { num_threads: Some(1), ..ServerOptions::default() }, coordinator).unwrap();
+        let id = jobs.handle("POST", "/jobs", r#"{ "kind": "simulate", "config": { "num_simulations": 1000, "seed": 42 } }"#).1["id"].as_u64().unwrap();
+
+        assert_eq!(wait(&jobs, id), JobStatus::Done);
+
+        let local = SimulationConfig::builder().simulations(1_000).seed(42).build().unwrap().run().unwrap();
+        assert_eq!(jobs.results(id).unwrap()[0].summaries()[0].average_rolls(), local.summaries()[0].average_rolls());
+    }
+
+    #[test]
+    fn test_jobs_rejected() {
+        let jobs = JobQueue::new(ServerOptions { max_running: 1, max_queued: 1, num_threads: Some(1) }).unwrap();
+
+        assert_eq!(jobs.handle("POST", "/jobs", "{").0, 400);
+        assert_eq!(jobs.handle("POST", "/jobs", r#"{ "kind": "simulate", "config": { "num_dice": 0 } }"#).0, 400);
+        assert_eq!(jobs.handle("POST", "/jobs", r#"{ "kind": "simulate", "config": { "num_threads": 4 } }"#).0, 400);
+        assert_eq!(jobs.handle("POST", "/jobs", r#"{ "kind": "sweep", "parameter": "sides", "values": [] }"#).0, 400);
+        assert_eq!(jobs.handle("GET", "/jobs/7", "").0, 404);
+        assert_eq!(jobs.handle("PUT", "/jobs", "").0, 404);
+
+        // Once the only worker is busy, and the queue is full, jobs are turned away until there is room.
+
+        let long = r#"{ "kind": "simulate", "config": { "num_simulations": 1000000000 } }"#;
+        let running = jobs.handle("POST", "/jobs", long).1["id"].as_u64().unwrap();
+
+        while !matches!(jobs.status(running), Some(JobStatus::Running { .. })) {
+            std::thread::yield_now();
+        }
+
+        let queued = jobs.handle("POST", "/jobs", long).1["id"].as_u64().unwrap();
+
+        assert_eq!(jobs.status(queued), Some(JobStatus::Queued { position: 0 }));
+        assert_eq!(jobs.handle("GET", &format!("/jobs/{}/results", queued), "").0, 409);
+        assert_eq!(jobs.handle("POST", "/jobs", long).0, 429);
+
+        assert!(jobs.remove(running));
+        assert!(jobs.remove(queued));
+    }
+}
\ No newline at end of file
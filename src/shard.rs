@@ -0,0 +1,300 @@
+//! Sharding a run over independent processes (e.g., the tasks of a Slurm or Kubernetes array job), with no networking.
+//!
+//! A [`Shard`] is one of a number of contiguous slices of the games of a run, so every process that is handed the same
+//! configuration and its own shard index plays its own games.  A seeded run rolls every game from the same seed as the
+//! whole run does, so its shards play exactly the games of the whole run.  Each shard writes its [`PartialResults`]
+//! (see [`SimulationConfig::run_shard`](crate::SimulationConfig::run_shard)), which [`merge`] into the results of the
+//! whole run.
+//!
+//! Partial results are stored as text: a header line, followed by a line per field of the form "name value", where a
+//! list is separated by commas, and a missing value is `-`.
+
+use std::{fmt::Write as _, path::Path, time::Duration};
+
+use crate::{error::{Result, TenziError}, metrics::{MetricSink, Moments}, results::{RunParameters, RunResults}, rules::RuleSet, types::{Num, Total}};
+
+/// The header that every partial results file starts with.
+const HEADER: &str = "tenzi-partial";
+
+/// One of a number of contiguous slices of the games of a run.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Shard {
+    index: Num,
+    count: Num,
+}
+
+/// The moments of the games of a shard, along with the run that they are a part of.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PartialResults {
+    shard: Shard,
+    parameters: RunParameters,
+    strategy: String,
+    seed: Option<u64>,
+    moments: Moments,
+    duration: Duration,
+}
+
+impl Shard {
+    /// Returns the shard at the given index (from 0) of the given number of shards, or fails with
+    /// [`TenziError::InvalidConfig`] if there is no such shard.
+    pub fn new(index: Num, count: Num) -> Result<Self> {
+        if index >= count {
+            return Err(TenziError::InvalidConfig(format!("there is no shard {} of {} shards", index, count)));
+        }
+
+        Ok(Self { index, count })
+    }
+
+    /// Returns the index of the shard.
+    pub fn index(&self) -> Num {
+        self.index
+    }
+
+    /// Returns the number of shards.
+    pub fn count(&self) -> Num {
+        self.count
+    }
+
+    /// Returns the index of the first game of the shard, and its number of games, in a run of the given number of
+    /// games, which are split as evenly as possible.
+    pub fn range(&self, num_simulations: Num) -> (Num, Num) {
+        let bound = |index: Num| (num_simulations as u128 * index as u128 / self.count as u128) as Num;
+        let first = bound(self.index);
+
+        (first, bound(self.index + 1) - first)
+    }
+}
+
+impl PartialResults {
+    pub(crate) fn new(shard: Shard, parameters: RunParameters, strategy: &str, seed: Option<u64>, moments: Moments, duration: Duration) -> Self {
+        Self { shard, parameters, strategy: strategy.to_string(), seed, moments, duration }
+    }
+
+    /// Returns the shard that the results are of.
+    pub fn shard(&self) -> Shard {
+        self.shard
+    }
+
+    /// Returns the parameters of the whole run.
+    pub fn parameters(&self) -> &RunParameters {
+        &self.parameters
+    }
+
+    /// Returns the strategy spec of the run.
+    pub fn strategy(&self) -> &str {
+        &self.strategy
+    }
+
+    /// Returns the seed of the run, if it is seeded.
+    pub fn seed(&self) -> Option<u64> {
+        self.seed
+    }
+
+    /// Returns the number of games of the shard.
+    pub fn num_games(&self) -> Num {
+        self.moments.num_games()
+    }
+
+    /// Serializes the partial results to their text format (see the [module](self) docs).
+    pub fn to_text(&self) -> String {
+        let mut text = format!("{}\n", HEADER);
+        let parameters = &self.parameters;
+
+        writeln!(text, "shard {} {}", self.shard.index, self.shard.count).unwrap();
+        writeln!(text, "sides {}", parameters.num_sides).unwrap();
+        writeln!(text, "dice {}", parameters.num_dice).unwrap();
+        writeln!(text, "simulations {}", parameters.num_simulations).unwrap();
+        writeln!(text, "initial-state {}", list(parameters.initial_state.as_deref())).unwrap();
+        writeln!(text, "rng {}", parameters.rng).unwrap();
+        writeln!(text, "rules {}", rules(&parameters.rules)).unwrap();
+        writeln!(text, "strategy {}", self.strategy).unwrap();
+        writeln!(text, "seed {}", self.seed.map_or("-".to_string(), |seed| seed.to_string())).unwrap();
+        writeln!(text, "duration {}", self.duration.as_secs_f64()).unwrap();
+        writeln!(text, "sums {}", list(Some(&self.moments.sums()))).unwrap();
+        writeln!(text, "histogram {}", list(self.moments.rolls_histogram())).unwrap();
+
+        text
+    }
+
+    /// Parses partial results from their text format.
+    pub fn from_text(text: &str) -> Result<Self> {
+        let mut lines = text.lines().map(str::trim).filter(|line| !line.is_empty());
+
+        if lines.next() != Some(HEADER) {
+            return Err(invalid("the header is malformed"));
+        }
+
+        let mut field = |name: &str| {
+            let line = lines.next().ok_or_else(|| invalid(format!("the field `{}` is missing", name)))?;
+
+            match line.split_once(' ') {
+                Some((field, value)) if field == name => Ok(value.trim()),
+                _ => Err(invalid(format!("the line `{}` is not the field `{}`", line, name))),
+            }
+        };
+
+        let shard = match field("shard")?.split_once(' ') {
+            Some((index, count)) => Shard::new(parse(index)?, parse(count)?).map_err(|e| invalid(e.to_string()))?,
+            None => return Err(invalid("the shard is not of the form \"index count\"")),
+        };
+
+        let parameters = RunParameters {
+            num_sides: parse(field("sides")?)?,
+            num_dice: parse(field("dice")?)?,
+            num_simulations: parse(field("simulations")?)?,
+            initial_state: parse_list(field("initial-state")?)?,
+            rng: field("rng")?.to_string(),
+            rules: match field("rules")? {
+                "-" => RuleSet::default(),
+                rules => rules.parse().map_err(|e: TenziError| invalid(e.to_string()))?,
+            },
+        };
+
+        let strategy = field("strategy")?.to_string();
+
+        let seed = match field("seed")? {
+            "-" => None,
+            seed => Some(parse(seed)?),
+        };
+
+        let duration = Duration::try_from_secs_f64(parse(field("duration")?)?).map_err(|e| invalid(e.to_string()))?;
+
+        let sums = parse_list(field("sums")?)?.and_then(|sums| <[Total; 5]>::try_from(sums).ok()).ok_or_else(|| invalid("the sums are not five numbers"))?;
+        let moments = Moments::from_sums(sums, parse_list(field("histogram")?)?).ok_or_else(|| invalid("the shard has more games than this build counts"))?;
+
+        Ok(Self { shard, parameters, strategy, seed, moments, duration })
+    }
+
+    /// Writes the partial results to a file.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        Ok(std::fs::write(path, self.to_text())?)
+    }
+
+    /// Reads partial results from a file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        Self::from_text(&std::fs::read_to_string(path)?)
+    }
+}
+
+/// Merges the partial results of every shard of a run into the results of the whole run, whose duration is the total
+/// time that the shards took.
+///
+/// Fails with [`TenziError::InvalidPartial`] if the partial results are of different runs, or if a shard is missing or
+/// repeated.
+pub fn merge(partials: impl IntoIterator<Item = PartialResults>) -> Result<RunResults> {
+    let mut partials = partials.into_iter().collect::<Vec<_>>();
+    partials.sort_by_key(|partial| partial.shard.index);
+
+    let Some(first) = partials.first() else {
+        return Err(invalid("there are no partial results to merge"));
+    };
+
+    let run = |partial: &PartialResults| (partial.shard.count, partial.parameters.clone(), partial.strategy.clone(), partial.seed, partial.moments.rolls_histogram().is_some());
+
+    if let Some(other) = partials.iter().find(|partial| run(partial) != run(first)) {
+        return Err(invalid(format!("shard {} is of a different run than shard {}", other.shard.index, first.shard.index)));
+    }
+
+    if let Some(index) = (0..first.shard.count).find(|&index| partials.iter().filter(|partial| partial.shard.index == index).count() != 1) {
+        return Err(invalid(format!("shard {} of {} is missing or repeated", index, first.shard.count)));
+    }
+
+    let (parameters, strategy) = (first.parameters.clone(), first.strategy.clone());
+    let duration = partials.iter().map(|partial| partial.duration).sum();
+
+    let mut partials = partials.into_iter();
+    let mut moments = partials.next().unwrap().moments;
+    partials.for_each(|partial| moments.merge(partial.moments));
+
+    if moments.num_games() != parameters.num_simulations {
+        return Err(invalid(format!("the shards have {} of the {} games of the run", moments.num_games(), parameters.num_simulations)));
+    }
+
+    Ok(RunResults::new(parameters, vec![moments.summarize(&strategy, duration)]))
+}
+
+/// Formats a list of numbers as the value of a field.
+fn list<T: ToString>(numbers: Option<&[T]>) -> String {
+    match numbers {
+        Some(numbers) => numbers.iter().map(T::to_string).collect::<Vec<_>>().join(","),
+        None => "-".to_string(),
+    }
+}
+
+/// Formats house rules as the value of a field.
+fn rules(rules: &RuleSet) -> String {
+    match rules.is_plain() {
+        true => "-".to_string(),
+        false => rules.to_string(),
+    }
+}
+
+/// Parses the value of a list field.
+fn parse_list<T: std::str::FromStr>(value: &str) -> Result<Option<Vec<T>>> {
+    match value {
+        "-" => Ok(None),
+        "" => Ok(Some(Vec::new())),
+        value => value.split(',').map(|number| parse(number.trim())).collect::<Result<Vec<_>>>().map(Some),
+    }
+}
+
+/// Parses a number from a field.
+fn parse<T: std::str::FromStr>(s: &str) -> Result<T> {
+    s.parse().map_err(|_| invalid(format!("`{}` is not a number", s)))
+}
+
+/// Builds an invalid partial results error.
+fn invalid(reason: impl Into<String>) -> TenziError {
+    TenziError::InvalidPartial(reason.into())
+}
+
+// Tests.
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CancelToken, ProgressHook, SimulationConfig};
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_shard_ranges() {
+        let ranges = (0..3).map(|index| Shard::new(index, 3).unwrap().range(10)).collect::<Vec<_>>();
+
+        assert_eq!(ranges, vec![(0, 3), (3, 3), (6, 4)]);
+        assert_eq!(Shard::new(1, 4).unwrap().range(2), (0, 1));
+        assert!(matches!(Shard::new(3, 3), Err(TenziError::InvalidConfig(_))));
+        assert!(matches!(Shard::new(0, 0), Err(TenziError::InvalidConfig(_))));
+    }
+
+    #[test]
+    fn test_merge_shards() {
+        let config = SimulationConfig::builder().strategy("divide").simulations(1_000).seed(42).histogram(true).build().unwrap();
+        let partials = (0..3).map(|index| config.run_shard(Shard::new(index, 3).unwrap(), &CancelToken::new(), ProgressHook::none()).unwrap()).collect::<Vec<_>>();
+
+        assert_eq!(partials.iter().map(PartialResults::num_games).collect::<Vec<_>>(), vec![333, 333, 334]);
+        assert_eq!(PartialResults::from_text(&partials[1].to_text()).unwrap(), partials[1]);
+
+        // The shards of a seeded run merge into exactly the results of the whole run, in any order.
+
+        let (merged, local) = (merge(partials.iter().rev().cloned()).unwrap(), config.run().unwrap());
+        let (merged, local) = (&merged.summaries()[0], &local.summaries()[0]);
+
+        assert_eq!(merged.strategy(), "divide");
+        assert_eq!(merged.num_simulations(), 1_000);
+        assert_eq!(merged.average_rolls(), local.average_rolls());
+        assert_eq!(merged.std_dev_steps(), local.std_dev_steps());
+        assert_eq!(merged.rolls_histogram(), local.rolls_histogram());
+
+        // Missing, repeated, and foreign shards do not merge.
+
+        let other = SimulationConfig::builder().strategy("divide").simulations(1_000).seed(7).histogram(true).build().unwrap();
+        let foreign = other.run_shard(Shard::new(2, 3).unwrap(), &CancelToken::new(), ProgressHook::none()).unwrap();
+
+        assert!(matches!(merge(partials[..2].to_vec()), Err(TenziError::InvalidPartial(_))));
+        assert!(matches!(merge([partials[0].clone(), partials[1].clone(), partials[1].clone()]), Err(TenziError::InvalidPartial(_))));
+        assert!(matches!(merge([partials[0].clone(), partials[1].clone(), foreign]), Err(TenziError::InvalidPartial(_))));
+        assert!(matches!(merge([]), Err(TenziError::InvalidPartial(_))));
+        assert!(matches!(PartialResults::from_text("tenzi-partial\nshard 3 3"), Err(TenziError::InvalidPartial(_))));
+        assert!(matches!(PartialResults::from_text("tenzi-corpus"), Err(TenziError::InvalidPartial(_))));
+    }
+}
\ No newline at end of file
@@ -1,413 +1,2171 @@
-use crate::{mode, rand::roll, types::Num};
+use alloc::{boxed::Box, format, string::{String, ToString}, vec, vec::Vec};
+
+use smallvec::{smallvec, SmallVec};
+
+use crate::{dice::{self, DiceRng, Roll, RollFn}, error::{Result, TenziError}, histogram::{self, Analysis, FaceMask, Sparse}, observer::{GameView, Observer}, rules::RuleSet, scratch::{Scratch, Span}, types::Num};
 
 // Primary enum.
 
-#[derive(Clone)]
-pub enum SimulationType {
-    Naive(NaiveSimulation),
-    Divide(DivideSimulation),
-    Merge(MergeSimulation),
-}
+/// The names of the available strategies.
+pub const STRATEGIES: [&str; 3] = ["naive", "divide", "merge"];
+
+/// The kinds of strategies available.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum StrategyKind {
+    Naive,
+    Divide,
+    Merge,
+}
+
+impl StrategyKind {
+    /// All of the kinds of strategies.
+    pub const ALL: [StrategyKind; 3] = [StrategyKind::Naive, StrategyKind::Divide, StrategyKind::Merge];
+
+    /// Returns the name of the strategy.
+    pub fn name(&self) -> &'static str {
+        match self {
+            StrategyKind::Naive => "naive",
+            StrategyKind::Divide => "divide",
+            StrategyKind::Merge => "merge",
+        }
+    }
+}
+
+impl StrategyKind {
+    /// Returns the metadata of the strategy.
+    pub fn meta(&self) -> &'static dyn StrategyMeta {
+        match self {
+            StrategyKind::Naive => &NaivePolicy { mode: None },
+            StrategyKind::Divide => &DividePolicy,
+            StrategyKind::Merge => &MergePolicy,
+        }
+    }
+}
+
+impl core::fmt::Display for StrategyKind {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl core::str::FromStr for StrategyKind {
+    type Err = TenziError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "naive" => Ok(StrategyKind::Naive),
+            "divide" => Ok(StrategyKind::Divide),
+            "merge" => Ok(StrategyKind::Merge),
+            _ => Err(TenziError::UnknownStrategy(s.to_string())),
+        }
+    }
+}
+
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SimulationType {
+    Naive(NaiveSimulation),
+    Divide(DivideSimulation),
+    Merge(MergeSimulation),
+    /// Externally defined policies cannot be serialized.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    Custom(CustomSimulation),
+    /// A built-in strategy with fixed-size buckets (see [`SimulationType::fast`]).
+    #[cfg_attr(feature = "serde", serde(skip))]
+    Fixed(StrategyKind, Box<dyn StrategyClone>),
+}
+
+impl SimulationType {
+    /// Builds the simulation for the given kind of strategy.
+    pub fn new(kind: StrategyKind, num_sides: Num, num_dice: Num) -> Self {
+        match kind {
+            StrategyKind::Naive => SimulationType::Naive(NaiveSimulation::new(num_sides, num_dice)),
+            StrategyKind::Divide => SimulationType::Divide(DivideSimulation::new(num_sides, num_dice)),
+            StrategyKind::Merge => SimulationType::Merge(MergeSimulation::new(num_sides, num_dice)),
+        }
+    }
+
+    /// Builds the simulation for the given kind of strategy, with fixed-size buckets if the number of sides is one of
+    /// [`FIXED_SIDES`], and with the usual heap-allocated buckets otherwise.
+    pub fn fast(kind: StrategyKind, num_sides: Num, num_dice: Num) -> Self {
+        match num_sides {
+            6 => Self::fixed::<6>(kind, num_dice),
+            8 => Self::fixed::<8>(kind, num_dice),
+            10 => Self::fixed::<10>(kind, num_dice),
+            12 => Self::fixed::<12>(kind, num_dice),
+            20 => Self::fixed::<20>(kind, num_dice),
+            _ => Self::new(kind, num_sides, num_dice),
+        }
+    }
+
+    /// Builds the simulation for the given kind of strategy, with `SIDES`-sided die and fixed-size buckets.
+    pub fn fixed<const SIDES: usize>(kind: StrategyKind, num_dice: Num) -> Self {
+        let num_sides = SIDES as Num;
+
+        let simulation: Box<dyn StrategyClone> = match kind {
+            StrategyKind::Naive => Box::new(FixedNaiveSimulation::<SIDES>::new(num_sides, num_dice)),
+            StrategyKind::Divide => Box::new(FixedDivideSimulation::<SIDES>::new(num_sides, num_dice)),
+            StrategyKind::Merge => Box::new(FixedMergeSimulation::<SIDES>::new(num_sides, num_dice)),
+        };
+
+        SimulationType::Fixed(kind, simulation)
+    }
+
+    /// Builds the simulation for the built-in strategy with the given name.
+    ///
+    /// Use a [`StrategyRegistry`](crate::registry::StrategyRegistry) to look up registered strategies as well.
+    pub fn from_name(name: &str, num_sides: Num, num_dice: Num) -> Result<Self> {
+        Ok(Self::new(name.parse()?, num_sides, num_dice))
+    }
+
+    /// Builds the simulation for an externally defined keep policy.
+    pub fn custom(policy: impl KeepPolicy + Clone + 'static, num_sides: Num, num_dice: Num) -> Self {
+        SimulationType::Custom(Game::with_policy(Box::new(policy), num_sides, num_dice))
+    }
+
+    /// Returns the name of the strategy, or "custom" for an externally defined keep policy.
+    pub fn name(&self) -> &'static str {
+        match self {
+            SimulationType::Naive(_) => StrategyKind::Naive.name(),
+            SimulationType::Divide(_) => StrategyKind::Divide.name(),
+            SimulationType::Merge(_) => StrategyKind::Merge.name(),
+            SimulationType::Custom(_) => "custom",
+            SimulationType::Fixed(kind, _) => kind.name(),
+        }
+    }
+
+    pub fn as_strategy(&self) -> &dyn Strategy {
+        match self {
+            SimulationType::Naive(sim) => sim as &dyn Strategy,
+            SimulationType::Divide(sim) => sim as &dyn Strategy,
+            SimulationType::Merge(sim) => sim as &dyn Strategy,
+            SimulationType::Custom(sim) => sim as &dyn Strategy,
+            SimulationType::Fixed(_, sim) => sim.as_ref(),
+        }
+    }
+
+    pub fn as_strategy_mut(&mut self) -> &mut dyn Strategy {
+        match self {
+            SimulationType::Naive(sim) => sim as &mut dyn Strategy,
+            SimulationType::Divide(sim) => sim as &mut dyn Strategy,
+            SimulationType::Merge(sim) => sim as &mut dyn Strategy,
+            SimulationType::Custom(sim) => sim as &mut dyn Strategy,
+            SimulationType::Fixed(_, sim) => sim.as_mut(),
+        }
+    }
+
+    /// Returns the current bucket state (i.e., the number of dice kept for each face).
+    pub fn buckets(&self) -> &[Num] {
+        self.as_strategy().buckets()
+    }
+
+    /// Returns an iterator that steps the simulation lazily, yielding a snapshot after each step.
+    #[cfg(feature = "std")]
+    pub fn steps(&mut self) -> Steps<'_, dyn Strategy + '_> {
+        Steps::new(self.as_strategy_mut())
+    }
+
+    /// Starts the simulation from the given bucket state (i.e., the dice that are already kept) rather than from scratch.
+    pub fn with_initial_state(mut self, state: &[Num]) -> Self {
+        self.as_strategy_mut().set_initial_state(state);
+        self
+    }
+
+    /// Rolls the dice of the simulation with the given source.
+    pub fn with_rng(mut self, rng: impl DiceRng + 'static) -> Self {
+        self.as_strategy_mut().set_rng(Box::new(rng));
+        self
+    }
+
+    /// Plays the simulation under the given house rules (see [`Strategy::set_rules`]).
+    ///
+    /// Fails with [`TenziError::InvalidConfig`] if a game under the rules could never end (see [`RuleSet::check`]).
+    pub fn with_rules(mut self, rules: RuleSet) -> Result<Self> {
+        self.as_strategy_mut().set_rules(rules)?;
+        Ok(self)
+    }
+}
+
+/// Ensures that a bucket state (i.e., the number of dice kept for each face) is valid for the given configuration.
+pub fn check_state(state: &[Num], num_sides: Num, num_dice: Num) -> Result<()> {
+    if state.len() != num_sides as usize {
+        return Err(TenziError::InvalidState(format!("expected {} buckets, but got {}", num_sides, state.len())));
+    }
+
+    if state.iter().sum::<Num>() > num_dice {
+        return Err(TenziError::InvalidState(format!("more dice than the {} available", num_dice)));
+    }
+
+    Ok(())
+}
+
+// Traits.
+
+/// A trait for a simulator that has "tracked" values.
+pub trait Tracked: Send + Sync {
+    /// Returns the number of rolls.
+    fn num_rolls(&self) -> Num;
+
+    /// Returns the number of steps.
+    fn num_steps(&self) -> Num;
+
+    /// Returns whether or not a "tenzi" has been achieved.
+    fn done(&self) -> bool;
+}
+
+/// A simulation strategy for the game "tenzi".
+///
+/// This is implemented by [`Game`] for every [`KeepPolicy`], so new strategies only need to implement the policy.
+pub trait Strategy: Tracked {
+    /// Returns the current bucket state (i.e., the number of dice kept for each face).
+    fn buckets(&self) -> &[Num];
+
+    /// Returns the number of sides on the die.
+    fn num_sides(&self) -> Num;
+
+    /// Returns the number of dice in the game.
+    fn num_dice(&self) -> Num;
+
+    /// Returns the number of dice to roll on the next step.
+    fn num_to_roll(&self) -> Num;
+
+    /// Sets the dice that are already kept before the first roll.
+    fn set_initial_state(&mut self, state: &[Num]);
+
+    /// Resets the game back to its initial state (i.e., the state set by [`Strategy::set_initial_state`], or no kept
+    /// dice), so that the same game can be played again without being rebuilt.
+    fn reset(&mut self);
+
+    /// Sets the source that the dice are rolled with on each step.
+    fn set_rng(&mut self, rng: Box<dyn DiceRng>);
+
+    /// Returns a source of the game's dice for the given block of a parallel run, if a clone of the game would roll the
+    /// same dice as every other clone (see [`DiceRng::split`]).
+    fn split_rng(&self, index: u64) -> Option<Box<dyn DiceRng>>;
+
+    /// Sets the house rules that the game is played by (see [`rules`](crate::rules)).
+    ///
+    /// The engine enforces the rules after every keep, whatever the policy decided, in order: every die on the locked
+    /// face is put back, then, if the policy would re-roll more dice than the rules allow, the largest groups of the dice it would
+    /// re-roll are kept instead, until only the limit is left to roll.  The first roll of a game (i.e., of every die
+    /// that is not kept in the initial state) is not a re-roll, so it is not limited.  A game never stalls: if the rules
+    /// would keep every die without a "tenzi", only the dice of the locked face (or of the face with the most dice) are
+    /// kept.
+    ///
+    /// Fails with [`TenziError::InvalidConfig`] if a game under the rules could never end (see [`RuleSet::check`]), in
+    /// which case the rules are left as they were.
+    fn set_rules(&mut self, rules: RuleSet) -> Result<()>;
+
+    /// Saves the state of the game in progress, including the policy's own state, so it can be restored later.
+    fn save(&self) -> SavedGame;
+
+    /// Restores a game saved with [`Strategy::save`], which must have the same number of sides and dice.
+    fn restore(&mut self, saved: &SavedGame) -> Result<()>;
+
+    /// Rolls the dice that are not kept with the game's dice source, and then lets the strategy decide which dice to
+    /// keep.
+    fn step(&mut self);
+
+    /// Like [`Strategy::step`], but rolls each die with the given function, which is handed the number of sides and
+    /// returns the face rolled (from 1 to the number of sides).
+    fn step_with(&mut self, roll: &mut dyn FnMut(Num) -> Num);
+}
+
+/// A helper trait that allows boxed strategies to be cloned.
+///
+/// This is implemented for every strategy that is `Clone`, so it never needs to be implemented by hand.
+pub trait StrategyClone: Strategy {
+    /// Clones the strategy into a box.
+    fn clone_box(&self) -> Box<dyn StrategyClone>;
+}
+
+impl<T: Strategy + Clone + 'static> StrategyClone for T {
+    fn clone_box(&self) -> Box<dyn StrategyClone> {
+        Box::new(self.clone())
+    }
+}
+
+impl Clone for Box<dyn StrategyClone> {
+    fn clone(&self) -> Self {
+        // The box is itself a strategy, so clone what it points to (rather than recursing into the box's own impl).
+        self.as_ref().clone_box()
+    }
+}
+
+impl Tracked for Box<dyn StrategyClone> {
+    fn num_rolls(&self) -> Num {
+        self.as_ref().num_rolls()
+    }
+
+    fn num_steps(&self) -> Num {
+        self.as_ref().num_steps()
+    }
+
+    fn done(&self) -> bool {
+        self.as_ref().done()
+    }
+}
+
+impl Strategy for Box<dyn StrategyClone> {
+    fn buckets(&self) -> &[Num] {
+        self.as_ref().buckets()
+    }
+
+    fn num_sides(&self) -> Num {
+        self.as_ref().num_sides()
+    }
+
+    fn num_dice(&self) -> Num {
+        self.as_ref().num_dice()
+    }
+
+    fn num_to_roll(&self) -> Num {
+        self.as_ref().num_to_roll()
+    }
+
+    fn set_initial_state(&mut self, state: &[Num]) {
+        self.as_mut().set_initial_state(state)
+    }
+
+    fn reset(&mut self) {
+        self.as_mut().reset()
+    }
+
+    fn set_rng(&mut self, rng: Box<dyn DiceRng>) {
+        self.as_mut().set_rng(rng)
+    }
+
+    fn split_rng(&self, index: u64) -> Option<Box<dyn DiceRng>> {
+        self.as_ref().split_rng(index)
+    }
+
+    fn set_rules(&mut self, rules: RuleSet) -> Result<()> {
+        self.as_mut().set_rules(rules)
+    }
+
+    fn save(&self) -> SavedGame {
+        self.as_ref().save()
+    }
+
+    fn restore(&mut self, saved: &SavedGame) -> Result<()> {
+        self.as_mut().restore(saved)
+    }
+
+    fn step(&mut self) {
+        self.as_mut().step()
+    }
+
+    fn step_with(&mut self, roll: &mut dyn FnMut(Num) -> Num) {
+        self.as_mut().step_with(roll)
+    }
+}
+
+/// The storage for a game's buckets (i.e., the number of dice for each face).
+///
+/// This is either [`InlineBuckets`] for any number of sides, or an array for a number of sides that is known at compile
+/// time, which lets the compiler unroll the loops over the buckets.  A `Vec` works too.
+pub trait Buckets: AsRef<[Num]> + AsMut<[Num]> + Clone + Send + Sync {
+    /// Returns empty buckets for die with the given number of sides.
+    fn zeroed(num_sides: Num) -> Self;
+}
+
+impl Buckets for Vec<Num> {
+    fn zeroed(num_sides: Num) -> Self {
+        vec![0; num_sides as usize]
+    }
+}
+
+/// The number of sides that [`InlineBuckets`] holds without an allocation.
+pub const INLINE_SIDES: usize = 32;
+
+/// The default storage for a game's buckets, which keeps them inline for up to [`INLINE_SIDES`] sides (i.e., every
+/// typical die), and only moves them to the heap for more.
+pub type InlineBuckets = SmallVec<[Num; INLINE_SIDES]>;
+
+/// A list of bucket indices, which is inline for as many sides as [`InlineBuckets`].
+type Faces = SmallVec<[usize; INLINE_SIDES]>;
+
+/// How many times more buckets than listed faces there must be before the listed faces are zeroed one at a time,
+/// rather than every bucket at once.
+const SPARSE_RATIO: usize = 8;
+
+/// The indices of the buckets with dice, in ascending order, which are also kept as a [`FaceMask`] while every index
+/// fits in one (i.e., for dice of up to [`FaceMask::MAX_FACES`] sides), so that they are counted and sorted with bit
+/// operations.
+#[derive(Clone, Default)]
+struct NonzeroFaces {
+    list: Faces,
+    mask: FaceMask,
+    wide: bool,
+}
+
+impl NonzeroFaces {
+    fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the mask of the indices, unless any is too large for one.
+    fn mask(&self) -> Option<FaceMask> {
+        (!self.wide).then_some(self.mask)
+    }
+
+    /// Lists the indices of the buckets with dice in place of the contents.
+    fn list(&mut self, buckets: &[Num]) {
+        self.clear();
+
+        for k in (0..buckets.len()).filter(|&k| buckets[k] != 0) {
+            self.push(k);
+        }
+    }
+
+    /// Adds an index, which may be out of order until [`NonzeroFaces::sort`].
+    fn push(&mut self, k: usize) {
+        self.list.push(k);
+
+        if k < FaceMask::MAX_FACES {
+            self.mask.insert(k);
+        } else {
+            self.wide = true;
+        }
+    }
+
+    /// Restores the order of the list after indices were pushed, which is a walk over the bits of the mask (if there
+    /// is one) rather than a sort.
+    fn sort(&mut self) {
+        match self.mask() {
+            Some(mask) => {
+                self.list.clear();
+                self.list.extend(mask.iter());
+            }
+            None => self.list.sort_unstable(),
+        }
+    }
+
+    fn retain(&mut self, mut keep: impl FnMut(usize) -> bool) {
+        let mask = &mut self.mask;
+
+        self.list.retain(|k| {
+            let kept = keep(*k);
+
+            if !kept {
+                mask.remove(*k);
+            }
+
+            kept
+        });
+    }
+
+    fn clear(&mut self) {
+        self.list.clear();
+        self.mask = FaceMask::EMPTY;
+        self.wide = false;
+    }
+
+    fn set_from(&mut self, other: &Self) {
+        self.list.clone_from(&other.list);
+        self.mask = other.mask;
+        self.wide = other.wide;
+    }
+}
+
+impl core::ops::Deref for NonzeroFaces {
+    type Target = [usize];
+
+    fn deref(&self) -> &[usize] {
+        &self.list
+    }
+}
+
+impl Buckets for InlineBuckets {
+    fn zeroed(num_sides: Num) -> Self {
+        smallvec![0; num_sides as usize]
+    }
+}
+
+impl<const SIDES: usize> Buckets for [Num; SIDES] {
+    fn zeroed(num_sides: Num) -> Self {
+        assert_eq!(num_sides as usize, SIDES, "the number of sides must match the size of the buckets");
+        [0; SIDES]
+    }
+}
+
+/// The buckets that a [`KeepPolicy`] decides over, which can be read like a slice, but only changed by zeroing out
+/// buckets (i.e., re-rolling those dice).
+///
+/// That way, the engine keeps the number of kept dice as the buckets are zeroed, rather than summing the buckets
+/// after every step.
+pub struct KeptBuckets<'a> {
+    buckets: &'a mut [Num],
+    nonzero: &'a [usize],
+    mask: Option<FaceMask>,
+    num_kept: Num,
+    max_rerolls: Option<Num>,
+    scratch: &'a mut Scratch,
+}
+
+impl<'a> KeptBuckets<'a> {
+    /// Wraps the buckets, which hold the given number of dice in the buckets at the given (ascending) indices (and in
+    /// the given mask, if they fit in one), along with the game's limit on re-rolls, and the (empty) scratch arena of
+    /// the keep.
+    fn new(buckets: &'a mut [Num], nonzero: &'a [usize], mask: Option<FaceMask>, num_kept: Num, max_rerolls: Option<Num>, scratch: &'a mut Scratch) -> Self {
+        Self { buckets, nonzero, mask, num_kept, max_rerolls, scratch }
+    }
+
+    /// Returns the number of dice that are kept so far.
+    pub fn num_kept(&self) -> Num {
+        self.num_kept
+    }
+
+    /// Returns the most dice that may be re-rolled after this keep, if the rules of the game limit them (see
+    /// [`Strategy::set_rules`]).
+    ///
+    /// A policy that zeroes out more dice than that has some of them put back by the engine, so a policy that cares
+    /// which ones can choose them itself.
+    pub fn max_rerolls(&self) -> Option<Num> {
+        self.max_rerolls
+    }
+
+    /// Returns the indices of the buckets with dice, in ascending order, which the engine maintains as the dice are
+    /// rolled so that a policy can skip the empty buckets (of which there are many, for dice with many sides).
+    ///
+    /// Buckets that were zeroed out during this keep are still listed (with a count of zero).
+    pub fn nonzero(&self) -> &[usize] {
+        self.nonzero
+    }
+
+    /// Returns the indices of the buckets with dice as a mask, unless the die has too many sides for one.
+    ///
+    /// Unlike [`KeptBuckets::nonzero`], the mask is kept up to date as buckets are zeroed out.
+    pub fn mask(&self) -> Option<FaceMask> {
+        self.mask
+    }
+
+    /// Returns the buckets as a sparse histogram over [`KeptBuckets::nonzero`] (with [`KeptBuckets::mask`]), for the
+    /// mode helpers in [`histogram`].
+    pub fn sparse(&self) -> Sparse<'_> {
+        Sparse::new(self.buckets, self.nonzero).with_mask(self.mask)
+    }
+
+    /// Returns the game's scratch arena, for the temporaries of this keep (which are freed before the next one).
+    pub fn scratch(&mut self) -> &mut Scratch {
+        self.scratch
+    }
+
+    /// Returns the mode, the top two modes, and the faces with the fewest dice, from a single pass over the buckets
+    /// with dice (see [`histogram::analyze`]).
+    pub fn analyze(&self) -> Analysis {
+        histogram::analyze(&self.sparse())
+    }
+
+    /// Allocates a slice in the scratch arena of whatever the given function appends to it, which is handed the
+    /// buckets as a sparse histogram (see [`KeptBuckets::sparse`]), e.g., to find the faces to re-roll.
+    pub fn collect_scratch(&mut self, append: impl FnOnce(&Sparse, &mut Scratch)) -> Span {
+        let counts = Sparse::new(self.buckets, self.nonzero).with_mask(self.mask);
+        self.scratch.collect_with(|scratch| append(&counts, scratch))
+    }
+
+    /// Zeroes out the bucket at the given index (i.e., of the face `k + 1`).
+    pub fn zero(&mut self, k: usize) {
+        self.num_kept -= self.buckets[k];
+        self.buckets[k] = 0;
+
+        if let Some(mask) = &mut self.mask {
+            mask.remove(k);
+        }
+    }
+
+    /// Zeroes out the buckets of the faces in the given slice of the scratch arena (e.g., from
+    /// [`KeptBuckets::collect_scratch`]).
+    pub fn zero_faces(&mut self, faces: Span) {
+        for &face in self.scratch.get(faces) {
+            let k = face as usize - 1;
+
+            self.num_kept -= self.buckets[k];
+            self.buckets[k] = 0;
+
+            if let Some(mask) = &mut self.mask {
+                mask.remove(k);
+            }
+        }
+    }
+
+    /// Zeroes out every bucket with dice that the predicate, which is handed each index and count, rejects.
+    pub fn retain(&mut self, mut keep: impl FnMut(usize, Num) -> bool) {
+        for &k in self.nonzero {
+            if self.buckets[k] != 0 && !keep(k, self.buckets[k]) {
+                self.num_kept -= self.buckets[k];
+                self.buckets[k] = 0;
+
+                if let Some(mask) = &mut self.mask {
+                    mask.remove(k);
+                }
+            }
+        }
+    }
+
+    /// Zeroes out every bucket but the ones at the given indices (of which there should be few, e.g., the modes), which
+    /// may repeat.
+    ///
+    /// With a mask, exactly the buckets with dice that are not kept are zeroed.  Otherwise, unless the buckets are
+    /// mostly empty, they are zeroed with a single fill (rather than one at a time), and the kept counts are put back.
+    pub fn keep_only(&mut self, kept: &[usize]) {
+        if let Some(mask) = self.mask {
+            let kept = FaceMask::from_faces(kept.iter().copied().filter(|&k| mask.contains(k))).unwrap();
+
+            for k in mask.difference(kept).iter() {
+                self.num_kept -= self.buckets[k];
+                self.buckets[k] = 0;
+            }
+
+            self.mask = Some(kept);
+
+            return;
+        }
+
+        let saved = kept.iter().map(|&k| (k, self.buckets[k])).collect::<SmallVec<[_; 2]>>();
+
+        if self.nonzero.len() * SPARSE_RATIO >= self.buckets.len() {
+            self.buckets.fill(0);
+        } else {
+            for &k in self.nonzero {
+                self.buckets[k] = 0;
+            }
+        }
+
+        self.num_kept = 0;
+
+        for (k, count) in saved {
+            if self.buckets[k] == 0 {
+                self.buckets[k] = count;
+                self.num_kept += count;
+            }
+        }
+    }
+
+    /// Zeroes out every bucket.
+    pub fn clear(&mut self) {
+        self.retain(|_, _| false);
+    }
+}
+
+impl core::ops::Deref for KeptBuckets<'_> {
+    type Target = [Num];
+
+    fn deref(&self) -> &[Num] {
+        self.buckets
+    }
+}
+
+/// A policy that decides which dice to keep after every roll.
+///
+/// This is the extension point for new strategies: the [`Game`] engine owns the dice, rolls them, and keeps
+/// track of the counters, and the policy only decides what to keep.
+pub trait KeepPolicy: KeepPolicyClone + Send + Sync {
+    /// Takes the buckets (i.e., the kept dice plus the dice just rolled, counted by face), and zeroes out the
+    /// buckets that the policy would like re-rolled.  The dice that are not zeroed out are the ones that are kept.
+    ///
+    /// We use this method as it prevents unnecessary allocations just to keep track of which dice to re-roll.
+    fn keep(&mut self, buckets: &mut KeptBuckets, num_dice: Num);
+
+    /// Returns whether, once every kept die shows the same face (and there are at least two of them) with one die
+    /// left to roll, the policy only keeps that die when it shows the same face.
+    ///
+    /// If so, the engine plays that wait (which is most of the end of a game) without calling the policy.
+    fn waits_for_last_die(&self) -> bool {
+        false
+    }
+
+    /// Clears any state that the policy has accumulated over a game, before the game is played again.
+    fn reset(&mut self) {}
+
+    /// Saves the state that the policy has accumulated over a game (see [`Strategy::save`]).
+    fn save(&self) -> Vec<Num> {
+        Vec::new()
+    }
+
+    /// Restores the state saved with [`KeepPolicy::save`] into a game with the given number of sides, failing with
+    /// [`TenziError::InvalidState`] if the state is not valid for it.
+    fn restore(&mut self, state: &[Num], _num_sides: Num) -> Result<()> {
+        match state {
+            [] => Ok(()),
+            _ => Err(TenziError::InvalidState("the policy does not have any state".to_string())),
+        }
+    }
+}
+
+/// The metadata that describes a strategy, which is how the strategies are listed and documented.
+///
+/// This is implemented by every built-in policy, and a [`StrategyRegistry`](crate::registry::StrategyRegistry) holds
+/// it for every strategy it can build.
+pub trait StrategyMeta: Send + Sync {
+    /// Returns the name that the strategy is specified by.
+    fn name(&self) -> &str;
+
+    /// Returns a one-line description of the strategy.
+    fn description(&self) -> &str;
+
+    /// Returns the parameter that the strategy is specified with (as "name=parameter"), if it takes one.
+    fn parameter(&self) -> Option<&ParameterSchema> {
+        None
+    }
+
+    /// Returns the numbers of sides that the strategy has a variant with fixed-size buckets for (see
+    /// [`SimulationType::fast`]).
+    fn fixed_sides(&self) -> &[Num] {
+        &[]
+    }
+}
+
+/// The parameter of a parameterized strategy (i.e., the "parameter" of a "name=parameter" spec).
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ParameterSchema {
+    /// The name of the parameter.
+    pub name: String,
+    /// A one-line description of the parameter.
+    pub description: String,
+}
+
+impl ParameterSchema {
+    /// Describes a parameter.
+    pub fn new(name: impl Into<String>, description: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            description: description.into(),
+        }
+    }
+}
+
+/// A helper trait that allows boxed policies to be cloned.
+///
+/// This is implemented for every policy that is `Clone`, so it never needs to be implemented by hand.
+pub trait KeepPolicyClone {
+    /// Clones the policy into a box.
+    fn clone_box(&self) -> Box<dyn KeepPolicy>;
+}
+
+impl<T: KeepPolicy + Clone + 'static> KeepPolicyClone for T {
+    fn clone_box(&self) -> Box<dyn KeepPolicy> {
+        Box::new(self.clone())
+    }
+}
+
+impl Clone for Box<dyn KeepPolicy> {
+    fn clone(&self) -> Self {
+        // The box is itself a policy, so clone what it points to (rather than recursing into the box's own impl).
+        self.as_ref().clone_box()
+    }
+}
+
+impl KeepPolicy for Box<dyn KeepPolicy> {
+    fn keep(&mut self, buckets: &mut KeptBuckets, num_dice: Num) {
+        self.as_mut().keep(buckets, num_dice)
+    }
+
+    fn waits_for_last_die(&self) -> bool {
+        self.as_ref().waits_for_last_die()
+    }
+
+    fn reset(&mut self) {
+        self.as_mut().reset()
+    }
+
+    fn save(&self) -> Vec<Num> {
+        self.as_ref().save()
+    }
+
+    fn restore(&mut self, state: &[Num], num_sides: Num) -> Result<()> {
+        self.as_mut().restore(state, num_sides)
+    }
+}
+
+// Engine.
+
+/// A single game of "tenzi", played with the given keep policy, (optionally) watched by the given observer, and
+/// with the buckets stored in the given [`Buckets`].
+///
+/// The dice are rolled with a [`DiceRng`], which is the thread-local rng unless set otherwise.
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Game<P, O = (), B = InlineBuckets> {
+    buckets: B,
+    initial_state: Option<Vec<Num>>,
+    num_dice: Num,
+    num_sides: Num,
+    num_to_roll: Num,
+    /// The house rules (see [`Strategy::set_rules`]).
+    #[cfg_attr(feature = "serde", serde(default))]
+    rules: RuleSet,
+
+    num_rolls: Num,
+    num_steps: Num,
+    done: bool,
+
+    /// The indices of the buckets with dice, in ascending order (which is rebuilt if it is missing, e.g., after the
+    /// game is deserialized).
+    #[cfg_attr(feature = "serde", serde(skip))]
+    nonzero: NonzeroFaces,
+    /// The indices of the buckets with dice in the initial state, so that a reset only touches the buckets with dice.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    initial_nonzero: NonzeroFaces,
+    /// The index of the face that every kept die shows, once the game is waiting for the last die (see
+    /// [`KeepPolicy::waits_for_last_die`]).
+    #[cfg_attr(feature = "serde", serde(skip))]
+    last_die: Option<usize>,
+    /// The arena for the policy's temporaries, which is reset before every keep.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    scratch: Scratch,
+    /// The count of each face that was rolled, before the keep, so that the dice that the policy would re-roll against
+    /// the rules can be put back (only under house rules).
+    #[cfg_attr(feature = "serde", serde(skip))]
+    rolled: Vec<(usize, Num)>,
+    /// The locked face, and the number of its dice that are locked (see [`RuleSet::lock_kept`]).
+    #[cfg_attr(feature = "serde", serde(default))]
+    locked: Option<(usize, Num)>,
+
+    policy: P,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    observer: O,
+    #[cfg_attr(feature = "serde", serde(skip, default = "dice::default_rng"))]
+    rng: Box<dyn DiceRng>,
+}
+
+impl<P: KeepPolicy + Default, B: Buckets> Game<P, (), B> {
+    pub fn new(num_sides: Num, num_dice: Num) -> Self {
+        Self::with_policy(P::default(), num_sides, num_dice)
+    }
+}
+
+impl<P: KeepPolicy, B: Buckets> Game<P, (), B> {
+    pub fn with_policy(policy: P, num_sides: Num, num_dice: Num) -> Self {
+        Self {
+            buckets: B::zeroed(num_sides),
+            initial_state: None,
+            num_dice,
+            num_sides,
+            num_to_roll: num_dice,
+            rules: RuleSet::default(),
+
+            num_rolls: 0,
+            num_steps: 0,
+            done: false,
+
+            nonzero: NonzeroFaces::new(),
+            initial_nonzero: NonzeroFaces::new(),
+            last_die: None,
+            scratch: Scratch::new(),
+            rolled: Vec::new(),
+            locked: None,
+
+            policy,
+            observer: (),
+            rng: dice::default_rng(),
+        }
+    }
+}
+
+impl<P: KeepPolicy, O: Observer, B: Buckets> Game<P, O, B> {
+    /// Attaches an observer to the game, which is then notified after each phase of every step.
+    pub fn with_observer<O2: Observer>(self, observer: O2) -> Game<P, O2, B> {
+        Game {
+            buckets: self.buckets,
+            initial_state: self.initial_state,
+            num_dice: self.num_dice,
+            num_sides: self.num_sides,
+            num_to_roll: self.num_to_roll,
+            rules: self.rules,
+
+            num_rolls: self.num_rolls,
+            num_steps: self.num_steps,
+            done: self.done,
+
+            nonzero: self.nonzero,
+            initial_nonzero: self.initial_nonzero,
+            last_die: self.last_die,
+            scratch: self.scratch,
+            rolled: self.rolled,
+            locked: self.locked,
+
+            policy: self.policy,
+            observer,
+            rng: self.rng,
+        }
+    }
+
+    /// Rolls the dice of the game with the given source.
+    pub fn with_rng(mut self, rng: impl DiceRng + 'static) -> Self {
+        self.rng = Box::new(rng);
+        self
+    }
+
+    /// Returns the keep policy.
+    pub fn policy(&self) -> &P {
+        &self.policy
+    }
+
+    /// Returns the observer.
+    pub fn observer(&self) -> &O {
+        &self.observer
+    }
+
+    /// Consumes the game, and returns the observer.
+    pub fn into_observer(self) -> O {
+        self.observer
+    }
+
+    /// Returns an iterator that steps the game lazily, yielding a snapshot after each step.
+    #[cfg(feature = "std")]
+    pub fn steps(&mut self) -> Steps<'_, Self> {
+        Steps::new(self)
+    }
+
+    /// Hands the observer a read-only view of the game.
+    fn notify(&mut self, notify: impl FnOnce(&mut O, &GameView)) {
+        let view = GameView {
+            buckets: self.buckets.as_ref(),
+            num_dice: self.num_dice,
+            num_rolls: self.num_rolls,
+            num_steps: self.num_steps,
+            done: self.done,
+        };
+
+        notify(&mut self.observer, &view);
+    }
+
+    /// Rolls the dice that are not kept, adds them to the buckets, and returns the index of the last face rolled (when
+    /// the dice are rolled one at a time, which they are for the last die).
+    fn roll(&mut self, rng: &mut (impl Roll + ?Sized)) -> usize {
+        // Any kept dice are listed, unless the list was lost (i.e., the game was deserialized).
+
+        if self.nonzero.is_empty() && self.num_to_roll < self.num_dice {
+            self.nonzero.list(self.buckets.as_ref());
+        }
+
+        let mut last = 0;
+
+        if self.num_to_roll as usize >= self.buckets.as_ref().len() {
+            // Rolling at least one die per side already costs as much as a scan, so just rescan afterwards (and let the
+            // source roll the whole pool at once).
+
+            rng.roll_into(self.num_sides, self.num_to_roll, self.buckets.as_mut());
+
+            self.nonzero.list(self.buckets.as_ref());
+        } else {
+            // Otherwise, list each face as it gets its first die, and restore the order once.
+
+            let listed = self.nonzero.len();
+
+            for _ in 0..self.num_to_roll {
+                last = rng.roll(self.num_sides) as usize - 1;
+
+                let bucket = &mut self.buckets.as_mut()[last];
+                *bucket += 1;
+
+                if *bucket == 1 {
+                    self.nonzero.push(last);
+                }
+            }
+
+            if self.nonzero.len() > listed {
+                self.nonzero.sort();
+            }
+        }
+
+        self.num_rolls += self.num_to_roll * self.rules.cost(self.num_steps);
+
+        last
+    }
+
+    /// Updates whether or not a "tenzi" has been achieved, and the number of dice to roll on the next step, from the
+    /// buckets with dice (which must already be listed).
+    fn update(&mut self) {
+        let buckets = self.buckets.as_ref();
+        let num_kept = self.nonzero.iter().map(|&k| buckets[k]).sum::<Num>();
+
+        self.num_to_roll = self.num_dice - num_kept;
+        self.done = self.nonzero.iter().any(|&k| buckets[k] == self.num_dice);
+        self.last_die = None;
+    }
+
+    /// Lets the policy decide what to keep after a roll.
+    fn keep(&mut self) {
+        // Every die is in the buckets after the roll, and the policy keeps count as it zeroes them out.
+
+        self.scratch.reset();
+
+        let plain = self.rules.is_plain();
+        let max_rerolls = self.rules.max_rerolls_after(self.num_to_roll);
+
+        if !plain {
+            let buckets = self.buckets.as_ref();
+
+            self.rolled.clear();
+            self.rolled.extend(self.nonzero.iter().map(|&k| (k, buckets[k])));
+        }
+
+        let mut buckets = KeptBuckets::new(self.buckets.as_mut(), &self.nonzero, self.nonzero.mask(), self.num_dice, max_rerolls, &mut self.scratch);
+        self.policy.keep(&mut buckets, self.num_dice);
+        let mut num_kept = buckets.num_kept();
+
+        if !plain {
+            num_kept = self.enforce_rules(num_kept, max_rerolls);
+        }
+
+        let buckets = self.buckets.as_ref();
+        self.nonzero.retain(|k| buckets[k] != 0);
+
+        // Check if we are done; otherwise, compute the number to roll on the next step (i.e., the total dice that are not kept).
+
+        self.num_to_roll = self.num_dice - num_kept;
+        self.done = self.nonzero.iter().any(|&k| buckets[k] == self.num_dice);
+
+        // Once all but one die show the same face, the rest of the game is a wait for the last die (unless the rules
+        // have a say in every keep, which then goes through them).
+
+        if plain && self.num_to_roll == 1 && self.num_dice > 2 && self.policy.waits_for_last_die() {
+            self.last_die = self.nonzero.iter().copied().find(|&k| buckets[k] == self.num_dice - 1);
+        }
+    }
+
+    /// Enforces the house rules on the policy's keep (see [`Strategy::set_rules`]), given the number of dice that it
+    /// kept and the most dice that may be re-rolled, and returns the number of dice that are kept then.
+    fn enforce_rules(&mut self, mut num_kept: Num, max_rerolls: Option<Num>) -> Num {
+        let buckets = self.buckets.as_mut();
+        let num_dice = self.num_dice;
+
+        // Put back the locked dice, along with every die that was rolled on the locked face, so the locked face always
+        // grows (even when the policy is after another face).
+
+        if let Some(&(k, count)) = self.locked.and_then(|(face, _)| self.rolled.iter().find(|&&(k, _)| k == face)).filter(|&&(k, count)| buckets[k] < count) {
+            num_kept += count - buckets[k];
+            buckets[k] = count;
+        }
+
+        // Put back the dice that the policy would re-roll beyond the limit, largest group first (i.e., the faces with the
+        // most dice that it zeroed out, with a stable sort, so ties go to the lower face).
+
+        if let Some(max_rerolls) = max_rerolls.filter(|&max_rerolls| num_dice - num_kept > max_rerolls) {
+            self.rolled.sort_by(|(_, a), (_, b)| b.cmp(a));
+
+            for &(k, count) in &self.rolled {
+                if buckets[k] >= count {
+                    continue;
+                }
+
+                let put_back = (count - buckets[k]).min(num_dice - num_kept - max_rerolls);
+
+                buckets[k] += put_back;
+                num_kept += put_back;
+
+                if num_dice - num_kept == max_rerolls {
+                    break;
+                }
+            }
+        }
+
+        // Lock the face with the most kept dice (the lowest, of a tie) once any die is kept, and then its kept dice.
+
+        let most = || self.nonzero.iter().rev().copied().filter(|&k| buckets[k] != 0).max_by_key(|&k| buckets[k]);
+
+        if self.rules.lock_kept {
+            self.locked = self.locked.map(|(k, _)| k).or_else(most).map(|k| (k, buckets[k]));
+        }
+
+        // Never keep every die without a "tenzi", which would stall the game: free the dice of the other faces than the
+        // locked one (or the most), smallest group first, but no more than the limit of re-rolls.
+
+        if num_kept == num_dice && !self.nonzero.iter().any(|&k| buckets[k] == num_dice) {
+            let face = self.locked.map(|(k, _)| k).or_else(most);
+            let mut to_free = max_rerolls.unwrap_or(num_dice);
+
+            self.rolled.clear();
+            self.rolled.extend(self.nonzero.iter().copied().filter(|&k| Some(k) != face && buckets[k] != 0).map(|k| (k, buckets[k])));
+            self.rolled.sort_by_key(|&(_, count)| count);
+
+            for &(k, count) in &self.rolled {
+                let freed = count.min(to_free);
+
+                buckets[k] -= freed;
+                num_kept -= freed;
+                to_free -= freed;
+
+                if to_free == 0 {
+                    break;
+                }
+            }
+        }
+
+        num_kept
+    }
+
+    /// Keeps the last die only if it shows the face of every other die, which is exactly what the policy would do
+    /// (see [`KeepPolicy::waits_for_last_die`]), without its scan over the buckets.
+    fn keep_last_die(&mut self, face: usize, rolled: usize) {
+        if rolled == face {
+            self.num_to_roll = 0;
+            self.done = true;
+        } else {
+            self.buckets.as_mut()[rolled] = 0;
+            self.nonzero.retain(|k| k != rolled);
+        }
+    }
+}
+
+impl<P: KeepPolicy, O: Observer, B: Buckets> Tracked for Game<P, O, B> {
+    fn num_rolls(&self) -> Num {
+        self.num_rolls
+    }
+
+    fn num_steps(&self) -> Num {
+        self.num_steps
+    }
+
+    fn done(&self) -> bool {
+        self.done
+    }
+}
+
+impl<P: KeepPolicy, O: Observer, B: Buckets> Strategy for Game<P, O, B> {
+    fn buckets(&self) -> &[Num] {
+        self.buckets.as_ref()
+    }
+
+    fn num_sides(&self) -> Num {
+        self.num_sides
+    }
+
+    fn num_dice(&self) -> Num {
+        self.num_dice
+    }
+
+    fn num_to_roll(&self) -> Num {
+        self.num_to_roll
+    }
+
+    fn set_initial_state(&mut self, state: &[Num]) {
+        self.initial_state = Some(state.to_vec());
+        self.initial_nonzero.list(state);
+        self.reset();
+    }
+
+    fn reset(&mut self) {
+        // Only the buckets with dice need to be zeroed (or set), which are listed unless the lists were lost (i.e.,
+        // the game was deserialized).
+
+        if self.nonzero.is_empty() && self.num_to_roll < self.num_dice {
+            self.nonzero.list(self.buckets.as_ref());
+        }
+
+        for &k in self.nonzero.iter() {
+            self.buckets.as_mut()[k] = 0;
+        }
+
+        self.nonzero.clear();
+
+        if let Some(state) = &self.initial_state {
+            if self.initial_nonzero.is_empty() {
+                self.initial_nonzero.list(state);
+            }
+
+            for &k in self.initial_nonzero.iter() {
+                self.buckets.as_mut()[k] = state[k];
+            }
+
+            self.nonzero.set_from(&self.initial_nonzero);
+        }
+
+        self.num_rolls = 0;
+        self.num_steps = 0;
+        self.locked = None;
+        self.policy.reset();
+
+        self.update();
+    }
+
+    fn set_rng(&mut self, rng: Box<dyn DiceRng>) {
+        self.rng = rng;
+    }
+
+    fn split_rng(&self, index: u64) -> Option<Box<dyn DiceRng>> {
+        self.rng.split(index)
+    }
+
+    fn set_rules(&mut self, rules: RuleSet) -> Result<()> {
+        rules.check()?;
+
+        self.rules = rules;
+        self.locked = None;
+
+        Ok(())
+    }
+
+    fn save(&self) -> SavedGame {
+        SavedGame {
+            num_sides: self.num_sides,
+            num_dice: self.num_dice,
+            buckets: self.buckets.as_ref().to_vec(),
+            initial_state: self.initial_state.clone(),
+            num_rolls: self.num_rolls,
+            num_steps: self.num_steps,
+            policy: self.policy.save(),
+        }
+    }
+
+    fn restore(&mut self, saved: &SavedGame) -> Result<()> {
+        if saved.num_sides != self.num_sides || saved.num_dice != self.num_dice {
+            return Err(TenziError::InvalidState(format!("the game was saved with {} {}-sided die, but this game has {} {}-sided die", saved.num_dice, saved.num_sides, self.num_dice, self.num_sides)));
+        }
+
+        check_state(&saved.buckets, self.num_sides, self.num_dice)?;
+
+        if let Some(state) = &saved.initial_state {
+            check_state(state, self.num_sides, self.num_dice)?;
+        }
+
+        self.policy.restore(&saved.policy, self.num_sides)?;
+
+        self.buckets.as_mut().copy_from_slice(&saved.buckets);
+        self.initial_state = saved.initial_state.clone();
+        self.num_rolls = saved.num_rolls;
+        self.num_steps = saved.num_steps;
+
+        self.nonzero.list(self.buckets.as_ref());
+        self.initial_nonzero.clear();
+
+        if let Some(state) = &self.initial_state {
+            self.initial_nonzero.list(state);
+        }
+
+        // The lock is not saved, so the face with the most kept dice is locked again.
+
+        let buckets = self.buckets.as_ref();
+        self.locked = None;
+
+        if self.rules.lock_kept {
+            self.locked = self.nonzero.iter().rev().copied().max_by_key(|&k| buckets[k]).map(|k| (k, buckets[k]));
+        }
+
+        self.update();
+
+        Ok(())
+    }
+
+    fn step(&mut self) {
+        // The source is moved out for the step, since the step borrows the whole game.
+
+        let mut rng = core::mem::replace(&mut self.rng, Box::new(dice::NoDice));
+        self.step_in(&mut rng);
+        self.rng = rng;
+    }
+
+    fn step_with(&mut self, roll: &mut dyn FnMut(Num) -> Num) {
+        self.step_in(&mut RollFn(roll));
+    }
+}
+
+impl<P: KeepPolicy, O: Observer, B: Buckets> Game<P, O, B> {
+    /// Steps the game, rolling the dice with the given source.
+    fn step_in(&mut self, rng: &mut (impl Roll + ?Sized)) {
+        self.notify(|observer, view| observer.before_roll(view));
+
+        // Perform a roll.
+
+        let rolled = self.roll(rng);
+
+        self.notify(|observer, view| observer.on_roll(view));
+
+        // Let the policy decide what to keep.
+
+        match self.last_die {
+            Some(face) => self.keep_last_die(face, rolled),
+            None => self.keep(),
+        }
+
+        // Update the state.
+
+        self.num_steps += 1;
+
+        self.notify(|observer, view| observer.on_keep(view));
+
+        if self.done {
+            self.notify(|observer, view| observer.on_done(view));
+        }
+    }
+}
+
+// Iteration.
+
+/// A snapshot of a game after a step.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Snapshot {
+    /// The buckets (i.e., the number of dice kept for each face).
+    pub buckets: Vec<Num>,
+    /// The number of rolls so far.
+    pub num_rolls: Num,
+    /// The number of steps so far.
+    pub num_steps: Num,
+    /// Whether or not a "tenzi" has been achieved.
+    pub done: bool,
+}
+
+impl Snapshot {
+    /// Takes a snapshot of the current state of a strategy.
+    pub fn of(strategy: &(impl Strategy + ?Sized)) -> Self {
+        Self {
+            buckets: strategy.buckets().to_vec(),
+            num_rolls: strategy.num_rolls(),
+            num_steps: strategy.num_steps(),
+            done: strategy.done(),
+        }
+    }
+}
+
+/// An in-progress game, saved with [`Strategy::save`], which restores the game exactly (including the policy's own
+/// state, like the face that the naive policy has settled on).
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SavedGame {
+    /// The number of sides on the die.
+    pub num_sides: Num,
+    /// The number of dice in the game.
+    pub num_dice: Num,
+    /// The buckets (i.e., the number of dice kept for each face).
+    pub buckets: Vec<Num>,
+    /// The bucket state that the game is reset to.
+    pub initial_state: Option<Vec<Num>>,
+    /// The number of rolls so far.
+    pub num_rolls: Num,
+    /// The number of steps so far.
+    pub num_steps: Num,
+    /// The policy's own state, as saved by [`KeepPolicy::save`].
+    pub policy: Vec<Num>,
+}
+
+/// An iterator that steps a game lazily, yielding a [`Snapshot`] after each step until a "tenzi" is achieved.
+///
+/// Dropping the iterator early simply leaves the game where it was, so it can be resumed later.
+#[cfg(feature = "std")]
+pub struct Steps<'a, S: Strategy + ?Sized> {
+    strategy: &'a mut S,
+}
+
+#[cfg(feature = "std")]
+impl<'a, S: Strategy + ?Sized> Steps<'a, S> {
+    pub fn new(strategy: &'a mut S) -> Self {
+        Self { strategy }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<S: Strategy + ?Sized> Iterator for Steps<'_, S> {
+    type Item = Snapshot;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.strategy.done() {
+            return None;
+        }
+
+        self.strategy.step();
+
+        Some(Snapshot::of(self.strategy))
+    }
+}
+
+// Policies.
+
+/// Always keep the most from the first roll.
+pub type NaiveSimulation = Game<NaivePolicy>;
+
+/// Keep the two most from the first roll.
+pub type DivideSimulation = Game<DividePolicy>;
+
+/// Only roll the group(s) with the lowest amount.
+pub type MergeSimulation = Game<MergePolicy>;
+
+/// Keep every face with at least a threshold of dice.
+pub type ThresholdSimulation = Game<ThresholdPolicy>;
+
+/// A game with an externally defined keep policy.
+pub type CustomSimulation = Game<Box<dyn KeepPolicy>>;
+
+/// The numbers of sides that [`SimulationType::fast`] builds games with fixed-size buckets for.
+pub const FIXED_SIDES: [Num; 5] = [6, 8, 10, 12, 20];
+
+/// [`NaiveSimulation`], with `SIDES`-sided die and fixed-size buckets.
+pub type FixedNaiveSimulation<const SIDES: usize> = Game<NaivePolicy, (), [Num; SIDES]>;
+
+/// [`DivideSimulation`], with `SIDES`-sided die and fixed-size buckets.
+pub type FixedDivideSimulation<const SIDES: usize> = Game<DividePolicy, (), [Num; SIDES]>;
+
+/// [`MergeSimulation`], with `SIDES`-sided die and fixed-size buckets.
+pub type FixedMergeSimulation<const SIDES: usize> = Game<MergePolicy, (), [Num; SIDES]>;
+
+/// Always keep the most from the first roll.
+#[derive(Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NaivePolicy {
+    mode: Option<Num>,
+}
+
+/// Keep the two most from the first roll.
+#[derive(Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DividePolicy;
+
+/// Only roll the group(s) with the lowest amount.
+#[derive(Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MergePolicy;
+
+/// Keep every face with at least a threshold of dice, and re-roll the rest.
+///
+/// So that every game ends, the mode is always kept, even below the threshold, and once the kept faces would hold every
+/// die, only the mode is kept.  The family then spans the heuristics in between keeping every pair (a threshold of 2)
+/// and only ever the mode (a threshold above half of the dice, or of 1).
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ThresholdPolicy {
+    threshold: Num,
+}
+
+// NaivePolicy.
+
+impl KeepPolicy for NaivePolicy {
+    fn keep(&mut self, buckets: &mut KeptBuckets, _num_dice: Num) {
+        // Get the mode, and cache it.
+
+        let mode = self.mode.unwrap_or_else(|| buckets.analyze().mode);
+
+        self.mode = Some(mode);
+        let mode_bucket = mode as usize - 1;
+
+        // Zero out the buckets that are not the mode.
+
+        buckets.keep_only(&[mode_bucket]);
+    }
+
+    fn waits_for_last_die(&self) -> bool {
+        true
+    }
+
+    fn reset(&mut self) {
+        self.mode = None;
+    }
+
+    fn save(&self) -> Vec<Num> {
+        self.mode.into_iter().collect()
+    }
+
+    fn restore(&mut self, state: &[Num], num_sides: Num) -> Result<()> {
+        self.mode = match *state {
+            [] => None,
+            [mode] if (1..=num_sides).contains(&mode) => Some(mode),
+            [mode] => return Err(TenziError::InvalidState(format!("the naive policy keeps the face {}, which a {}-sided die does not have", mode, num_sides))),
+            _ => return Err(TenziError::InvalidState("the naive policy only saves the face it keeps".to_string())),
+        };
+
+        Ok(())
+    }
+}
+
+impl StrategyMeta for NaivePolicy {
+    fn name(&self) -> &str {
+        StrategyKind::Naive.name()
+    }
+
+    fn description(&self) -> &str {
+        "Always keep the most from the first roll."
+    }
+
+    fn fixed_sides(&self) -> &[Num] {
+        &FIXED_SIDES
+    }
+}
+
+// DividePolicy.
+
+impl KeepPolicy for DividePolicy {
+    fn keep(&mut self, buckets: &mut KeptBuckets, num_dice: Num) {
+        // Get the modes.  Need to compute every time, as it may change.
+
+        let (mode1, mode2) = buckets.analyze().top_two;
+
+        // As soon as one of the modes passes the midpoint, let's then move forward with only that one.
+
+        let (mode1_bucket, mode2_bucket) = if buckets[mode1 as usize - 1] >= num_dice / 2 {
+            (mode1 as usize - 1, mode1 as usize - 1)
+        } else {
+            (mode1 as usize - 1, mode2 as usize - 1)
+        };
+
+        // Zero out the buckets that are not the modes.
+
+        buckets.keep_only(&[mode1_bucket, mode2_bucket]);
+    }
+
+    fn waits_for_last_die(&self) -> bool {
+        // With a single kept face, that face has at least half of the dice, so it is the only one kept.
+
+        true
+    }
+}
+
+impl StrategyMeta for DividePolicy {
+    fn name(&self) -> &str {
+        StrategyKind::Divide.name()
+    }
+
+    fn description(&self) -> &str {
+        "Keep the two most from the first roll."
+    }
+
+    fn fixed_sides(&self) -> &[Num] {
+        &FIXED_SIDES
+    }
+}
+
+// MergePolicy.
+
+impl KeepPolicy for MergePolicy {
+    fn keep(&mut self, buckets: &mut KeptBuckets, _num_dice: Num) {
+        // Find the anti-modes (in the scratch arena, so that steps do not allocate), from a single pass over the buckets.
+
+        let analysis = buckets.analyze();
+        let anti_modes = buckets.collect_scratch(|counts, scratch| analysis.extend_anti_modes(counts, scratch));
+
+        // Zero out the buckets that are anti modes.
+
+        buckets.zero_faces(anti_modes);
+    }
+
+    fn waits_for_last_die(&self) -> bool {
+        // With a single kept face of at least two dice, the last die is the only anti-mode (unless it matches).
+
+        true
+    }
+}
+
+impl StrategyMeta for MergePolicy {
+    fn name(&self) -> &str {
+        StrategyKind::Merge.name()
+    }
+
+    fn description(&self) -> &str {
+        "Only roll the group(s) with the lowest amount."
+    }
+
+    fn fixed_sides(&self) -> &[Num] {
+        &FIXED_SIDES
+    }
+}
+
+// ThresholdPolicy.
+
+impl ThresholdPolicy {
+    /// Returns the policy that keeps every face with at least the given number of dice, which must be at least 1.
+    pub fn new(threshold: Num) -> Result<Self> {
+        match threshold {
+            0 => Err(TenziError::InvalidConfig("the threshold must be at least 1".to_string())),
+            threshold => Ok(Self { threshold }),
+        }
+    }
+
+    /// Returns the fewest dice that a face needs to be kept.
+    pub fn threshold(&self) -> Num {
+        self.threshold
+    }
+}
+
+impl KeepPolicy for ThresholdPolicy {
+    fn keep(&mut self, buckets: &mut KeptBuckets, num_dice: Num) {
+        let mode_bucket = buckets.analyze().mode as usize - 1;
+
+        // Zero out the buckets below the threshold, but the mode.
+
+        buckets.retain(|k, count| count >= self.threshold || k == mode_bucket);
+
+        // Keeping every die on more than one face would leave nothing to roll, so roll all but the mode.
+
+        if buckets.num_kept() == num_dice && buckets[mode_bucket] != num_dice {
+            buckets.keep_only(&[mode_bucket]);
+        }
+    }
+
+    fn waits_for_last_die(&self) -> bool {
+        // The last die is either below the threshold, or (at a threshold of 1) would complete every die on two faces.
+
+        true
+    }
+}
+
+// Tests.
+
+// The games are rolled with seeded dice, which need `std`.
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::rand::SeededDice;
+
+    #[test]
+    fn test_naive_simulation() {
+        let num_sides = 6;
+        let num_dice = 10;
+        let mut sim = NaiveSimulation::new(num_sides, num_dice).with_rng(SeededDice::new(42));
+
+        let expected_mode = 4;
+        let expected_steps = 11;
+        let expected_rols = 35;
+
+        while !sim.done() {
+            sim.step();
+        }
+
+        let mode = sim.policy().mode.unwrap();
+
+        assert_eq!(mode, expected_mode);
+        assert_eq!(sim.num_steps(), expected_steps);
+        assert_eq!(sim.num_rolls(), expected_rols);
+    }
+
+    #[test]
+    fn test_naive_simulation_step() {
+        let num_sides = 6;
+        let num_dice = 10;
+        let mut sim = NaiveSimulation::new(num_sides, num_dice).with_rng(SeededDice::new(42));
+        
+        assert_eq!(sim.buckets(), &[0, 0, 0, 0, 0, 0]);
+        sim.step();
+        assert_eq!(sim.buckets(), &[0, 0, 0, 3, 0, 0]);
+        sim.step();
+        assert_eq!(sim.buckets(), &[0, 0, 0, 5, 0, 0]);
+        sim.step();
+        assert_eq!(sim.buckets(), &[0, 0, 0, 7, 0, 0]);
+    }
+
+    #[test]
+    fn test_naive_simulation_initial_state() {
+        let num_sides = 6;
+        let num_dice = 10;
+        let mut sim = SimulationType::Naive(NaiveSimulation::new(num_sides, num_dice)).with_initial_state(&[0, 0, 6, 0, 0, 0]).with_rng(SeededDice::new(42));
+        let strategy = sim.as_strategy_mut();
+
+        assert_eq!(strategy.num_to_roll(), 4);
+        strategy.step();
+        assert_eq!(strategy.buckets(), &[0, 0, 7, 0, 0, 0]);
+        assert_eq!(strategy.num_rolls(), 4);
+    }
+
+    #[test]
+    fn test_initial_state_done() {
+        let num_sides = 6;
+        let num_dice = 10;
+        let mut sim = SimulationType::Merge(MergeSimulation::new(num_sides, num_dice)).with_initial_state(&[0, 10, 0, 0, 0, 0]);
+        let strategy = sim.as_strategy_mut();
+
+        assert!(strategy.done());
+        assert_eq!(strategy.num_rolls(), 0);
+        assert_eq!(strategy.num_steps(), 0);
+    }
+
+    #[test]
+    fn test_reset_many_sides() {
+        // A reset only touches the buckets with dice, which must still leave every other bucket empty.
+
+        let mut state = vec![0; 100_000];
+        state[50_000] = 2;
+
+        let mut sim = NaiveSimulation::new(100_000, 3).with_rng(SeededDice::new(42));
+        sim.set_initial_state(&state);
+        sim.step();
+        sim.reset();
+
+        assert_eq!(sim.buckets(), state.as_slice());
+        assert_eq!(sim.num_to_roll(), 1);
+
+        sim.set_initial_state(&vec![0; 100_000]);
+
+        assert!(sim.buckets().iter().all(|&count| count == 0));
+        assert_eq!(sim.num_to_roll(), 3);
+    }
+
+    #[test]
+    fn test_nonzero_mask() {
+        // The mask follows the buckets through a game, until the die has too many sides for one.
+
+        for num_sides in [6, 100, 200] {
+            let mut sim = MergeSimulation::new(num_sides, 50).with_rng(SeededDice::new(42));
+
+            while !sim.done() {
+                sim.step();
+
+                let listed = sim.nonzero.iter().copied().filter(|&k| sim.buckets()[k] != 0).collect::<Vec<_>>();
 
-impl SimulationType {
-    pub fn as_strategy_mut(&mut self) -> &mut dyn Strategy {
-        match self {
-            SimulationType::Naive(sim) => sim as &mut dyn Strategy,
-            SimulationType::Divide(sim) => sim as &mut dyn Strategy,
-            SimulationType::Merge(sim) => sim as &mut dyn Strategy,
+                assert!(sim.nonzero.is_sorted());
+                assert_eq!(listed, (0..num_sides as usize).filter(|&k| sim.buckets()[k] != 0).collect::<Vec<_>>());
+
+                if num_sides as usize <= FaceMask::MAX_FACES {
+                    assert_eq!(sim.nonzero.mask(), FaceMask::of(sim.buckets()));
+                }
+            }
         }
     }
-}
 
-// Traits.
+    #[test]
+    fn test_huge_pool() {
+        // The first steps roll their pools in parallel, and still count every die.
 
-/// A trait for a simulator that has "tracked" values.
-pub trait Tracked: Send + Sync {
-    /// Returns the number of rolls.
-    fn num_rolls(&self) -> Num;
+        let mut sim = NaiveSimulation::new(6, 200_000);
 
-    /// Returns the number of steps.
-    fn num_steps(&self) -> Num;
+        sim.step();
+        assert_eq!(sim.num_rolls(), 200_000);
 
-    /// Returns whether or not a "tenzi" has been achieved.
-    fn done(&self) -> bool;
-}
+        while !sim.done() {
+            sim.step();
+        }
 
-/// A trait for a simulator that allows "tracked" values to be set.
-trait SetTracked: Tracked {
-    /// Sets the number of rolls.
-    fn set_num_rolls(&mut self, num_rolls: Num);
+        assert_eq!(sim.buckets().iter().sum::<Num>(), 200_000);
+    }
 
-    /// Sets the number of steps.
-    fn set_num_steps(&mut self, num_steps: Num);
+    #[test]
+    fn test_step_with() {
+        // Cycle through the faces, so that every face is rolled equally (and the merge policy re-rolls only the first).
 
-    /// Sets whether or not a "tenzi" has been achieved.
-    fn set_done(&mut self, done: bool);
-}
+        let mut face = 0;
+        let mut roll = |num_sides: Num| {
+            face = face % num_sides + 1;
+            face
+        };
 
-/// A simulation for the game "tenzi".
-trait Simulation: Tracked + SetTracked {
-    /// Returns a mutable reference to the dice.
-    fn buckets(&mut self) -> &mut [Num];
+        let mut sim = MergeSimulation::new(6, 12);
+        sim.step_with(&mut roll);
 
-    /// Returns the number of sides on the die.
-    fn num_sides(&self) -> Num;
+        assert_eq!(sim.buckets(), &[0, 2, 2, 2, 2, 2]);
+        assert_eq!(sim.num_to_roll(), 2);
+        assert_eq!(sim.num_rolls(), 12);
+    }
 
-    /// Returns the number of dice to roll.
-    fn num_to_roll(&self) -> Num;
-}
+    #[test]
+    fn test_threshold_simulation() {
+        // Roll four 1s, three 2s, two 3s, and a 4.
+
+        let faces = [1, 1, 1, 1, 2, 2, 2, 3, 3, 4];
+        let keep = |threshold: Num| {
+            let mut faces = faces.iter().copied();
+            let mut sim = ThresholdSimulation::with_policy(ThresholdPolicy::new(threshold).unwrap(), 6, 10);
+            sim.step_with(&mut |_| faces.next().unwrap());
+            (sim.buckets().to_vec(), sim.num_to_roll())
+        };
 
-/// A simulation strategy for the game "tenzi".
-#[allow(private_bounds)]
-pub trait Strategy: Simulation {
-    /// Rolls the dice, and returns the number rolled.
-    fn roll(&mut self) {
-        let num_to_roll = self.num_to_roll();
-        let num_sides = self.num_sides();
-        let buckets = self.buckets();
+        assert_eq!(keep(2), (vec![4, 3, 2, 0, 0, 0], 1));
+        assert_eq!(keep(3), (vec![4, 3, 0, 0, 0, 0], 3));
 
-        let mut num_rolls = 0;
+        // A threshold above the mode keeps only the mode, and so does keeping every die on more than one face.
 
-        for _ in 0..num_to_roll {
-            let roll = roll(num_sides);
-            buckets[roll - 1] += 1;
-            num_rolls += 1;
-        }
+        assert_eq!(keep(5), (vec![4, 0, 0, 0, 0, 0], 6));
+        assert_eq!(keep(1), (vec![4, 0, 0, 0, 0, 0], 6));
 
-        self.set_num_rolls(self.num_rolls() + num_rolls);
+        assert!(ThresholdPolicy::new(0).is_err());
     }
-    
-    /// Takes the rolls, and returns the indexes to re-roll.
-    /// Zeroes out the rolls that the strategy would like re-rolled.
-    /// The dice that are not zeroed out are the ones that are kept.
-    /// 
-    /// We use this method as it prevents unnecessary allocations just to keep track of which dice to re-roll.
-    fn step(&mut self);
-}
 
-// Declarative macros for the different simulation strategies.
+    #[test]
+    fn test_reset() {
+        let mut sim = SimulationType::Naive(NaiveSimulation::new(6, 10)).with_initial_state(&[0, 0, 6, 0, 0, 0]);
+        let strategy = sim.as_strategy_mut();
 
-macro_rules! impl_tracked {
-    ($type:ty) => {
-        impl Tracked for $type {
-            fn num_rolls(&self) -> Num {
-                self.num_rolls
-            }
+        while !strategy.done() {
+            strategy.step();
+        }
 
-            fn num_steps(&self) -> Num {
-                self.num_steps
-            }
+        strategy.reset();
 
-            fn done(&self) -> bool {
-                self.done
-            }
-        }
-    };
-}
+        assert_eq!(strategy.buckets(), &[0, 0, 6, 0, 0, 0]);
+        assert_eq!(strategy.num_to_roll(), 4);
+        assert_eq!(strategy.num_rolls(), 0);
+        assert_eq!(strategy.num_steps(), 0);
+        assert!(!strategy.done());
 
-macro_rules! impl_set_tracked {
-    ($type:ty) => {
-        impl SetTracked for $type {
-            fn set_num_rolls(&mut self, num_rolls: Num) {
-                self.num_rolls = num_rolls;
-            }
+        let SimulationType::Naive(naive) = &sim else {
+            unreachable!();
+        };
 
-            fn set_num_steps(&mut self, num_steps: Num) {
-                self.num_steps = num_steps;
-            }
+        assert_eq!(naive.policy().mode, None);
+    }
 
-            fn set_done(&mut self, done: bool) {
-                self.done = done;
+    #[test]
+    fn test_custom_simulation() {
+        /// Keeps every die that shows a six.
+        #[derive(Clone)]
+        struct Sixes;
+
+        impl KeepPolicy for Sixes {
+            fn keep(&mut self, buckets: &mut KeptBuckets, _num_dice: Num) {
+                buckets.retain(|k, _| k == 5);
             }
         }
-    };
-}
 
-macro_rules! impl_simulation {
-    ($type:ty) => {
-        impl Simulation for $type {
-            fn buckets(&mut self) -> &mut [Num] {
-                &mut self.buckets
-            }
-        
-            fn num_sides(&self) -> Num {
-                self.num_sides
-            }
+        let mut sim = SimulationType::custom(Sixes, 6, 10).clone();
+        let strategy = sim.as_strategy_mut();
 
-            fn num_to_roll(&self) -> Num {
-                self.num_to_roll
+        while !strategy.done() {
+            strategy.step();
+        }
+
+        assert_eq!(strategy.buckets(), &[0, 0, 0, 0, 0, 10]);
+        assert!(strategy.num_rolls() >= 10);
+    }
+
+    #[test]
+    fn test_rules() {
+        /// Re-rolls every die.
+        #[derive(Clone)]
+        struct Nothing;
+
+        impl KeepPolicy for Nothing {
+            fn keep(&mut self, buckets: &mut KeptBuckets, _num_dice: Num) {
+                buckets.clear();
             }
         }
-    };
-}
 
-/// Always keep the most from the first roll.
-#[derive(Clone)]
-pub struct NaiveSimulation {
-    buckets: Vec<Num>,
-    num_dice: Num,
-    num_sides: Num,
-    num_to_roll: Num,
+        fn play(sim: &mut SimulationType, faces: &[Num]) {
+            let mut faces = faces.iter().copied();
+            sim.as_strategy_mut().step_with(&mut |_| faces.next().unwrap());
+        }
 
-    num_rolls: Num,
-    num_steps: Num,
-    mode: Option<Num>,
-    done: bool,
-}
+        // The largest groups of the dice that the policy would re-roll are kept instead, until only the limit is left.
 
-impl NaiveSimulation {
-    pub fn new(num_sides: Num, num_dice: Num) -> Self {
-        Self {
-            buckets: vec![0; num_sides],
-            num_dice,
-            num_sides,
-            num_to_roll: num_dice,
+        let mut sim = SimulationType::custom(Nothing, 6, 6).with_rules(RuleSet { max_rerolls: Some(2), ..RuleSet::default() }).unwrap();
+        play(&mut sim, &[1, 1, 1, 2, 2, 3]);
 
-            num_rolls: 0,
-            num_steps: 0,
-            mode: None,
-            done: false,
-        }
-    }
-}
+        assert_eq!(sim.buckets(), &[3, 1, 0, 0, 0, 0]);
+        assert_eq!(sim.as_strategy().num_to_roll(), 2);
 
-/// Keep the two most from the first roll.
-#[derive(Clone)]
-pub struct DivideSimulation {
-    buckets: Vec<Num>,
-    num_dice: Num,
-    num_sides: Num,
-    num_to_roll: Num,
+        // A roll of at least two dice keeps one more die, and a roll of the last die does not.
 
-    num_rolls: Num,
-    num_steps: Num,
-    done: bool,
-}
+        let mut sim = SimulationType::custom(Nothing, 6, 3).with_rules(RuleSet { keep_new_die: true, ..RuleSet::default() }).unwrap();
+        play(&mut sim, &[4, 2, 4]);
+        assert_eq!(sim.buckets(), &[0, 0, 0, 1, 0, 0]);
+        play(&mut sim, &[2, 5]);
+        assert_eq!(sim.buckets(), &[0, 1, 0, 1, 0, 0]);
+        play(&mut sim, &[3]);
+        assert_eq!(sim.buckets(), &[0, 0, 0, 0, 0, 0]);
 
-impl DivideSimulation {
-    pub fn new(num_sides: Num, num_dice: Num) -> Self {
-        Self {
-            buckets: vec![0; num_sides],
-            num_dice,
-            num_sides,
-            num_to_roll: num_dice,
+        // The kept dice of the locked face are never re-rolled, and every die kept without a "tenzi" only keeps them.
 
-            num_rolls: 0,
-            num_steps: 0,
-            done: false,
-        }
-    }
-}
+        let mut sim = SimulationType::fast(StrategyKind::Divide, 6, 6).with_rules(RuleSet { lock_kept: true, ..RuleSet::default() }).unwrap();
+        play(&mut sim, &[1, 1, 2, 2, 3, 4]);
+        assert_eq!(sim.buckets(), &[2, 2, 0, 0, 0, 0]);
+        play(&mut sim, &[2, 2]);
+        assert_eq!(sim.buckets(), &[2, 0, 0, 0, 0, 0]);
 
-/// Only roll the group(s) with the lowest amount.
-#[derive(Clone)]
-pub struct MergeSimulation {
-    buckets: Vec<Num>,
-    num_dice: Num,
-    num_sides: Num,
-    num_to_roll: Num,
+        // Every die rolled after the given number of steps counts as two rolls.
 
-    num_rolls: Num,
-    num_steps: Num,
-    done: bool,
-}
+        let mut sim = SimulationType::custom(Nothing, 6, 2).with_rules(RuleSet { double_cost_after: Some(1), ..RuleSet::default() }).unwrap();
+        play(&mut sim, &[1, 2]);
+        play(&mut sim, &[1, 2]);
+        assert_eq!(sim.as_strategy().num_rolls(), 6);
 
-impl MergeSimulation {
-    pub fn new(num_sides: Num, num_dice: Num) -> Self {
-        Self {
-            buckets: vec![0; num_sides],
-            num_dice,
-            num_sides,
-            num_to_roll: num_dice,
+        // Every strategy still finishes under every rule, without ever re-rolling more than the limit (even when the game
+        // would stall on its locked face).
 
-            num_rolls: 0,
-            num_steps: 0,
-            done: false,
+        let limited = RuleSet { max_rerolls: Some(3), keep_new_die: true, double_cost_after: Some(5), lock_kept: false };
+
+        for rules in [limited, RuleSet { lock_kept: true, ..limited }] {
+            for kind in StrategyKind::ALL {
+                for seed in 0..100 {
+                    let mut sim = SimulationType::fast(kind, 6, 10).with_rules(rules).unwrap().with_rng(SeededDice::new(seed));
+                    let strategy = sim.as_strategy_mut();
+
+                    strategy.step();
+
+                    while !strategy.done() {
+                        assert!(strategy.num_to_roll() <= 3);
+                        strategy.step();
+                    }
+                }
+            }
         }
-    }
-}
 
-// Implementations.
+        // Rules under which a game could never end are rejected, and the game keeps the rules it had.
 
-// NaiveSimulation.
+        let never_ends = RuleSet { max_rerolls: Some(0), ..RuleSet::default() };
 
-impl_tracked!(NaiveSimulation);
-impl_set_tracked!(NaiveSimulation);
-impl_simulation!(NaiveSimulation);
+        assert!(matches!(SimulationType::custom(Nothing, 6, 6).with_rules(never_ends), Err(TenziError::InvalidConfig(_))));
 
-impl Strategy for NaiveSimulation {
-    fn step(&mut self) {
-        // Perform a roll.
+        let mut sim = SimulationType::custom(Nothing, 6, 2).with_rules(RuleSet { double_cost_after: Some(1), ..RuleSet::default() }).unwrap();
 
-        self.roll();
+        assert!(matches!(sim.as_strategy_mut().set_rules(never_ends), Err(TenziError::InvalidConfig(_))));
 
-        // Get the mode, and cache it.
+        play(&mut sim, &[1, 2]);
+        play(&mut sim, &[1, 2]);
+        assert_eq!(sim.as_strategy().num_rolls(), 6);
+    }
 
-        let mode = self.mode.unwrap_or_else(|| {
-            mode::mode_from_counts(&self.buckets)
-        });
+    #[test]
+    fn test_observer() {
+        /// Records the phases it observes, and the number of dice in the buckets at each.
+        #[derive(Default)]
+        struct Recorder {
+            phases: Vec<(&'static str, Num)>,
+        }
 
-        self.mode = Some(mode);
-        let mode_bucket = mode - 1;
+        impl Observer for Recorder {
+            fn on_roll(&mut self, view: &GameView) {
+                self.phases.push(("roll", view.buckets.iter().sum()));
+            }
 
-        // Zero out the buckets that are not the mode.
+            fn on_keep(&mut self, view: &GameView) {
+                self.phases.push(("keep", view.buckets.iter().sum()));
+            }
 
-        for k in 0..self.buckets.len() {
-            if k != mode_bucket {
-                self.buckets[k] = 0;
+            fn on_done(&mut self, view: &GameView) {
+                self.phases.push(("done", view.num_steps));
             }
         }
 
-        // Check if we are done; otherwise, compute the number to roll on the next step (i.e., the total dice that are not in the mode bucket).
+        let mut sim = NaiveSimulation::new(6, 10).with_observer(Recorder::default());
 
-        if self.buckets[mode_bucket] == self.num_dice {
-            self.set_done(true);
-        } else {
-            self.num_to_roll = self.num_dice - self.buckets[mode_bucket];
+        while !sim.done() {
+            sim.step();
         }
 
-        // Update the state.
+        let num_steps = sim.num_steps();
+        let phases = sim.into_observer().phases;
 
-        self.set_num_steps(self.num_steps() + 1);
+        assert_eq!(phases.len(), 2 * num_steps as usize + 1);
+        assert_eq!(phases[0], ("roll", 10));
+        assert_eq!(phases[phases.len() - 2], ("keep", 10));
+        assert_eq!(phases[phases.len() - 1], ("done", num_steps));
+        assert!(phases.iter().filter(|(phase, _)| *phase == "roll").all(|&(_, count)| count == 10));
     }
-}
 
-// DivideSimulation.
+    #[test]
+    fn test_steps() {
+        let mut sim = NaiveSimulation::new(6, 10);
+
+        let snapshots = sim.steps().collect::<Vec<_>>();
+        let last = snapshots.last().unwrap();
+
+        assert_eq!(snapshots.len(), sim.num_steps() as usize);
+        assert_eq!(snapshots[0].num_steps, 1);
+        assert_eq!(snapshots[0].num_rolls, 10);
+        assert!(snapshots[..snapshots.len() - 1].iter().all(|s| !s.done));
+        assert!(last.done);
+        assert_eq!(last.buckets.iter().sum::<Num>(), 10);
+        assert_eq!(sim.steps().next(), None);
+    }
 
-impl_tracked!(DivideSimulation);
-impl_set_tracked!(DivideSimulation);
-impl_simulation!(DivideSimulation);
+    #[test]
+    fn test_steps_stop_early() {
+        let mut sim = SimulationType::Merge(MergeSimulation::new(6, 20));
 
-impl Strategy for DivideSimulation {
-    fn step(&mut self) {
-        // Perform a roll.
+        let first_two = sim.steps().take(2).collect::<Vec<_>>();
 
-        self.roll();
+        assert_eq!(first_two.len(), 2);
+        assert_eq!(sim.as_strategy().num_steps(), 2);
+        assert_eq!(sim.steps().next().unwrap().num_steps, 3);
+    }
 
-        // Get the modes.  Need to compute every time, as it may change.
+    #[test]
+    fn test_fixed_matches_vec() {
+        // Seed both games the same, so they see the same rolls.
 
-        let (mode1, mode2) = mode::top_two_modes_from_counts(&self.buckets);
+        let play = |sim: SimulationType| {
+            let mut sim = sim.with_rng(SeededDice::new(42));
+            (sim.steps().collect::<Vec<_>>(), sim.name())
+        };
 
-        // As soon as one of the modes passes the midpoint, let's then move forward with only that one.
+        for kind in StrategyKind::ALL {
+            let (fixed, fixed_name) = play(SimulationType::fast(kind, 6, 10));
+            let (vec, vec_name) = play(SimulationType::new(kind, 6, 10));
 
-        let (mode1_bucket, mode2_bucket) = if self.buckets[mode1 - 1] >= self.num_dice / 2 {
-            (mode1 - 1, mode1 - 1)
-        } else {
-            (mode1 - 1, mode2 - 1)
-        };
+            assert_eq!(fixed, vec);
+            assert_eq!(fixed_name, vec_name);
+        }
 
-        // Zero out the buckets that are not the modes.
+        assert!(matches!(SimulationType::fast(StrategyKind::Naive, 6, 10), SimulationType::Fixed(StrategyKind::Naive, _)));
+        assert!(matches!(SimulationType::fast(StrategyKind::Naive, 7, 10), SimulationType::Naive(_)));
+    }
 
-        for k in 0..self.buckets.len() {
-            if k != mode1_bucket && k != mode2_bucket {
-                self.buckets[k] = 0;
+    #[test]
+    fn test_waits_for_last_die() {
+        /// Plays the policy without the engine's wait for the last die.
+        #[derive(Clone)]
+        struct Slow<P>(P);
+
+        impl<P: KeepPolicy + Clone + 'static> KeepPolicy for Slow<P> {
+            fn keep(&mut self, buckets: &mut KeptBuckets, num_dice: Num) {
+                self.0.keep(buckets, num_dice)
             }
         }
 
-        // Check if we are done; otherwise, compute the number to roll on the next step (i.e., the total dice that are not in the mode bucket).
-
-        let num_to_keep = self.buckets.iter().sum::<Num>();
+        fn play<P: KeepPolicy>(game: Game<P>, seed: u64) -> Vec<Snapshot> {
+            game.with_rng(SeededDice::new(seed)).steps().collect()
+        }
 
-        if num_to_keep == self.num_dice {
-            self.set_done(true);
-        } else {
-            self.num_to_roll = self.num_dice - num_to_keep;
+        for seed in 0..20 {
+            assert_eq!(play(NaiveSimulation::new(6, 10), seed), play(Game::with_policy(Slow(NaivePolicy::default()), 6, 10), seed));
+            assert_eq!(play(DivideSimulation::new(6, 10), seed), play(Game::with_policy(Slow(DividePolicy), 6, 10), seed));
+            assert_eq!(play(MergeSimulation::new(6, 3), seed), play(Game::with_policy(Slow(MergePolicy), 6, 3), seed));
         }
+    }
 
-        // Update the state.
+    #[test]
+    fn test_kept_buckets() {
+        let mut counts = [3, 1, 4, 2];
+        let nonzero = [0, 1, 2, 3];
+        let mut scratch = Scratch::new();
+        let mask = FaceMask::of(&counts);
+        let mut buckets = KeptBuckets::new(&mut counts, &nonzero, mask, 10, None, &mut scratch);
+
+        buckets.zero(1);
+        assert_eq!(buckets.num_kept(), 9);
+
+        buckets.retain(|_, count| count >= 3);
+        assert_eq!(buckets.num_kept(), 7);
+        assert_eq!(&*buckets, &[3, 0, 4, 0]);
+        assert_eq!(buckets.mask(), FaceMask::from_faces([0, 2]));
+
+        buckets.clear();
+        assert_eq!(buckets.num_kept(), 0);
+        assert_eq!(buckets.mask(), Some(FaceMask::EMPTY));
+    }
 
-        self.set_num_steps(self.num_steps() + 1);
+    #[test]
+    fn test_kept_buckets_scratch() {
+        let mut counts = [3, 1, 4, 1];
+        let nonzero = [0, 1, 2, 3];
+        let mut scratch = Scratch::new();
+        let mask = FaceMask::of(&counts);
+        let mut buckets = KeptBuckets::new(&mut counts, &nonzero, mask, 9, None, &mut scratch);
+
+        let anti_modes = buckets.collect_scratch(|counts, scratch| histogram::extend_anti_modes(counts, scratch));
+        assert_eq!(buckets.scratch().get(anti_modes), &[2, 4]);
+
+        buckets.zero_faces(anti_modes);
+        assert_eq!(buckets.num_kept(), 7);
+        assert_eq!(&*buckets, &[3, 0, 4, 0]);
+        assert_eq!(buckets.mask(), FaceMask::from_faces([0, 2]));
     }
-}
 
-// MergeSimulation.
+    #[test]
+    fn test_scratch_is_reused() {
+        // The arena is reset before every keep, so it only ever holds one keep's temporaries.
 
-impl_tracked!(MergeSimulation);
-impl_set_tracked!(MergeSimulation);
-impl_simulation!(MergeSimulation);
+        let mut sim = MergeSimulation::new(100, 100).with_rng(SeededDice::new(42));
 
-impl Strategy for MergeSimulation {
-    fn step(&mut self) {
-        // Perform a roll.
+        while !sim.done() {
+            sim.step();
+            assert!(sim.scratch.len() <= 100);
+        }
+    }
 
-        self.roll();
+    #[test]
+    fn test_kept_buckets_keep_only() {
+        // Whether the buckets are zeroed by their mask, filled at once (dense), or zeroed one at a time (sparse), the
+        // same buckets are kept.
+
+        let nonzero = [0, 1, 2, 3];
+        let mut scratch = Scratch::new();
+
+        for masked in [true, false] {
+            let mut counts = [3, 1, 4, 2];
+            let mask = FaceMask::of(&counts).filter(|_| masked);
+            let mut buckets = KeptBuckets::new(&mut counts, &nonzero, mask, 10, None, &mut scratch);
+
+            buckets.keep_only(&[2, 0, 2]);
+            assert_eq!(buckets.num_kept(), 7);
+            assert_eq!(&*buckets, &[3, 0, 4, 0]);
+            assert_eq!(buckets.mask(), FaceMask::from_faces([0, 2]).filter(|_| masked));
+        }
 
-        // Find the anti-modes.
+        let mut counts = vec![0; 100];
+        counts[10] = 6;
+        counts[90] = 4;
+        let nonzero = [10, 90];
+        let mut buckets = KeptBuckets::new(&mut counts, &nonzero, None, 10, None, &mut scratch);
 
-        let anti_modes = mode::anti_modes(&self.buckets);
+        buckets.keep_only(&[90]);
+        assert_eq!(buckets.num_kept(), 4);
+        assert!(buckets.iter().enumerate().all(|(k, &count)| count == if k == 90 { 4 } else { 0 }));
+    }
 
-        // Zero out the buckets that are anti modes.
+    #[test]
+    fn test_inline_buckets() {
+        // Typical dice stay inline, and larger ones spill to the heap (and still play the same).
 
-        for k in anti_modes {
-            self.buckets[k - 1] = 0;
-        }
+        assert!(!InlineBuckets::zeroed(20).spilled());
+        assert!(InlineBuckets::zeroed(100).spilled());
 
-        // Check if we are done; otherwise, compute the number to roll on the next step (i.e., the total dice that are not in the mode bucket).
+        let inline = MergeSimulation::new(100, 50).with_rng(SeededDice::new(42)).steps().collect::<Vec<_>>();
+        let heap = Game::<MergePolicy, (), Vec<Num>>::new(100, 50).with_rng(SeededDice::new(42)).steps().collect::<Vec<_>>();
 
-        let num_to_keep = self.buckets.iter().sum::<Num>();
+        assert_eq!(inline, heap);
+    }
 
-        if num_to_keep == self.num_dice {
-            self.set_done(true);
-        } else {
-            self.num_to_roll = self.num_dice - num_to_keep;
-        }
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_round_trip() {
+        let mut sim = SimulationType::Naive(NaiveSimulation::new(6, 10)).with_rng(SeededDice::new(42));
+        sim.as_strategy_mut().step();
 
-        // Update the state.
+        let json = serde_json::to_string(&sim).unwrap();
+        let parsed = serde_json::from_str::<SimulationType>(&json).unwrap();
+
+        let SimulationType::Naive(parsed) = parsed else {
+            panic!("expected a naive simulation");
+        };
 
-        self.set_num_steps(self.num_steps() + 1);
+        assert_eq!(Snapshot::of(&parsed), Snapshot::of(sim.as_strategy()));
+        assert_eq!(parsed.policy().mode, Some(4));
     }
-}
 
-// Tests.
+    #[test]
+    fn test_save_restore() {
+        let mut sim = SimulationType::Naive(NaiveSimulation::new(6, 10)).with_rng(SeededDice::new(42));
+        let strategy = sim.as_strategy_mut();
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+        strategy.step();
+        strategy.step();
 
-    #[test]
-    fn test_naive_simulation() {
-        let num_sides = 6;
-        let num_dice = 10;
-        let mut sim = NaiveSimulation::new(num_sides, num_dice);
+        let saved = strategy.save();
+        assert_eq!(saved.policy, vec![4]);
 
-        let expected_mode = 5;
-        let expected_steps = 20;
-        let expected_rols = 58;
+        // Restore into a fresh game, and check that it plays on exactly like the original.
 
-        while !sim.done() {
-            sim.step();
-        }
+        let mut restored = SimulationType::fast(StrategyKind::Naive, 6, 10).with_rng(SeededDice::new(7));
+        restored.as_strategy_mut().restore(&saved).unwrap();
+        sim.as_strategy_mut().set_rng(Box::new(SeededDice::new(7)));
 
-        let mode = sim.mode.unwrap();
+        assert_eq!(restored.steps().collect::<Vec<_>>(), sim.steps().collect::<Vec<_>>());
+        assert!(matches!(NaiveSimulation::new(6, 12).restore(&saved), Err(TenziError::InvalidState(_))));
+        assert!(matches!(MergeSimulation::new(6, 10).restore(&saved), Err(TenziError::InvalidState(_))));
 
-        assert_eq!(mode, expected_mode);
-        assert_eq!(sim.num_steps(), expected_steps);
-        assert_eq!(sim.num_rolls(), expected_rols);
+        // The naive policy only restores a face that the die has.
+
+        for mode in [0, 7] {
+            let saved = SavedGame { policy: vec![mode], ..saved.clone() };
+            assert!(matches!(NaiveSimulation::new(6, 10).restore(&saved), Err(TenziError::InvalidState(_))), "{}", mode);
+        }
     }
 
     #[test]
-    fn test_naive_simulation_step() {
-        let num_sides = 6;
-        let num_dice = 10;
-        let mut sim = NaiveSimulation::new(num_sides, num_dice);
-        
-        assert_eq!(sim.buckets(), &[0, 0, 0, 0, 0, 0]);
-        sim.step();
-        assert_eq!(sim.buckets(), &[0, 0, 0, 0, 3, 0]);
-        sim.step();
-        assert_eq!(sim.buckets(), &[0, 0, 0, 0, 5, 0]);
-        sim.step();
-        assert_eq!(sim.buckets(), &[0, 0, 0, 0, 6, 0]);
+    fn test_check_state() {
+        assert!(check_state(&[0, 0, 6, 0, 0, 0], 6, 10).is_ok());
+        assert!(check_state(&[0, 0, 6, 0, 0], 6, 10).is_err());
+        assert!(check_state(&[0, 0, 6, 0, 0, 5], 6, 10).is_err());
     }
 
     #[test]
     fn test_divide_simulation() {
         let num_sides = 6;
         let num_dice = 20;
-        let mut sim = DivideSimulation::new(num_sides, num_dice);
+        let mut sim = DivideSimulation::new(num_sides, num_dice).with_rng(SeededDice::new(42));
 
-        let expected_steps = 26;
-        let expected_rols = 129;
+        let expected_steps = 23;
+        let expected_rols = 86;
 
         while !sim.done() {
             sim.step();
@@ -421,29 +2179,29 @@ mod tests {
     fn test_divide_simulation_step() {
         let num_sides = 6;
         let num_dice = 20;
-        let mut sim = DivideSimulation::new(num_sides, num_dice);
+        let mut sim = DivideSimulation::new(num_sides, num_dice).with_rng(SeededDice::new(42));
         
         assert_eq!(sim.buckets(), &[0, 0, 0, 0, 0, 0]);
         sim.step();
-        assert_eq!(sim.buckets(), &[0, 0, 0, 4, 6, 0]);
+        assert_eq!(sim.buckets(), &[6, 0, 0, 7, 0, 0]);
         sim.step();
-        assert_eq!(sim.buckets(), &[0, 0, 0, 6, 7, 0]);
+        assert_eq!(sim.buckets(), &[7, 0, 0, 8, 0, 0]);
         sim.step();
-        assert_eq!(sim.buckets(), &[0, 0, 0, 7, 7, 0]);
+        assert_eq!(sim.buckets(), &[7, 0, 0, 9, 0, 0]);
         sim.step();
-        assert_eq!(sim.buckets(), &[0, 0, 0, 9, 7, 0]);
+        assert_eq!(sim.buckets(), &[0, 0, 0, 10, 0, 0]);
         sim.step();
-        assert_eq!(sim.buckets(), &[0, 0, 0, 11, 0, 0]);
+        assert_eq!(sim.buckets(), &[0, 0, 0, 12, 0, 0]);
     }
 
     #[test]
     fn test_merge_simulation() {
         let num_sides = 6;
         let num_dice = 20;
-        let mut sim = MergeSimulation::new(num_sides, num_dice);
+        let mut sim = MergeSimulation::new(num_sides, num_dice).with_rng(SeededDice::new(42));
 
-        let expected_steps = 46;
-        let expected_rols = 111;
+        let expected_steps = 37;
+        let expected_rols = 86;
 
         while !sim.done() {
             sim.step();
@@ -457,31 +2215,32 @@ mod tests {
     fn test_merge_simulation_step() {
         let num_sides = 6;
         let num_dice = 20;
-        let mut sim = MergeSimulation::new(num_sides, num_dice);
+        let mut sim = MergeSimulation::new(num_sides, num_dice).with_rng(SeededDice::new(42));
         
         assert_eq!(sim.buckets(), &[0, 0, 0, 0, 0, 0]);
         sim.step();
-        assert_eq!(sim.buckets(), &[3, 3, 3, 4, 6, 0]);
+        assert_eq!(sim.buckets(), &[6, 0, 3, 7, 0, 2]);
         sim.step();
-        assert_eq!(sim.buckets(), &[0, 4, 0, 4, 6, 0]);
+        assert_eq!(sim.buckets(), &[6, 0, 4, 7, 0, 0]);
         sim.step();
-        assert_eq!(sim.buckets(), &[2, 5, 0, 5, 7, 0]);
+        assert_eq!(sim.buckets(), &[7, 0, 0, 8, 0, 0]);
         sim.step();
-        assert_eq!(sim.buckets(), &[2, 5, 0, 5, 7, 0]);
+        assert_eq!(sim.buckets(), &[7, 0, 2, 9, 0, 0]);
         sim.step();
-        assert_eq!(sim.buckets(), &[2, 5, 0, 5, 7, 0]);
+        assert_eq!(sim.buckets(), &[7, 0, 3, 9, 0, 0]);
         sim.step();
-        assert_eq!(sim.buckets(), &[0, 5, 0, 6, 7, 0]);
+        assert_eq!(sim.buckets(), &[7, 0, 3, 9, 0, 0]);
         sim.step();
-        assert_eq!(sim.buckets(), &[0, 6, 0, 6, 7, 0]);
+        assert_eq!(sim.buckets(), &[7, 0, 3, 9, 0, 0]);
         sim.step();
-        assert_eq!(sim.buckets(), &[0, 7, 0, 0, 7, 0]);
+        assert_eq!(sim.buckets(), &[7, 0, 0, 10, 0, 0]);
         sim.step();
-        assert_eq!(sim.buckets(), &[0, 10, 0, 0, 7, 0]);
+        assert_eq!(sim.buckets(), &[7, 0, 0, 11, 0, 0]);
         sim.step();
-        assert_eq!(sim.buckets(), &[0, 11, 0, 0, 7, 0]);
+        assert_eq!(sim.buckets(), &[8, 0, 0, 11, 0, 0]);
     }
 
+    #[cfg(feature = "nightly")]
     #[bench]
     fn bench_naive_simulation(b: &mut test::Bencher) {
         let num_sides = 100;
@@ -496,6 +2255,21 @@ mod tests {
         });
     }
 
+    #[cfg(feature = "nightly")]
+    #[bench]
+    fn bench_fixed_naive_simulation(b: &mut test::Bencher) {
+        let num_dice = 1_000;
+
+        b.iter(|| {
+            let mut sim = FixedNaiveSimulation::<20>::new(20, num_dice);
+
+            while !sim.done() {
+                sim.step();
+            }
+        });
+    }
+
+    #[cfg(feature = "nightly")]
     #[bench]
     fn bench_divide_simulation(b: &mut test::Bencher) {
         let num_sides = 100;
@@ -510,6 +2284,7 @@ mod tests {
         });
     }
 
+    #[cfg(feature = "nightly")]
     #[bench]
     fn bench_merge_simulation(b: &mut test::Bencher) {
         let num_sides = 100;
@@ -518,6 +2293,36 @@ mod tests {
         b.iter(|| {
             let mut sim = MergeSimulation::new(num_sides, num_dice);
 
+            while !sim.done() {
+                sim.step();
+            }
+        });
+    }
+    // Many more sides than dice, where a step only visits the faces that have dice.
+
+    #[cfg(feature = "nightly")]
+    #[bench]
+    fn bench_divide_simulation_many_sides(b: &mut test::Bencher) {
+        let num_sides = 10_000;
+        let num_dice = 100;
+
+        b.iter(|| {
+            let mut sim = DivideSimulation::new(num_sides, num_dice);
+
+            while !sim.done() {
+                sim.step();
+            }
+        });
+    }
+
+    #[cfg(feature = "nightly")]
+    #[bench]
+    fn bench_naive_simulation_reused_many_sides(b: &mut test::Bencher) {
+        let mut sim = NaiveSimulation::new(1_000, 5);
+
+        b.iter(|| {
+            sim.reset();
+
             while !sim.done() {
                 sim.step();
             }
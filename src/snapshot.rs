@@ -0,0 +1,157 @@
+use std::{fmt::Write as _, path::Path};
+
+use crate::{error::{Result, TenziError}, registry::StrategyRegistry, simulation::{SavedGame, SimulationType}, types::Num};
+
+/// The header that every snapshot file starts with.
+const HEADER: &str = "tenzi-snapshot";
+
+/// A single in-progress game, saved along with the strategy it is played with, so it can be restored later.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GameSnapshot {
+    pub strategy: String,
+    pub game: SavedGame,
+}
+
+impl GameSnapshot {
+    /// Saves a game in progress, which is played with the given strategy spec.
+    pub fn take(strategy: &str, simulation: &SimulationType) -> Self {
+        Self {
+            strategy: strategy.to_string(),
+            game: simulation.as_strategy().save(),
+        }
+    }
+
+    /// Builds the strategy from the registry, and restores the game into it.
+    pub fn restore(&self, registry: &StrategyRegistry) -> Result<SimulationType> {
+        let mut simulation = registry.build(&self.strategy, self.game.num_sides, self.game.num_dice)?;
+        simulation.as_strategy_mut().restore(&self.game)?;
+
+        Ok(simulation)
+    }
+
+    /// Serializes the snapshot to its text format: a header line, followed by one "name value" line per field, where
+    /// lists are comma separated (and "-" is an empty or missing list).
+    pub fn to_text(&self) -> String {
+        let game = &self.game;
+        let mut text = format!("{} {} {} {}\n", HEADER, game.num_sides, game.num_dice, self.strategy);
+
+        writeln!(text, "buckets {}", join(&game.buckets)).unwrap();
+        writeln!(text, "initial {}", game.initial_state.as_deref().map_or("-".to_string(), join)).unwrap();
+        writeln!(text, "rolls {}", game.num_rolls).unwrap();
+        writeln!(text, "steps {}", game.num_steps).unwrap();
+        writeln!(text, "policy {}", join(&game.policy)).unwrap();
+
+        text
+    }
+
+    /// Parses a snapshot from its text format.
+    pub fn from_text(text: &str) -> Result<Self> {
+        let mut lines = text.lines().filter(|l| !l.trim().is_empty());
+
+        let header = lines.next().ok_or_else(|| invalid("the snapshot is empty"))?.split_whitespace().collect::<Vec<_>>();
+
+        let [HEADER, num_sides, num_dice, strategy] = header.as_slice() else {
+            return Err(invalid("the snapshot header is malformed"));
+        };
+
+        let mut field = |name: &str| {
+            let line = lines.next().ok_or_else(|| invalid(format!("the snapshot is missing `{}`", name)))?;
+
+            match line.split_once(' ') {
+                Some((n, value)) if n == name => Ok(value.trim()),
+                _ => Err(invalid(format!("expected `{}`, but got `{}`", name, line))),
+            }
+        };
+
+        let buckets = parse_list(field("buckets")?)?;
+        let initial_state = match field("initial")? {
+            "-" => None,
+            list => Some(parse_list(list)?),
+        };
+        let num_rolls = parse_num(field("rolls")?)?;
+        let num_steps = parse_num(field("steps")?)?;
+        let policy = parse_list(field("policy")?)?;
+
+        Ok(Self {
+            strategy: strategy.to_string(),
+            game: SavedGame {
+                num_sides: parse_num(num_sides)?,
+                num_dice: parse_num(num_dice)?,
+                buckets,
+                initial_state,
+                num_rolls,
+                num_steps,
+                policy,
+            },
+        })
+    }
+
+    /// Writes the snapshot to a file.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        Ok(std::fs::write(path, self.to_text())?)
+    }
+
+    /// Reads a snapshot from a file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let text = std::fs::read_to_string(path)?;
+        Self::from_text(&text)
+    }
+}
+
+/// Joins a list for the text format.
+fn join(list: &[Num]) -> String {
+    match list {
+        [] => "-".to_string(),
+        _ => list.iter().map(|n| n.to_string()).collect::<Vec<_>>().join(","),
+    }
+}
+
+/// Parses a list from a snapshot field.
+fn parse_list(s: &str) -> Result<Vec<Num>> {
+    match s {
+        "-" => Ok(Vec::new()),
+        _ => s.split(',').map(parse_num).collect(),
+    }
+}
+
+/// Parses a number from a snapshot field.
+fn parse_num(s: &str) -> Result<Num> {
+    s.parse::<Num>().map_err(|_| invalid(format!("`{}` is not a number", s)))
+}
+
+/// Builds an invalid snapshot error.
+fn invalid(reason: impl Into<String>) -> TenziError {
+    TenziError::InvalidSnapshot(reason.into())
+}
+
+// Tests.
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{rand::SeededDice, simulation::{NaiveSimulation, Snapshot}};
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_text_round_trip() {
+        let mut sim = SimulationType::Naive(NaiveSimulation::new(6, 10)).with_initial_state(&[0, 1, 0, 0, 0, 0]).with_rng(SeededDice::new(42));
+        sim.as_strategy_mut().step();
+
+        let snapshot = GameSnapshot::take("naive", &sim);
+        let parsed = GameSnapshot::from_text(&snapshot.to_text()).unwrap();
+
+        assert_eq!(parsed, snapshot);
+
+        let restored = parsed.restore(&StrategyRegistry::new()).unwrap();
+
+        assert_eq!(Snapshot::of(restored.as_strategy()), Snapshot::of(sim.as_strategy()));
+    }
+
+    #[test]
+    fn test_from_text_malformed() {
+        assert!(matches!(GameSnapshot::from_text(""), Err(TenziError::InvalidSnapshot(_))));
+        assert!(matches!(GameSnapshot::from_text("tenzi-snapshot 6 10 merge\nbuckets 0,0,0,0,0,0\nrolls 0"), Err(TenziError::InvalidSnapshot(_))));
+        assert!(matches!(GameSnapshot::from_text("tenzi-trace 6 10 merge\n"), Err(TenziError::InvalidSnapshot(_))));
+    }
+}
\ No newline at end of file
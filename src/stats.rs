@@ -0,0 +1,242 @@
+//! Statistics of the number of rolls of recorded games (e.g., the histogram of a record file), so that an expensive run
+//! is simulated once, and analyzed as often as needed.
+//!
+//! Besides the percentiles and the tail probabilities, the histogram can be fit by a distribution, where the number of
+//! rolls is shifted to start at the fewest that any game took (i.e., usually the number of dice, which is a "tenzi" on
+//! the first roll), and the parameters are estimated by the method of moments.  The fit is judged by the largest gap
+//! between its cumulative distribution and the observed one (i.e., the Kolmogorov-Smirnov statistic).
+
+use crate::{error::{Result, TenziError}, types::{Float, Num}};
+
+/// A family of distributions to fit the number of rolls with (see [`fit`]).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Fit {
+    /// The negative binomial distribution, which has room for the number of rolls to be more spread out than its
+    /// mean alone implies (and it always is).
+    #[cfg_attr(feature = "serde", serde(rename = "nbinom"))]
+    NegativeBinomial,
+    /// The Poisson distribution, which is mostly a baseline for the negative binomial one.
+    #[cfg_attr(feature = "serde", serde(rename = "poisson"))]
+    Poisson,
+}
+
+impl Fit {
+    /// Returns the name of the family.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Fit::NegativeBinomial => "nbinom",
+            Fit::Poisson => "poisson",
+        }
+    }
+}
+
+impl core::fmt::Display for Fit {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl core::str::FromStr for Fit {
+    type Err = TenziError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "nbinom" | "negative-binomial" => Ok(Fit::NegativeBinomial),
+            "poisson" => Ok(Fit::Poisson),
+            _ => Err(TenziError::InvalidConfig(format!("`{}` is not a distribution to fit (try `nbinom` or `poisson`)", s))),
+        }
+    }
+}
+
+/// A distribution of the number of rolls, which is fit to a histogram (see [`fit`]).
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum FittedDistribution {
+    /// The number of rolls beyond the offset is the number of failures before the `r`th success of trials that
+    /// succeed with probability `p`.
+    NegativeBinomial { offset: Num, r: Float, p: Float },
+    /// The number of rolls beyond the offset is Poisson with the given mean.
+    Poisson { offset: Num, lambda: Float },
+}
+
+impl FittedDistribution {
+    /// Returns the probability of each number of rolls, up to (and including) the given one.
+    pub fn pmf(&self, max_rolls: Num) -> Vec<Float> {
+        let (offset, first) = match *self {
+            FittedDistribution::NegativeBinomial { offset, r, p } => (offset, p.powf(r)),
+            FittedDistribution::Poisson { offset, lambda } => (offset, (-lambda).exp()),
+        };
+
+        let ratio = |k: Float| match *self {
+            FittedDistribution::NegativeBinomial { r, p, .. } => (k - 1.0 + r) / k * (1.0 - p),
+            FittedDistribution::Poisson { lambda, .. } => lambda / k,
+        };
+
+        let mut pmf = vec![0.0; max_rolls as usize + 1];
+        let mut probability = first;
+
+        // Every probability is the previous one times a ratio, which avoids computing the factorials.
+
+        for (k, rolls) in (offset as usize..pmf.len()).enumerate() {
+            if k > 0 {
+                probability *= ratio(k as Float);
+            }
+
+            pmf[rolls] = probability;
+        }
+
+        pmf
+    }
+
+    /// Returns the mean number of rolls.
+    pub fn mean(&self) -> Float {
+        match *self {
+            FittedDistribution::NegativeBinomial { offset, r, p } => offset as Float + r * (1.0 - p) / p,
+            FittedDistribution::Poisson { offset, lambda } => offset as Float + lambda,
+        }
+    }
+}
+
+/// A distribution fit to a histogram, and how well it fits.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FitReport {
+    pub distribution: FittedDistribution,
+    /// The largest gap between the fit's cumulative distribution and the observed one.
+    pub ks_statistic: Float,
+}
+
+/// Returns the number of rolls that the given percent of the games took at most (i.e., the smallest number of rolls
+/// whose share of the games, with every game that took fewer, reaches the percent).
+///
+/// Fails with [`TenziError::InvalidConfig`] if the histogram is empty, or if the percent is not above 0 and at most 100.
+pub fn percentile(histogram: &[Num], percent: Float) -> Result<Num> {
+    let num_games = num_games(histogram)?;
+
+    if !(percent > 0.0 && percent <= 100.0) {
+        return Err(TenziError::InvalidConfig(format!("`{}` is not a percentile above 0 and at most 100", percent)));
+    }
+
+    let rank = (percent / 100.0 * num_games as Float).ceil() as u64;
+    let mut cumulative = 0;
+
+    for (rolls, &count) in histogram.iter().enumerate() {
+        cumulative += count as u64;
+
+        if cumulative >= rank {
+            return Ok(rolls as Num);
+        }
+    }
+
+    unreachable!("the last bin reaches every game")
+}
+
+/// Returns the share of the games that took at least the given number of rolls.
+///
+/// Fails with [`TenziError::InvalidConfig`] if the histogram is empty.
+pub fn tail_probability(histogram: &[Num], min_rolls: Num) -> Result<Float> {
+    let num_games = num_games(histogram)?;
+    let tail = histogram.iter().skip(min_rolls as usize).map(|&count| count as u64).sum::<u64>();
+
+    Ok(tail as Float / num_games as Float)
+}
+
+/// Fits a distribution of the given family to the histogram (see the [module](self) docs).
+///
+/// Fails with [`TenziError::InvalidConfig`] if the histogram is empty, or if the family cannot fit it (e.g., every
+/// game took the same number of rolls, or a negative binomial fit of games that are less spread out than Poisson ones).
+pub fn fit(histogram: &[Num], family: Fit) -> Result<FitReport> {
+    let num_games = num_games(histogram)? as Float;
+    let offset = histogram.iter().position(|&count| count != 0).unwrap() as Num;
+
+    // The moments of the number of rolls beyond the offset.
+
+    let shifted = || histogram.iter().enumerate().skip(offset as usize).map(|(rolls, &count)| ((rolls as Num - offset) as Float, count as Float));
+    let mean = shifted().map(|(k, count)| k * count).sum::<Float>() / num_games;
+    let variance = shifted().map(|(k, count)| (k - mean).powi(2) * count).sum::<Float>() / num_games;
+
+    if mean <= 0.0 {
+        return Err(TenziError::InvalidConfig("every game took the same number of rolls, so there is nothing to fit".to_string()));
+    }
+
+    let distribution = match family {
+        Fit::NegativeBinomial if variance <= mean => return Err(TenziError::InvalidConfig(format!("the games are not more spread out than Poisson ones (a variance of {:.4} for a mean of {:.4}), so a negative binomial cannot fit them", variance, mean))),
+        Fit::NegativeBinomial => FittedDistribution::NegativeBinomial { offset, r: mean * mean / (variance - mean), p: mean / variance },
+        Fit::Poisson => FittedDistribution::Poisson { offset, lambda: mean },
+    };
+
+    let pmf = distribution.pmf(histogram.len() as Num - 1);
+    let (mut observed, mut expected, mut ks_statistic) = (0.0, 0.0, 0.0 as Float);
+
+    for (&count, probability) in histogram.iter().zip(pmf) {
+        observed += count as Float / num_games;
+        expected += probability;
+        ks_statistic = ks_statistic.max((observed - expected).abs());
+    }
+
+    Ok(FitReport { distribution, ks_statistic })
+}
+
+/// Returns the number of games in the histogram, which fails if there are none.
+fn num_games(histogram: &[Num]) -> Result<u64> {
+    match histogram.iter().map(|&count| count as u64).sum::<u64>() {
+        0 => Err(TenziError::InvalidConfig("there are no games to compute statistics from".to_string())),
+        num_games => Ok(num_games),
+    }
+}
+
+// Tests.
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_percentile_and_tail() {
+        // Ten games: one of 10 rolls, four of 12, and five of 15.
+
+        let mut histogram = vec![0; 16];
+        histogram[10] = 1;
+        histogram[12] = 4;
+        histogram[15] = 5;
+
+        assert_eq!(percentile(&histogram, 10.0).unwrap(), 10);
+        assert_eq!(percentile(&histogram, 50.0).unwrap(), 12);
+        assert_eq!(percentile(&histogram, 51.0).unwrap(), 15);
+        assert_eq!(percentile(&histogram, 100.0).unwrap(), 15);
+
+        assert_eq!(tail_probability(&histogram, 12).unwrap(), 0.9);
+        assert_eq!(tail_probability(&histogram, 13).unwrap(), 0.5);
+        assert_eq!(tail_probability(&histogram, 100).unwrap(), 0.0);
+
+        assert!(matches!(percentile(&histogram, 0.0), Err(TenziError::InvalidConfig(_))));
+        assert!(matches!(percentile(&histogram, 101.0), Err(TenziError::InvalidConfig(_))));
+        assert!(matches!(tail_probability(&[0, 0], 1), Err(TenziError::InvalidConfig(_))));
+    }
+
+    #[test]
+    fn test_fit() {
+        // A histogram drawn exactly from a negative binomial is fit by it, and not as well by a Poisson.
+
+        let truth = FittedDistribution::NegativeBinomial { offset: 10, r: 3.0, p: 0.2 };
+        let histogram = truth.pmf(200).iter().map(|probability| (probability * 1e9).round() as Num).collect::<Vec<_>>();
+
+        let nbinom = fit(&histogram, Fit::NegativeBinomial).unwrap();
+        let FittedDistribution::NegativeBinomial { offset, r, p } = nbinom.distribution else { unreachable!() };
+
+        assert_eq!(offset, 10);
+        assert!((r - 3.0).abs() < 1e-3 && (p - 0.2).abs() < 1e-4);
+        assert!((nbinom.distribution.mean() - truth.mean()).abs() < 1e-3);
+        assert!(nbinom.ks_statistic < 1e-4);
+        assert!(fit(&histogram, Fit::Poisson).unwrap().ks_statistic > 0.1);
+
+        // Games that are less spread out than Poisson ones cannot be fit by a negative binomial, and a single bin has
+        // nothing to fit.
+
+        assert!(matches!(fit(&[0, 0, 5, 5], Fit::NegativeBinomial), Err(TenziError::InvalidConfig(_))));
+        assert!(matches!(fit(&[0, 0, 5], Fit::Poisson), Err(TenziError::InvalidConfig(_))));
+        assert_eq!("nbinom".parse::<Fit>().unwrap(), Fit::NegativeBinomial);
+    }
+}
\ No newline at end of file
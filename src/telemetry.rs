@@ -0,0 +1,202 @@
+//! The metrics of a server, which it serves at `GET /metrics` in the Prometheus text format, so that a long-running
+//! server can be scraped like any other service:
+//!
+//! * `tenzi_simulations_total` and `tenzi_rolls_total` count the games that were played, and their rolls, by strategy
+//!   (including those of runs that were cancelled partway).
+//! * `tenzi_strategy_seconds_total` counts the time that was spent playing each strategy, so its throughput is the
+//!   rate of its games over the rate of its time.
+//! * `tenzi_jobs_total` counts the jobs that finished, by outcome ("done", "failed", or "cancelled"), and
+//!   `tenzi_job_duration_seconds` is a histogram of how long they ran.
+//! * `tenzi_jobs_queued` and `tenzi_jobs_running` are the jobs that are waiting and running, out of `tenzi_workers`, and
+//!   `tenzi_worker_busy_seconds_total` counts the time that the workers spent running jobs, so their utilization is its
+//!   rate over the number of workers.
+//! * `tenzi_cluster_idle_workers` is the number of workers that a coordinator has waiting, if the server has one.
+
+use std::{collections::BTreeMap, fmt::Write as _, sync::Mutex, time::{Duration, Instant}};
+
+use crate::{results::RunResults, types::Float};
+
+/// The upper bounds (in seconds) of the buckets of the histogram of job durations.
+const DURATION_BUCKETS: [f64; 8] = [0.01, 0.1, 1.0, 10.0, 60.0, 300.0, 1800.0, 3600.0];
+
+/// The counters and gauges of a server.
+pub(crate) struct ServerMetrics {
+    num_workers: usize,
+    inner: Mutex<Inner>,
+}
+
+#[derive(Default)]
+struct Inner {
+    strategies: BTreeMap<String, StrategyCounters>,
+    jobs: BTreeMap<&'static str, u64>,
+    durations: [u64; DURATION_BUCKETS.len()],
+    duration_sum: f64,
+    running: BTreeMap<u64, Instant>,
+    busy: Duration,
+}
+
+#[derive(Default)]
+struct StrategyCounters {
+    games: u64,
+    rolls: u64,
+    time: Duration,
+}
+
+/// The state of a server's queue at the time it is scraped.
+pub(crate) struct QueueGauges {
+    pub num_queued: usize,
+    pub idle_workers: Option<usize>,
+}
+
+impl ServerMetrics {
+    pub(crate) fn new(num_workers: usize) -> Self {
+        Self { num_workers, inner: Mutex::default() }
+    }
+
+    /// Counts the games of a run (complete or not) toward its strategies.
+    pub(crate) fn record_run(&self, results: &RunResults) {
+        let mut inner = self.inner.lock().unwrap();
+
+        for summary in results.summaries() {
+            let counters = inner.strategies.entry(summary.strategy().to_string()).or_default();
+
+            counters.games += summary.num_simulations() as u64;
+            counters.rolls += (summary.average_rolls() * summary.num_simulations() as Float).round() as u64;
+            counters.time += summary.duration();
+        }
+    }
+
+    /// Marks the job as running on a worker.
+    pub(crate) fn job_started(&self, id: u64) {
+        self.inner.lock().unwrap().running.insert(id, Instant::now());
+    }
+
+    /// Marks the job as finished with the given outcome, and counts its duration.
+    pub(crate) fn job_finished(&self, id: u64, outcome: &'static str) {
+        let mut inner = self.inner.lock().unwrap();
+
+        let Some(started) = inner.running.remove(&id) else {
+            return;
+        };
+
+        let duration = started.elapsed();
+        let seconds = duration.as_secs_f64();
+
+        *inner.jobs.entry(outcome).or_default() += 1;
+        inner.busy += duration;
+        inner.duration_sum += seconds;
+
+        // The buckets are cumulative, as Prometheus expects.
+
+        for (bucket, _) in inner.durations.iter_mut().zip(DURATION_BUCKETS).filter(|(_, bound)| seconds <= *bound) {
+            *bucket += 1;
+        }
+    }
+
+    /// Renders the metrics in the Prometheus text format.
+    pub(crate) fn render(&self, gauges: QueueGauges) -> String {
+        let inner = self.inner.lock().unwrap();
+        let now = Instant::now();
+        let mut text = String::new();
+
+        let header = |text: &mut String, name: &str, kind: &str, help: &str| {
+            writeln!(text, "# HELP {} {}", name, help).unwrap();
+            writeln!(text, "# TYPE {} {}", name, kind).unwrap();
+        };
+
+        header(&mut text, "tenzi_simulations_total", "counter", "The number of games that were played, by strategy.");
+        for (strategy, counters) in &inner.strategies {
+            writeln!(text, "tenzi_simulations_total{{strategy=\"{}\"}} {}", escape(strategy), counters.games).unwrap();
+        }
+
+        header(&mut text, "tenzi_rolls_total", "counter", "The number of rolls of the games that were played, by strategy.");
+        for (strategy, counters) in &inner.strategies {
+            writeln!(text, "tenzi_rolls_total{{strategy=\"{}\"}} {}", escape(strategy), counters.rolls).unwrap();
+        }
+
+        header(&mut text, "tenzi_strategy_seconds_total", "counter", "The time that was spent playing the games of each strategy.");
+        for (strategy, counters) in &inner.strategies {
+            writeln!(text, "tenzi_strategy_seconds_total{{strategy=\"{}\"}} {}", escape(strategy), counters.time.as_secs_f64()).unwrap();
+        }
+
+        header(&mut text, "tenzi_jobs_total", "counter", "The number of jobs that finished, by outcome.");
+        for outcome in ["done", "failed", "cancelled"] {
+            writeln!(text, "tenzi_jobs_total{{outcome=\"{}\"}} {}", outcome, inner.jobs.get(outcome).unwrap_or(&0)).unwrap();
+        }
+
+        let count = inner.jobs.values().sum::<u64>();
+
+        header(&mut text, "tenzi_job_duration_seconds", "histogram", "The time that the jobs that finished ran for.");
+        for (bound, bucket) in DURATION_BUCKETS.iter().zip(inner.durations) {
+            writeln!(text, "tenzi_job_duration_seconds_bucket{{le=\"{}\"}} {}", bound, bucket).unwrap();
+        }
+        writeln!(text, "tenzi_job_duration_seconds_bucket{{le=\"+Inf\"}} {}", count).unwrap();
+        writeln!(text, "tenzi_job_duration_seconds_sum {}", inner.duration_sum).unwrap();
+        writeln!(text, "tenzi_job_duration_seconds_count {}", count).unwrap();
+
+        header(&mut text, "tenzi_jobs_queued", "gauge", "The number of jobs that are waiting to run.");
+        writeln!(text, "tenzi_jobs_queued {}", gauges.num_queued).unwrap();
+
+        header(&mut text, "tenzi_jobs_running", "gauge", "The number of jobs that are running.");
+        writeln!(text, "tenzi_jobs_running {}", inner.running.len()).unwrap();
+
+        header(&mut text, "tenzi_workers", "gauge", "The number of jobs that can run at once.");
+        writeln!(text, "tenzi_workers {}", self.num_workers).unwrap();
+
+        // The jobs that are still running count toward the busy time so far, so that long jobs do not show up all at once.
+
+        let busy = inner.busy + inner.running.values().map(|started| now - *started).sum::<Duration>();
+
+        header(&mut text, "tenzi_worker_busy_seconds_total", "counter", "The time that the workers spent running jobs.");
+        writeln!(text, "tenzi_worker_busy_seconds_total {}", busy.as_secs_f64()).unwrap();
+
+        if let Some(idle_workers) = gauges.idle_workers {
+            header(&mut text, "tenzi_cluster_idle_workers", "gauge", "The number of workers that the coordinator has waiting for a shard.");
+            writeln!(text, "tenzi_cluster_idle_workers {}", idle_workers).unwrap();
+        }
+
+        text
+    }
+}
+
+/// Escapes a label value.
+fn escape(value: &str) -> String {
+    value.replace('\\', r"\\").replace('"', "\\\"").replace('\n', r"\n")
+}
+
+// Tests.
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SimulationConfig;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_render() {
+        let metrics = ServerMetrics::new(2);
+        let results = SimulationConfig::builder().strategy("divide").simulations(1_000).seed(42).build().unwrap().run().unwrap();
+        let rolls = (results.summaries()[0].average_rolls() * 1_000.0).round() as u64;
+
+        metrics.job_started(1);
+        metrics.record_run(&results);
+        metrics.job_finished(1, "done");
+        metrics.job_started(2);
+        metrics.job_finished(3, "failed");
+
+        let text = metrics.render(QueueGauges { num_queued: 4, idle_workers: None });
+        let value = |name: &str| text.lines().find_map(|line| line.strip_prefix(name).and_then(|value| value.strip_prefix(' '))).unwrap().to_string();
+
+        assert_eq!(value("tenzi_simulations_total{strategy=\"divide\"}"), "1000");
+        assert_eq!(value("tenzi_rolls_total{strategy=\"divide\"}"), rolls.to_string());
+        assert_eq!(value("tenzi_jobs_total{outcome=\"done\"}"), "1");
+        assert_eq!(value("tenzi_jobs_total{outcome=\"failed\"}"), "0");
+        assert_eq!(value("tenzi_job_duration_seconds_bucket{le=\"3600\"}"), "1");
+        assert_eq!(value("tenzi_job_duration_seconds_count"), "1");
+        assert_eq!(value("tenzi_jobs_queued"), "4");
+        assert_eq!(value("tenzi_jobs_running"), "1");
+        assert_eq!(value("tenzi_workers"), "2");
+        assert!(!text.contains("tenzi_cluster_idle_workers"));
+        assert_eq!(escape("a\"b\\c"), r#"a\"b\\c"#);
+    }
+}
\ No newline at end of file
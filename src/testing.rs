@@ -0,0 +1,380 @@
+//! Utilities for verifying strategies against the rules of the game, for strategy authors (and the built-in tests).
+//!
+//! Games are driven either by scripted dice ([`ScriptedDice`]) or by seeded ones, and every step is checked against the
+//! invariants that the engine guarantees: the buckets never hold more than the dice in the game, the counters advance
+//! by exactly one step and the dice that were rolled, a "tenzi" is reported exactly when it is achieved, the largest
+//! group of kept dice never shrinks, and the game finishes.
+
+use std::path::Path;
+
+use crate::{dice::DiceRng, error::{Result, TenziError}, rand::SeededDice, simulation::{SimulationType, Strategy}, trace::GameTrace, types::{Float, Num}};
+
+/// The number of steps after which a game is assumed to never finish.
+pub const DEFAULT_MAX_STEPS: Num = 10_000;
+
+/// The environment variable that makes [`assert_golden`] (re)write the golden files rather than compare with them.
+pub const UPDATE_GOLDEN: &str = "TENZI_UPDATE_GOLDEN";
+
+/// A dice source that rolls a scripted sequence of faces, in order.
+///
+/// Rolling past the end of the script panics, since a scripted test that runs out of dice is a broken test.
+#[derive(Clone, Debug)]
+pub struct ScriptedDice {
+    dice: Vec<Num>,
+    next: usize,
+}
+
+impl ScriptedDice {
+    /// Returns a source that rolls the given faces, in order.
+    pub fn new(dice: impl Into<Vec<Num>>) -> Self {
+        Self { dice: dice.into(), next: 0 }
+    }
+
+    /// Returns the number of faces left in the script.
+    pub fn remaining(&self) -> usize {
+        self.dice.len() - self.next
+    }
+}
+
+impl DiceRng for ScriptedDice {
+    fn roll(&mut self, num_sides: Num) -> Num {
+        let face = *self.dice.get(self.next).unwrap_or_else(|| panic!("the script ran out of dice after {} rolls", self.next));
+        assert!(face >= 1 && face <= num_sides, "the scripted face `{}` is not a face of a {}-sided die", face, num_sides);
+
+        self.next += 1;
+        face
+    }
+}
+
+/// Plays the game to completion with its own dice source, checking the invariants after every step, and returns the
+/// number of steps it took.
+///
+/// Fails with [`TenziError::InvariantViolation`] on the first step that breaks an invariant, or if the game is not done
+/// after `max_steps`.
+pub fn check_game(strategy: &mut dyn Strategy, max_steps: Num) -> Result<Num> {
+    while !strategy.done() {
+        if strategy.num_steps() >= max_steps {
+            return Err(violation(strategy.num_steps(), format!("the game did not finish within {} steps", max_steps)));
+        }
+
+        let before = Before::of(strategy);
+        strategy.step();
+        before.check(strategy)?;
+    }
+
+    Ok(strategy.num_steps())
+}
+
+/// The outcome of checking every way that a game can play out (see [`check_exhaustive`]).
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ExhaustiveReport {
+    /// The number of distinct rolls (i.e., the number of dice for each face) that were played.
+    pub num_rolls_played: u64,
+    /// The probability that the game is done within the depth.
+    pub done_probability: Float,
+    /// The expected number of rolls of the games that are done within the depth (or zero if none are).
+    pub expected_rolls: Float,
+    /// The expected number of steps of the games that are done within the depth (or zero if none are).
+    pub expected_steps: Float,
+}
+
+/// Plays out every possible sequence of rolls (up to `max_depth` steps) from the game's current state, checking the
+/// invariants after every step.
+///
+/// Rolls are enumerated by the number of dice that show each face (with their multinomial probability), since that is
+/// all a policy sees.  This is only feasible for tiny games (e.g., 3 dice with 3 sides).  A game with nothing left to
+/// roll that is not done can never finish, so it is a violation.
+pub fn check_exhaustive(simulation: &SimulationType, max_depth: Num) -> Result<ExhaustiveReport> {
+    let mut report = ExhaustiveReport::default();
+    let mut faces = Vec::new();
+
+    explore(simulation, max_depth, 1.0, &mut faces, &mut report)?;
+
+    if report.done_probability > 0.0 {
+        report.expected_rolls /= report.done_probability;
+        report.expected_steps /= report.done_probability;
+    }
+
+    Ok(report)
+}
+
+fn explore(simulation: &SimulationType, depth: Num, probability: Float, faces: &mut Vec<Num>, report: &mut ExhaustiveReport) -> Result<()> {
+    let strategy = simulation.as_strategy();
+
+    if strategy.done() {
+        report.done_probability += probability;
+        report.expected_rolls += probability * strategy.num_rolls() as Float;
+        report.expected_steps += probability * strategy.num_steps() as Float;
+        return Ok(());
+    }
+
+    if depth == 0 {
+        return Ok(());
+    }
+
+    if strategy.num_to_roll() == 0 {
+        return Err(violation(strategy.num_steps(), format!("nothing is left to roll, but the game is not done (after rolling {:?})", faces)));
+    }
+
+    for counts in compositions(strategy.num_to_roll(), strategy.num_sides()) {
+        let mut next = simulation.clone();
+        let before = Before::of(next.as_strategy());
+
+        // Roll the dice face by face, in order.
+
+        let roll = (1..).zip(&counts).flat_map(|(face, &count)| std::iter::repeat_n(face, count as usize)).collect::<Vec<Num>>();
+        let mut dice = roll.iter().copied();
+        next.as_strategy_mut().step_with(&mut |_| dice.next().unwrap());
+
+        let mark = faces.len();
+        faces.extend_from_slice(&roll);
+
+        before.check(next.as_strategy()).map_err(|e| match e {
+            TenziError::InvariantViolation { step, reason } => violation(step, format!("{} (after rolling {:?})", reason, faces)),
+            e => e,
+        })?;
+
+        report.num_rolls_played += 1;
+        explore(&next, depth - 1, probability * multinomial(&counts), faces, report)?;
+
+        faces.truncate(mark);
+    }
+
+    Ok(())
+}
+
+/// Returns every way to split the dice over the faces (i.e., the number of dice that show each face).
+fn compositions(num_dice: Num, num_sides: Num) -> Vec<Vec<Num>> {
+    if num_sides == 1 {
+        return vec![vec![num_dice]];
+    }
+
+    (0..=num_dice).flat_map(|first| {
+        compositions(num_dice - first, num_sides - 1).into_iter().map(move |mut rest| {
+            rest.insert(0, first);
+            rest
+        })
+    }).collect()
+}
+
+/// Returns the probability of rolling the given number of dice for each face.
+fn multinomial(counts: &[Num]) -> Float {
+    let num_sides = counts.len() as Float;
+    let mut probability = 1.0;
+    let mut rolled = 0;
+
+    // Build up n! / (k1! k2! ...) / s^n one die at a time, to stay in range.
+
+    for &count in counts {
+        for k in 1..=count {
+            rolled += 1;
+            probability *= rolled as Float / (k as Float * num_sides);
+        }
+    }
+
+    probability
+}
+
+/// The state of a game before a step, which the step is checked against.
+struct Before {
+    num_rolls: Num,
+    num_steps: Num,
+    num_to_roll: Num,
+    largest: Num,
+}
+
+impl Before {
+    fn of(strategy: &dyn Strategy) -> Self {
+        Self {
+            num_rolls: strategy.num_rolls(),
+            num_steps: strategy.num_steps(),
+            num_to_roll: strategy.num_to_roll(),
+            largest: strategy.buckets().iter().copied().max().unwrap_or(0),
+        }
+    }
+
+    /// Checks the invariants of the step that the game just took.
+    fn check(&self, strategy: &dyn Strategy) -> Result<()> {
+        let step = strategy.num_steps();
+        let buckets = strategy.buckets();
+        let kept = buckets.iter().sum::<Num>();
+
+        if buckets.len() != strategy.num_sides() as usize {
+            return Err(violation(step, format!("there are {} buckets for a {}-sided die", buckets.len(), strategy.num_sides())));
+        }
+
+        if kept > strategy.num_dice() {
+            return Err(violation(step, format!("{} dice are kept, but there are only {}", kept, strategy.num_dice())));
+        }
+
+        if strategy.num_to_roll() != strategy.num_dice() - kept {
+            return Err(violation(step, format!("{} dice are to be rolled, but {} are not kept", strategy.num_to_roll(), strategy.num_dice() - kept)));
+        }
+
+        if step != self.num_steps + 1 || strategy.num_rolls() != self.num_rolls + self.num_to_roll {
+            return Err(violation(step, format!("the counters went from {} rolls and {} steps to {} and {}", self.num_rolls, self.num_steps, strategy.num_rolls(), step)));
+        }
+
+        if strategy.done() != buckets.contains(&strategy.num_dice()) {
+            return Err(violation(step, format!("the game reports done = {}, but the buckets are {:?}", strategy.done(), buckets)));
+        }
+
+        let largest = buckets.iter().copied().max().unwrap_or(0);
+        if largest < self.largest {
+            return Err(violation(step, format!("the largest group of kept dice shrank from {} to {}", self.largest, largest)));
+        }
+
+        Ok(())
+    }
+}
+
+/// Plays the game with the given scripted dice, checking the invariants after every step (see [`check_game`]).
+pub fn check_scripted(simulation: &SimulationType, dice: &[Num]) -> Result<Num> {
+    let mut simulation = simulation.clone().with_rng(ScriptedDice::new(dice));
+    check_game(simulation.as_strategy_mut(), DEFAULT_MAX_STEPS)
+}
+
+/// Plays many games from the given seed, checking the invariants of every one (see [`check_game`]).
+pub fn check_seeded(simulation: &SimulationType, num_games: Num, seed: u64) -> Result<()> {
+    let mut simulation = simulation.clone().with_rng(SeededDice::new(seed));
+
+    for _ in 0..num_games {
+        let strategy = simulation.as_strategy_mut();
+        strategy.reset();
+        check_game(strategy, DEFAULT_MAX_STEPS)?;
+    }
+
+    Ok(())
+}
+
+/// Compares a trace with the golden trace in the given file, and panics with both if they differ.
+///
+/// If the file does not exist, or the [`UPDATE_GOLDEN`] environment variable is set, the trace is written to the file
+/// instead, so that golden traces are recorded by running the tests once.
+pub fn assert_golden(path: impl AsRef<Path>, trace: &GameTrace) {
+    let path = path.as_ref();
+    let actual = trace.to_text();
+
+    if !path.exists() || std::env::var_os(UPDATE_GOLDEN).is_some() {
+        trace.save(path).unwrap_or_else(|e| panic!("unable to write the golden trace `{}`: {}", path.display(), e));
+        return;
+    }
+
+    let expected = std::fs::read_to_string(path).unwrap_or_else(|e| panic!("unable to read the golden trace `{}`: {}", path.display(), e));
+
+    assert!(expected == actual, "the trace does not match the golden trace `{}` (set `{}` to update it)\n\nexpected:\n{}\nactual:\n{}", path.display(), UPDATE_GOLDEN, expected, actual);
+}
+
+/// Builds an invariant violation error.
+fn violation(step: Num, reason: impl Into<String>) -> TenziError {
+    TenziError::InvariantViolation { step, reason: reason.into() }
+}
+
+// Tests.
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::{Game, KeepPolicy, KeptBuckets, StrategyKind, ThresholdPolicy};
+    use pretty_assertions::assert_eq;
+
+    /// Keeps every die, no matter what was rolled.
+    #[derive(Clone)]
+    struct KeepAll;
+
+    impl KeepPolicy for KeepAll {
+        fn keep(&mut self, _buckets: &mut KeptBuckets, _num_dice: Num) {}
+    }
+
+    /// Re-rolls every die, forever.
+    #[derive(Clone)]
+    struct KeepNone;
+
+    impl KeepPolicy for KeepNone {
+        fn keep(&mut self, buckets: &mut KeptBuckets, _num_dice: Num) {
+            buckets.clear();
+        }
+    }
+
+    #[test]
+    fn test_builtins_keep_the_invariants() {
+        for kind in StrategyKind::ALL {
+            check_seeded(&SimulationType::new(kind, 6, 10), 200, 42).unwrap();
+            check_seeded(&SimulationType::fast(kind, 6, 10), 200, 42).unwrap();
+        }
+
+        for threshold in 1..=11 {
+            check_seeded(&SimulationType::custom(ThresholdPolicy::new(threshold).unwrap(), 6, 10), 200, 42).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_check_exhaustive() {
+        // With one die, every game is done after the first roll.
+
+        let report = check_exhaustive(&SimulationType::new(StrategyKind::Naive, 6, 1), 3).unwrap();
+
+        assert_eq!(report.num_rolls_played, 6);
+        assert!((report.done_probability - 1.0).abs() < 1e-6);
+        assert!((report.expected_rolls - 1.0).abs() < 1e-6);
+
+        for kind in StrategyKind::ALL {
+            let report = check_exhaustive(&SimulationType::new(kind, 3, 3), 6).unwrap();
+            assert!(report.done_probability > 0.8 && report.done_probability <= 1.0 + 1e-6);
+        }
+
+        assert!(matches!(check_exhaustive(&SimulationType::custom(KeepAll, 2, 2), 3), Err(TenziError::InvariantViolation { .. })));
+    }
+
+    #[test]
+    fn test_multinomial() {
+        // Two dice with two faces: one of each is twice as likely as two of either.
+
+        assert_eq!(multinomial(&[1, 1]), 0.5);
+        assert_eq!(multinomial(&[2, 0]), 0.25);
+        assert_eq!(compositions(2, 2), vec![vec![0, 2], vec![1, 1], vec![2, 0]]);
+    }
+
+    #[test]
+    fn test_check_scripted() {
+        let steps = check_scripted(&SimulationType::new(StrategyKind::Merge, 2, 3), &[1, 1, 2, 1]).unwrap();
+
+        assert_eq!(steps, 2);
+    }
+
+    #[test]
+    fn test_violations() {
+        // Keeping mixed dice leaves nothing to roll, and re-rolling every die never gets anywhere.
+
+        assert!(check_scripted(&SimulationType::custom(KeepAll, 2, 2), &[1, 1]).is_ok());
+        assert!(matches!(check_scripted(&SimulationType::custom(KeepAll, 2, 2), &[1, 2]), Err(TenziError::InvariantViolation { .. })));
+        assert!(matches!(check_seeded(&SimulationType::custom(KeepNone, 6, 10), 1, 42), Err(TenziError::InvariantViolation { .. })));
+    }
+
+    #[test]
+    #[should_panic(expected = "ran out of dice")]
+    fn test_scripted_dice_exhausted() {
+        let mut dice = ScriptedDice::new([1]);
+        dice.roll(6);
+        dice.roll(6);
+    }
+
+    #[test]
+    fn test_assert_golden() {
+        let path = std::env::temp_dir().join(format!("tenzi-golden-{}.trace", std::process::id()));
+        let trace = GameTrace::replay("merge", SimulationType::new(StrategyKind::Merge, 2, 3), &[1, 1, 2, 1]).unwrap();
+
+        assert_golden(&path, &trace);
+        assert_golden(&path, &trace);
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_check_game() {
+        let mut game = Game::<_, (), [Num; 6]>::with_policy(KeepAll, 6, 1);
+        game.set_rng(Box::new(ScriptedDice::new([4])));
+
+        assert_eq!(check_game(&mut game, 1).unwrap(), 1);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,177 @@
+//! Population dynamics of the strategies: a population of players races itself, and the share of each strategy grows
+//! or shrinks with how often its players win, generation after generation, until the mix settles.
+//!
+//! Every strategy first races every other one head to head (see [`race`](crate::race)), which gives the payoff of each
+//! matchup: the probability of winning it, with a tie counted as half a win.  The fitness of a strategy in a generation
+//! is its expected payoff against an opponent drawn from the population, and the shares follow the (discrete)
+//! replicator dynamics, where each share is scaled by the fitness of its strategy relative to the average fitness of the
+//! population.
+//!
+//! The mix that the population settles on tells which strategies are robust against the others, rather than merely
+//! good on average: a strategy only survives if it holds its own against whatever the rest of the population became.
+
+use crate::{error::{Result, TenziError}, race, registry::StrategyRegistry, types::{Float, Num}};
+
+/// The payoffs of the head-to-head races between strategies, which drive the population dynamics.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Tournament {
+    pub strategies: Vec<String>,
+    /// The payoff of each matchup: `payoffs[i][j]` is the probability that strategy `i` beats strategy `j` head to
+    /// head, with a tie counted as half a win (so `payoffs[i][j] + payoffs[j][i]` is one).
+    pub payoffs: Vec<Vec<Float>>,
+}
+
+/// The shares of the strategies in the population, generation after generation (see [`Tournament::evolve`]).
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Evolution {
+    /// The shares of the strategies in each generation, starting from the initial ones.
+    pub generations: Vec<Vec<Float>>,
+    /// Whether the shares settled (i.e., no share changed by more than the tolerance in the last generation) before
+    /// the generations ran out.
+    pub converged: bool,
+}
+
+impl Evolution {
+    /// Returns the shares of the last generation, which are the equilibrium mix if the shares converged.
+    pub fn equilibrium(&self) -> &[Float] {
+        self.generations.last().expect("there is always an initial generation")
+    }
+}
+
+impl Tournament {
+    /// Races every pair of the given strategies head to head, with the given number of coupled races per matchup.
+    ///
+    /// Every matchup is seeded from the given seed (if any), like [`race_seeded`](race::race_seeded), so that the
+    /// payoffs are reproducible, and the matchups are played on common dice.
+    pub fn new(registry: &StrategyRegistry, strategies: &[String], num_sides: Num, num_dice: Num, num_simulations: Num, seed: Option<u64>) -> Result<Self> {
+        if strategies.len() < 2 {
+            return Err(TenziError::TooFewPlayers(strategies.len() as Num));
+        }
+
+        let players = strategies.iter().map(|strategy| registry.build(strategy, num_sides, num_dice)).collect::<Result<Vec<_>>>()?;
+        let mut payoffs = vec![vec![0.5; strategies.len()]; strategies.len()];
+
+        // A strategy ties itself on average, and the payoffs of a matchup add up to one, so only one side of each matchup
+        // needs to be raced.
+
+        for i in 0..players.len() {
+            for j in i + 1..players.len() {
+                let pair = [players[i].clone(), players[j].clone()];
+
+                let output = match seed {
+                    Some(seed) => race::race_seeded(&pair, num_simulations, seed)?,
+                    None => race::race(&pair, num_simulations)?,
+                };
+
+                payoffs[i][j] = output.win_probabilities[0] + output.tie_probability / 2.0;
+                payoffs[j][i] = 1.0 - payoffs[i][j];
+            }
+        }
+
+        Ok(Self { strategies: strategies.to_vec(), payoffs })
+    }
+
+    /// Returns the fitness of each strategy in a population with the given shares (i.e., its expected payoff against an
+    /// opponent drawn from the population).
+    pub fn fitness(&self, shares: &[Float]) -> Vec<Float> {
+        self.payoffs.iter().map(|row| row.iter().zip(shares).map(|(payoff, share)| payoff * share).sum()).collect()
+    }
+
+    /// Evolves a population from the given shares (which are normalized), for up to the given number of generations, or
+    /// until no share changes by more than the tolerance.
+    ///
+    /// Fails if there is not a share for each strategy, or if the shares are not a mix (i.e., one is negative, or they
+    /// are all zero).
+    pub fn evolve(&self, initial: &[Float], max_generations: Num, tolerance: Float) -> Result<Evolution> {
+        let total = initial.iter().sum::<Float>();
+
+        if initial.len() != self.strategies.len() {
+            return Err(TenziError::InvalidConfig(format!("expected a share for each of the {} strategies, but got {}", self.strategies.len(), initial.len())));
+        }
+
+        if initial.iter().any(|&share| share < 0.0) || total <= 0.0 {
+            return Err(TenziError::InvalidConfig(format!("the shares {:?} are not a mix of the strategies", initial)));
+        }
+
+        let mut generations = vec![initial.iter().map(|share| share / total).collect::<Vec<_>>()];
+        let mut converged = false;
+
+        while !converged && generations.len() <= max_generations as usize {
+            let shares = generations.last().unwrap();
+            let fitness = self.fitness(shares);
+            let average = shares.iter().zip(&fitness).map(|(share, fitness)| share * fitness).sum::<Float>();
+
+            let next = shares.iter().zip(&fitness).map(|(share, fitness)| share * fitness / average).collect::<Vec<_>>();
+
+            converged = next.iter().zip(shares).all(|(next, share)| (next - share).abs() <= tolerance);
+            generations.push(next);
+        }
+
+        Ok(Evolution { generations, converged })
+    }
+}
+
+// Tests.
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn tournament(payoffs: Vec<Vec<Float>>) -> Tournament {
+        Tournament { strategies: (0..payoffs.len()).map(|k| k.to_string()).collect(), payoffs }
+    }
+
+    #[test]
+    fn test_evolve() {
+        // A dominant strategy takes over the population.
+
+        let dominant = tournament(vec![vec![0.5, 0.6], vec![0.4, 0.5]]);
+        let evolution = dominant.evolve(&[1.0, 9.0], 10_000, 1e-6).unwrap();
+
+        assert_eq!(evolution.generations[0], vec![0.1, 0.9]);
+        assert!(evolution.converged);
+        assert!(evolution.equilibrium()[0] > 0.999);
+
+        // In a cycle (i.e., rock, paper, scissors), a fair mix stays put, and an extinct strategy stays extinct.
+
+        let cycle = tournament(vec![vec![0.5, 0.9, 0.1], vec![0.1, 0.5, 0.9], vec![0.9, 0.1, 0.5]]);
+        let fair = cycle.evolve(&[1.0, 1.0, 1.0], 100, 1e-6).unwrap();
+
+        assert!(fair.converged);
+        assert_eq!(fair.generations.len(), 2);
+        assert!(cycle.evolve(&[1.0, 0.0, 1.0], 10_000, 1e-6).unwrap().equilibrium()[1] == 0.0);
+
+        // The shares must be a mix of the strategies.
+
+        assert!(matches!(cycle.evolve(&[1.0, 1.0], 100, 1e-6), Err(TenziError::InvalidConfig(_))));
+        assert!(matches!(cycle.evolve(&[1.0, -1.0, 1.0], 100, 1e-6), Err(TenziError::InvalidConfig(_))));
+        assert!(matches!(cycle.evolve(&[0.0, 0.0, 0.0], 100, 1e-6), Err(TenziError::InvalidConfig(_))));
+    }
+
+    #[test]
+    fn test_tournament() {
+        let registry = StrategyRegistry::new();
+        let strategies = ["naive".to_string(), "divide".to_string(), "merge".to_string()];
+        let tournament = Tournament::new(&registry, &strategies, 6, 10, 2_000, Some(42)).unwrap();
+
+        assert_eq!(Tournament::new(&registry, &strategies, 6, 10, 2_000, Some(42)).unwrap(), tournament);
+
+        for i in 0..strategies.len() {
+            assert_eq!(tournament.payoffs[i][i], 0.5);
+
+            for j in 0..strategies.len() {
+                assert!((tournament.payoffs[i][j] + tournament.payoffs[j][i] - 1.0).abs() < 1e-6);
+            }
+        }
+
+        // Merge takes more turns than naive, so it loses the race, and dies out of the population.
+
+        assert!(tournament.payoffs[2][0] < 0.5);
+        assert!(tournament.evolve(&[1.0, 1.0, 1.0], 10_000, 1e-6).unwrap().equilibrium()[2] < 0.01);
+
+        assert!(matches!(Tournament::new(&registry, &strategies[..1], 6, 10, 100, None), Err(TenziError::TooFewPlayers(1))));
+    }
+}
\ No newline at end of file
@@ -0,0 +1,286 @@
+use std::{fmt::Write as _, path::Path};
+
+use crate::{error::{Result, TenziError}, simulation::{SimulationType, Snapshot}, types::Num};
+
+/// The header that every trace file starts with.
+const HEADER: &str = "tenzi-trace";
+
+/// A recorded single game of "tenzi".
+///
+/// The first frame is the state before any roll, and every subsequent frame is the state after a step.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GameTrace {
+    pub num_sides: Num,
+    pub num_dice: Num,
+    pub strategy: String,
+    pub frames: Vec<TraceFrame>,
+}
+
+/// The state of a game after a step.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TraceFrame {
+    pub num_rolls: Num,
+    pub num_steps: Num,
+    pub buckets: Vec<Num>,
+}
+
+impl From<Snapshot> for TraceFrame {
+    fn from(snapshot: Snapshot) -> Self {
+        Self {
+            num_rolls: snapshot.num_rolls,
+            num_steps: snapshot.num_steps,
+            buckets: snapshot.buckets,
+        }
+    }
+}
+
+impl GameTrace {
+    /// Plays a single game to completion, recording the state after every step.
+    pub fn record(strategy: &str, mut simulation: SimulationType) -> Self {
+        let initial = Snapshot::of(simulation.as_strategy());
+        let frames = std::iter::once(initial).chain(simulation.steps()).map(TraceFrame::from).collect();
+
+        Self::from_frames(strategy, frames)
+    }
+
+    /// Plays a single game to completion with the given dice (i.e., the faces in the order they were rolled) rather
+    /// than an rng, recording the state after every step.
+    ///
+    /// This is how a physical game is replayed through a strategy.  Fails if a die is not a face of the game's die,
+    /// or if the dice run out before the game is done.
+    pub fn replay(strategy: &str, mut simulation: SimulationType, dice: &[Num]) -> Result<Self> {
+        let game = simulation.as_strategy_mut();
+        let num_sides = game.num_sides();
+
+        if let Some(face) = dice.iter().find(|&&face| face == 0 || face > num_sides) {
+            return Err(TenziError::InvalidDice(format!("`{}` is not a face of a {}-sided die", face, num_sides)));
+        }
+
+        let mut remaining = dice.iter().copied();
+        let mut frames = vec![TraceFrame::from(Snapshot::of(game))];
+
+        while !game.done() {
+            // Check that the whole roll is available up front, so that the game is never left mid-roll.
+
+            if remaining.len() < game.num_to_roll() as usize {
+                return Err(TenziError::DiceExhausted((dice.len() - remaining.len()) as Num));
+            }
+
+            game.step_with(&mut |_| remaining.next().unwrap());
+            frames.push(TraceFrame::from(Snapshot::of(game)));
+        }
+
+        Ok(Self::from_frames(strategy, frames))
+    }
+
+    fn from_frames(strategy: &str, frames: Vec<TraceFrame>) -> Self {
+        // Once the game is done, every die is kept.
+
+        let num_sides = frames[0].buckets.len() as Num;
+        let num_dice = frames.last().unwrap().buckets.iter().sum();
+
+        Self {
+            num_sides,
+            num_dice,
+            strategy: strategy.to_string(),
+            frames,
+        }
+    }
+
+    /// Serializes the trace to its text format: a header line, followed by one line per frame of
+    /// the form "rolls steps b1,b2,...".
+    pub fn to_text(&self) -> String {
+        let mut text = format!("{} {} {} {}\n", HEADER, self.num_sides, self.num_dice, self.strategy);
+
+        for frame in &self.frames {
+            let buckets = frame.buckets.iter().map(|b| b.to_string()).collect::<Vec<_>>().join(",");
+            writeln!(text, "{} {} {}", frame.num_rolls, frame.num_steps, buckets).unwrap();
+        }
+
+        text
+    }
+
+    /// Parses a trace from its text format.
+    pub fn from_text(text: &str) -> Result<Self> {
+        let mut lines = text.lines().filter(|l| !l.trim().is_empty());
+
+        let header = lines.next().ok_or_else(|| invalid("the trace is empty"))?.split_whitespace().collect::<Vec<_>>();
+
+        let [header, num_sides, num_dice, strategy] = header.as_slice() else {
+            return Err(invalid("the trace header is malformed"));
+        };
+
+        if *header != HEADER {
+            return Err(invalid("the trace header is malformed"));
+        }
+
+        let num_sides = parse_num(num_sides)?;
+        let num_dice = parse_num(num_dice)?;
+
+        let frames = lines.map(|line| {
+            let [num_rolls, num_steps, buckets] = line.split_whitespace().collect::<Vec<_>>()[..] else {
+                return Err(invalid(format!("the trace frame `{}` is malformed", line)));
+            };
+
+            let buckets = buckets.split(',').map(parse_num).collect::<Result<Vec<_>, _>>()?;
+
+            if buckets.len() != num_sides as usize {
+                return Err(invalid(format!("the trace frame `{}` does not have {} buckets", line, num_sides)));
+            }
+
+            Ok(TraceFrame {
+                num_rolls: parse_num(num_rolls)?,
+                num_steps: parse_num(num_steps)?,
+                buckets,
+            })
+        }).collect::<Result<Vec<_>, _>>()?;
+
+        if frames.is_empty() {
+            return Err(invalid("the trace has no frames"));
+        }
+
+        Ok(Self {
+            num_sides,
+            num_dice,
+            strategy: strategy.to_string(),
+            frames,
+        })
+    }
+
+    /// Returns the index of the first frame at which the traces diverge (i.e., where the buckets or the number of
+    /// rolls differ, or where one trace has ended but the other has not), or `None` if they are the same game.
+    ///
+    /// Frames are aligned by step, so this is the first step where the two strategies (or the two versions of one)
+    /// decided differently, or were rolled different dice.  Fails if the traces are not of the same game size.
+    pub fn divergence(&self, other: &Self) -> Result<Option<usize>> {
+        if (self.num_sides, self.num_dice) != (other.num_sides, other.num_dice) {
+            return Err(invalid(format!("cannot compare a trace of {} {}-sided dice to one of {} {}-sided dice", self.num_dice, self.num_sides, other.num_dice, other.num_sides)));
+        }
+
+        let len = self.frames.len().max(other.frames.len());
+
+        Ok((0..len).find(|&k| self.frames.get(k) != other.frames.get(k)))
+    }
+
+    /// Writes the trace to a file.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        Ok(std::fs::write(path, self.to_text())?)
+    }
+
+    /// Reads a trace from a file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let text = std::fs::read_to_string(path)?;
+        Self::from_text(&text)
+    }
+}
+
+/// Parses recorded dice (i.e., the faces in the order they were rolled), separated by whitespace or commas.
+///
+/// Everything after a `#` on a line is a comment, so each roll can be written (and annotated) on its own line.
+pub fn parse_dice(text: &str) -> Result<Vec<Num>> {
+    text.lines()
+        .flat_map(|line| line.split('#').next().unwrap().split(|c: char| c == ',' || c.is_whitespace()))
+        .filter(|face| !face.is_empty())
+        .map(|face| face.parse::<Num>().map_err(|_| TenziError::InvalidDice(format!("`{}` is not a number", face))))
+        .collect()
+}
+
+/// Reads recorded dice from a file (see [`parse_dice`]).
+pub fn load_dice(path: impl AsRef<Path>) -> Result<Vec<Num>> {
+    parse_dice(&std::fs::read_to_string(path)?)
+}
+
+/// Parses a number from a trace field.
+fn parse_num(s: &str) -> Result<Num> {
+    s.parse::<Num>().map_err(|_| invalid(format!("`{}` is not a number", s)))
+}
+
+/// Builds an invalid trace error.
+fn invalid(reason: impl Into<String>) -> TenziError {
+    TenziError::InvalidTrace(reason.into())
+}
+
+// Tests.
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::MergeSimulation;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_record() {
+        let trace = GameTrace::record("merge", SimulationType::Merge(MergeSimulation::new(6, 10)));
+        let last = trace.frames.last().unwrap();
+
+        assert_eq!(trace.frames[0].buckets, vec![0; 6]);
+        assert_eq!(trace.frames.len(), last.num_steps as usize + 1);
+        assert!(last.buckets.contains(&10));
+    }
+
+    #[test]
+    fn test_text_round_trip() {
+        let trace = GameTrace::record("merge", SimulationType::Merge(MergeSimulation::new(6, 10)));
+        let parsed = GameTrace::from_text(&trace.to_text()).unwrap();
+
+        assert_eq!(parsed, trace);
+    }
+
+    #[test]
+    fn test_replay() {
+        // Roll a 3 with every die, so the game is done after the first roll.
+
+        let trace = GameTrace::replay("merge", SimulationType::Merge(MergeSimulation::new(6, 4)), &[3, 3, 3, 3]).unwrap();
+
+        assert_eq!(trace.frames.len(), 2);
+        assert_eq!(trace.frames[1].buckets, vec![0, 0, 4, 0, 0, 0]);
+        assert_eq!(trace.frames[1].num_rolls, 4);
+    }
+
+    #[test]
+    fn test_divergence() {
+        let replay = |strategy: &str, dice: &[Num]| GameTrace::replay(strategy, SimulationType::fast(strategy.parse().unwrap(), 6, 6), dice).unwrap();
+
+        // Naive only keeps the mode of the first roll, but divide keeps a second group too.
+
+        let dice = [1, 1, 2, 3, 4, 5, 2, 2, 2, 6, 1, 1, 1, 1, 2, 2, 2, 2];
+        let naive = replay("naive", &dice);
+        let divide = replay("divide", &dice);
+
+        assert_eq!(naive.divergence(&naive).unwrap(), None);
+        assert_eq!(naive.divergence(&divide).unwrap(), Some(1));
+        assert_eq!(naive.frames[1].buckets, vec![2, 0, 0, 0, 0, 0]);
+        assert_eq!(divide.frames[1].buckets, vec![2, 1, 0, 0, 0, 0]);
+
+        // A trace that ends early diverges where it ends, and only traces of the same game size compare.
+
+        let short = GameTrace { frames: naive.frames[..2].to_vec(), ..naive.clone() };
+
+        assert_eq!(short.divergence(&naive).unwrap(), Some(2));
+        assert!(matches!(naive.divergence(&GameTrace { num_dice: 5, ..naive.clone() }), Err(TenziError::InvalidTrace(_))));
+    }
+
+    #[test]
+    fn test_replay_invalid() {
+        let sim = || SimulationType::Merge(MergeSimulation::new(6, 4));
+
+        assert!(matches!(GameTrace::replay("merge", sim(), &[1, 2, 3, 4]), Err(TenziError::DiceExhausted(4))));
+        assert!(matches!(GameTrace::replay("merge", sim(), &[1, 2, 7, 4]), Err(TenziError::InvalidDice(_))));
+    }
+
+    #[test]
+    fn test_parse_dice() {
+        assert_eq!(parse_dice("1 2,3\n# a comment\n4, 5 # another\n").unwrap(), vec![1, 2, 3, 4, 5]);
+        assert!(matches!(parse_dice("1 x"), Err(TenziError::InvalidDice(_))));
+    }
+
+    #[test]
+    fn test_from_text_malformed() {
+        assert!(GameTrace::from_text("").is_err());
+        assert!(GameTrace::from_text("not-a-trace 6 10 merge\n0 0 0,0,0,0,0,0").is_err());
+        assert!(GameTrace::from_text("tenzi-trace 6 10 merge\n0 0 0,0,0").is_err());
+        assert!(GameTrace::from_text("tenzi-trace 6 10 merge\n").is_err());
+    }
+}
\ No newline at end of file
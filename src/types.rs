@@ -1,5 +1,89 @@
-use std::sync::atomic::AtomicUsize;
+//! The numeric types used throughout the simulator.
+//!
+//! By default, counters are `usize` and floats are `f64`.  The `num-u32` / `num-u64` features switch the counter type
+//! (e.g., `num-u64` avoids overflowing accumulators on 32-bit and WASM targets), and the `float-f32` feature switches
+//! the float type.
+//!
+//! The counter type is also the element of a game's buckets (one count per face), so `num-u32` halves them: a 64-byte
+//! cache line then holds 16 buckets rather than 8, and a SIMD register twice as many lanes.  On the strategy and mode
+//! benchmarks (10d6 through 50d20), that made no difference beyond the noise, since a game's time goes to rolling the
+//! dice rather than to scanning a few buckets, so the default stays `usize` (which indexes without a cast).  The
+//! `bench` command prints the width that it was built with, so that both layouts can be compared on a given machine.
+//!
+//! The running totals over the games of a run (e.g., of the squared number of rolls, which the standard deviation is
+//! computed from) are a [`Total`] whatever the counter type, so that no run of any length overflows them: the squares
+//! overflow a `u32` in about a million games of 10d6, and a `u64` in a few trillion games of thousands of rolls.
 
-pub type Num = usize;
-pub type AtomicNum = AtomicUsize;
+#[cfg(all(feature = "num-u32", feature = "num-u64"))]
+compile_error!("The `num-u32` and `num-u64` features are mutually exclusive.");
+
+#[cfg(feature = "num-u32")]
+mod num {
+    pub type Num = u32;
+    pub type AtomicNum = core::sync::atomic::AtomicU32;
+}
+
+#[cfg(feature = "num-u64")]
+mod num {
+    pub type Num = u64;
+    pub type AtomicNum = core::sync::atomic::AtomicU64;
+}
+
+#[cfg(not(any(feature = "num-u32", feature = "num-u64")))]
+mod num {
+    pub type Num = usize;
+    pub type AtomicNum = core::sync::atomic::AtomicUsize;
+}
+
+pub use num::{AtomicNum, Num};
+
+/// The type of the running totals over the games of a run (see the [module](self) docs).
+pub type Total = u128;
+
+/// Serializes a [`Total`] as a number while it fits a `u64`, and as a string of its digits beyond that, since neither
+/// serde's internally tagged enums (e.g., the messages of a cluster) nor most JSON readers handle wider numbers.
+#[cfg(all(feature = "std", feature = "serde"))]
+pub(crate) mod serde_total {
+    use alloc::string::ToString;
+
+    use super::Total;
+
+    pub fn serialize<S: serde::Serializer>(total: &Total, serializer: S) -> Result<S::Ok, S::Error> {
+        match u64::try_from(*total) {
+            Ok(total) => serializer.serialize_u64(total),
+            Err(_) => serializer.serialize_str(&total.to_string()),
+        }
+    }
+
+    pub fn deserialize<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Total, D::Error> {
+        struct TotalVisitor;
+
+        impl serde::de::Visitor<'_> for TotalVisitor {
+            type Value = Total;
+
+            fn expecting(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+                f.write_str("a non-negative integer, or a string of its digits")
+            }
+
+            fn visit_u64<E: serde::de::Error>(self, total: u64) -> Result<Total, E> {
+                Ok(total as Total)
+            }
+
+            fn visit_u128<E: serde::de::Error>(self, total: u128) -> Result<Total, E> {
+                Ok(total)
+            }
+
+            fn visit_str<E: serde::de::Error>(self, total: &str) -> Result<Total, E> {
+                total.parse().map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_any(TotalVisitor)
+    }
+}
+
+#[cfg(feature = "float-f32")]
+pub type Float = f32;
+
+#[cfg(not(feature = "float-f32"))]
 pub type Float = f64;
\ No newline at end of file
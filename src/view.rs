@@ -0,0 +1,124 @@
+use std::io::{Stdout, Write};
+
+use colored::Colorize;
+use crossterm::{cursor, event::{self, Event, KeyCode, KeyEventKind}, execute, terminal};
+
+use tenzi_sim::{trace::GameTrace, types::Num};
+
+/// The widest that a bucket's bar may be drawn.
+const MAX_BAR_WIDTH: Num = 50;
+
+/// Steps through a recorded game trace, rendering the bucket state as bars.
+///
+/// Keys: right / `l` / space steps forward, left / `h` steps backward, home / `g` and end / `G` jump
+/// to the start and end, and `q` / escape exits.
+pub fn view(trace: &GameTrace) -> std::io::Result<()> {
+    let mut stdout = std::io::stdout();
+
+    terminal::enable_raw_mode()?;
+    execute!(stdout, terminal::EnterAlternateScreen, cursor::Hide)?;
+
+    let result = run(trace, &mut stdout);
+
+    execute!(stdout, cursor::Show, terminal::LeaveAlternateScreen)?;
+    terminal::disable_raw_mode()?;
+
+    result
+}
+
+fn run(trace: &GameTrace, stdout: &mut Stdout) -> std::io::Result<()> {
+    let last = trace.frames.len() - 1;
+    let mut index = 0;
+
+    loop {
+        execute!(stdout, terminal::Clear(terminal::ClearType::All), cursor::MoveTo(0, 0))?;
+
+        // Raw mode does not translate newlines, so return the carriage explicitly.
+
+        for line in render(trace, index) {
+            write!(stdout, "{}\r\n", line)?;
+        }
+        stdout.flush()?;
+
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+
+        match key.code {
+            KeyCode::Right | KeyCode::Char('l') | KeyCode::Char(' ') => index = (index + 1).min(last),
+            KeyCode::Left | KeyCode::Char('h') => index = index.saturating_sub(1),
+            KeyCode::Home | KeyCode::Char('g') => index = 0,
+            KeyCode::End | KeyCode::Char('G') => index = last,
+            KeyCode::Esc | KeyCode::Char('q') => return Ok(()),
+            _ => {}
+        }
+    }
+}
+
+/// Renders a single frame of the trace as lines of text.
+pub fn render(trace: &GameTrace, index: usize) -> Vec<String> {
+    let frame = &trace.frames[index];
+    let previous = index.checked_sub(1).map(|k| &trace.frames[k]);
+
+    let scale = |count: Num| (count * MAX_BAR_WIDTH).div_ceil(trace.num_dice.max(1)).min(MAX_BAR_WIDTH);
+    let face_width = trace.num_sides.to_string().len();
+
+    let mut lines = vec![
+        format!("Game trace of `{}` with {} {}-sided die.", trace.strategy.cyan(), trace.num_dice.to_string().cyan(), trace.num_sides.to_string().cyan()),
+        format!("Frame {} of {}: {} rolls, {} steps.", index.to_string().cyan(), (trace.frames.len() - 1).to_string().cyan(), frame.num_rolls.to_string().green(), frame.num_steps.to_string().green()),
+        String::new(),
+    ];
+
+    for (k, &count) in frame.buckets.iter().enumerate() {
+        let bar = "█".repeat(scale(count) as usize);
+
+        // Highlight the buckets that changed since the previous frame.
+
+        let bar = match previous.map(|p| p.buckets[k].cmp(&count)) {
+            Some(std::cmp::Ordering::Less) => bar.green(),
+            Some(std::cmp::Ordering::Greater) => bar.red(),
+            _ => bar.normal(),
+        };
+
+        lines.push(format!("{:>width$} │ {} {}", k + 1, bar, count, width = face_width));
+    }
+
+    lines.push(String::new());
+    lines.push(format!("{}", "←/h: back  →/l/space: forward  g/G: start/end  q: quit".dimmed()));
+
+    lines
+}
+
+// Tests.
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tenzi_sim::trace::TraceFrame;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_render_bars() {
+        colored::control::set_override(false);
+
+        let trace = GameTrace {
+            num_sides: 3,
+            num_dice: 10,
+            strategy: "naive".to_string(),
+            frames: vec![
+                TraceFrame { num_rolls: 0, num_steps: 0, buckets: vec![0, 0, 0] },
+                TraceFrame { num_rolls: 10, num_steps: 1, buckets: vec![0, 5, 0] },
+            ],
+        };
+
+        let lines = render(&trace, 1);
+
+        assert_eq!(lines[1], "Frame 1 of 1: 10 rolls, 1 steps.");
+        assert_eq!(lines[3], "1 │  0");
+        assert_eq!(lines[4], format!("2 │ {} 5", "█".repeat(25)));
+    }
+}
\ No newline at end of file
@@ -0,0 +1,129 @@
+//! WebAssembly exports for running the simulator in a browser, enabled by the `wasm` feature.
+//!
+//! Build with `cargo rustc --lib --release --target wasm32-unknown-unknown --no-default-features --features wasm
+//! --crate-type cdylib`, and then generate the JS glue with `wasm-bindgen --target web`.  The dice are rolled with the
+//! browser's `crypto.getRandomValues`, and the simulations run on the calling thread, since rayon falls back to it
+//! when threads are not available.
+
+use wasm_bindgen::prelude::*;
+
+use crate::{error::{Result, TenziError}, registry::StrategyRegistry, simulation::SimulationType, SimulationConfig};
+
+/// Runs the simulations described by a JSON config (e.g., `{"num_sides": 6, "strategy": "merge"}`, where every field
+/// is optional), and returns the results as JSON.
+#[wasm_bindgen(js_name = runSimulations)]
+pub fn run_simulations(config_json: &str) -> Result<String, JsError> {
+    Ok(run(config_json)?)
+}
+
+/// Returns the names of the built-in strategies that take no parameter (i.e., that are specified by their name alone).
+#[wasm_bindgen]
+pub fn strategies() -> Vec<String> {
+    StrategyRegistry::default().plain_names().map(str::to_string).collect()
+}
+
+/// A single game, which is played one step at a time.
+#[wasm_bindgen(js_name = Game)]
+pub struct WasmGame {
+    simulation: SimulationType,
+}
+
+#[wasm_bindgen(js_class = Game)]
+impl WasmGame {
+    /// Starts a game with the given strategy spec (e.g., "merge").
+    #[wasm_bindgen(constructor)]
+    pub fn new(strategy: &str, num_sides: u32, num_dice: u32) -> Result<WasmGame, JsError> {
+        Ok(Self::build(strategy, num_sides, num_dice)?)
+    }
+
+    /// Rolls the dice that are not kept, and lets the strategy decide which to keep.  Returns whether or not a
+    /// "tenzi" has been achieved.
+    pub fn step(&mut self) -> bool {
+        let strategy = self.simulation.as_strategy_mut();
+
+        if !strategy.done() {
+            strategy.step();
+        }
+
+        strategy.done()
+    }
+
+    /// Returns the number of dice kept for each face.
+    pub fn buckets(&self) -> Vec<u32> {
+        self.simulation.buckets().iter().map(|&count| count as u32).collect()
+    }
+
+    /// Returns the number of dice that will be rolled on the next step.
+    #[wasm_bindgen(js_name = numToRoll)]
+    pub fn num_to_roll(&self) -> u32 {
+        self.simulation.as_strategy().num_to_roll() as u32
+    }
+
+    /// Returns the number of dice rolled so far.
+    #[wasm_bindgen(js_name = numRolls)]
+    pub fn num_rolls(&self) -> u32 {
+        self.simulation.as_strategy().num_rolls() as u32
+    }
+
+    /// Returns the number of steps taken so far.
+    #[wasm_bindgen(js_name = numSteps)]
+    pub fn num_steps(&self) -> u32 {
+        self.simulation.as_strategy().num_steps() as u32
+    }
+
+    /// Returns whether or not a "tenzi" has been achieved.
+    pub fn done(&self) -> bool {
+        self.simulation.as_strategy().done()
+    }
+
+    /// Starts the game over with the same strategy.
+    pub fn reset(&mut self) {
+        self.simulation.as_strategy_mut().reset();
+    }
+}
+
+impl WasmGame {
+    fn build(strategy: &str, num_sides: u32, num_dice: u32) -> Result<Self> {
+        let config = SimulationConfig::builder().sides(num_sides as _).dice(num_dice as _).strategy(strategy).build()?;
+
+        Ok(Self { simulation: config.simulation() })
+    }
+}
+
+fn run(config_json: &str) -> Result<String> {
+    let config: SimulationConfig = serde_json::from_str(config_json).map_err(|e| TenziError::InvalidConfig(e.to_string()))?;
+    let results = config.run()?;
+
+    serde_json::to_string(&results).map_err(|e| TenziError::InvalidConfig(e.to_string()))
+}
+
+// Tests.
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_run() {
+        let results = run(r#"{"strategy": "merge", "initial_state": [0, 10, 0, 0, 0, 0], "num_simulations": 10}"#).unwrap();
+
+        assert!(results.contains(r#""average_rolls":0.0"#));
+        assert!(matches!(run(r#"{"num_sides": 0}"#), Err(TenziError::InvalidConfig(_))));
+    }
+
+    #[test]
+    fn test_game() {
+        let mut game = WasmGame::build("merge", 6, 10).unwrap();
+
+        while !game.step() {}
+
+        assert!(game.done());
+        assert_eq!(game.buckets().iter().sum::<u32>(), 10);
+
+        game.reset();
+
+        assert_eq!(game.num_rolls(), 0);
+        assert!(matches!(WasmGame::build("bogus", 6, 10), Err(TenziError::UnknownStrategy(_))));
+    }
+}
\ No newline at end of file